@@ -0,0 +1,3 @@
+-- Add down migration script here
+drop index if exists idx_crawl_jobs_state_updated_at;
+drop table crawl_jobs;
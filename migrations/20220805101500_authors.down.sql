@@ -0,0 +1,3 @@
+-- Add down migration script here
+drop index if exists idx_authors_name;
+drop table authors;
@@ -0,0 +1,12 @@
+-- Add up migration script here
+create table if not exists authors
+(
+    id          integer not null,
+    name        text    not null,
+    created_at  text    not null,
+    searched_at text,
+    primary key (id)
+);
+
+drop index if exists idx_authors_name;
+create unique index idx_authors_name on authors (name);
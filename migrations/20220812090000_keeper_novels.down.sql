@@ -0,0 +1,5 @@
+-- Add down migration script here
+drop table novel_relations;
+drop index if exists idx_novels_name_author;
+drop table novels;
+drop table sorts;
@@ -0,0 +1,44 @@
+-- Add up migration script here
+create table if not exists sorts
+(
+    id               integer not null,
+    created_at       text    not null,
+    updated_at       text,
+    name             text    not null,
+    relation_kind_id text,
+    relation_id      integer,
+    primary key (id)
+);
+
+create table if not exists novels
+(
+    id                integer not null,
+    name              text    not null,
+    created_at        text    not null,
+    updated_at        text    not null,
+    cover             text,
+    cover_path        text,
+    author            text    not null,
+    intro             text,
+    last_updated_at   text,
+    last_section      integer,
+    last_section_name text,
+    word_count        integer,
+    tags              text,
+    category          text,
+    aliases           text,
+    primary key (id)
+);
+
+drop index if exists idx_novels_name_author;
+create unique index idx_novels_name_author on novels (name, author);
+
+create table if not exists novel_relations
+(
+    spider_kind_id  text    not null,
+    novel_id        integer not null,
+    spider_novel_id integer not null,
+    score           integer not null default 0,
+    source_url      text,
+    primary key (spider_kind_id, novel_id, spider_novel_id)
+);
@@ -0,0 +1,30 @@
+-- Add up migration script here
+create table if not exists sections
+(
+    id              integer not null,
+    spider_id       text    not null,
+    spider_novel_id integer not null,
+    novel_id        integer,
+    seq             integer not null,
+    name            text    not null,
+    update_at       text,
+    content_key     text    not null,
+    content_hash    text    not null,
+    size            integer not null,
+    created_at      text    not null,
+    updated_at      text    not null,
+    primary key (id)
+);
+
+drop index if exists idx_sections_spider_novel_seq;
+create unique index idx_sections_spider_novel_seq on sections (spider_id, spider_novel_id, seq);
+
+drop index if exists idx_sections_novel_id_seq;
+create index idx_sections_novel_id_seq on sections (novel_id, seq);
+
+create table if not exists section_contents
+(
+    key  text not null,
+    data blob not null,
+    primary key (key)
+);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+drop table fts_sections;
+drop table fts_novels;
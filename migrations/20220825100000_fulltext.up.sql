@@ -0,0 +1,20 @@
+-- Add up migration script here
+-- tokens为分词后以空格分隔的内容，中文按二元组切分；chars为其中的中文单字，用于搜索单字
+-- rowid与小说id、章节id相同，更新索引时按rowid删除旧的记录
+create virtual table if not exists fts_novels using fts5
+(
+    tokens,
+    chars,
+    novel_id unindexed,
+    name unindexed,
+    intro unindexed
+);
+
+create virtual table if not exists fts_sections using fts5
+(
+    tokens,
+    chars,
+    section_id unindexed,
+    novel_id unindexed,
+    seq unindexed
+);
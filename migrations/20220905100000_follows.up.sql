@@ -0,0 +1,22 @@
+-- Add up migration script here
+create table if not exists follows
+(
+    id         integer not null,
+    novel_id   integer not null,
+    subscriber text    not null,
+    created_at text    not null,
+    primary key (id)
+);
+
+drop index if exists idx_follows_novel_subscriber;
+create unique index idx_follows_novel_subscriber on follows (novel_id, subscriber);
+
+create table if not exists novel_watches
+(
+    novel_id          integer not null,
+    last_section_name text,
+    last_updated_at   text,
+    section_count     integer not null default 0,
+    checked_at        text    not null,
+    primary key (novel_id)
+);
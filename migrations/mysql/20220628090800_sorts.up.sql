@@ -0,0 +1,8 @@
+-- Add up migration script here
+create table if not exists ddxsku_spider_sorts
+(
+    id   bigint       not null,
+    name varchar(255) not null,
+    link text         not null,
+    primary key (id)
+)
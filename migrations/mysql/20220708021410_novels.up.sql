@@ -0,0 +1,12 @@
+-- Add up migration script here
+create table if not exists ddxsku_spider_novels
+(
+    id           bigint       not null,
+    raw_id       varchar(255) not null,
+    name         varchar(255) not null,
+    author       varchar(255) not null,
+    raw_link     text         not null,
+    section_link text         not null,
+    primary key (id),
+    unique index idx_ddxsku_spider_novels_name_author (name, author)
+);
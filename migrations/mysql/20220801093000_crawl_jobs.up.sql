@@ -0,0 +1,16 @@
+-- Add up migration script here
+create table if not exists crawl_jobs
+(
+    id         bigint       not null,
+    kind       varchar(64)  not null,
+    spider_id  varchar(255) not null,
+    target_id  bigint       not null,
+    `cursor`   int          not null default 0,
+    state      varchar(32)  not null,
+    attempts   int          not null default 0,
+    last_error text,
+    created_at datetime(6)  not null,
+    updated_at datetime(6)  not null,
+    primary key (id),
+    index idx_crawl_jobs_state_updated_at (state, updated_at)
+);
@@ -0,0 +1,10 @@
+-- Add up migration script here
+create table if not exists authors
+(
+    id          bigint       not null,
+    name        varchar(255) not null,
+    created_at  datetime(6)  not null,
+    searched_at datetime(6),
+    primary key (id),
+    unique index idx_authors_name (name)
+);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+drop table novel_relations;
+drop table novels;
+drop table sorts;
@@ -0,0 +1,42 @@
+-- Add up migration script here
+create table if not exists sorts
+(
+    id               bigint       not null,
+    created_at       datetime(6)  not null,
+    updated_at       datetime(6),
+    name             varchar(255) not null,
+    relation_kind_id varchar(255),
+    relation_id      bigint,
+    primary key (id)
+);
+
+create table if not exists novels
+(
+    id                bigint       not null,
+    name              varchar(255) not null,
+    created_at        datetime(6)  not null,
+    updated_at        datetime(6)  not null,
+    cover             text,
+    cover_path        text,
+    author            varchar(255) not null,
+    intro             text,
+    last_updated_at   datetime(6),
+    last_section      bigint,
+    last_section_name varchar(255),
+    word_count        bigint,
+    tags              json,
+    category          varchar(255),
+    aliases           json,
+    primary key (id),
+    unique index idx_novels_name_author (name, author)
+);
+
+create table if not exists novel_relations
+(
+    spider_kind_id  varchar(255) not null,
+    novel_id        bigint       not null,
+    spider_novel_id bigint       not null,
+    score           int          not null default 0,
+    source_url      text,
+    primary key (spider_kind_id, novel_id, spider_novel_id)
+);
@@ -0,0 +1,26 @@
+-- Add up migration script here
+create table if not exists sections
+(
+    id              bigint       not null,
+    spider_id       varchar(255) not null,
+    spider_novel_id bigint       not null,
+    novel_id        bigint,
+    seq             int          not null,
+    name            varchar(255) not null,
+    update_at       datetime(6),
+    content_key     varchar(255) not null,
+    content_hash    char(64)     not null,
+    size            bigint       not null,
+    created_at      datetime(6)  not null,
+    updated_at      datetime(6)  not null,
+    primary key (id),
+    unique index idx_sections_spider_novel_seq (spider_id, spider_novel_id, seq),
+    index idx_sections_novel_id_seq (novel_id, seq)
+);
+
+create table if not exists section_contents
+(
+    `key` varchar(255) not null,
+    data  longblob     not null,
+    primary key (`key`)
+);
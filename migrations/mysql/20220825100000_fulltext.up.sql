@@ -0,0 +1,24 @@
+-- Add up migration script here
+-- tokens为分词后以空格分隔的内容，中文按二元组切分，使用ngram解析器的全文索引匹配
+-- chars为其中的中文单字，用于搜索单字，ngram索引不包含单字的词，使用like匹配
+create table if not exists fts_novels
+(
+    novel_id bigint       not null,
+    name     varchar(255) not null,
+    intro    text,
+    tokens   longtext     not null,
+    chars    text         not null,
+    primary key (novel_id),
+    fulltext index idx_fts_novels_tokens (tokens) with parser ngram
+);
+
+create table if not exists fts_sections
+(
+    section_id bigint   not null,
+    novel_id   bigint,
+    seq        int      not null,
+    tokens     longtext not null,
+    chars      text     not null,
+    primary key (section_id),
+    fulltext index idx_fts_sections_tokens (tokens) with parser ngram
+);
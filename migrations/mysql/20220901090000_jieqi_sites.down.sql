@@ -0,0 +1,11 @@
+-- 只保留ddxsku的数据
+alter table ddxsku_spider_novels drop index idx_ddxsku_spider_novels_site_name_author;
+alter table ddxsku_spider_sorts drop index idx_ddxsku_spider_sorts_site;
+
+delete from ddxsku_spider_novels where site <> 'ddxsku';
+delete from ddxsku_spider_sorts where site <> 'ddxsku';
+
+alter table ddxsku_spider_novels drop column site;
+alter table ddxsku_spider_sorts drop column site;
+
+create unique index idx_ddxsku_spider_novels_name_author on ddxsku_spider_novels (name, author);
@@ -0,0 +1,8 @@
+-- 同一jieqi模板的多个站点共用数据表，以site(爬虫id)区分
+alter table ddxsku_spider_sorts add column site varchar(255) not null default 'ddxsku';
+alter table ddxsku_spider_novels add column site varchar(255) not null default 'ddxsku';
+
+create index idx_ddxsku_spider_sorts_site on ddxsku_spider_sorts (site);
+
+alter table ddxsku_spider_novels drop index idx_ddxsku_spider_novels_name_author;
+create unique index idx_ddxsku_spider_novels_site_name_author on ddxsku_spider_novels (site, name, author);
@@ -0,0 +1,20 @@
+-- Add up migration script here
+create table if not exists follows
+(
+    id         bigint       not null,
+    novel_id   bigint       not null,
+    subscriber varchar(255) not null,
+    created_at datetime(6)  not null,
+    primary key (id),
+    unique index idx_follows_novel_subscriber (novel_id, subscriber)
+);
+
+create table if not exists novel_watches
+(
+    novel_id          bigint       not null,
+    last_section_name varchar(255),
+    last_updated_at   datetime(6),
+    section_count     bigint       not null default 0,
+    checked_at        datetime(6)  not null,
+    primary key (novel_id)
+);
@@ -0,0 +1,8 @@
+-- Add up migration script here
+create table if not exists cookie_jars
+(
+    spider_id  varchar(255) not null,
+    cookies    text         not null,
+    updated_at datetime(6)  not null,
+    primary key (spider_id)
+);
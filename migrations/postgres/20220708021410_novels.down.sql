@@ -0,0 +1,3 @@
+-- Add down migration script here
+drop index if exists idx_ddxsku_spider_novels_name_author;
+drop table ddxsku_spider_novels;
@@ -0,0 +1,18 @@
+-- Add up migration script here
+create table if not exists crawl_jobs
+(
+    id         bigint      not null,
+    kind       text        not null,
+    spider_id  text        not null,
+    target_id  bigint      not null,
+    cursor     integer     not null default 0,
+    state      text        not null,
+    attempts   integer     not null default 0,
+    last_error text,
+    created_at timestamptz not null,
+    updated_at timestamptz not null,
+    primary key (id)
+);
+
+drop index if exists idx_crawl_jobs_state_updated_at;
+create index idx_crawl_jobs_state_updated_at on crawl_jobs (state, updated_at);
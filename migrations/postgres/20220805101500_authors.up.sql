@@ -0,0 +1,12 @@
+-- Add up migration script here
+create table if not exists authors
+(
+    id          bigint      not null,
+    name        text        not null,
+    created_at  timestamptz not null,
+    searched_at timestamptz,
+    primary key (id)
+);
+
+drop index if exists idx_authors_name;
+create unique index idx_authors_name on authors (name);
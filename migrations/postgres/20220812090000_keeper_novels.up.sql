@@ -0,0 +1,44 @@
+-- Add up migration script here
+create table if not exists sorts
+(
+    id               bigint      not null,
+    created_at       timestamptz not null,
+    updated_at       timestamptz,
+    name             text        not null,
+    relation_kind_id text,
+    relation_id      bigint,
+    primary key (id)
+);
+
+create table if not exists novels
+(
+    id                bigint      not null,
+    name              text        not null,
+    created_at        timestamptz not null,
+    updated_at        timestamptz not null,
+    cover             text,
+    cover_path        text,
+    author            text        not null,
+    intro             text,
+    last_updated_at   timestamptz,
+    last_section      bigint,
+    last_section_name text,
+    word_count        bigint,
+    tags              jsonb,
+    category          text,
+    aliases           jsonb,
+    primary key (id)
+);
+
+drop index if exists idx_novels_name_author;
+create unique index idx_novels_name_author on novels (name, author);
+
+create table if not exists novel_relations
+(
+    spider_kind_id  text    not null,
+    novel_id        bigint  not null,
+    spider_novel_id bigint  not null,
+    score           integer not null default 0,
+    source_url      text,
+    primary key (spider_kind_id, novel_id, spider_novel_id)
+);
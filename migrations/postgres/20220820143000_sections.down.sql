@@ -0,0 +1,5 @@
+-- Add down migration script here
+drop table section_contents;
+drop index if exists idx_sections_novel_id_seq;
+drop index if exists idx_sections_spider_novel_seq;
+drop table sections;
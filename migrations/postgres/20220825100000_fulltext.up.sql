@@ -0,0 +1,28 @@
+-- Add up migration script here
+-- tokens为分词后以空格分隔的内容，中文按二元组切分，使用simple配置的tsvector匹配
+-- chars为其中的中文单字，用于搜索单字
+create table if not exists fts_novels
+(
+    novel_id bigint not null,
+    name     text   not null,
+    intro    text,
+    tokens   text   not null,
+    chars    text   not null,
+    primary key (novel_id)
+);
+
+create index if not exists idx_fts_novels_tokens on fts_novels using gin (to_tsvector('simple', tokens));
+create index if not exists idx_fts_novels_chars on fts_novels using gin (to_tsvector('simple', chars));
+
+create table if not exists fts_sections
+(
+    section_id bigint  not null,
+    novel_id   bigint,
+    seq        integer not null,
+    tokens     text    not null,
+    chars      text    not null,
+    primary key (section_id)
+);
+
+create index if not exists idx_fts_sections_tokens on fts_sections using gin (to_tsvector('simple', tokens));
+create index if not exists idx_fts_sections_chars on fts_sections using gin (to_tsvector('simple', chars));
@@ -0,0 +1,4 @@
+-- Add down migration script here
+drop table novel_watches;
+drop index if exists idx_follows_novel_subscriber;
+drop table follows;
@@ -0,0 +1,22 @@
+-- Add up migration script here
+create table if not exists follows
+(
+    id         bigint      not null,
+    novel_id   bigint      not null,
+    subscriber text        not null,
+    created_at timestamptz not null,
+    primary key (id)
+);
+
+drop index if exists idx_follows_novel_subscriber;
+create unique index idx_follows_novel_subscriber on follows (novel_id, subscriber);
+
+create table if not exists novel_watches
+(
+    novel_id          bigint      not null,
+    last_section_name text,
+    last_updated_at   timestamptz,
+    section_count     bigint      not null default 0,
+    checked_at        timestamptz not null,
+    primary key (novel_id)
+);
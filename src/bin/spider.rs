@@ -1 +1,580 @@
-fn main() {}
+use std::env;
+use std::io::{BufRead, IsTerminal};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use log::{error, info, warn};
+
+use spider_novel::common::db::connect;
+use spider_novel::common::events;
+use spider_novel::common::proxy;
+use spider_novel::common::snowid;
+use spider_novel::config::{self, Config};
+use spider_novel::ddxsku::DDSpider;
+use spider_novel::keeper::metrics;
+use spider_novel::keeper::metrics::Metrics;
+use spider_novel::keeper::notify::Debounced;
+use spider_novel::keeper::Keeper;
+#[cfg(feature = "wasm")]
+use spider_novel::plugin::WasmSpider;
+use spider_novel::webook::credential::{CredentialStore, Credentials};
+use spider_novel::webook::session::{self, Session};
+
+const USAGE: &str = "usage:
+    spider [--config PATH] <command>
+
+    spider run [--workers N]                   执行任务队列，配置文件修改后自动重新加载
+    spider config check                        校验配置文件
+    spider spiders                             查看已注册的爬虫
+    spider plan <spider_id>                    按爬虫能力为全部分类添加爬取任务
+    spider proxies                             检查代理并查看评分
+    spider cookies [spider_id]                 查看保存的cookie
+    spider cookies clear <spider_id>           清除爬虫保存的cookie
+    spider credentials list                    查看已保存登录信息的书源
+    spider credentials set <source>            从标准输入读取书源的登录信息，每行一个 key=value
+    spider credentials remove <source>         删除书源的登录信息
+    spider db migrate                          执行所有未执行的迁移
+    spider db status                           查看迁移状态
+    spider db rollback [owner] [--steps N]     回滚最近执行的迁移
+
+    未指定 --config 时依次使用 SPIDER_CONFIG 环境变量与 ./spider.toml";
+
+// 检查配置文件是否修改的间隔
+const CONFIG_WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+// 关闭时等待爬虫任务结束的时间
+const SHUTDOWN_DEADLINE: Duration = Duration::from_secs(30);
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenv::dotenv().ok();
+    tracing_subscriber::fmt::init();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut args: Vec<&str> = args.iter().map(|x| x.as_str()).collect();
+
+    let path = match args.as_slice() {
+        ["--config", path, ..] => {
+            let path = PathBuf::from(*path);
+            args.drain(..2);
+            Some(path)
+        }
+        _ => Config::discover(),
+    };
+    let config = match &path {
+        Some(x) => Config::load(x)?,
+        None => Config::default(),
+    };
+
+    match args.as_slice() {
+        ["db", rest @ ..] => db(&config, rest).await,
+        ["run", rest @ ..] => run(path, config, rest).await,
+        ["spiders"] => spiders(&config).await,
+        ["plan", id] => plan(&config, id).await,
+        ["proxies"] => proxies(&config).await,
+        ["cookies", rest @ ..] => cookies(&config, rest).await,
+        ["credentials", rest @ ..] => credentials(&config, rest),
+        ["config", "check"] => {
+            match &path {
+                Some(x) => println!("{} ok", x.display()),
+                None => println!("no config file, using defaults"),
+            }
+            Ok(())
+        }
+        _ => {
+            println!("{USAGE}");
+            Ok(())
+        }
+    }
+}
+
+struct App {
+    keeper: Keeper,
+    // 关闭时发送等待中的通知
+    notifier: Option<Arc<Debounced>>,
+    // 使用jieqi模板的爬虫，以配置中的名称区分
+    jieqi: Vec<(String, DDSpider)>,
+    // wasm插件爬虫的配置名称与爬虫id
+    wasm: Vec<(String, String)>,
+}
+
+// load为true时加载爬虫的分类，需要先执行迁移
+async fn app(config: &Config, load: bool) -> Result<App> {
+    let ids = config.id_gen()?;
+    // 未注入id服务的地方同样使用配置中的节点
+    snowid::install(ids.clone());
+
+    let db = Arc::new(connect(&config.database_url()?).await?);
+    let mut keeper = Keeper::with_policy(db.clone(), config.policy.to_policy()?);
+    keeper.set_id_gen(ids.clone());
+    config.content.install(&mut keeper, db.clone())?;
+
+    let mut jieqi: Vec<(String, DDSpider)> = Vec::new();
+    // 停用的爬虫同样注册，运行时可以通过配置重新启用
+    for (name, x) in config.jieqi_spiders() {
+        let mut spider = DDSpider::with_config(db.clone(), &x)
+            .with_context(|| format!("创建爬虫失败; spider={name}"))?
+            .with_id_gen(ids.clone());
+        if let Some((other, _)) = jieqi
+            .iter()
+            .find(|(_, s)| s.spider_id() == spider.spider_id())
+        {
+            bail!(
+                "爬虫id重复，请为其中一个设置 spiders.<name>.id; id={}, spiders={other},{name}",
+                spider.spider_id()
+            );
+        }
+        if load {
+            spider.load_sorts().await?;
+        }
+        keeper.add_spider_info(spider.info(), spider.clone())?;
+        keeper.set_enabled(spider.spider_id(), x.enabled)?;
+        if load && x.cookies {
+            keeper.enable_cookies(spider.spider_id()).await?;
+        }
+        jieqi.push((name, spider));
+    }
+
+    let wasm = add_wasm_spiders(&mut keeper, config, load).await?;
+
+    let notifier = config.notify.notifier()?;
+    if let Some(x) = &notifier {
+        keeper.set_notifier(x.clone());
+    }
+
+    let app = App {
+        keeper,
+        notifier,
+        jieqi,
+        wasm,
+    };
+    app.apply_scores(config);
+    if load {
+        app.add_sessions(config)?;
+    }
+
+    Ok(app)
+}
+
+#[cfg(feature = "wasm")]
+async fn add_wasm_spiders(
+    keeper: &mut Keeper,
+    config: &Config,
+    load: bool,
+) -> Result<Vec<(String, String)>> {
+    let mut v = Vec::new();
+    for (name, x) in config.wasm_spiders() {
+        let mut spider =
+            WasmSpider::with_config(&x).with_context(|| format!("创建爬虫失败; spider={name}"))?;
+        if load && x.enabled {
+            spider
+                .load_sorts()
+                .await
+                .with_context(|| format!("加载插件分类失败; spider={name}"))?;
+        }
+
+        let id = String::from(spider.spider_id());
+        keeper
+            .add_spider_info(spider.info(), spider)
+            .with_context(|| format!("注册爬虫失败; spider={name}"))?;
+        keeper.set_enabled(&id, x.enabled)?;
+        if load && x.cookies {
+            keeper.enable_cookies(&id).await?;
+        }
+        v.push((name, id));
+    }
+
+    Ok(v)
+}
+
+#[cfg(not(feature = "wasm"))]
+async fn add_wasm_spiders(
+    _: &mut Keeper,
+    config: &Config,
+    _: bool,
+) -> Result<Vec<(String, String)>> {
+    if !config.wasm_spiders().is_empty() {
+        bail!("使用wasm插件需要启用 wasm 特性");
+    }
+
+    Ok(Vec::new())
+}
+
+impl App {
+    // 配置名称与爬虫id
+    fn spider_ids(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.jieqi
+            .iter()
+            .map(|(name, x)| (name.as_str(), x.spider_id()))
+            .chain(
+                self.wasm
+                    .iter()
+                    .map(|(name, id)| (name.as_str(), id.as_str())),
+            )
+    }
+
+    // 为配置了登录的爬虫注册会话，登录信息从凭据文件读取
+    fn add_sessions(&self, config: &Config) -> Result<()> {
+        for x in self.keeper.registry() {
+            let configured = self
+                .spider_ids()
+                .any(|(name, id)| id == x.info.id && config.spider(name).login.is_some());
+            if x.info.capabilities.login_required && !configured {
+                warn!("爬虫需要登录但没有配置 login; spider_id={}", x.info.id);
+            }
+        }
+
+        let spiders: Vec<_> = self
+            .spider_ids()
+            .filter_map(|(name, id)| config.spider(name).login.map(|x| (name, id, x)))
+            .collect();
+        if spiders.is_empty() {
+            return Ok(());
+        }
+
+        let store = CredentialStore::open_env(&config.session.credentials)?;
+        for (name, id, x) in spiders {
+            let source = x
+                .to_source()
+                .with_context(|| format!("spiders.{name}.login 配置错误"))?;
+            let credentials = store.get(&x.source).cloned();
+            if credentials.is_none() {
+                warn!(
+                    "没有书源的登录信息，可以通过 spider credentials set 添加; spider={name}, source={}",
+                    x.source
+                );
+            }
+            session::register(Arc::new(Session::new(id, Arc::new(source), credentials)));
+        }
+
+        Ok(())
+    }
+
+    fn apply_scores(&self, config: &Config) {
+        for (name, id) in self.spider_ids() {
+            if let Some(score) = config.spider(name).score {
+                if let Err(e) = self.keeper.set_spider_score(id, score) {
+                    error!("设置爬虫评分失败; spider={name}, err={e}");
+                }
+            }
+        }
+    }
+
+    // 应用重新加载后的配置
+    fn reload(&self, config: &Config) {
+        match config.policy.to_policy() {
+            Ok(x) => self.keeper.set_policy(x),
+            Err(e) => error!("应用策略失败: {e:#}"),
+        }
+
+        for (name, spider) in &self.jieqi {
+            if let Err(e) = spider.apply(&config.spider(name)) {
+                error!("应用爬虫配置失败; spider={name}, err={e:#}");
+            }
+        }
+
+        for (name, id) in self.spider_ids() {
+            let enabled = config.spider(name).enabled;
+            if let Err(e) = self.keeper.set_enabled(id, enabled) {
+                error!("设置爬虫状态失败; spider={name}, err={e}");
+            }
+        }
+
+        self.apply_scores(config);
+    }
+}
+
+async fn run(path: Option<PathBuf>, config: Config, args: &[&str]) -> Result<()> {
+    let workers = match args {
+        ["--workers", n] => n
+            .parse()
+            .map_err(|_| anyhow!("--workers requires a number"))?,
+        [] => 1,
+        _ => {
+            println!("{USAGE}");
+            return Ok(());
+        }
+    };
+
+    let metrics_addr = config.metrics_addr()?;
+    let proxy_pool = config.proxy.to_pool()?;
+    app(&config, false).await?.keeper.migrate().await?;
+    let app = Arc::new(app(&config, true).await?);
+    let cancel = app.keeper.cancel_token();
+
+    let mut canaries = Vec::new();
+    for (name, id) in app.spider_ids() {
+        if let Some(x) = &config.spider(name).canary {
+            canaries.push((String::from(id), x.to_canary()?));
+        }
+    }
+
+    // 配置文件修改后重新加载策略与爬虫设置
+    if let Some(path) = path {
+        let mut rx = config::watch(path, config, CONFIG_WATCH_INTERVAL, cancel.clone());
+        let app = app.clone();
+        tokio::spawn(async move {
+            while rx.changed().await.is_ok() {
+                let config = rx.borrow().clone();
+                app.reload(&config);
+            }
+        });
+    }
+
+    // 爬取事件输出到日志
+    events::spawn_logger(cancel.clone());
+
+    // 监控指标
+    if let Some(addr) = metrics_addr {
+        let metrics = Arc::new(Metrics::new());
+        metrics.spawn(cancel.clone());
+
+        let app = app.clone();
+        let cancel = cancel.clone();
+        tokio::spawn(async move {
+            let render = move || {
+                let app = app.clone();
+                let metrics = metrics.clone();
+                async move { app.keeper.metrics_text(&metrics).await }
+            };
+            if let Err(e) = metrics::serve(addr, cancel, render).await {
+                error!("监控指标服务失败: {e:#}");
+            }
+        });
+    }
+
+    // 所有爬虫通过代理池访问网站，定时检查代理
+    if let Some(pool) = proxy_pool {
+        let pool = Arc::new(pool);
+        proxy::install(pool.clone());
+        let cancel = cancel.clone();
+        tokio::spawn(async move { pool.run_checks(cancel).await });
+    }
+
+    // 解析健康检查，未通过时暂停对应爬虫的任务
+    let runner = app.clone();
+    tokio::spawn(async move { runner.keeper.run_canaries(canaries).await });
+
+    let runner = app.clone();
+    let jobs = tokio::spawn(async move { runner.keeper.run_jobs(workers).await });
+
+    tokio::signal::ctrl_c().await?;
+    info!("正在关闭");
+    // 爬虫任务超时未结束时仍然继续关闭，发送等待中的通知
+    if let Err(e) = app.keeper.shutdown(SHUTDOWN_DEADLINE).await {
+        error!("{e:#}");
+    }
+    // 超时的爬虫任务可能让worker一直等待，同样只等待一个期限
+    let r = match tokio::time::timeout(SHUTDOWN_DEADLINE, jobs).await {
+        Ok(x) => x.map_err(anyhow::Error::from).and_then(|x| x),
+        Err(_) => Err(anyhow!(
+            "等待任务队列结束超时; deadline={SHUTDOWN_DEADLINE:?}"
+        )),
+    };
+    if let Some(x) = &app.notifier {
+        x.flush().await;
+    }
+
+    r
+}
+
+async fn spiders(config: &Config) -> Result<()> {
+    let keeper = app(config, false).await?.keeper;
+    for x in keeper.registry() {
+        let c = x.info.capabilities;
+        println!(
+            "{}  {}  {}  {}  {}  {}  [{}]",
+            if x.enabled { "enabled " } else { "disabled" },
+            x.info.id,
+            x.info.name,
+            x.info.version,
+            x.info.source.as_str(),
+            c.pagination.as_str(),
+            c.names().join(",")
+        );
+    }
+
+    Ok(())
+}
+
+async fn plan(config: &Config, id: &str) -> Result<()> {
+    app(config, false).await?.keeper.migrate().await?;
+    let keeper = app(config, true).await?.keeper;
+    let n = keeper.plan_sorts(id).await?;
+    println!("{n} jobs planned for {id}");
+
+    Ok(())
+}
+
+async fn proxies(config: &Config) -> Result<()> {
+    let pool = config
+        .proxy
+        .to_pool()?
+        .ok_or_else(|| anyhow!("没有设置代理"))?;
+    pool.check().await?;
+
+    for x in pool.status() {
+        let latency = x
+            .latency
+            .map_or(String::from("-"), |x| format!("{}ms", x.as_millis()));
+        println!(
+            "{}  {}  success={}  failure={}  latency={latency}  score={:.3}",
+            if x.quarantined {
+                "quarantined"
+            } else {
+                "available  "
+            },
+            x.url,
+            x.success,
+            x.failure,
+            x.score
+        );
+    }
+
+    Ok(())
+}
+
+async fn cookies(config: &Config, args: &[&str]) -> Result<()> {
+    let keeper = app(config, false).await?.keeper;
+
+    match args {
+        ["clear", id] => {
+            if keeper.clear_cookies(id).await? {
+                println!("cookies cleared for {id}");
+            } else {
+                println!("no cookies for {id}");
+            }
+        }
+        [] | [_] => {
+            for (id, cookies) in keeper.cookies().await? {
+                if args.first().is_some_and(|x| *x != id) {
+                    continue;
+                }
+                for x in cookies {
+                    let expires = x
+                        .expires
+                        .map_or(String::from("session"), |x| x.to_rfc3339());
+                    println!(
+                        "{id}  {}{}  {}={}  {expires}",
+                        x.domain, x.path, x.name, x.value
+                    );
+                }
+            }
+        }
+        _ => println!("{USAGE}"),
+    }
+
+    Ok(())
+}
+
+fn credentials(config: &Config, args: &[&str]) -> Result<()> {
+    let mut store = CredentialStore::open_env(&config.session.credentials)?;
+
+    match args {
+        ["list"] => {
+            // 只显示字段名，不输出密码
+            for x in store.sources() {
+                let keys: Vec<&str> = store
+                    .get(x)
+                    .into_iter()
+                    .flat_map(|c| c.keys().map(|k| k.as_str()))
+                    .collect();
+                println!("{x}  [{}]", keys.join(","));
+            }
+        }
+        ["set", source] => {
+            let x = read_credentials()?;
+            store.set(source, x)?;
+            println!("credentials saved for {source}");
+        }
+        ["remove", source] => {
+            if store.remove(source)? {
+                println!("credentials removed for {source}");
+            } else {
+                println!("no credentials for {source}");
+            }
+        }
+        _ => println!("{USAGE}"),
+    }
+
+    Ok(())
+}
+
+// 从标准输入读取登录信息，每行一个 key=value，空行或输入结束时停止
+// 不使用命令行参数，避免密码出现在shell历史与进程列表中
+fn read_credentials() -> Result<Credentials> {
+    let stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        eprintln!("每行输入一个 key=value，例如 username=a，输入空行结束");
+    }
+
+    let mut x = Credentials::new();
+    for (n, line) in stdin.lock().lines().enumerate() {
+        let line = line?;
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() {
+            break;
+        }
+        // 错误信息中不包含输入的内容
+        let (k, v) = line
+            .split_once('=')
+            .ok_or_else(|| anyhow!("登录信息格式错误，需要 key=value; line={}", n + 1))?;
+        x.insert(String::from(k.trim()), String::from(v));
+    }
+    if x.is_empty() {
+        bail!("没有输入登录信息");
+    }
+
+    Ok(x)
+}
+
+async fn db(config: &Config, args: &[&str]) -> Result<()> {
+    let keeper = app(config, false).await?.keeper;
+    let migrator = keeper.migrator();
+    let db = keeper.db();
+
+    match args {
+        ["migrate"] => {
+            let done = migrator.up(db).await?;
+            for (owner, id) in &done {
+                println!("applied  {owner}  {id}");
+            }
+            println!("{} migration(s) applied", done.len());
+        }
+        ["status"] => {
+            for x in migrator.status(db).await? {
+                match x.applied_at {
+                    Some(at) => println!("applied  {}  {}  {at}", x.owner, x.id),
+                    None => println!("pending  {}  {}", x.owner, x.id),
+                }
+            }
+        }
+        ["rollback", rest @ ..] => {
+            let mut owner = None;
+            let mut steps = 1;
+            let mut iter = rest.iter();
+            while let Some(x) = iter.next() {
+                match *x {
+                    "--steps" => {
+                        steps = iter
+                            .next()
+                            .and_then(|x| x.parse().ok())
+                            .ok_or_else(|| anyhow!("--steps requires a number"))?;
+                    }
+                    x => owner = Some(x),
+                }
+            }
+
+            let done = migrator.down(db, owner, steps).await?;
+            for (owner, id) in &done {
+                println!("rolled back  {owner}  {id}");
+            }
+            println!("{} migration(s) rolled back", done.len());
+        }
+        _ => println!("{USAGE}"),
+    }
+
+    Ok(())
+}
@@ -1,4 +1,12 @@
+pub mod challenge;
+pub mod cookiejar;
+pub mod date;
+pub mod db;
 pub mod doc;
+pub mod events;
 pub mod httputils;
+pub mod migrate;
+pub mod proxy;
 pub mod sender;
+pub mod shutdown;
 pub mod snowid;
@@ -0,0 +1,360 @@
+use std::ffi::CString;
+use std::os::raw::{c_int, c_void};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use libquickjs_sys as q;
+use reqwest::Url;
+use serde::Deserialize;
+
+use crate::common::doc::WrapDocument;
+
+// 解决验证后重新请求的最大次数，仍然是验证页面时放弃
+pub const MAX_ATTEMPTS: usize = 2;
+
+// 验证页面通常很小，超过该大小的页面不检查，503页面除外
+const MAX_CHALLENGE_BYTES: usize = 32 * 1024;
+
+// 正常状态码的页面中，除脚本外的文字不超过该数量时才可能是验证页面
+const MAX_CHALLENGE_TEXT: usize = 200;
+
+// 验证脚本可以执行的时间与使用的内存，超出时放弃验证
+pub(crate) const SCRIPT_TIMEOUT: Duration = Duration::from_secs(2);
+pub(crate) const SCRIPT_MEMORY: usize = 16 * 1024 * 1024;
+
+// meta refresh 等待时间的上限
+const MAX_REFRESH_DELAY: Duration = Duration::from_secs(5);
+
+// 需要人工处理的验证页面的特征
+const BLOCKED_MARKERS: [&str; 6] = [
+    "captcha",
+    "cf-chl",
+    "challenge-form",
+    "checking your browser",
+    "请输入验证码",
+    "人机验证",
+];
+
+// 脚本设置cookie后刷新或跳转页面的特征
+const RELOAD_MARKERS: [&str; 5] = [
+    "location.reload",
+    "location.href",
+    "location.replace",
+    "location.assign",
+    "location=",
+];
+
+// 模拟执行验证脚本所需的浏览器环境，脚本设置的cookie保存在 __cookies 中
+const PRELUDE: &str = r#"
+var __cookies = [];
+var location = {
+    href: __url,
+    reload: function() {},
+    replace: function(u) { this.href = u; },
+    assign: function(u) { this.href = u; }
+};
+var document = { location: location, referrer: "" };
+Object.defineProperty(document, "cookie", {
+    get: function() {
+        return __cookies.map(function(c) { return c.split(";")[0]; }).join("; ");
+    },
+    set: function(v) { __cookies.push(String(v)); }
+});
+var navigator = { userAgent: __ua, language: "zh-CN", webdriver: false };
+var window = this;
+window.location = location;
+window.document = document;
+window.navigator = navigator;
+function setTimeout(f) { if (typeof f === "function") { f(); } else { eval(f); } return 0; }
+"#;
+
+const USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/98.0.4758.80 Safari/537.36 Edg/98.0.1108.43";
+
+// 反爬虫验证页面
+#[derive(Debug, Clone, PartialEq)]
+pub enum Challenge {
+    // 通过 meta refresh 跳转，跳转前可能由脚本设置cookie
+    MetaRefresh {
+        url: Option<String>,
+        delay: u64,
+        script: Option<String>,
+    },
+    // 内联脚本通过 document.cookie 设置cookie后刷新页面
+    Script {
+        script: String,
+    },
+    // 验证码等无法自动处理的验证
+    Blocked {
+        reason: String,
+    },
+}
+
+impl Challenge {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Challenge::MetaRefresh { .. } => "meta_refresh",
+            Challenge::Script { .. } => "script",
+            Challenge::Blocked { .. } => "blocked",
+        }
+    }
+}
+
+// 解决验证得到的cookie与重新请求的地址
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Solution {
+    // 与Set-Cookie格式相同
+    pub cookies: Vec<String>,
+    // 未设置时重新请求原来的地址
+    pub redirect: Option<String>,
+    pub delay: Duration,
+}
+
+#[derive(Deserialize)]
+struct ScriptResult {
+    cookies: Vec<String>,
+    href: String,
+}
+
+// 检查响应是否为验证页面
+// 503、403与429的页面按验证页面的特征检查，其他状态码的页面只有没有内容、
+// 并且由脚本设置cookie后刷新时才是验证页面
+pub fn detect(status: u16, body: &str) -> Option<Challenge> {
+    if body.len() > MAX_CHALLENGE_BYTES && status != 503 {
+        return None;
+    }
+
+    let doc = WrapDocument::parse(body);
+    let scripts: Vec<String> = doc
+        .select("script")
+        .iter()
+        .filter(|x| x.attr("src").is_none())
+        .filter_map(|x| x.text())
+        .filter(|x| x.contains("document.cookie"))
+        .collect();
+    let script = (!scripts.is_empty()).then(|| scripts.join("\n;\n"));
+
+    let refresh = doc
+        .select("meta")
+        .iter()
+        .filter(|x| {
+            x.attr("http-equiv")
+                .is_some_and(|x| x.eq_ignore_ascii_case("refresh"))
+        })
+        .find_map(|x| x.attr("content"));
+
+    if !matches!(status, 403 | 429 | 503) {
+        let reload = refresh.is_some()
+            || script.as_deref().is_some_and(|x| {
+                let x: String = x.chars().filter(|c| !c.is_whitespace()).collect();
+                RELOAD_MARKERS.iter().any(|m| x.contains(m))
+            });
+        if script.is_none() || !reload || visible_text(&doc) > MAX_CHALLENGE_TEXT {
+            return None;
+        }
+    }
+
+    if let Some(x) = refresh {
+        let (delay, url) = parse_refresh(&x);
+        return Some(Challenge::MetaRefresh { url, delay, script });
+    }
+    if let Some(script) = script {
+        return Some(Challenge::Script { script });
+    }
+
+    let lower = body.to_lowercase();
+    if let Some(x) = BLOCKED_MARKERS.iter().find(|x| lower.contains(*x)) {
+        return Some(Challenge::Blocked {
+            reason: format!("status={status}, marker={x}"),
+        });
+    }
+
+    None
+}
+
+// 页面中除脚本与样式之外的文字数量，不计空白
+fn visible_text(doc: &WrapDocument) -> usize {
+    let count = |x: String| x.chars().filter(|c| !c.is_whitespace()).count();
+    let all = doc.select("body").text().map_or(0, count);
+    let hidden: usize = doc
+        .select("body script, body style, body noscript")
+        .iter()
+        .filter_map(|x| x.text())
+        .map(count)
+        .sum();
+
+    all.saturating_sub(hidden)
+}
+
+// content的格式为 "5; url=/next"
+fn parse_refresh(content: &str) -> (u64, Option<String>) {
+    let (delay, rest) = content.split_once(';').unwrap_or((content, ""));
+    let delay = delay.trim().parse().unwrap_or(0);
+
+    let rest = rest.trim();
+    let url = match rest.split_once('=') {
+        Some((k, v)) if k.trim().eq_ignore_ascii_case("url") => v.trim(),
+        _ => rest,
+    };
+    let url = url.trim_matches(|c| c == '\'' || c == '"');
+
+    (delay, (!url.is_empty()).then(|| String::from(url)))
+}
+
+// 执行验证脚本，无法解决时返回错误
+pub fn solve(challenge: &Challenge, url: &Url) -> Result<Solution> {
+    let (script, refresh, delay) = match challenge {
+        Challenge::MetaRefresh { url, delay, script } => {
+            (script.as_deref(), url.as_deref(), *delay)
+        }
+        Challenge::Script { script } => (Some(script.as_str()), None, 0),
+        Challenge::Blocked { reason } => bail!("需要人工处理的验证; {reason}"),
+    };
+
+    let mut solution = Solution {
+        delay: Duration::from_secs(delay).min(MAX_REFRESH_DELAY),
+        ..Solution::default()
+    };
+    if let Some(x) = refresh {
+        solution.redirect = Some(String::from(url.join(x)?.as_str()));
+    }
+
+    if let Some(script) = script {
+        let x = run(script, url.as_str())?;
+        if x.cookies.is_empty() {
+            bail!("验证脚本没有设置cookie");
+        }
+        solution.cookies = x.cookies;
+        // 脚本修改了页面地址时跳转到新地址
+        if x.href != url.as_str() {
+            solution.redirect = Some(String::from(url.join(&x.href)?.as_str()));
+        }
+    }
+
+    Ok(solution)
+}
+
+// 脚本在限制了执行时间与内存的环境中执行，超出限制时返回错误
+fn run(script: &str, url: &str) -> Result<ScriptResult> {
+    let js = Sandbox::new(SCRIPT_TIMEOUT, SCRIPT_MEMORY)?;
+    js.exec(&format!(
+        "var __url = {}; var __ua = {};",
+        serde_json::to_string(url)?,
+        serde_json::to_string(USER_AGENT)?
+    ))?;
+    js.exec(PRELUDE)?;
+    js.exec(script).context("执行验证脚本失败")?;
+
+    // 脚本可能直接给 location 赋值
+    let x = js.eval_string(
+        r#"JSON.stringify({
+            cookies: __cookies,
+            href: typeof location === "string" ? location : String(location.href)
+        })"#,
+    )?;
+    serde_json::from_str(&x).context("验证脚本的结果格式错误")
+}
+
+// quick_js::Context 不能中断执行中的脚本，直接使用QuickJS设置中断回调与内存上限
+// 执行网站提供的脚本时都应该使用，脚本执行会阻塞线程，在异步任务中需要放到 spawn_blocking 中
+pub(crate) struct Sandbox {
+    runtime: *mut q::JSRuntime,
+    context: *mut q::JSContext,
+    // 中断回调读取的截止时间，在运行环境释放之前不能移动
+    deadline: Box<Instant>,
+    timeout: Duration,
+}
+
+// 超过截止时间时返回非0，QuickJS中断执行并抛出异常
+unsafe extern "C" fn interrupt(_: *mut q::JSRuntime, opaque: *mut c_void) -> c_int {
+    let deadline = &*(opaque as *const Instant);
+    (Instant::now() >= *deadline) as c_int
+}
+
+impl Sandbox {
+    // timeout 为从创建开始计算的所有脚本的执行时间
+    pub(crate) fn new(timeout: Duration, memory: usize) -> Result<Self> {
+        let deadline = Box::new(Instant::now() + timeout);
+        unsafe {
+            let runtime = q::JS_NewRuntime();
+            if runtime.is_null() {
+                bail!("创建js运行环境失败");
+            }
+            q::JS_SetMemoryLimit(runtime, memory as _);
+            let opaque = &*deadline as *const Instant as *mut c_void;
+            q::JS_SetInterruptHandler(runtime, Some(interrupt), opaque);
+
+            let context = q::JS_NewContext(runtime);
+            if context.is_null() {
+                q::JS_FreeRuntime(runtime);
+                bail!("创建js运行环境失败");
+            }
+
+            Ok(Self {
+                runtime,
+                context,
+                deadline,
+                timeout,
+            })
+        }
+    }
+
+    pub(crate) fn exec(&self, code: &str) -> Result<()> {
+        let v = self.eval(code)?;
+        unsafe { q::JS_FreeValue(self.context, v) };
+        Ok(())
+    }
+
+    // 执行代码，返回结果转换成的字符串
+    pub(crate) fn eval_string(&self, code: &str) -> Result<String> {
+        let v = self.eval(code)?;
+        let x = self.to_string(v);
+        unsafe { q::JS_FreeValue(self.context, v) };
+        x.ok_or_else(|| anyhow!("js的结果不能转换为字符串"))
+    }
+
+    fn eval(&self, code: &str) -> Result<q::JSValue> {
+        let input = CString::new(code).context("js中不能包含\\0")?;
+        let v = unsafe {
+            q::JS_Eval(
+                self.context,
+                input.as_ptr(),
+                code.len() as _,
+                c"challenge.js".as_ptr(),
+                q::JS_EVAL_TYPE_GLOBAL as _,
+            )
+        };
+        if !unsafe { q::JS_IsException(v) } {
+            return Ok(v);
+        }
+
+        let e = unsafe { q::JS_GetException(self.context) };
+        let message = self.to_string(e);
+        unsafe { q::JS_FreeValue(self.context, e) };
+        if Instant::now() >= *self.deadline {
+            bail!("执行超时; timeout={}ms", self.timeout.as_millis());
+        }
+        bail!("{}", message.as_deref().unwrap_or("unknown error"))
+    }
+
+    fn to_string(&self, v: q::JSValue) -> Option<String> {
+        let mut len = 0;
+        let p = unsafe { q::JS_ToCStringLen2(self.context, &mut len, v, 0) };
+        if p.is_null() {
+            return None;
+        }
+
+        let bytes = unsafe { std::slice::from_raw_parts(p as *const u8, len as usize) };
+        let x = String::from_utf8_lossy(bytes).into_owned();
+        unsafe { q::JS_FreeCString(self.context, p) };
+        Some(x)
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        unsafe {
+            q::JS_FreeContext(self.context);
+            q::JS_FreeRuntime(self.runtime);
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+use chrono::{DateTime, Duration, Utc};
+use reqwest::header::HeaderValue;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use static_init::dynamic;
+
+// 以爬虫id区分的cookie，同一个爬虫的并发任务共享
+#[dynamic]
+static JARS: RwLock<HashMap<String, Arc<CookieJar>>> = RwLock::new(HashMap::new());
+
+// Duration::seconds 可以表示的最大秒数
+const MAX_AGE: i64 = i64::MAX / 1000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    pub domain: String,
+    // 没有Domain属性时只发送给设置cookie的主机
+    pub host_only: bool,
+    pub path: String,
+    pub secure: bool,
+    // 没有过期时间的cookie在运行期间与保存后一直有效
+    pub expires: Option<DateTime<Utc>>,
+}
+
+impl Cookie {
+    // 解析Set-Cookie，url为设置cookie的页面
+    pub fn parse(header: &str, url: &Url) -> Option<Self> {
+        let mut parts = header.split(';');
+        let (name, value) = parts.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+
+        let host = url.host_str()?.to_lowercase();
+        let mut x = Cookie {
+            name: String::from(name),
+            value: String::from(value.trim().trim_matches('"')),
+            domain: host.clone(),
+            host_only: true,
+            path: default_path(url),
+            secure: false,
+            expires: None,
+        };
+
+        let mut max_age = None;
+        for attr in parts {
+            let (k, v) = attr.split_once('=').unwrap_or((attr, ""));
+            let v = v.trim();
+            match k.trim().to_lowercase().as_str() {
+                "domain" if !v.is_empty() => {
+                    let domain = v.trim_start_matches('.').to_lowercase();
+                    // 不能为其他网站设置cookie
+                    if host != domain && !host.ends_with(&format!(".{domain}")) {
+                        return None;
+                    }
+                    x.domain = domain;
+                    x.host_only = false;
+                }
+                "path" if v.starts_with('/') => x.path = String::from(v),
+                "secure" => x.secure = true,
+                "expires" => {
+                    x.expires = DateTime::parse_from_rfc2822(&v.replace('-', " "))
+                        .ok()
+                        .map(|x| x.with_timezone(&Utc))
+                }
+                "max-age" => max_age = v.parse::<i64>().ok(),
+                _ => {}
+            }
+        }
+        // Max-Age优先于Expires，小于等于0时立即过期，超出时间范围时视为没有过期时间
+        if let Some(n) = max_age {
+            x.expires = if n <= 0 {
+                Some(Utc::now())
+            } else {
+                Utc::now().checked_add_signed(Duration::seconds(n.min(MAX_AGE)))
+            };
+        }
+
+        Some(x)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        matches!(self.expires, Some(x) if x <= Utc::now())
+    }
+
+    fn matches(&self, url: &Url) -> bool {
+        let host = match url.host_str() {
+            Some(x) => x.to_lowercase(),
+            None => return false,
+        };
+        let domain = if self.host_only {
+            host == self.domain
+        } else {
+            host == self.domain || host.ends_with(&format!(".{}", self.domain))
+        };
+        let path = url.path();
+        let path = path == self.path
+            || (path.starts_with(&self.path)
+                && (self.path.ends_with('/') || path[self.path.len()..].starts_with('/')));
+
+        domain && path && (!self.secure || url.scheme() == "https") && !self.is_expired()
+    }
+
+    fn same(&self, other: &Cookie) -> bool {
+        self.name == other.name && self.domain == other.domain && self.path == other.path
+    }
+}
+
+// 页面地址的目录作为cookie的默认路径
+fn default_path(url: &Url) -> String {
+    let path = url.path();
+    match path.rfind('/') {
+        Some(0) | None => String::from("/"),
+        Some(n) => String::from(&path[..n]),
+    }
+}
+
+// 一个书源的cookie，保存页面设置的cookie并在之后的请求中发送
+#[derive(Default)]
+pub struct CookieJar {
+    cookies: Mutex<Vec<Cookie>>,
+    // 上次保存后是否有修改
+    dirty: AtomicBool,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 从保存的cookie恢复，丢弃已经过期的
+    pub fn with_cookies(cookies: Vec<Cookie>) -> Self {
+        let cookies = cookies.into_iter().filter(|x| !x.is_expired()).collect();
+        Self {
+            cookies: Mutex::new(cookies),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Cookie>> {
+        self.cookies.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // 保存响应设置的cookie，已过期的cookie会删除同名的cookie
+    pub fn store<'a>(&self, url: &Url, headers: impl IntoIterator<Item = &'a HeaderValue>) {
+        let mut cookies = self.lock();
+        for x in headers {
+            let x = match x.to_str().ok().and_then(|x| Cookie::parse(x, url)) {
+                Some(x) => x,
+                None => continue,
+            };
+
+            cookies.retain(|c| !c.same(&x));
+            if !x.is_expired() {
+                cookies.push(x);
+            }
+            self.dirty.store(true, Ordering::SeqCst);
+        }
+    }
+
+    pub fn insert(&self, cookie: Cookie) {
+        let mut cookies = self.lock();
+        cookies.retain(|c| !c.same(&cookie));
+        cookies.push(cookie);
+        self.dirty.store(true, Ordering::SeqCst);
+    }
+
+    // 请求url时发送的Cookie请求头
+    pub fn header(&self, url: &Url) -> Option<String> {
+        let cookies = self.lock();
+        let mut v: Vec<&Cookie> = cookies.iter().filter(|x| x.matches(url)).collect();
+        if v.is_empty() {
+            return None;
+        }
+        // 路径更长的cookie在前
+        v.sort_by_key(|c| std::cmp::Reverse(c.path.len()));
+
+        let v: Vec<String> = v
+            .iter()
+            .map(|x| format!("{}={}", x.name, x.value))
+            .collect();
+        Some(v.join("; "))
+    }
+
+    // 未过期的全部cookie
+    pub fn cookies(&self) -> Vec<Cookie> {
+        self.lock()
+            .iter()
+            .filter(|x| !x.is_expired())
+            .cloned()
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        self.lock().clear();
+        self.dirty.store(true, Ordering::SeqCst);
+    }
+
+    // 返回上次调用后是否有修改
+    pub fn take_dirty(&self) -> bool {
+        self.dirty.swap(false, Ordering::SeqCst)
+    }
+
+    pub fn mark_dirty(&self) {
+        self.dirty.store(true, Ordering::SeqCst);
+    }
+}
+
+// 为爬虫启用cookie，之后通过httputils发送的请求都使用该jar
+pub fn register(spider_id: &str, jar: Arc<CookieJar>) {
+    JARS.write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(String::from(spider_id), jar);
+}
+
+pub fn unregister(spider_id: &str) -> Option<Arc<CookieJar>> {
+    JARS.write()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(spider_id)
+}
+
+pub fn jar(spider_id: &str) -> Option<Arc<CookieJar>> {
+    JARS.read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(spider_id)
+        .cloned()
+}
+
+// 爬虫没有启用cookie时注册一个新的
+pub fn jar_or_register(spider_id: &str) -> Arc<CookieJar> {
+    JARS.write()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(String::from(spider_id))
+        .or_default()
+        .clone()
+}
+
+// 已启用cookie的爬虫id
+pub fn spider_ids() -> Vec<String> {
+    let mut v: Vec<String> = JARS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .keys()
+        .cloned()
+        .collect();
+    v.sort();
+    v
+}
@@ -0,0 +1,201 @@
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+// 解析结果的精度，用于在多个来源的时间中选择更精确的一个
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Precision {
+    Day,
+    Hour,
+    Minute,
+    Second,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParsedDate {
+    pub at: DateTime<Utc>,
+    pub precision: Precision,
+}
+
+// 中文网站常见的时间格式解析
+// 支持 2022-07-08、22-07-08、07-08 12:30、今天 12:30、昨天、3小时前、2022年7月8日 与Unix时间戳
+// 没有时区信息的时间按网站时区解释
+#[derive(Debug, Clone, Copy)]
+pub struct DateParser {
+    tz: FixedOffset,
+    // 固定的当前时间，测试使用
+    now: Option<DateTime<Utc>>,
+}
+
+impl Default for DateParser {
+    // 国内网站默认东八区
+    fn default() -> Self {
+        Self::new(FixedOffset::east(8 * 3600))
+    }
+}
+
+impl DateParser {
+    pub fn new(tz: FixedOffset) -> Self {
+        Self { tz, now: None }
+    }
+
+    // 固定当前时间，相对时间与省略年份的时间基于该时间计算
+    pub fn with_now(mut self, now: DateTime<Utc>) -> Self {
+        self.now = Some(now);
+        self
+    }
+
+    pub fn timezone(&self) -> FixedOffset {
+        self.tz
+    }
+
+    pub fn parse(&self, x: &str) -> Option<ParsedDate> {
+        let now = self.now.unwrap_or_else(Utc::now);
+
+        // 去掉 "更新时间：" 之类的前缀
+        let x = x.rsplit('：').next().unwrap_or(x).trim();
+        if x.is_empty() {
+            return None;
+        }
+
+        if x == "刚刚" {
+            return Some(ParsedDate {
+                at: now,
+                precision: Precision::Second,
+            });
+        }
+
+        if let Some(x) = x.strip_suffix('前') {
+            return relative(x.trim(), now);
+        }
+
+        if x.chars().all(|c| c.is_ascii_digit()) {
+            return timestamp(x);
+        }
+
+        let today = now.with_timezone(&self.tz).date().naive_local();
+        for (prefix, days) in [("今天", 0), ("昨天", 1), ("前天", 2)] {
+            if let Some(rest) = x.strip_prefix(prefix) {
+                let date = today - Duration::days(days);
+                return self.local(date, rest);
+            }
+        }
+
+        // 日期与时间以空白、T 或 日 分隔
+        let (date, time) = match x.find(|c: char| c.is_whitespace() || c == 'T' || c == '日') {
+            Some(idx) => {
+                let sep = x[idx..].chars().next().unwrap();
+                (&x[..idx], &x[idx + sep.len_utf8()..])
+            }
+            None => (x, ""),
+        };
+
+        let nums = numbers(date);
+        let date = match nums.as_slice() {
+            [y, m, d] => {
+                let y = if *y < 100 { 2000 + *y } else { *y };
+                NaiveDate::from_ymd_opt(y as i32, *m, *d)?
+            }
+            // 省略年份时使用当前年份，结果在未来时使用上一年
+            [m, d] => {
+                let date = NaiveDate::from_ymd_opt(today.year(), *m, *d)?;
+                if date > today + Duration::days(1) {
+                    NaiveDate::from_ymd_opt(today.year() - 1, *m, *d)?
+                } else {
+                    date
+                }
+            }
+            _ => return None,
+        };
+
+        self.local(date, time)
+    }
+
+    // date为网站时区的日期，time为可选的时分秒
+    fn local(&self, date: NaiveDate, time: &str) -> Option<ParsedDate> {
+        let nums = numbers(time);
+        let (t, precision) = match nums.as_slice() {
+            [] => (date.and_hms_opt(0, 0, 0)?, Precision::Day),
+            [h] => (date.and_hms_opt(*h, 0, 0)?, Precision::Hour),
+            [h, m] => (date.and_hms_opt(*h, *m, 0)?, Precision::Minute),
+            [h, m, s] => (date.and_hms_opt(*h, *m, *s)?, Precision::Second),
+            _ => return None,
+        };
+
+        Some(ParsedDate {
+            at: self.to_utc(&t)?,
+            precision,
+        })
+    }
+
+    pub fn to_utc(&self, t: &NaiveDateTime) -> Option<DateTime<Utc>> {
+        self.tz
+            .from_local_datetime(t)
+            .single()
+            .map(|x| x.with_timezone(&Utc))
+    }
+}
+
+// 字符串中所有的数字
+fn numbers(x: &str) -> Vec<u32> {
+    x.split(|c: char| !c.is_ascii_digit())
+        .filter(|x| !x.is_empty())
+        .filter_map(|x| x.parse().ok())
+        .collect()
+}
+
+// 3小时前、5分钟前、2天前
+fn relative(x: &str, now: DateTime<Utc>) -> Option<ParsedDate> {
+    let idx = x.find(|c: char| !c.is_ascii_digit())?;
+    let n: i64 = x[..idx].parse().ok()?;
+
+    // 每个单位的秒数
+    let (unit, precision) = match x[idx..].trim() {
+        "秒" | "秒钟" => (1, Precision::Second),
+        "分" | "分钟" => (60, Precision::Minute),
+        "小时" | "个小时" => (60 * 60, Precision::Hour),
+        "天" => (24 * 60 * 60, Precision::Day),
+        "周" | "星期" => (7 * 24 * 60 * 60, Precision::Day),
+        _ => return None,
+    };
+
+    // 数字过大时溢出，视为无法解析
+    let ms = n.checked_mul(unit)?.checked_mul(1000)?;
+
+    Some(ParsedDate {
+        at: now.checked_sub_signed(Duration::milliseconds(ms))?,
+        precision,
+    })
+}
+
+// 10位为秒，13位为毫秒
+fn timestamp(x: &str) -> Option<ParsedDate> {
+    let n: i64 = x.parse().ok()?;
+    let at = match x.len() {
+        10 => Utc.timestamp_opt(n, 0).single()?,
+        13 => Utc.timestamp_millis_opt(n).single()?,
+        _ => return None,
+    };
+
+    Some(ParsedDate {
+        at,
+        precision: Precision::Second,
+    })
+}
+
+// 解析 +08:00 或 +0800 格式的时区
+pub fn parse_offset(x: &str) -> Option<FixedOffset> {
+    let x = x.trim();
+    let (sign, rest) = match x.chars().next()? {
+        '+' => (1, &x[1..]),
+        '-' => (-1, &x[1..]),
+        _ => return None,
+    };
+    let rest = rest.replace(':', "");
+    // 非ASCII字符按字节切分会panic
+    if rest.len() != 4 || !rest.is_ascii() {
+        return None;
+    }
+
+    let h: i32 = rest[..2].parse().ok()?;
+    let m: i32 = rest[2..].parse().ok()?;
+    FixedOffset::east_opt(sign * (h * 3600 + m * 60))
+}
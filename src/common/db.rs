@@ -0,0 +1,73 @@
+use anyhow::{anyhow, Result};
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{
+    ActiveModelTrait, ConnectionTrait, Database, DbBackend, DbConn, DbErr, EntityTrait, Insert,
+    QueryTrait,
+};
+use serde::Deserialize;
+
+// 连接数据库，根据url的协议选择后端，对应的cargo特性没有开启时返回错误
+pub async fn connect(url: &str) -> Result<DbConn> {
+    let scheme = url.split(':').next().unwrap_or_default();
+    let (feature, enabled) = match scheme {
+        "sqlite" => ("sqlite", cfg!(feature = "sqlite")),
+        "postgres" | "postgresql" => ("postgres", cfg!(feature = "postgres")),
+        "mysql" => ("mysql", cfg!(feature = "mysql")),
+        _ => return Err(anyhow!("不支持的数据库; url={url}")),
+    };
+    if !enabled {
+        return Err(anyhow!(
+            "没有开启{scheme}对应的cargo特性，请使用 --features {feature} 重新编译"
+        ));
+    }
+
+    Ok(Database::connect(url).await?)
+}
+
+// 原生sql中第idx个参数的占位符，从1开始
+pub fn placeholder(backend: DbBackend, idx: usize) -> String {
+    match backend {
+        DbBackend::Postgres => format!("${idx}"),
+        _ => String::from("?"),
+    }
+}
+
+// 原生sql中n个以逗号分隔的参数占位符
+pub fn placeholders(backend: DbBackend, n: usize) -> String {
+    (1..=n)
+        .map(|x| placeholder(backend, x))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// 插入一行，与唯一索引冲突时保留已有的行
+// mysql不支持 do nothing，使用不改变数据的更新代替
+pub async fn insert_or_ignore<A, C>(
+    db: &C,
+    x: A,
+    unique: &[<A::Entity as EntityTrait>::Column],
+) -> Result<(), DbErr>
+where
+    A: ActiveModelTrait,
+    C: ConnectionTrait,
+{
+    let backend = db.get_database_backend();
+    let mut conflict = OnConflict::columns(unique.iter().copied());
+    match backend {
+        DbBackend::MySql => conflict.update_columns(unique.iter().copied()),
+        _ => conflict.do_nothing(),
+    };
+
+    let mut stmt = Insert::one(x).into_query();
+    stmt.on_conflict(conflict);
+    let _ = db.execute(backend.build(&stmt)).await?;
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    // 未设置时使用 DATABASE_URL 环境变量
+    pub url: Option<String>,
+}
@@ -41,7 +41,7 @@ impl WrapDocument {
 impl<'a> WrapSelection<'a> {
     pub fn text(&self) -> Option<String> {
         let x = self.inner.text();
-        if x.len() > 0 {
+        if !x.is_empty() {
             Some(x.to_string())
         } else {
             None
@@ -0,0 +1,198 @@
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use serde::{Serialize, Serializer};
+use static_init::dynamic;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+
+use crate::common::shutdown::CancellationToken;
+use crate::spider::CrawlError;
+
+// 订阅者处理不及时时，超过该数量的旧事件会被丢弃
+const DEFAULT_CAPACITY: usize = 4096;
+
+// 爬虫与keeper共用的事件总线
+#[dynamic]
+static BUS: EventBus = EventBus::new(DEFAULT_CAPACITY);
+
+// 爬取过程中的事件，供日志、监控指标与接口服务订阅
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type")]
+pub enum Event {
+    PageFetched(PageFetched),
+    NovelParsed(NovelParsed),
+    SectionStored(SectionStored),
+    CrawlFailed(CrawlFailed),
+    JobFinished(JobFinished),
+}
+
+// 获取到一个页面，包括非2xx的响应
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PageFetched {
+    pub spider_id: String,
+    pub url: String,
+    pub status: u16,
+    // 响应体字节数
+    pub bytes: u64,
+    #[serde(serialize_with = "millis")]
+    pub elapsed: Duration,
+}
+
+// 从列表页解析出一本小说，耗时包括获取详情页
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NovelParsed {
+    pub spider_id: String,
+    // 爬虫中的小说id
+    pub novel_id: i64,
+    pub name: String,
+    pub bytes: u64,
+    #[serde(serialize_with = "millis")]
+    pub elapsed: Duration,
+}
+
+// 章节已保存
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SectionStored {
+    pub spider_id: String,
+    // 爬虫中的小说id
+    pub novel_id: i64,
+    pub seq: u32,
+    pub bytes: u64,
+    #[serde(serialize_with = "millis")]
+    pub elapsed: Duration,
+}
+
+// 爬取失败，kind为 CrawlError::kind
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CrawlFailed {
+    pub spider_id: String,
+    pub url: Option<String>,
+    pub kind: String,
+    pub error: String,
+    #[serde(serialize_with = "millis")]
+    pub elapsed: Duration,
+}
+
+// 任务执行结束，outcome为 finished、cancelled 或 failed
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JobFinished {
+    pub job_id: i64,
+    pub kind: String,
+    pub spider_id: String,
+    pub outcome: String,
+    // 第几次执行，大于1时为重试
+    pub attempt: i32,
+    // 处理的小说或章节数量
+    pub items: u64,
+    pub bytes: u64,
+    #[serde(serialize_with = "millis")]
+    pub elapsed: Duration,
+}
+
+impl Event {
+    pub fn spider_id(&self) -> &str {
+        match self {
+            Event::PageFetched(x) => &x.spider_id,
+            Event::NovelParsed(x) => &x.spider_id,
+            Event::SectionStored(x) => &x.spider_id,
+            Event::CrawlFailed(x) => &x.spider_id,
+            Event::JobFinished(x) => &x.spider_id,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct EventBus {
+    tx: broadcast::Sender<Event>,
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity);
+        Self { tx }
+    }
+
+    // 没有订阅者时直接丢弃
+    pub fn publish(&self, event: Event) {
+        let _ = self.tx.send(event);
+    }
+
+    // 只能收到订阅之后发布的事件
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.tx.subscribe()
+    }
+}
+
+pub fn publish(event: Event) {
+    BUS.publish(event)
+}
+
+pub fn subscribe() -> broadcast::Receiver<Event> {
+    BUS.subscribe()
+}
+
+// 发布爬取失败事件
+pub fn crawl_failed(spider_id: &str, url: Option<&str>, e: &CrawlError, elapsed: Duration) {
+    publish(Event::CrawlFailed(CrawlFailed {
+        spider_id: String::from(spider_id),
+        url: url.map(String::from),
+        kind: String::from(e.kind()),
+        error: e.to_string(),
+        elapsed,
+    }))
+}
+
+// 将事件输出到日志，页面与章节级别的事件使用debug级别
+pub fn spawn_logger(cancel: CancellationToken) -> JoinHandle<()> {
+    let mut rx = subscribe();
+
+    tokio::spawn(async move {
+        loop {
+            let event = tokio::select! {
+                x = rx.recv() => x,
+                _ = cancel.cancelled() => return,
+            };
+
+            match event {
+                Ok(x) => log_event(&x),
+                Err(RecvError::Lagged(n)) => warn!("日志处理不及时，丢弃了 {n} 个事件"),
+                Err(RecvError::Closed) => return,
+            }
+        }
+    })
+}
+
+fn log_event(event: &Event) {
+    match event {
+        Event::PageFetched(x) => debug!(
+            "获取页面; spider_id={}, url={}, status={}, bytes={}, elapsed={:?}",
+            x.spider_id, x.url, x.status, x.bytes, x.elapsed
+        ),
+        Event::NovelParsed(x) => debug!(
+            "解析小说; spider_id={}, novel_id={}, name={}, elapsed={:?}",
+            x.spider_id, x.novel_id, x.name, x.elapsed
+        ),
+        Event::SectionStored(x) => debug!(
+            "保存章节; spider_id={}, novel_id={}, seq={}, bytes={}, elapsed={:?}",
+            x.spider_id, x.novel_id, x.seq, x.bytes, x.elapsed
+        ),
+        Event::CrawlFailed(x) => warn!(
+            "爬取失败; spider_id={}, url={}, kind={}, err={}",
+            x.spider_id,
+            x.url.as_deref().unwrap_or("-"),
+            x.kind,
+            x.error
+        ),
+        Event::JobFinished(x) => info!(
+            "任务结束; id={}, kind={}, spider_id={}, outcome={}, items={}, bytes={}, elapsed={:?}",
+            x.job_id, x.kind, x.spider_id, x.outcome, x.items, x.bytes, x.elapsed
+        ),
+    }
+}
+
+// 耗时序列化为毫秒
+fn millis<S: Serializer>(d: &Duration, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_u64(d.as_millis() as u64)
+}
@@ -1,16 +1,438 @@
-use reqwest::header::HeaderValue;
-use reqwest::{header, Client};
+use std::borrow::Cow;
+use std::future::Future;
+use std::time::Instant;
+
+use log::{info, warn};
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::redirect::Policy;
+use reqwest::{header, Client, Method, Request, RequestBuilder, Response, StatusCode};
 use static_init::dynamic;
+use thiserror::Error;
+
+use crate::common::challenge;
+use crate::common::cookiejar;
+use crate::common::events;
+use crate::common::events::{CrawlFailed, Event, PageFetched};
+use crate::common::proxy::{self, Lease};
+use crate::webook::session;
+
+#[dynamic]
+pub static CLIENT: Client = Client::builder()
+    .default_headers(default_headers())
+    .build()
+    .unwrap();
 
+// 不跟随重定向，由execute处理重定向，以保存每次跳转中设置的cookie
 #[dynamic]
-pub static CLIENT: Client = {
-    let h: header::HeaderMap = vec![
+static NO_REDIRECT_CLIENT: Client = Client::builder()
+    .default_headers(default_headers())
+    .redirect(Policy::none())
+    .build()
+    .unwrap();
+
+// 通过代理访问网站被封禁或连接失败时，更换代理重试的次数
+const MAX_PROXY_RETRIES: usize = 2;
+
+// 跟随重定向的最大次数，与reqwest默认的相同
+const MAX_REDIRECTS: usize = 10;
+
+pub(crate) fn default_headers() -> header::HeaderMap {
+    vec![
         (header::USER_AGENT,
          HeaderValue::from_static("Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/98.0.4758.80 Safari/537.36 Edg/98.0.1108.43")),
-    ].into_iter().collect();
+    ].into_iter().collect()
+}
+
+// 为请求分配代理，tried为这次请求已经失败的代理，不再分配
+// 没有安装代理池时直接访问；代理都不可用时返回错误，配置了 direct_fallback 时直接访问
+fn lease_proxy(req: &Request, tried: &mut Vec<String>) -> Result<Option<Lease>, FetchError> {
+    let pool = match proxy::pool() {
+        Some(x) => x,
+        None => return Ok(None),
+    };
+    let host = req.url().host_str().unwrap_or_default();
+
+    match pool.lease(host, tried) {
+        Some(x) => {
+            tried.push(String::from(x.proxy()));
+            Ok(Some(x))
+        }
+        None if pool.options().direct_fallback => {
+            warn!("没有可用的代理，直接访问; host={host}");
+            Ok(None)
+        }
+        None => Err(FetchError::NoProxy(format!(
+            "host={host}, tried={}",
+            tried.len()
+        ))),
+    }
+}
+
+// 更换代理重试，还有没有使用过的可用代理时才重试
+fn can_retry(tried: &[String], retries: usize) -> bool {
+    retries < MAX_PROXY_RETRIES && proxy::pool().is_some_and(|x| x.has_available(tried))
+}
+
+// 发送请求，爬虫启用了cookie时携带并保存cookie，lease为分配的代理
+// 重定向的每次跳转都保存设置的cookie，并为下一个地址重新选择cookie
+async fn execute(
+    spider_id: &str,
+    mut req: Request,
+    lease: Option<Lease>,
+) -> reqwest::Result<(Response, Option<Lease>)> {
+    let jar = cookiejar::jar(spider_id);
+    let host = req.url().host_str().unwrap_or_default().to_string();
+
+    let mut redirects = 0;
+    loop {
+        if let Some(jar) = &jar {
+            req.headers_mut().remove(header::COOKIE);
+            if let Some(x) = jar.header(req.url()) {
+                if let Ok(x) = HeaderValue::from_str(&x) {
+                    req.headers_mut().insert(header::COOKIE, x);
+                }
+            }
+        }
+
+        // 307与308需要使用相同的请求体重新发送
+        let next = req.try_clone();
+        let r = match &lease {
+            Some(x) => x.client().execute(req).await,
+            None => NO_REDIRECT_CLIENT.execute(req).await,
+        };
+        let resp = match r {
+            Ok(x) => x,
+            Err(e) => {
+                if let Some(x) = lease {
+                    warn!("代理请求失败; proxy={}, host={host}, err={e}", x.proxy());
+                    x.fail();
+                }
+                return Err(e);
+            }
+        };
+
+        if let Some(jar) = &jar {
+            jar.store(resp.url(), resp.headers().get_all(header::SET_COOKIE));
+        }
+
+        if redirects == MAX_REDIRECTS {
+            return Ok((resp, lease));
+        }
+        match redirect(&resp, next) {
+            Some(x) => req = x,
+            None => return Ok((resp, lease)),
+        }
+        redirects += 1;
+    }
+}
+
+// 重定向的下一个请求，不是重定向或者无法重新发送时返回None
+fn redirect(resp: &Response, prev: Option<Request>) -> Option<Request> {
+    let status = resp.status();
+    if !status.is_redirection() {
+        return None;
+    }
+    let url = resp
+        .headers()
+        .get(header::LOCATION)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| resp.url().join(x).ok())?;
+    let mut prev = prev?;
+    let cross_site = resp.url().host_str() != url.host_str();
+
+    let mut req = match status {
+        StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT => prev,
+        StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND | StatusCode::SEE_OTHER => {
+            // 除HEAD之外都改为不带请求体的GET
+            let method = match *prev.method() {
+                Method::HEAD => Method::HEAD,
+                _ => Method::GET,
+            };
+            let mut x = Request::new(method, url.clone());
+            *x.timeout_mut() = prev.timeout().copied();
+            let headers = prev.headers_mut();
+            headers.remove(header::CONTENT_TYPE);
+            headers.remove(header::CONTENT_LENGTH);
+            *x.headers_mut() = std::mem::take(headers);
+            x
+        }
+        _ => return None,
+    };
+
+    // 跳转到其他网站时不发送认证信息
+    if cross_site {
+        req.headers_mut().remove(header::AUTHORIZATION);
+    }
+    *req.url_mut() = url;
+
+    Some(req)
+}
+
+// 发送请求并读取页面，返回状态码与页面内容
+// 爬虫启用了cookie时携带并保存cookie，被封禁时与fetch一样更换代理重试，不处理反爬虫验证
+pub async fn send(spider_id: &str, req: RequestBuilder) -> Result<(u16, String), FetchError> {
+    send_with(
+        spider_id,
+        req,
+        |resp| resp.text(),
+        |x| Cow::Borrowed(x.as_str()),
+    )
+    .await
+}
 
-    Client::builder().default_headers(h).build().unwrap()
-};
+// 下载的二进制内容
+#[derive(Debug)]
+pub struct Download {
+    pub status: u16,
+    pub headers: HeaderMap,
+    // 最多读取limit+1个字节，由调用者判断是否超出限制；响应头中的长度超出时不读取
+    pub data: Vec<u8>,
+}
+
+// 与send一样下载图片等二进制内容，返回反爬虫验证页面时先通过fetch解决验证，再重新下载一次
+pub async fn download(spider_id: &str, url: &str, limit: usize) -> Result<Download, FetchError> {
+    let mut solved = false;
+    loop {
+        let (status, (headers, data)) = send_with(
+            spider_id,
+            CLIENT.get(url),
+            |resp| read_limited(resp, limit),
+            |x| String::from_utf8_lossy(&x.1),
+        )
+        .await?;
+
+        let html = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|x| x.to_str().ok())
+            .is_some_and(|x| x.contains("html"));
+        if solved || !html || challenge::detect(status, &String::from_utf8_lossy(&data)).is_none() {
+            return Ok(Download {
+                status,
+                headers,
+                data,
+            });
+        }
+        // 验证得到的cookie保存在爬虫的cookie中
+        fetch_page(spider_id, url).await?;
+        solved = true;
+    }
+}
+
+async fn read_limited(mut resp: Response, limit: usize) -> reqwest::Result<(HeaderMap, Vec<u8>)> {
+    let headers = resp.headers().clone();
+    let mut data = Vec::new();
+    if resp.content_length().is_some_and(|x| x as usize > limit) {
+        return Ok((headers, data));
+    }
+
+    while let Some(chunk) = resp.chunk().await? {
+        data.extend_from_slice(&chunk);
+        if data.len() > limit {
+            break;
+        }
+    }
+
+    Ok((headers, data))
+}
+
+// 发送请求并通过read读取响应，text为判断封禁页面时使用的内容
+async fn send_with<T, R, F>(
+    spider_id: &str,
+    req: RequestBuilder,
+    read: R,
+    text: fn(&T) -> Cow<'_, str>,
+) -> Result<(u16, T), FetchError>
+where
+    R: Fn(Response) -> F,
+    F: Future<Output = reqwest::Result<T>>,
+{
+    let mut req = req.build()?;
+    let mut retries = 0;
+    let mut tried = Vec::new();
+    loop {
+        // 请求体不能复制时不重试
+        let next = req.try_clone();
+        let url = req.url().clone();
+        let lease = lease_proxy(&req, &mut tried)?;
+        let (resp, lease) = match execute(spider_id, req, lease).await {
+            Ok(x) => x,
+            Err(e) => match next {
+                Some(x) if can_retry(&tried, retries) => {
+                    retries += 1;
+                    warn!("更换代理重试; spider_id={spider_id}, url={url}, err={e}");
+                    req = x;
+                    continue;
+                }
+                _ => return Err(e.into()),
+            },
+        };
+        let status = resp.status().as_u16();
+        let body = read(resp).await?;
+
+        if !lease.is_some_and(|x| x.finish(status, &text(&body))) {
+            return Ok((status, body));
+        }
+        match next {
+            Some(x) if can_retry(&tried, retries) => {
+                retries += 1;
+                req = x;
+            }
+            _ => {
+                return Err(FetchError::Banned(format!(
+                    "更换代理后仍然被封禁; url={url}, status={status}"
+                )))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum FetchError {
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    // 反爬虫验证无法自动解决
+    #[error("anti-bot challenge not solved: {0}")]
+    Challenge(String),
+    // 需要登录的爬虫登录失败或登录后页面仍然显示未登录
+    #[error("login failed: {0}")]
+    Login(String),
+    // 更换代理后仍然返回封禁页面
+    #[error("banned by site: {0}")]
+    Banned(String),
+    // 代理池中的代理都在隔离中或已经失败，并且没有允许直接访问
+    #[error("no proxy available: {0}")]
+    NoProxy(String),
+}
+
+impl FetchError {
+    // 事件与监控指标中的错误类型
+    pub fn kind(&self) -> &'static str {
+        match self {
+            FetchError::Http(_) => "disconnect",
+            FetchError::Challenge(_) => "challenge",
+            FetchError::Login(_) => "login_failed",
+            FetchError::Banned(_) => "banned",
+            FetchError::NoProxy(_) => "no_proxy",
+        }
+    }
+}
+
+// 与get相同，遇到反爬虫验证时解决后重试，并发布获取页面的事件
+pub async fn fetch(spider_id: &str, url: &str) -> Result<String, FetchError> {
+    let start = Instant::now();
+    // 需要登录的爬虫由会话获取，页面显示未登录时重新登录
+    let r = match session::session(spider_id) {
+        Some(x) => x.fetch(url).await,
+        None => fetch_page(spider_id, url).await,
+    };
+
+    match r {
+        Ok((status, text)) => {
+            events::publish(Event::PageFetched(PageFetched {
+                spider_id: String::from(spider_id),
+                url: String::from(url),
+                status,
+                bytes: text.len() as u64,
+                elapsed: start.elapsed(),
+            }));
+
+            Ok(text)
+        }
+        Err(e) => {
+            events::publish(Event::CrawlFailed(CrawlFailed {
+                spider_id: String::from(spider_id),
+                url: Some(String::from(url)),
+                kind: String::from(e.kind()),
+                error: e.to_string(),
+                elapsed: start.elapsed(),
+            }));
+
+            Err(e)
+        }
+    }
+}
+
+pub(crate) async fn fetch_page(spider_id: &str, url: &str) -> Result<(u16, String), FetchError> {
+    let mut target = String::from(url);
+    let mut attempt = 0;
+    let mut retries = 0;
+    let mut tried = Vec::new();
+    loop {
+        let req = CLIENT.get(&target).build()?;
+        let lease = lease_proxy(&req, &mut tried)?;
+        let (resp, lease) = match execute(spider_id, req, lease).await {
+            Ok(x) => x,
+            // 代理连接失败时更换代理
+            Err(e) if can_retry(&tried, retries) => {
+                retries += 1;
+                warn!("更换代理重试; spider_id={spider_id}, url={target}, err={e}");
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let status = resp.status().as_u16();
+        let page = resp.url().clone();
+        let text = resp.text().await?;
+
+        let x = challenge::detect(status, &text);
+        // 验证页面不是封禁页面
+        if let Some(lease) = lease {
+            let banned = match &x {
+                Some(_) => lease.finish(200, ""),
+                None => lease.finish(status, &text),
+            };
+            if banned && can_retry(&tried, retries) {
+                retries += 1;
+                continue;
+            }
+            if banned {
+                return Err(FetchError::Banned(format!(
+                    "更换代理后仍然被封禁; url={page}, status={status}"
+                )));
+            }
+        }
+
+        let x = match x {
+            Some(x) => x,
+            None => return Ok((status, text)),
+        };
+        if attempt == challenge::MAX_ATTEMPTS {
+            return Err(FetchError::Challenge(format!(
+                "验证解决后仍然是验证页面; kind={}, url={page}",
+                x.kind()
+            )));
+        }
+        info!(
+            "遇到反爬虫验证; spider_id={spider_id}, kind={}, url={page}",
+            x.kind()
+        );
+
+        // 脚本在单独的线程中执行
+        let solved = {
+            let page = page.clone();
+            tokio::task::spawn_blocking(move || challenge::solve(&x, &page)).await
+        };
+        let solution = match solved {
+            Ok(Ok(x)) => x,
+            Ok(Err(e)) => return Err(FetchError::Challenge(format!("{e:#}; url={page}"))),
+            Err(e) => return Err(FetchError::Challenge(format!("{e}; url={page}"))),
+        };
+
+        // 没有启用cookie的爬虫也保存验证得到的cookie，避免每个请求都重新验证
+        if !solution.cookies.is_empty() {
+            let jar = cookiejar::jar_or_register(spider_id);
+            let v: Vec<HeaderValue> = solution
+                .cookies
+                .iter()
+                .filter_map(|x| HeaderValue::from_str(x).ok())
+                .collect();
+            jar.store(&page, &v);
+        }
+        tokio::time::sleep(solution.delay).await;
+        target = solution.redirect.unwrap_or_else(|| String::from(url));
+        attempt += 1;
+    }
+}
 
 pub async fn get(url: &str) -> reqwest::Result<String> {
     let resp = CLIENT.execute(CLIENT.get(url).build().unwrap()).await?;
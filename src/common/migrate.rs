@@ -0,0 +1,396 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use log::info;
+use sea_orm::{ConnectionTrait, DbBackend, DbConn, DbErr, Statement, TransactionTrait};
+
+use crate::common::db::{placeholder, placeholders};
+
+// 记录已经执行的迁移的表
+const MIGRATION_TABLE: &str = "schema_migrations";
+
+// 从 migrations 目录中嵌入一个迁移，file为不带 .up.sql/.down.sql 后缀的文件名
+// sqlite的脚本位于 migrations/ 下，postgres与mysql的脚本分别位于对应名字的子目录中
+#[macro_export]
+macro_rules! migration {
+    ($file:literal) => {
+        $crate::common::migrate::Migration {
+            id: $file,
+            sqlite: $crate::migration!(@script "/migrations/", $file),
+            postgres: $crate::migration!(@script "/migrations/postgres/", $file),
+            mysql: $crate::migration!(@script "/migrations/mysql/", $file),
+        }
+    };
+    (@script $dir:literal, $file:literal) => {
+        $crate::common::migrate::Script {
+            up: include_str!(concat!(env!("CARGO_MANIFEST_DIR"), $dir, $file, ".up.sql")),
+            down: include_str!(concat!(env!("CARGO_MANIFEST_DIR"), $dir, $file, ".down.sql")),
+        }
+    };
+}
+
+// 一个数据库迁移，id以时间戳开头，按id的顺序执行
+#[derive(Debug, Clone)]
+pub struct Migration {
+    pub id: &'static str,
+    pub sqlite: Script,
+    pub postgres: Script,
+    pub mysql: Script,
+}
+
+#[derive(Debug, Clone)]
+pub struct Script {
+    pub up: &'static str,
+    pub down: &'static str,
+}
+
+impl Migration {
+    // 获取对应数据库的迁移脚本
+    pub fn script(&self, backend: DbBackend) -> &Script {
+        match backend {
+            DbBackend::Sqlite => &self.sqlite,
+            DbBackend::Postgres => &self.postgres,
+            DbBackend::MySql => &self.mysql,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MigrationStatus {
+    pub owner: String,
+    pub id: String,
+    pub applied_at: Option<DateTime<Utc>>,
+}
+
+// 管理keeper与各个爬虫注册的迁移，owner为迁移的所有者
+#[derive(Default)]
+pub struct Migrator {
+    groups: Vec<(String, Vec<Migration>)>,
+}
+
+impl Migrator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, owner: &str, mut migrations: Vec<Migration>) {
+        if migrations.is_empty() || self.groups.iter().any(|x| x.0 == owner) {
+            return;
+        }
+
+        migrations.sort_by(|a, b| a.id.cmp(b.id));
+        self.groups.push((String::from(owner), migrations));
+    }
+
+    pub fn owners(&self) -> impl Iterator<Item = &str> {
+        self.groups.iter().map(|x| x.0.as_str())
+    }
+
+    // 执行所有未执行的迁移，返回本次执行的迁移
+    pub async fn up(&self, db: &DbConn) -> Result<Vec<(String, String)>> {
+        ensure_table(db).await?;
+
+        let mut done = Vec::new();
+        for (owner, migrations) in &self.groups {
+            let applied = applied(db, Some(owner)).await?;
+            for x in migrations
+                .iter()
+                .filter(|x| applied.iter().all(|(_, id, _)| id != x.id))
+            {
+                info!("执行迁移; owner={owner}, id={}", x.id);
+
+                let txn = db.begin().await?;
+                execute_script(&txn, x.script(txn.get_database_backend()).up)
+                    .await
+                    .map_err(|e| anyhow!("执行迁移失败; owner={owner}, id={}, err={e}", x.id))?;
+                txn.execute(Statement::from_sql_and_values(
+                    txn.get_database_backend(),
+                    &format!(
+                        "insert into {MIGRATION_TABLE} (owner, id, applied_at) values ({})",
+                        placeholders(txn.get_database_backend(), 3)
+                    ),
+                    vec![
+                        owner.as_str().into(),
+                        x.id.into(),
+                        Utc::now().to_rfc3339().into(),
+                    ],
+                ))
+                .await?;
+                txn.commit().await?;
+
+                done.push((owner.clone(), String::from(x.id)));
+            }
+        }
+
+        Ok(done)
+    }
+
+    // 回滚最近执行的steps个迁移，owner为None时不区分所有者
+    pub async fn down(
+        &self,
+        db: &DbConn,
+        owner: Option<&str>,
+        steps: usize,
+    ) -> Result<Vec<(String, String)>> {
+        ensure_table(db).await?;
+
+        let mut applied = applied(db, owner).await?;
+        // 按执行顺序倒序回滚
+        applied.sort_by(|a, b| (b.2, &b.1).cmp(&(a.2, &a.1)));
+
+        let mut done = Vec::new();
+        for (owner, id, _) in applied.into_iter().take(steps) {
+            let x = self
+                .groups
+                .iter()
+                .find(|x| x.0 == owner)
+                .and_then(|x| x.1.iter().find(|x| x.id == id))
+                .ok_or_else(|| anyhow!("迁移没有注册，无法回滚; owner={owner}, id={id}"))?;
+
+            info!("回滚迁移; owner={owner}, id={id}");
+
+            let txn = db.begin().await?;
+            execute_script(&txn, x.script(txn.get_database_backend()).down)
+                .await
+                .map_err(|e| anyhow!("回滚迁移失败; owner={owner}, id={id}, err={e}"))?;
+            txn.execute(Statement::from_sql_and_values(
+                txn.get_database_backend(),
+                &format!(
+                    "delete from {MIGRATION_TABLE} where owner = {} and id = {}",
+                    placeholder(txn.get_database_backend(), 1),
+                    placeholder(txn.get_database_backend(), 2),
+                ),
+                vec![owner.as_str().into(), id.as_str().into()],
+            ))
+            .await?;
+            txn.commit().await?;
+
+            done.push((owner, id));
+        }
+
+        Ok(done)
+    }
+
+    // 所有已注册的迁移的执行状态
+    pub async fn status(&self, db: &DbConn) -> Result<Vec<MigrationStatus>> {
+        ensure_table(db).await?;
+
+        let applied = applied(db, None).await?;
+        let mut status = Vec::new();
+        for (owner, migrations) in &self.groups {
+            for x in migrations {
+                status.push(MigrationStatus {
+                    owner: owner.clone(),
+                    id: String::from(x.id),
+                    applied_at: applied
+                        .iter()
+                        .find(|(o, id, _)| o == owner && id == x.id)
+                        .map(|x| x.2),
+                });
+            }
+        }
+
+        Ok(status)
+    }
+}
+
+async fn ensure_table(db: &DbConn) -> Result<()> {
+    db.execute(Statement::from_string(
+        db.get_database_backend(),
+        format!(
+            "create table if not exists {MIGRATION_TABLE} \
+             (owner varchar(255) not null, id varchar(255) not null, applied_at varchar(64) not null, \
+             primary key (owner, id))"
+        ),
+    ))
+    .await?;
+
+    Ok(())
+}
+
+// 已经执行的迁移，(所有者, id, 执行时间)
+async fn applied(db: &DbConn, owner: Option<&str>) -> Result<Vec<(String, String, DateTime<Utc>)>> {
+    let backend = db.get_database_backend();
+    let stmt = match owner {
+        Some(x) => Statement::from_sql_and_values(
+            backend,
+            &format!(
+                "select owner, id, applied_at from {MIGRATION_TABLE} where owner = {}",
+                placeholder(backend, 1)
+            ),
+            vec![x.into()],
+        ),
+        None => Statement::from_string(
+            backend,
+            format!("select owner, id, applied_at from {MIGRATION_TABLE}"),
+        ),
+    };
+
+    let mut x = Vec::new();
+    for row in db.query_all(stmt).await? {
+        let owner: String = row.try_get("", "owner")?;
+        let id: String = row.try_get("", "id")?;
+        let applied_at: String = row.try_get("", "applied_at")?;
+        let applied_at = DateTime::parse_from_rfc3339(&applied_at)
+            .map(|x| x.with_timezone(&Utc))
+            .unwrap_or_else(|_| Utc::now());
+
+        x.push((owner, id, applied_at));
+    }
+
+    Ok(x)
+}
+
+// 执行一个迁移脚本，sqlite可以一次执行多条语句，其他数据库按语句拆分后逐条执行
+async fn execute_script<C: ConnectionTrait>(db: &C, sql: &str) -> Result<(), DbErr> {
+    let backend = db.get_database_backend();
+    if backend == DbBackend::Sqlite {
+        if !statements(backend, sql).is_empty() {
+            db.execute(Statement::from_string(backend, String::from(sql)))
+                .await?;
+        }
+        return Ok(());
+    }
+
+    for x in statements(backend, sql) {
+        db.execute(Statement::from_string(backend, x)).await?;
+    }
+
+    Ok(())
+}
+
+// 将sql文件拆分为单条语句，忽略空语句与只有注释的语句
+// 字符串、引号中的标识符、注释、postgres的$tag$块以及begin...end块中的分号不会拆分语句
+pub fn statements(backend: DbBackend, sql: &str) -> Vec<String> {
+    let bytes = sql.as_bytes();
+    let mut list = Vec::new();
+    let mut start = 0;
+    // 当前语句中是否有注释以外的内容
+    let mut has_code = false;
+    // begin...end 与 case...end 的嵌套层数
+    let mut depth = 0usize;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let c = bytes[i];
+        match c {
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                i = find(bytes, i + 2, b"\n").unwrap_or(bytes.len());
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i = find(bytes, i + 2, b"*/").map_or(bytes.len(), |x| x + 2);
+                continue;
+            }
+            b'\'' | b'"' | b'`' => {
+                has_code = true;
+                i = skip_quoted(backend, bytes, i);
+                continue;
+            }
+            b'$' if backend == DbBackend::Postgres => {
+                has_code = true;
+                if let Some(tag) = dollar_tag(bytes, i) {
+                    let body = i + tag.len();
+                    i = find(bytes, body, tag).map_or(bytes.len(), |x| x + tag.len());
+                    continue;
+                }
+            }
+            b';' if depth == 0 => {
+                if has_code {
+                    list.push(String::from(sql[start..i].trim()));
+                }
+                start = i + 1;
+                has_code = false;
+            }
+            c if is_word(c) && (i == 0 || !is_word(bytes[i - 1])) => {
+                has_code = true;
+                let end = word_end(bytes, i);
+                let word = sql[i..end].to_ascii_lowercase();
+                let next = next_word(sql, end);
+                match word.as_str() {
+                    // 事务语句中的begin不是语句块
+                    "begin"
+                        if !matches!(next.as_str(), "" | "transaction" | "work")
+                            && !sql[end..].trim_start().starts_with(';') =>
+                    {
+                        depth += 1
+                    }
+                    "case" => depth += 1,
+                    // mysql的end if/end loop等结束的块没有计入层数
+                    "end" if !matches!(next.as_str(), "if" | "loop" | "while" | "repeat") => {
+                        depth = depth.saturating_sub(1)
+                    }
+                    _ => {}
+                }
+                i = end;
+                continue;
+            }
+            c if !c.is_ascii_whitespace() => has_code = true,
+            _ => {}
+        }
+        i += 1;
+    }
+
+    if has_code {
+        list.push(String::from(sql[start..].trim()));
+    }
+
+    list
+}
+
+fn is_word(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_'
+}
+
+fn word_end(bytes: &[u8], start: usize) -> usize {
+    (start..bytes.len())
+        .find(|x| !is_word(bytes[*x]))
+        .unwrap_or(bytes.len())
+}
+
+// start之后的下一个单词，小写
+fn next_word(sql: &str, start: usize) -> String {
+    let rest = sql[start..].trim_start();
+    let end = word_end(rest.as_bytes(), 0);
+    rest[..end].to_ascii_lowercase()
+}
+
+fn find(bytes: &[u8], start: usize, pat: &[u8]) -> Option<usize> {
+    bytes
+        .get(start..)?
+        .windows(pat.len())
+        .position(|x| x == pat)
+        .map(|x| x + start)
+}
+
+// 跳过引号中的内容，返回结束引号之后的位置，两个连续的引号为转义
+// mysql的字符串中还可以使用反斜杠转义
+fn skip_quoted(backend: DbBackend, bytes: &[u8], start: usize) -> usize {
+    let quote = bytes[start];
+    let mut i = start + 1;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && backend == DbBackend::MySql && quote != b'`' {
+            i += 2;
+            continue;
+        }
+        if bytes[i] == quote {
+            if bytes.get(i + 1) == Some(&quote) {
+                i += 2;
+                continue;
+            }
+            return i + 1;
+        }
+        i += 1;
+    }
+
+    bytes.len()
+}
+
+// postgres的$tag$，tag可以为空，不能以数字开头以免与$1参数混淆
+fn dollar_tag(bytes: &[u8], start: usize) -> Option<&[u8]> {
+    let end = (start + 1..bytes.len()).find(|x| !is_word(bytes[*x]))?;
+    if bytes[end] != b'$' || bytes.get(start + 1).is_some_and(u8::is_ascii_digit) {
+        return None;
+    }
+
+    Some(&bytes[start..=end])
+}
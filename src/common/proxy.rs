@@ -0,0 +1,457 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context, Result};
+use log::{info, warn};
+use reqwest::redirect::Policy;
+use reqwest::{Client, Proxy, Url};
+use serde::Deserialize;
+use static_init::dynamic;
+
+use crate::common::httputils::default_headers;
+use crate::common::shutdown::CancellationToken;
+use crate::config::parse_duration;
+
+// 所有爬虫共享的代理池，未安装时直接访问
+#[dynamic]
+static POOL: RwLock<Option<Arc<ProxyPool>>> = RwLock::new(None);
+
+// 封禁页面的特征，与配置中的合并
+const BAN_MARKERS: [&str; 5] = [
+    "访问过于频繁",
+    "ip被封",
+    "ip已被封",
+    "access denied",
+    "too many requests",
+];
+
+// 封禁页面通常很小，更大的页面即使包含封禁文字也是正常内容
+const MAX_BAN_PAGE_BYTES: usize = 4 * 1024;
+
+// 延迟的平滑系数，越大越偏向最近的结果
+const LATENCY_ALPHA: f64 = 0.3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProxyOptions {
+    // 健康检查访问的地址
+    pub check_url: Option<String>,
+    pub check_interval: Duration,
+    pub timeout: Duration,
+    // 返回封禁页面的代理暂停使用的时间
+    pub quarantine: Duration,
+    pub ban_markers: Vec<String>,
+    // 全部代理都不可用时直接访问网站，默认返回错误，避免暴露本机地址
+    pub direct_fallback: bool,
+}
+
+impl Default for ProxyOptions {
+    fn default() -> Self {
+        Self {
+            check_url: None,
+            check_interval: Duration::from_secs(300),
+            timeout: Duration::from_secs(10),
+            quarantine: Duration::from_secs(1800),
+            ban_markers: vec![],
+            direct_fallback: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Success(Duration),
+    // 连接失败或超时
+    Failure,
+    // 网站返回了封禁页面
+    Banned,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Stats {
+    success: u64,
+    failure: u64,
+    // 平滑后的延迟，没有成功的请求时为None
+    latency: Option<Duration>,
+    quarantined_until: Option<Instant>,
+}
+
+// 一个代理及其统计
+pub struct ProxyEntry {
+    url: String,
+    client: Client,
+    stats: Mutex<Stats>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProxyStatus {
+    pub url: String,
+    pub success: u64,
+    pub failure: u64,
+    pub latency: Option<Duration>,
+    pub score: f64,
+    pub quarantined: bool,
+}
+
+impl ProxyEntry {
+    fn new(url: &str, timeout: Duration) -> Result<Self> {
+        let scheme = url.split("://").next().unwrap_or_default();
+        if !matches!(scheme, "http" | "https" | "socks5" | "socks5h") {
+            bail!("不支持的代理协议: {url}");
+        }
+
+        let proxy = Proxy::all(url).with_context(|| format!("代理地址格式错误: {url}"))?;
+        // 重定向由httputils处理，以保存每次跳转中设置的cookie
+        let client = Client::builder()
+            .default_headers(default_headers())
+            .redirect(Policy::none())
+            .proxy(proxy)
+            .timeout(timeout)
+            .build()?;
+
+        Ok(Self {
+            url: String::from(url),
+            client,
+            stats: Mutex::new(Stats::default()),
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    fn stats(&self) -> std::sync::MutexGuard<'_, Stats> {
+        self.stats.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn is_quarantined(&self) -> bool {
+        matches!(self.stats().quarantined_until, Some(x) if x > Instant::now())
+    }
+
+    // 成功率越高、延迟越低分数越高，没有记录的代理成功率按一半计算
+    pub fn score(&self) -> f64 {
+        let x = self.stats();
+        let rate = (x.success as f64 + 1.0) / ((x.success + x.failure) as f64 + 2.0);
+        let latency = x.latency.map_or(1.0, |x| x.as_secs_f64());
+
+        rate / (1.0 + latency)
+    }
+
+    pub fn status(&self) -> ProxyStatus {
+        let score = self.score();
+        let x = self.stats();
+        ProxyStatus {
+            url: self.url.clone(),
+            success: x.success,
+            failure: x.failure,
+            latency: x.latency,
+            score,
+            quarantined: matches!(x.quarantined_until, Some(t) if t > Instant::now()),
+        }
+    }
+}
+
+// 代理池，按主机名分配代理，同一主机尽量使用同一个代理
+pub struct ProxyPool {
+    proxies: Vec<Arc<ProxyEntry>>,
+    // 主机名与分配的代理下标
+    sticky: Mutex<HashMap<String, usize>>,
+    options: ProxyOptions,
+}
+
+impl ProxyPool {
+    pub fn new(urls: &[String], options: ProxyOptions) -> Result<Self> {
+        let proxies = urls
+            .iter()
+            .map(|x| ProxyEntry::new(x, options.timeout).map(Arc::new))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            proxies,
+            sticky: Mutex::new(HashMap::new()),
+            options,
+        })
+    }
+
+    // 为请求分配代理，请求结束后通过返回值记录结果，exclude中的代理不会分配
+    pub fn lease(self: &Arc<Self>, host: &str, exclude: &[String]) -> Option<Lease> {
+        let proxy = self.pick(host, exclude)?;
+        Some(Lease {
+            pool: self.clone(),
+            proxy,
+            host: String::from(host),
+            start: Instant::now(),
+        })
+    }
+
+    pub fn options(&self) -> &ProxyOptions {
+        &self.options
+    }
+
+    fn sticky(&self) -> std::sync::MutexGuard<'_, HashMap<String, usize>> {
+        self.sticky.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // 为主机分配代理，跳过exclude中的代理，没有可用的代理时返回None
+    pub fn pick(&self, host: &str, exclude: &[String]) -> Option<Arc<ProxyEntry>> {
+        let usable = |x: &ProxyEntry| !x.is_quarantined() && !exclude.contains(&x.url);
+
+        let mut sticky = self.sticky();
+        if let Some(x) = sticky.get(host).and_then(|x| self.proxies.get(*x)) {
+            if usable(x) {
+                return Some(x.clone());
+            }
+        }
+
+        let (idx, x) = self
+            .proxies
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| usable(x))
+            .max_by(|(ia, a), (ib, b)| {
+                // 分数相同时使用配置中靠前的
+                a.score()
+                    .partial_cmp(&b.score())
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then(ib.cmp(ia))
+            })?;
+        sticky.insert(String::from(host), idx);
+
+        Some(x.clone())
+    }
+
+    // 除exclude中的代理之外是否还有可用的代理
+    pub fn has_available(&self, exclude: &[String]) -> bool {
+        self.proxies
+            .iter()
+            .any(|x| !x.is_quarantined() && !exclude.contains(&x.url))
+    }
+
+    // 记录代理访问主机的结果
+    pub fn report(&self, proxy: &ProxyEntry, host: &str, outcome: Outcome) {
+        {
+            let mut x = proxy.stats();
+            match outcome {
+                Outcome::Success(latency) => {
+                    x.success += 1;
+                    x.latency = Some(match x.latency {
+                        Some(old) => {
+                            old.mul_f64(1.0 - LATENCY_ALPHA) + latency.mul_f64(LATENCY_ALPHA)
+                        }
+                        None => latency,
+                    });
+                }
+                Outcome::Failure => x.failure += 1,
+                Outcome::Banned => {
+                    x.failure += 1;
+                    x.quarantined_until = Some(Instant::now() + self.options.quarantine);
+                }
+            }
+        }
+
+        match outcome {
+            Outcome::Success(_) => {}
+            Outcome::Failure => {
+                // 失败后该主机重新选择代理
+                self.sticky().remove(host);
+            }
+            Outcome::Banned => {
+                warn!("代理被封禁，暂停使用; proxy={}, host={host}", proxy.url);
+                let idx = self.proxies.iter().position(|x| x.url == proxy.url);
+                self.sticky().retain(|_, x| Some(*x) != idx);
+            }
+        }
+    }
+
+    // 页面是否为封禁页面，403与429总是封禁，其他页面较小时按封禁文字判断
+    pub fn is_banned(&self, status: u16, body: &str) -> bool {
+        if status == 403 || status == 429 {
+            return true;
+        }
+        if body.len() > MAX_BAN_PAGE_BYTES {
+            return false;
+        }
+
+        let body = body.to_lowercase();
+        BAN_MARKERS.iter().any(|x| body.contains(x))
+            || self
+                .options
+                .ban_markers
+                .iter()
+                .any(|x| body.contains(&x.to_lowercase()))
+    }
+
+    // 通过每个代理访问检查地址，记录成功率与延迟
+    pub async fn check(&self) -> Result<()> {
+        let url = match &self.options.check_url {
+            Some(x) => x,
+            None => bail!("没有设置代理的检查地址"),
+        };
+        let host = reqwest::Url::parse(url)?
+            .host_str()
+            .unwrap_or_default()
+            .to_string();
+
+        let checks = self.proxies.iter().map(|x| {
+            let host = host.as_str();
+            async move {
+                let start = Instant::now();
+                let r = x
+                    .client
+                    .get(url)
+                    .send()
+                    .await
+                    .and_then(|x| x.error_for_status());
+                let outcome = match r {
+                    Ok(_) => Outcome::Success(start.elapsed()),
+                    Err(_) => Outcome::Failure,
+                };
+                self.report(x, host, outcome);
+            }
+        });
+        futures::future::join_all(checks).await;
+
+        Ok(())
+    }
+
+    // 定时检查代理，直到cancel被取消
+    pub async fn run_checks(&self, cancel: CancellationToken) {
+        if self.options.check_url.is_none() {
+            return;
+        }
+
+        loop {
+            if let Err(e) = self.check().await {
+                warn!("检查代理失败: {e:#}");
+            }
+            let available = self.proxies.iter().filter(|x| !x.is_quarantined()).count();
+            info!(
+                "检查代理; available={available}, total={}",
+                self.proxies.len()
+            );
+
+            tokio::select! {
+                _ = tokio::time::sleep(self.options.check_interval) => {},
+                _ = cancel.cancelled() => return,
+            }
+        }
+    }
+
+    pub fn status(&self) -> Vec<ProxyStatus> {
+        self.proxies.iter().map(|x| x.status()).collect()
+    }
+}
+
+// 一次请求使用的代理
+pub struct Lease {
+    pool: Arc<ProxyPool>,
+    proxy: Arc<ProxyEntry>,
+    host: String,
+    start: Instant,
+}
+
+impl Lease {
+    pub fn client(&self) -> &Client {
+        &self.proxy.client
+    }
+
+    pub fn proxy(&self) -> &str {
+        &self.proxy.url
+    }
+
+    // 根据响应记录结果，返回是否为封禁页面，5xx记为失败
+    pub fn finish(self, status: u16, body: &str) -> bool {
+        let banned = self.pool.is_banned(status, body);
+        let outcome = if banned {
+            Outcome::Banned
+        } else if status >= 500 {
+            Outcome::Failure
+        } else {
+            Outcome::Success(self.start.elapsed())
+        };
+        self.pool.report(&self.proxy, &self.host, outcome);
+
+        banned
+    }
+
+    pub fn fail(self) {
+        self.pool.report(&self.proxy, &self.host, Outcome::Failure);
+    }
+}
+
+// 安装代理池，之后通过httputils发送的请求都使用代理
+pub fn install(pool: Arc<ProxyPool>) {
+    *POOL.write().unwrap_or_else(|e| e.into_inner()) = Some(pool);
+}
+
+pub fn uninstall() {
+    *POOL.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+pub fn pool() -> Option<Arc<ProxyPool>> {
+    POOL.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+// 代理池，未设置代理时直接访问网站
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProxyConfig {
+    // 支持 http://、https://、socks5://，可以包含用户名与密码
+    pub proxies: Vec<String>,
+    // 健康检查访问的地址，未设置时不检查
+    pub check_url: Option<String>,
+    pub check_interval: String,
+    pub timeout: String,
+    // 返回封禁页面的代理暂停使用的时间
+    pub quarantine: String,
+    // 封禁页面中的文字，403与429状态码总是作为封禁
+    pub ban_markers: Vec<String>,
+    // 全部代理都不可用时直接访问网站，默认请求失败
+    pub direct_fallback: bool,
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        Self {
+            proxies: vec![],
+            check_url: None,
+            check_interval: String::from("5m"),
+            timeout: String::from("10s"),
+            quarantine: String::from("30m"),
+            ban_markers: vec![],
+            direct_fallback: false,
+        }
+    }
+}
+
+impl ProxyConfig {
+    // 没有设置代理时返回None
+    pub fn to_pool(&self) -> Result<Option<ProxyPool>> {
+        if self.proxies.is_empty() {
+            return Ok(None);
+        }
+
+        if let Some(x) = &self.check_url {
+            Url::parse(x).with_context(|| format!("check_url={x}"))?;
+        }
+        let duration = |name: &str, x: &str| -> Result<std::time::Duration> {
+            parse_duration(x)
+                .and_then(|d| d.to_std().map_err(|_| anyhow!("不能小于0")))
+                .with_context(|| format!("{name}={x}"))
+        };
+        let options = ProxyOptions {
+            check_url: self.check_url.clone(),
+            check_interval: duration("check_interval", &self.check_interval)?,
+            timeout: duration("timeout", &self.timeout)?,
+            quarantine: duration("quarantine", &self.quarantine)?,
+            ban_markers: self.ban_markers.clone(),
+            direct_fallback: self.direct_fallback,
+        };
+
+        Ok(Some(ProxyPool::new(&self.proxies, options)?))
+    }
+}
@@ -0,0 +1,78 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+pub use tokio_util::sync::CancellationToken;
+
+// 追踪已经派发出去的任务，关闭时可以等待它们全部结束
+#[derive(Clone, Default)]
+pub struct TaskTracker {
+    inner: Arc<TrackerInner>,
+}
+
+#[derive(Default)]
+struct TrackerInner {
+    running: AtomicUsize,
+    notify: Notify,
+}
+
+// 任务结束（包括panic与abort）时减少计数
+struct TaskGuard {
+    inner: Arc<TrackerInner>,
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        if self.inner.running.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.inner.notify.notify_waiters();
+        }
+    }
+}
+
+impl TaskTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn<F>(&self, fut: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.inner.running.fetch_add(1, Ordering::SeqCst);
+        let guard = TaskGuard {
+            inner: self.inner.clone(),
+        };
+
+        tokio::spawn(async move {
+            let out = fut.await;
+            drop(guard);
+
+            out
+        })
+    }
+
+    // 正在执行的任务数量
+    pub fn len(&self) -> usize {
+        self.inner.running.load(Ordering::SeqCst)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // 等待所有任务结束
+    pub async fn wait(&self) {
+        loop {
+            // 先注册通知再检查计数，避免错过唤醒
+            let notified = self.inner.notify.notified();
+            if self.is_empty() {
+                return;
+            }
+
+            notified.await;
+        }
+    }
+}
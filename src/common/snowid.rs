@@ -1,22 +1,205 @@
-use snowflake::SnowflakeIdGenerator;
-use tokio::sync::Mutex;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-static mut GEN: Option<Mutex<SnowflakeIdGenerator>> = None;
+use anyhow::{bail, Context, Result};
+use log::warn;
+use serde::Deserialize;
+use static_init::dynamic;
 
-pub fn set(machine_id: i32, node_id: i32) {
-    unsafe {
-        GEN = Some(Mutex::new(SnowflakeIdGenerator::new(machine_id, node_id)));
+// 布局与原 rs-snowflake 保持一致，已入库的id不受影响
+// | 时间戳(ms, UNIX_EPOCH) | machine 5bit | node 5bit | 序号 12bit |
+const MACHINE_BITS: u32 = 5;
+const NODE_BITS: u32 = 5;
+const SEQ_BITS: u32 = 12;
+const MAX_WORKER_ID: i32 = (1 << MACHINE_BITS) - 1;
+const SEQ_MASK: u16 = (1 << SEQ_BITS) - 1;
+
+pub const ENV_MACHINE_ID: &str = "SNOWFLAKE_MACHINE_ID";
+pub const ENV_NODE_ID: &str = "SNOWFLAKE_NODE_ID";
+
+// 进程默认的id服务，延迟到首次使用时初始化，保证 dotenv 已加载
+// 环境变量的值无效时不能继续运行；spider 命令启动时按配置替换，未设置节点时拒绝启动
+#[dynamic(lazy)]
+static GLOBAL: RwLock<IdGen> = RwLock::new(match IdGen::from_env() {
+    Ok(Some(x)) => x,
+    Ok(None) => {
+        warn!("没有设置{ENV_MACHINE_ID}，按单节点使用(1, 1)");
+        IdGen::snowflake(1, 1).expect("默认节点id合法")
     }
+    Err(e) => panic!("snowflake环境变量无效: {e:#}"),
+});
+
+// id生成服务，clone后共享同一状态，通过Keeper注入到数据函数
+#[derive(Clone)]
+pub struct IdGen {
+    inner: Arc<Inner>,
+}
+
+enum Inner {
+    Snowflake(Snowflake),
+    // 确定性模式，测试使用
+    Sequential(AtomicI64),
+}
+
+struct Snowflake {
+    prefix: i64,
+    state: Mutex<State>,
 }
 
-pub async fn id() -> i64 {
-    unsafe {
-        if let Some(gen) = GEN.as_ref() {
-            let mut gen = gen.lock().await;
+struct State {
+    last_ms: i64,
+    seq: u16,
+}
+
+impl IdGen {
+    pub fn snowflake(machine_id: i32, node_id: i32) -> Result<Self> {
+        for (name, v) in [("machine_id", machine_id), ("node_id", node_id)] {
+            if !(0..=MAX_WORKER_ID).contains(&v) {
+                bail!("节点id超出范围; {name}={v}, max={MAX_WORKER_ID}");
+            }
+        }
+
+        let prefix =
+            ((machine_id as i64) << (NODE_BITS + SEQ_BITS)) | ((node_id as i64) << SEQ_BITS);
+        Ok(Self {
+            inner: Arc::new(Inner::Snowflake(Snowflake {
+                prefix,
+                state: Mutex::new(State { last_ms: 0, seq: 0 }),
+            })),
+        })
+    }
+
+    // 从 start 开始依次递增
+    pub fn sequential(start: i64) -> Self {
+        Self {
+            inner: Arc::new(Inner::Sequential(AtomicI64::new(start))),
+        }
+    }
+
+    // 读取 SNOWFLAKE_MACHINE_ID 与 SNOWFLAKE_NODE_ID，没有设置 machine_id 时返回None，node_id 默认为1
+    pub fn from_env() -> Result<Option<Self>> {
+        match env_ids()? {
+            (Some(machine_id), node_id) => {
+                Ok(Some(Self::snowflake(machine_id, node_id.unwrap_or(1))?))
+            }
+            (None, _) => Ok(None),
+        }
+    }
+
+    pub fn generate(&self) -> i64 {
+        self.generate_at(now_ms())
+    }
+
+    // 以指定的当前时间(ms)生成，便于测试时钟回拨
+    pub fn generate_at(&self, now_ms: i64) -> i64 {
+        match self.inner.as_ref() {
+            Inner::Snowflake(x) => x.generate(now_ms),
+            Inner::Sequential(x) => x.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+}
+
+impl Snowflake {
+    fn generate(&self, now: i64) -> i64 {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        if now < state.last_ms {
+            // 时钟回拨，沿用上次的时间戳继续分配，保证单调不重复
+            warn!("检测到时钟回拨; ms={}", state.last_ms - now);
+        }
 
-            gen.generate()
+        if now > state.last_ms {
+            state.last_ms = now;
+            state.seq = 0;
+        } else if state.seq < SEQ_MASK {
+            state.seq += 1;
         } else {
-            panic!("snowflake is not init")
+            // 当前毫秒序号用尽，借用下一毫秒
+            state.last_ms += 1;
+            state.seq = 0;
         }
+
+        (state.last_ms << (MACHINE_BITS + NODE_BITS + SEQ_BITS)) | self.prefix | state.seq as i64
+    }
+}
+
+// 环境变量中的 (machine_id, node_id)，未设置的为None，不是整数时返回错误
+pub fn env_ids() -> Result<(Option<i32>, Option<i32>)> {
+    let read = |key: &str| -> Result<Option<i32>> {
+        match std::env::var(key) {
+            Ok(v) => v
+                .trim()
+                .parse()
+                .map(Some)
+                .with_context(|| format!("{key}不是整数; value={v}")),
+            Err(_) => Ok(None),
+        }
+    };
+
+    Ok((read(ENV_MACHINE_ID)?, read(ENV_NODE_ID)?))
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+// 替换进程默认的id服务，之后创建的Keeper与爬虫使用新的服务
+pub fn install(gen: IdGen) {
+    *GLOBAL.write().unwrap_or_else(|e| e.into_inner()) = gen;
+}
+
+pub fn set(machine_id: i32, node_id: i32) -> Result<()> {
+    install(IdGen::snowflake(machine_id, node_id)?);
+
+    Ok(())
+}
+
+pub fn global() -> IdGen {
+    GLOBAL.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+// 未设置的节点使用 SNOWFLAKE_MACHINE_ID 与 SNOWFLAKE_NODE_ID 环境变量
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SnowflakeConfig {
+    pub machine_id: Option<i32>,
+    // 未设置时为1
+    pub node_id: Option<i32>,
+    // 只有一个进程写数据库时可以不设置 machine_id，使用1
+    pub single_node: bool,
+}
+
+impl SnowflakeConfig {
+    // 没有设置 machine_id 时只检查 node_id，启动时由 id_gen 报错
+    pub fn validate(&self) -> Result<()> {
+        let (machine_id, node_id) = self.ids()?;
+        IdGen::snowflake(machine_id.unwrap_or(1), node_id)?;
+
+        Ok(())
+    }
+
+    // 多个进程写同一个数据库时节点必须不同，因此没有设置 machine_id 时需要显式声明单节点
+    pub fn id_gen(&self) -> Result<IdGen> {
+        match self.ids()? {
+            (Some(machine_id), node_id) => IdGen::snowflake(machine_id, node_id),
+            (None, node_id) if self.single_node => IdGen::snowflake(1, node_id),
+            (None, _) => bail!(
+                "没有设置 snowflake.machine_id 或 {ENV_MACHINE_ID}，只有一个进程写数据库时可以设置 snowflake.single_node = true"
+            ),
+        }
+    }
+
+    // 配置优先，未设置时使用环境变量，环境变量的值无效时返回错误
+    fn ids(&self) -> Result<(Option<i32>, i32)> {
+        let (machine_id, node_id) = env_ids()?;
+
+        Ok((
+            self.machine_id.or(machine_id),
+            self.node_id.or(node_id).unwrap_or(1),
+        ))
     }
 }
@@ -0,0 +1,394 @@
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::Duration;
+use log::{error, info, warn};
+use serde::Deserialize;
+use tokio::sync::watch;
+
+use crate::common::shutdown::CancellationToken;
+use crate::common::snowid::IdGen;
+
+// 各部分的配置定义在使用它的模块中，这里只负责加载、校验与重新加载
+pub use crate::common::db::DatabaseConfig;
+pub use crate::common::proxy::ProxyConfig;
+pub use crate::common::snowid::SnowflakeConfig;
+pub use crate::keeper::canary::{CanaryConfig, CanaryPageConfig};
+pub use crate::keeper::content::{ContentConfig, S3ContentConfig};
+pub use crate::keeper::metrics::MetricsConfig;
+pub use crate::keeper::notify::{NotifyConfig, SmtpConfig, WebhookConfig};
+pub use crate::keeper::PolicyConfig;
+pub use crate::spider::{SpiderConfig, TEMPLATE_JIEQI, TEMPLATE_WASM};
+pub use crate::webook::session::{LoginConfig, SessionConfig};
+
+// 未指定配置文件时查找的默认路径
+pub const DEFAULT_CONFIG_PATH: &str = "spider.toml";
+
+// 指定配置文件路径的环境变量
+pub const ENV_CONFIG_PATH: &str = "SPIDER_CONFIG";
+
+// discovery 目录中作为站点描述与插件的文件扩展名
+const RULE_EXTENSIONS: &[&str] = &["toml", "yaml", "yml"];
+const PLUGIN_EXTENSIONS: &[&str] = &["wasm"];
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub database: DatabaseConfig,
+    pub snowflake: SnowflakeConfig,
+    pub policy: PolicyConfig,
+    // 以爬虫名称为键，例如 ddxsku
+    pub spiders: BTreeMap<String, SpiderConfig>,
+    pub discovery: DiscoveryConfig,
+    pub notify: NotifyConfig,
+    pub metrics: MetricsConfig,
+    pub session: SessionConfig,
+    pub proxy: ProxyConfig,
+    pub content: ContentConfig,
+}
+
+// 从目录中发现爬虫，文件名（不含扩展名）作为爬虫名称
+// 与 spiders 中同名的配置合并，配置中已经设置的文件优先
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiscoveryConfig {
+    // 站点描述目录，其中的 toml、yaml 文件注册为jieqi模板的爬虫
+    pub rules: Option<PathBuf>,
+    // 插件目录，其中的 wasm 文件注册为wasm模板的爬虫
+    pub plugins: Option<PathBuf>,
+}
+
+impl Config {
+    // 根据扩展名解析 toml 或 yaml，并校验
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read_to_string(path)
+            .with_context(|| format!("读取配置文件失败; path={}", path.display()))?;
+
+        let ext = path
+            .extension()
+            .and_then(|x| x.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        let mut config = Self::parse(&data, &ext)
+            .with_context(|| format!("解析配置文件失败; path={}", path.display()))?;
+        config.discover_spiders()?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    // format为 toml、yaml 或 yml
+    pub fn parse(data: &str, format: &str) -> Result<Self> {
+        let config = match format {
+            "toml" => toml::from_str(data)?,
+            "yaml" | "yml" => serde_yaml::from_str(data)?,
+            x => bail!("不支持的配置文件格式: {x}"),
+        };
+
+        Ok(config)
+    }
+
+    // 依次使用 SPIDER_CONFIG 与默认路径，都不存在时返回None
+    pub fn discover() -> Option<PathBuf> {
+        if let Ok(x) = std::env::var(ENV_CONFIG_PATH) {
+            return Some(PathBuf::from(x));
+        }
+
+        let path = PathBuf::from(DEFAULT_CONFIG_PATH);
+        path.exists().then_some(path)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if let Some(url) = &self.database.url {
+            if !url.contains("://") {
+                bail!("database.url 格式错误: {url}");
+            }
+        }
+
+        self.snowflake.validate().context("snowflake 配置错误")?;
+        self.policy.to_policy().context("policy 配置错误")?;
+
+        for (name, x) in &self.spiders {
+            x.validate()
+                .with_context(|| format!("spiders.{name} 配置错误"))?;
+        }
+
+        self.notify.validate().context("notify 配置错误")?;
+        self.metrics_addr()?;
+        self.proxy.to_pool().context("proxy 配置错误")?;
+        self.content.validate().context("content 配置错误")?;
+
+        Ok(())
+    }
+
+    pub fn database_url(&self) -> Result<String> {
+        match &self.database.url {
+            Some(x) => Ok(x.clone()),
+            None => std::env::var("DATABASE_URL").map_err(|_| {
+                anyhow!("require database.url in config or DATABASE_URL environment variable")
+            }),
+        }
+    }
+
+    pub fn metrics_addr(&self) -> Result<Option<SocketAddr>> {
+        match &self.metrics.listen {
+            Some(x) => {
+                let addr = x
+                    .parse()
+                    .with_context(|| format!("metrics.listen 格式错误: {x}"))?;
+                Ok(Some(addr))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn id_gen(&self) -> Result<IdGen> {
+        self.snowflake.id_gen()
+    }
+
+    // 将 discovery 目录中的站点描述与插件加入 spiders，目录不存在时报错
+    pub fn discover_spiders(&mut self) -> Result<()> {
+        let rules = self
+            .discovery
+            .rules
+            .clone()
+            .map(|x| (x, TEMPLATE_JIEQI, RULE_EXTENSIONS));
+        let plugins = self
+            .discovery
+            .plugins
+            .clone()
+            .map(|x| (x, TEMPLATE_WASM, PLUGIN_EXTENSIONS));
+        for (dir, template, exts) in rules.into_iter().chain(plugins) {
+            for (name, path) in spider_files(&dir, exts)? {
+                let x = self.spiders.entry(name.clone()).or_default();
+                match x.template.as_deref() {
+                    None => x.template = Some(String::from(template)),
+                    Some(t) if t == template => {}
+                    Some(t) => bail!(
+                        "发现的爬虫与已有配置的模板不同; spider={name}, template={t}, path={}",
+                        path.display()
+                    ),
+                }
+
+                let file = if template == TEMPLATE_WASM {
+                    &mut x.plugin
+                } else {
+                    &mut x.profile
+                };
+                if file.is_none() {
+                    info!("发现爬虫; spider={name}, path={}", path.display());
+                    *file = Some(path);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // 未在配置中出现的爬虫默认启用
+    pub fn spider(&self, name: &str) -> SpiderConfig {
+        self.spiders.get(name).cloned().unwrap_or_default()
+    }
+
+    // 使用jieqi模板的爬虫，名称为ddxsku的爬虫默认使用内置描述
+    pub fn jieqi_spiders(&self) -> Vec<(String, SpiderConfig)> {
+        let mut v: Vec<_> = self
+            .spiders
+            .iter()
+            .filter(|(name, x)| {
+                x.template.as_deref() == Some(TEMPLATE_JIEQI)
+                    || (x.template.is_none() && name.as_str() == "ddxsku")
+            })
+            .map(|(name, x)| (name.clone(), x.clone()))
+            .collect();
+        if !self.spiders.contains_key("ddxsku") {
+            v.push((String::from("ddxsku"), SpiderConfig::default()));
+        }
+
+        v
+    }
+
+    // 使用wasm插件的爬虫
+    pub fn wasm_spiders(&self) -> Vec<(String, SpiderConfig)> {
+        self.spiders
+            .iter()
+            .filter(|(_, x)| x.template.as_deref() == Some(TEMPLATE_WASM))
+            .map(|(name, x)| (name.clone(), x.clone()))
+            .collect()
+    }
+
+    // 返回需要重启才能生效的变更项
+    pub fn restart_required(&self, new: &Config) -> Vec<&'static str> {
+        let mut v = Vec::new();
+        if self.database != new.database {
+            v.push("database");
+        }
+        if self.snowflake != new.snowflake {
+            v.push("snowflake");
+        }
+        if self.notify != new.notify {
+            v.push("notify");
+        }
+        if self.metrics != new.metrics {
+            v.push("metrics");
+        }
+        if self.proxy != new.proxy {
+            v.push("proxy");
+        }
+        if self.discovery != new.discovery {
+            v.push("discovery");
+        }
+        if self.session != new.session {
+            v.push("session");
+        }
+        if self.content != new.content {
+            v.push("content");
+        }
+        let canaries = |c: &Config| {
+            c.spiders
+                .iter()
+                .map(|(k, v)| (k.clone(), v.canary.clone()))
+                .collect::<Vec<_>>()
+        };
+        if canaries(self) != canaries(new) {
+            v.push("spiders.*.canary");
+        }
+        // 爬虫在启动时注册，启用与停用可以在运行时修改
+        let names = |c: &Config| {
+            c.jieqi_spiders()
+                .into_iter()
+                .chain(c.wasm_spiders())
+                .map(|(k, v)| {
+                    let x = (v.template, v.id, v.profile, v.plugin, v.fuel, v.memory_mb);
+                    (k, x, v.cookies, v.login)
+                })
+                .collect::<Vec<_>>()
+        };
+        if names(self) != names(new) {
+            v.push("spiders");
+        }
+
+        v
+    }
+}
+
+// 目录中扩展名匹配的文件，按文件名排序，返回 (爬虫名称, 路径)
+fn spider_files(dir: &Path, exts: &[&str]) -> Result<Vec<(String, PathBuf)>> {
+    let entries = std::fs::read_dir(dir)
+        .with_context(|| format!("读取爬虫目录失败; path={}", dir.display()))?;
+
+    let mut v: Vec<(String, PathBuf)> = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let ext = path
+            .extension()
+            .and_then(|x| x.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        if !path.is_file() || !exts.contains(&ext.as_str()) {
+            continue;
+        }
+
+        let name = match path.file_stem().and_then(|x| x.to_str()) {
+            Some(x) if !x.trim().is_empty() => String::from(x),
+            _ => bail!("爬虫文件名无效; path={}", path.display()),
+        };
+        if let Some((_, other)) = v.iter().find(|(x, _)| *x == name) {
+            bail!(
+                "爬虫名称重复; spider={name}, paths={},{}",
+                other.display(),
+                path.display()
+            );
+        }
+        v.push((name, path));
+    }
+    v.sort();
+
+    Ok(v)
+}
+
+// 解析 "30s", "5m", "2h", "7d" 格式的时间间隔，纯数字表示秒
+pub fn parse_duration(x: &str) -> Result<Duration> {
+    let x = x.trim();
+    let (num, unit) = match x.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+        Some((idx, _)) => x.split_at(idx),
+        None => (x, "s"),
+    };
+
+    let n: i64 = num.parse().map_err(|_| anyhow!("时间格式错误: {x}"))?;
+    // 每个单位的毫秒数
+    let unit = match unit.trim() {
+        "ms" => 1,
+        "s" => 1000,
+        "m" => 60 * 1000,
+        "h" => 60 * 60 * 1000,
+        "d" => 24 * 60 * 60 * 1000,
+        _ => bail!("时间格式错误: {x}"),
+    };
+
+    // 以毫秒计算，不溢出i64时一定在Duration的范围内
+    let ms = n
+        .checked_mul(unit)
+        .ok_or_else(|| anyhow!("时间超出范围: {x}"))?;
+
+    Ok(Duration::milliseconds(ms))
+}
+
+// 定期检查配置文件的修改时间，修改后重新加载，校验失败时保留原配置
+pub fn watch(
+    path: PathBuf,
+    initial: Config,
+    interval: std::time::Duration,
+    cancel: CancellationToken,
+) -> watch::Receiver<Arc<Config>> {
+    let (tx, rx) = watch::channel(Arc::new(initial));
+
+    let modified = |path: &Path| -> Option<SystemTime> {
+        std::fs::metadata(path).and_then(|x| x.modified()).ok()
+    };
+
+    tokio::spawn(async move {
+        let mut last = modified(&path);
+
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {},
+                _ = cancel.cancelled() => return,
+                _ = tx.closed() => return,
+            }
+
+            let now = modified(&path);
+            if now == last {
+                continue;
+            }
+            last = now;
+
+            let config = match Config::load(&path) {
+                Ok(x) => x,
+                Err(e) => {
+                    error!("重新加载配置失败，继续使用原配置: {e:#}");
+                    continue;
+                }
+            };
+
+            let old = tx.borrow().clone();
+            if *old == config {
+                continue;
+            }
+            let restart = old.restart_required(&config);
+            if !restart.is_empty() {
+                warn!("以下配置修改需要重启后生效: {}", restart.join(", "));
+            }
+
+            info!("配置已重新加载; path={}", path.display());
+            let _ = tx.send(Arc::new(config));
+        }
+    });
+
+    rx
+}
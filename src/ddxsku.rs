@@ -1,10 +1,12 @@
-use std::sync::Arc;
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use async_recursion::async_recursion;
 use async_trait::async_trait;
-use chrono::{DateTime, Utc};
 use log::{info, warn};
+use reqwest::Url;
 use sea_orm::{DbConn, TransactionTrait};
 use serde_json::json;
 use tera::{Context, Tera};
@@ -12,66 +14,47 @@ use tokio::sync::mpsc::Receiver;
 use tokio::sync::mpsc::{channel, Sender};
 use tokio::sync::Semaphore;
 
+use crate::common::date::ParsedDate;
 use crate::common::doc::{WrapDocument, WrapSelection};
-use crate::common::httputils::{get, CLIENT};
+use crate::common::events;
+use crate::common::events::{Event, NovelParsed};
+use crate::common::httputils::{fetch, send, CLIENT};
+use crate::common::migrate::Migration;
 use crate::common::sender::WrapSender;
+use crate::common::shutdown::{CancellationToken, TaskTracker};
+use crate::common::snowid::{self, IdGen};
 use crate::ddxsku::data::{add_or_recover, add_or_recover_novel, clear_sort, novel_by_id, sorts};
+use crate::ddxsku::profile::{Profile, Selectors};
+use crate::migration;
 use crate::spider;
 use crate::spider::{
-    CrawlError, Novel, NovelID, NovelState, Position, Section, Sort, SortID, Spider,
-    SpiderMetadata, Support,
+    CrawlError, Novel, NovelID, NovelState, Position, Probe, ProbeKind, Section, Sort, SortID,
+    SourceKind, Spider, SpiderConfig, SpiderInfo, SpiderMetadata, Support,
 };
 
 pub mod data;
-
-// 默认并发大小
-const DEFAULT_CONCURRENT_MAX: usize = 100;
-
-// 网站地址
-const DATA_URL: &str = "http://www.ddxsku.com";
-
-// 搜索地址
-const SEARCH_URL: &str = "http://www.ddxsku.com/search/";
-
-// 获取最后一条分页
-const SELECT_LAST_PAGE: &str = r#"a.last"#;
-
-// 获取小说列表
-const SELECT_NOVEL_TABLE: &str = r#"tbody > tr"#;
-
-// 获取列表中的小说条目
-const SELECT_NOVEL_ITEM: &str = r#"td"#;
-
-// 获取小说封面链接
-const SELECT_NOVEL_COVER: &str = r#"dl div.fl:first-of-type img"#;
-
-// 获取小说最近更新时间
-const SELECT_NOVEL_LAST_UPDATED_AT: &str =
-    r#"div.fl:last-of-type > table > tbody > tr:nth-of-type(2) > td:last-of-type"#;
-
-// 获取小说简介
-const SELECT_NOVEL_INTRO: &str = r#"dl#content > dd:last-of-type > p:nth-of-type(2)"#;
-
-// 获取最新章节
-const SELECT_NOVEL_LAST_SECTION: &str = r"dl#content > dd:last-of-type > p > a";
-
-// 获取小说状态
-const SELECT_NOVEL_STATE: &str =
-    "dl#content > dd:nth-of-type(2) > div > table > tbody > tr:first-of-type > td:last-of-type";
-
-// 获取小说章节
-const SELECT_NOVEL_SECTIONS: &str = r#"table#at > tbody > tr > td > a"#;
-
-// 获取小说内容
-const SELECT_NOVEL_CONTENT: &str = r#"dd#contents"#;
+pub mod profile;
+
+// 内置站点的爬虫id，与网站地址无关，更换域名后数据仍然属于同一个爬虫
+const DATA_ID: &str = profile::DDXSKU_ID;
+
+// 列表页中的一本小说：名字、链接、最新章节、章节链接、作者、更新时间、状态
+type ListNovel = (
+    String,
+    String,
+    Option<String>,
+    Option<String>,
+    String,
+    Option<ParsedDate>,
+    Option<NovelState>,
+);
 
 // 获取html中的属性
 macro_rules! elem_attr {
     ($doc: expr, attr=$name:expr, $or:tt) => {{
-        if let Some(x) = $doc.attr($name) {
-            x
-        } else {
-            $or
+        match $doc.attr($name) {
+            Some(x) => x,
+            None => $or,
         }
     }};
 }
@@ -79,19 +62,64 @@ macro_rules! elem_attr {
 // 获取html中文本
 macro_rules! elem_text {
     ($doc: expr, $or:tt) => {{
-        if let Some(x) = $doc.text() {
-            x
-        } else {
-            $or
+        match $doc.text() {
+            Some(x) => x,
+            None => $or,
         }
     }};
 }
 
+// 当前使用的站点描述与对应的并发限制
+#[derive(Debug)]
+pub struct Site {
+    pub profile: Profile,
+    smp: Arc<Semaphore>,
+}
+
+impl Site {
+    pub fn new(profile: Profile) -> Self {
+        Self {
+            smp: Arc::new(Semaphore::new(profile.concurrency)),
+            profile,
+        }
+    }
+
+    pub fn from_config(config: &SpiderConfig) -> Result<Self> {
+        Ok(Self::new(Profile::from_config(config)?))
+    }
+
+    // 数据库中保存的链接可能是旧域名，请求前替换为当前的网站地址
+    pub fn rebase(&self, link: &str) -> String {
+        let (mut url, base) = match (Url::parse(link), Url::parse(&self.profile.base_url)) {
+            (Ok(url), Ok(base)) => (url, base),
+            _ => return String::from(link),
+        };
+        if url.origin() == base.origin() {
+            return String::from(link);
+        }
+
+        let ok = url.set_scheme(base.scheme()).is_ok()
+            && url.set_host(base.host_str()).is_ok()
+            && url.set_port(base.port()).is_ok();
+        if ok {
+            url.to_string()
+        } else {
+            String::from(link)
+        }
+    }
+}
+
+// 通用的jieqi模板爬虫，由站点描述驱动，默认为ddxsku
 #[derive(Clone)]
 pub struct DDSpider {
     db: Arc<DbConn>,
-    smp: Arc<Semaphore>,
+    // 爬虫id，区分同一模板的不同站点
+    id: Arc<str>,
+    // 分类与小说记录的id服务
+    ids: IdGen,
+    site: Arc<RwLock<Arc<Site>>>,
     templates: Arc<(Tera, Vec<Sort>)>,
+    tracker: TaskTracker,
 }
 
 pub struct SortEntity {
@@ -99,21 +127,117 @@ pub struct SortEntity {
     pub link: String,
 }
 
+// 小说详情页中解析出的信息
+#[derive(Default)]
+struct NovelDetail {
+    cover: Option<String>,
+    updated_at: Option<ParsedDate>,
+    last_section: Option<String>,
+    state: Option<NovelState>,
+    intro: Option<String>,
+    category: Option<String>,
+    word_count: Option<u64>,
+    tags: Vec<String>,
+    aliases: Vec<String>,
+}
+
+// 解析字数，支持 "123456字" 与 "12.5万字" 两种格式
+pub fn parse_word_count(x: &str) -> Option<u64> {
+    let x = x.trim().trim_end_matches('字').trim();
+    let (num, unit) = match x.strip_suffix('万') {
+        Some(x) => (x.trim(), 10_000.0),
+        None => (x, 1.0),
+    };
+
+    let n: f64 = num.replace(',', "").parse().ok()?;
+    if !n.is_finite() || n < 0.0 {
+        return None;
+    }
+
+    Some((n * unit).round() as u64)
+}
+
 impl DDSpider {
     pub fn new(db: Arc<DbConn>) -> Self {
-        Self {
+        Self::with_profile(db, Profile::default()).expect("内置站点描述无效")
+    }
+
+    pub fn with_profile(db: Arc<DbConn>, profile: Profile) -> Result<Self> {
+        profile.validate()?;
+
+        let id = Arc::from(profile.id.as_str());
+
+        Ok(Self {
             db,
-            smp: Arc::new(Semaphore::new(DEFAULT_CONCURRENT_MAX)),
+            id,
+            ids: snowid::global(),
+            site: Arc::new(RwLock::new(Arc::new(Site::new(profile)))),
             templates: Arc::new((Tera::default(), vec![])),
+            tracker: TaskTracker::new(),
+        })
+    }
+
+    pub fn with_config(db: Arc<DbConn>, config: &SpiderConfig) -> Result<Self> {
+        Self::with_profile(db, Profile::from_config(config)?)
+    }
+
+    // 使用指定的id服务，通常与keeper共用
+    pub fn with_id_gen(mut self, ids: IdGen) -> Self {
+        self.ids = ids;
+        self
+    }
+
+    // 应用新的配置，正在执行的任务继续使用原来的设置
+    pub fn apply(&self, config: &SpiderConfig) -> Result<()> {
+        let site = Site::from_config(config)?;
+        if site.profile.id.as_str() != &*self.id {
+            bail!(
+                "站点id不能在运行时修改; id={}, new_id={}",
+                self.id,
+                site.profile.id
+            );
+        }
+
+        info!(
+            "应用站点配置; id={}, base_url={}, concurrency={}",
+            self.id, site.profile.base_url, site.profile.concurrency
+        );
+        *self.site.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(site);
+
+        Ok(())
+    }
+
+    // 当前实例的爬虫id，内置的ddxsku与 SpiderMetadata::id 相同
+    pub fn spider_id(&self) -> &str {
+        &self.id
+    }
+
+    // 注册到keeper的描述，内置的ddxsku以外的站点来自配置中的站点描述
+    pub fn info(&self) -> SpiderInfo {
+        let site = self.site();
+        SpiderInfo {
+            id: self.id.to_string(),
+            name: site.profile.name.clone(),
+            version: String::from(env!("CARGO_PKG_VERSION")),
+            source: if &*self.id == DATA_ID {
+                SourceKind::Builtin
+            } else {
+                SourceKind::Rule
+            },
+            capabilities: Self::SUPPORTED.into(),
         }
     }
 
+    pub fn site(&self) -> Arc<Site> {
+        self.site.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
     pub async fn set_sort(&mut self, data: &Vec<SortEntity>) -> Result<()> {
         // 开启事务
         let txn = self.db.begin().await?;
 
         // 删除原来的数据
-        clear_sort(&txn).await?;
+        clear_sort(&txn, &self.id).await?;
 
         // 新添加的数据的模板引擎
         let mut engine = Tera::default();
@@ -123,7 +247,7 @@ impl DDSpider {
             // 添加模板
             engine.add_raw_template(&x.name, &x.link)?;
             // 写入到数据
-            let id = add_or_recover(&txn, &x.name, &x.link).await?;
+            let id = add_or_recover(&txn, &self.ids, &self.id, &x.name, &x.link).await?;
             sorts.push(Sort {
                 id: id.into(),
                 name: String::from(&x.name),
@@ -139,7 +263,7 @@ impl DDSpider {
 
     pub async fn load_sorts(&mut self) -> Result<()> {
         let db: &DbConn = &self.db;
-        let x = sorts(db).await?;
+        let x = sorts(db, &self.id).await?;
 
         let mut engine = Tera::default();
         let mut sorts = Vec::new();
@@ -162,7 +286,6 @@ impl DDSpider {
             .1
             .iter()
             .find(|x| x.id == *sort_id)
-            .take()
             .map(|x| &x.name)
             .ok_or(CrawlError::ResourceNotFound)?;
 
@@ -171,7 +294,7 @@ impl DDSpider {
             .0
             .render(
                 name,
-                &Context::from_serialize(&json!({
+                &Context::from_serialize(json!({
                     "page": idx,
                 }
                 ))
@@ -179,37 +302,29 @@ impl DDSpider {
             )
             .map_err(|e| CrawlError::SpiderInnerFailed(e.into()))?;
 
-        Ok(link)
+        Ok(self.site().rebase(&link))
     }
 
     // 返回一个小说元素的迭代器
     fn novels_from_page<'a>(
         page: &'a WrapDocument,
-    ) -> impl Iterator<
-        Item = (
-            String,
-            String,
-            Option<String>,
-            Option<String>,
-            String,
-            Option<DateTime<Utc>>,
-            Option<NovelState>,
-        ),
-    > + 'a {
-        page.select(SELECT_NOVEL_TABLE)
+        profile: &'a Profile,
+    ) -> impl Iterator<Item = ListNovel> + 'a {
+        page.select(&profile.selectors.novel_table)
             .iter()
             .skip(1)
-            .map(|x| {
-                let r: Vec<WrapSelection<'_>> = x.select(SELECT_NOVEL_ITEM).iter().collect();
+            .map(move |x| {
+                let r: Vec<WrapSelection<'_>> =
+                    x.select(&profile.selectors.novel_item).iter().collect();
                 r
             })
-            .map(|x| {
+            .filter_map(move |x| {
                 // 获取小说名，若没有则失败
-                let name = elem_text!(x.get(0)?, {
+                let name = elem_text!(x.first()?, {
                     return None;
                 });
                 // 获取小说链接，若没有则失败
-                let link = elem_attr!(x.get(0)?.children(), attr = "href", {
+                let link = elem_attr!(x.first()?.children(), attr = "href", {
                     return None;
                 });
 
@@ -226,28 +341,13 @@ impl DDSpider {
                     .unwrap_or(String::from("unknown"));
 
                 // 获取最近更新时间
-                let last_updated_at: Option<DateTime<Utc>> = x
+                let last_updated_at: Option<ParsedDate> = x
                     .get(4)
                     .and_then(|x| x.text())
-                    .and_then(|x| {
-                        DateTime::parse_from_str(
-                            &format!("{x} 21:00:09 +08:00"),
-                            "%Y-%m-%d %H:%M:%S %z",
-                        )
-                        .ok()
-                    })
-                    .map(|x| x.into());
+                    .and_then(|x| profile.parse_list_date(&x));
 
                 // 获取完结状态
-                let state = x.get(5).and_then(|x| x.text()).and_then(|x| {
-                    let state = match x.trim() {
-                        "连载中" => NovelState::Updating,
-                        "完本" => NovelState::Finished,
-                        _ => NovelState::Updating,
-                    };
-
-                    Some(state)
-                });
+                let state = x.get(5).and_then(|x| x.text()).map(|x| profile.state(&x));
 
                 Some((
                     name,
@@ -259,95 +359,193 @@ impl DDSpider {
                     state,
                 ))
             })
-            .filter(|x| x.is_some())
-            .map(|x| x.unwrap())
     }
 
-    // 解析页面信息 封面链接，最近更新时间，最近更新章节，状态，简介
-    fn parse_detail_novel2(
-        page: &WrapDocument,
-    ) -> (
-        Option<String>,
-        Option<DateTime<Utc>>,
-        Option<String>,
-        Option<NovelState>,
-        Option<String>,
-    ) {
-        let cover = page.select(SELECT_NOVEL_COVER).attr("href");
+    // 解析页面信息 封面链接，最近更新时间，最近更新章节，状态，简介，分类，字数，标签，别名
+    // base为页面地址，用于将相对的封面链接转换为绝对链接
+    fn parse_detail_novel2(page: &WrapDocument, base: &str, profile: &Profile) -> NovelDetail {
+        let selectors = &profile.selectors;
+        let cover = page
+            .select(&selectors.novel_cover)
+            .attr("src")
+            .and_then(|x| {
+                Url::parse(base)
+                    .and_then(|base| base.join(x.trim()))
+                    .map(|x| x.to_string())
+                    .ok()
+            });
 
-        let updated_at: Option<DateTime<Utc>> = page
-            .select(SELECT_NOVEL_LAST_UPDATED_AT)
+        let updated_at: Option<ParsedDate> = page
+            .select(&selectors.novel_last_updated_at)
             .text()
-            .and_then(|x| {
-                DateTime::parse_from_str(&format!("{x} +08:00"), "%Y-%m-%d %H:%M:%S %z").ok()
-            })
-            .map(|x| x.into());
+            .and_then(|x| profile.parse_detail_date(&x));
 
-        let intro = page.select(SELECT_NOVEL_INTRO).text();
+        let intro = page.select(&selectors.novel_intro).text();
 
-        let last_section = page.select(SELECT_NOVEL_LAST_SECTION).text();
+        let last_section = page.select(&selectors.novel_last_section).text();
 
-        let state = page.select(SELECT_NOVEL_STATE).text().and_then(|x| {
-            let state = match x.trim() {
-                "连载中" => NovelState::Updating,
-                "完本" => NovelState::Finished,
-                _ => NovelState::Updating,
-            };
+        let state = page
+            .select(&selectors.novel_state)
+            .text()
+            .map(|x| profile.state(&x));
 
-            Some(state)
-        });
+        // 小说信息表格中的内容，标签与值交替出现
+        let info: Vec<String> = page
+            .select(&selectors.novel_info_cell)
+            .iter()
+            .map(|x| x.text().unwrap_or_default())
+            .collect();
+        let info_value = |label: &str| {
+            if label.is_empty() {
+                return None;
+            }
+            info.iter()
+                .position(|x| {
+                    x.replace(|c: char| c.is_whitespace() || c == '：' || c == ':', "")
+                        .contains(label)
+                })
+                .and_then(|idx| info.get(idx + 1))
+                .map(|x| x.trim().to_string())
+                .filter(|x| !x.is_empty())
+        };
 
-        (cover, updated_at, last_section, state, intro)
+        let category = info_value(&profile.info_labels.category);
+        let word_count =
+            info_value(&profile.info_labels.word_count).and_then(|x| parse_word_count(&x));
+        let split = |x: String| -> Vec<String> {
+            x.split(['/', '、', ',', '，'])
+                .map(|x| x.trim())
+                .filter(|x| !x.is_empty())
+                .map(String::from)
+                .collect()
+        };
+        let tags = info_value(&profile.info_labels.tags)
+            .map(split)
+            .unwrap_or_default();
+        let aliases = info_value(&profile.info_labels.aliases)
+            .map(split)
+            .unwrap_or_default();
+
+        NovelDetail {
+            cover,
+            updated_at,
+            last_section,
+            state,
+            intro,
+            category,
+            word_count,
+            tags,
+            aliases,
+        }
     }
 
-    // 获取封面链接，最近更新时间，简介
-    fn parse_detail_novel(
+    async fn parse_novels_from_page(
+        &self,
         page: &WrapDocument,
-    ) -> (Option<String>, Option<DateTime<Utc>>, Option<String>) {
-        let (cover, updated_at, _, _, intro) = Self::parse_detail_novel2(page);
-
-        (cover, updated_at, intro)
-    }
-
-    async fn parse_novels_from_page(&self, page: &WrapDocument) -> Vec<Result<Novel>> {
+        cancel: &CancellationToken,
+    ) -> Vec<Result<Novel>> {
         let mut handlers = Vec::with_capacity(10);
+        let site = self.site();
 
         for (name, link, last_section, section_link, author, mut last_updated_at, state) in
-            Self::novels_from_page(page)
+            Self::novels_from_page(page, &site.profile)
         {
-            let db = self.db.clone();
-            let permit = self.smp.clone().acquire_owned().await.unwrap();
-
-            let handler = tokio::spawn(async move {
-                // 获取小说详细信息
-                let mut cover = None;
-                let mut intro = None;
-                if let Some(x) = get(&link)
-                    .await
-                    .ok()
-                    .and_then(|x| Some(Self::parse_detail_novel(&WrapDocument::parse(&x))))
-                {
-                    last_updated_at = x.1;
-                    cover = x.0;
-                    intro = x.2;
+            // 没有章节链接时无法获取章节，跳过这一行并记为解析失败
+            let section_link = match section_link {
+                Some(x) => x,
+                None => {
+                    warn!("列表页中的小说没有章节链接; name: {name}, url: {link}");
+                    let e = CrawlError::ParseFailed;
+                    events::crawl_failed(&self.id, Some(&link), &e, Duration::ZERO);
+                    continue;
                 }
+            };
+            let db = self.db.clone();
+            let ids = self.ids.clone();
+            let spider_id = self.id.clone();
+            // 取消后不再派发新的任务
+            let permit = tokio::select! {
+                permit = site.smp.clone().acquire_owned() => permit.unwrap(),
+                _ = cancel.cancelled() => break,
+            };
+            let cancel = cancel.clone();
+            let site = site.clone();
+
+            let handler = self.tracker.spawn(async move {
+                let start = Instant::now();
+                let mut bytes = 0;
+
+                // 获取小说详细信息，取消时跳过详情页，但仍然保存列表页中已经获取到的信息
+                let detail = tokio::select! {
+                    x = fetch(&spider_id, &link) => match x {
+                        Ok(x) => Some(x),
+                        Err(e) => {
+                            // 详情页获取失败时只保存列表页中的信息
+                            warn!("获取小说详情页失败; url: {link}, err: {e}");
+                            let e = CrawlError::ParseFailed;
+                            events::crawl_failed(&spider_id, Some(&link), &e, start.elapsed());
+                            None
+                        }
+                    },
+                    _ = cancel.cancelled() => None,
+                };
+                let detail = match detail {
+                    Some(x) => {
+                        bytes = x.len() as u64;
+                        let detail = Self::parse_detail_novel2(
+                            &WrapDocument::parse(&x),
+                            &link,
+                            &site.profile,
+                        );
+                        // 列表页通常只有日期，使用精度更高的时间
+                        last_updated_at = match (last_updated_at, detail.updated_at) {
+                            (Some(a), Some(b)) if a.precision > b.precision => Some(a),
+                            (a, None) => a,
+                            (_, b) => b,
+                        };
+
+                        detail
+                    }
+                    None => NovelDetail::default(),
+                };
 
                 // 存储小说信息
-                let id =
-                    add_or_recover_novel(&db, &name, &link, &section_link.unwrap(), &author, "0")
-                        .await?;
+                let id = add_or_recover_novel(
+                    &db,
+                    &ids,
+                    &spider_id,
+                    &name,
+                    &link,
+                    &section_link,
+                    &author,
+                    "0",
+                )
+                .await?;
 
                 drop(permit);
 
+                events::publish(Event::NovelParsed(NovelParsed {
+                    spider_id: spider_id.to_string(),
+                    novel_id: id,
+                    name: name.clone(),
+                    bytes,
+                    elapsed: start.elapsed(),
+                }));
+
                 Ok::<Novel, anyhow::Error>(Novel {
                     id: id.into(),
                     name,
-                    cover,
+                    cover: detail.cover,
                     author,
-                    intro,
-                    last_updated_at,
+                    intro: detail.intro,
+                    last_updated_at: last_updated_at.map(|x| x.at),
                     last_updated_section_name: last_section,
                     state,
+                    word_count: detail.word_count,
+                    tags: detail.tags,
+                    category: detail.category,
+                    aliases: detail.aliases,
+                    source_url: Some(link),
                 })
             });
 
@@ -368,7 +566,13 @@ impl DDSpider {
     }
 
     #[async_recursion]
-    async fn send_novels(&self, id: &SortID, tx: Sender<spider::Result<Novel>>, pos: Position) {
+    async fn send_novels(
+        &self,
+        id: &SortID,
+        tx: Sender<spider::Result<Novel>>,
+        pos: Position,
+        cancel: CancellationToken,
+    ) {
         macro_rules! send_err_abort {
             ($expression:expr, $tx: expr) => {
                 match $expression {
@@ -392,15 +596,18 @@ impl DDSpider {
             };
         }
 
+        if cancel.is_cancelled() {
+            return;
+        }
+
         match pos {
             x @ (Position::Full | Position::First | Position::Last) => {
                 let first_url = send_err_abort!(self.render_sort_link(id, 1), tx);
 
                 let page = WrapDocument::parse(&send_err_abort!(
-                    get(&first_url).await.map_err(|e| CrawlError::Disconnect {
-                        seq: Some(1),
-                        reason: e,
-                    }),
+                    fetch(&self.id, &first_url)
+                        .await
+                        .map_err(|e| CrawlError::fetch(Some(1), e)),
                     tx
                 ));
 
@@ -411,20 +618,23 @@ impl DDSpider {
                     }
 
                     send_iter_or_abort!(
-                        self.parse_novels_from_page(&page)
+                        self.parse_novels_from_page(&page, &cancel)
                             .await
                             .into_iter()
-                            .map(|x| { x.map_err(|e| CrawlError::SpiderInnerFailed(e.into())) })
+                            .map(|x| x.map_err(CrawlError::SpiderInnerFailed))
                             .collect::<Vec<_>>(),
                         tx
                     );
                 }
 
-                let page_num: i32 = if let Some(last) = page.select(SELECT_LAST_PAGE).text() {
+                let last = page.select(&self.site().profile.selectors.last_page).text();
+                let page_num: i32 = if let Some(last) = last {
                     match last.parse() {
                         Ok(x) => x,
                         Err(_) => {
-                            let _ = tx.send(Err(CrawlError::ParseFailed)).await;
+                            let e = CrawlError::ParseFailed;
+                            events::crawl_failed(&self.id, Some(&first_url), &e, Duration::ZERO);
+                            let _ = tx.send(Err(e)).await;
                             return;
                         }
                     }
@@ -439,11 +649,13 @@ impl DDSpider {
                     }
                     Position::Full => {
                         return self
-                            .send_novels(id, tx, Position::Range(2..page_num + 1))
+                            .send_novels(id, tx, Position::Range(2..page_num + 1), cancel)
                             .await;
                     }
                     Position::Last => {
-                        return self.send_novels(id, tx, Position::Specify(page_num)).await;
+                        return self
+                            .send_novels(id, tx, Position::Specify(page_num), cancel)
+                            .await;
                     }
                     _ => unreachable!(),
                 }
@@ -457,34 +669,39 @@ impl DDSpider {
                 }
 
                 let page = WrapDocument::parse(&send_err_abort!(
-                    get(&page_link).await.map_err(|e| CrawlError::Disconnect {
-                        seq: Some(idx),
-                        reason: e,
-                    }),
+                    fetch(&self.id, &page_link)
+                        .await
+                        .map_err(|e| CrawlError::fetch(Some(idx), e)),
                     tx
                 ));
 
                 send_iter_or_abort!(
-                    self.parse_novels_from_page(&page)
+                    self.parse_novels_from_page(&page, &cancel)
                         .await
                         .into_iter()
-                        .map(|x| { x.map_err(|e| CrawlError::SpiderInnerFailed(e.into())) })
+                        .map(|x| x.map_err(CrawlError::SpiderInnerFailed))
                         .collect::<Vec<_>>(),
                     tx
                 );
             }
             Position::Range(range) => {
-                let smp = Arc::new(Semaphore::new(DEFAULT_CONCURRENT_MAX));
+                let smp = Arc::new(Semaphore::new(self.site().profile.concurrency));
                 for x in range {
-                    let id = id.clone();
+                    let id = *id;
                     let tx = tx.clone();
                     let runner = self.clone();
+                    let cancel = cancel.clone();
 
-                    let permit = smp.clone().acquire_owned().await.unwrap();
-                    // 对并发执行做限制
+                    // 对并发执行做限制，取消后不再派发新的页面
+                    let permit = tokio::select! {
+                        permit = smp.clone().acquire_owned() => permit.unwrap(),
+                        _ = cancel.cancelled() => break,
+                    };
                     info!("开始爬取页面编号 {x}");
-                    tokio::spawn(async move {
-                        runner.send_novels(&id, tx, Position::Specify(x)).await;
+                    self.tracker.spawn(async move {
+                        runner
+                            .send_novels(&id, tx, Position::Specify(x), cancel)
+                            .await;
 
                         drop(permit);
                     });
@@ -495,15 +712,17 @@ impl DDSpider {
 
     fn sections_from_page<'a>(
         page: &'a WrapDocument,
-    ) -> impl Iterator<Item = (String, Option<String>)> + 'a {
-        page.select(SELECT_NOVEL_SECTIONS)
+        selectors: &'a Selectors,
+    ) -> impl Iterator<Item = (usize, String, Option<String>)> + 'a {
+        page.select(&selectors.novel_sections)
             .iter()
             .enumerate()
             .map(|x| {
                 let name = x.1.text().unwrap_or(format!("unknown-{}", x.0 + 1));
                 let link = x.1.attr("href");
 
-                (name, link)
+                // 章节序号使用在目录中的位置，不受选取范围影响
+                (x.0, name, link)
             })
     }
 }
@@ -512,11 +731,20 @@ impl SpiderMetadata for DDSpider {
     const SUPPORTED: Support = Support {
         get_sort: true,
         get_novel_from_sort: true,
-        search_novel: false,
+        search_novel: true,
+        search_author: true,
     };
 
     fn id() -> &'static str {
-        DATA_URL
+        DATA_ID
+    }
+
+    fn migrations() -> Vec<Migration> {
+        vec![
+            migration!("20220628090800_sorts"),
+            migration!("20220708021410_novels"),
+            migration!("20220901090000_jieqi_sites"),
+        ]
     }
 }
 
@@ -530,15 +758,17 @@ impl Spider for DDSpider {
         &self,
         id: &SortID,
         pos: Position,
+        cancel: &CancellationToken,
     ) -> spider::Result<Receiver<spider::Result<Novel>>> {
         // 检查sort id 是否存在
         let _ = self.render_sort_link(id, 1)?;
 
         let (tx, rx) = channel(10);
         let runner = self.clone();
-        let id = id.clone();
-        tokio::spawn(async move {
-            runner.send_novels(&id, tx, pos).await;
+        let id = *id;
+        let cancel = cancel.clone();
+        self.tracker.spawn(async move {
+            runner.send_novels(&id, tx, pos, cancel).await;
         });
 
         Ok(rx)
@@ -548,6 +778,7 @@ impl Spider for DDSpider {
         &self,
         id: &NovelID,
         pos: Position,
+        cancel: &CancellationToken,
     ) -> spider::Result<Receiver<spider::Result<Section>>> {
         macro_rules! send_or_abort {
             ($tx:expr, $val: expr) => {
@@ -563,18 +794,22 @@ impl Spider for DDSpider {
                 return Err(CrawlError::ResourceNotFound);
             }
         };
-        let page = WrapDocument::parse(&get(&novel.section_link).await.map_err(|e| {
-            CrawlError::Disconnect {
-                seq: None,
-                reason: e,
-            }
-        })?);
+        let site = self.site();
+        let section_link = site.rebase(&novel.section_link);
+        let page = WrapDocument::parse(
+            &fetch(&self.id, &section_link)
+                .await
+                .map_err(|e| CrawlError::fetch(None, e))?,
+        );
 
         let (tx, rx) = channel(50);
-        let id = id.clone();
-        let smp = self.smp.clone();
-        tokio::spawn(async move {
-            let mut iter = Self::sections_from_page(&page);
+        let spider_id = self.id.clone();
+        let id = *id;
+        let smp = site.smp.clone();
+        let tracker = self.tracker.clone();
+        let cancel = cancel.clone();
+        self.tracker.spawn(async move {
+            let mut iter = Self::sections_from_page(&page, &site.profile.selectors);
             let sections = match pos {
                 Position::Full => iter.collect(),
                 pos @ (Position::First | Position::Last | Position::Specify(_)) => {
@@ -594,60 +829,72 @@ impl Spider for DDSpider {
                     v
                 }
                 Position::Range(range) => iter
-                    .enumerate()
-                    .filter(|(idx, _)| range.contains(&((*idx as i32) + 1)))
-                    .map(|(_, x)| x)
+                    .filter(|(idx, _, _)| range.contains(&((*idx as i32) + 1)))
                     .collect(),
             };
 
             let order_tx = WrapSender::wrap(tx.clone());
-            for x in sections.into_iter().enumerate() {
-                let seq = x.0;
-                let info = x.1;
-                if info.1.is_none() {
-                    send_or_abort!(tx, Err(CrawlError::MissSectionLink(seq as i32)));
-                    continue;
-                }
-
-                let link = info.1.unwrap();
+            for (seq, name, link) in sections {
+                let link = match link {
+                    Some(x) => x,
+                    None => {
+                        let e = CrawlError::MissSectionLink(seq as i32);
+                        events::crawl_failed(&spider_id, None, &e, Duration::ZERO);
+                        send_or_abort!(tx, Err(e));
+                        continue;
+                    }
+                };
 
-                let permit = smp.clone().acquire_owned().await.unwrap();
+                // 取消后不再派发新的章节
+                let permit = tokio::select! {
+                    permit = smp.clone().acquire_owned() => permit.unwrap(),
+                    _ = cancel.cancelled() => return,
+                };
                 let tx = match order_tx.permit_owned().await {
                     Ok(x) => x,
                     Err(_) => return,
                 };
+                let cancel = cancel.clone();
+                let site = site.clone();
+                let spider_id = spider_id.clone();
 
                 // 并发执行
-                tokio::spawn(async move {
-                    let doc = match get(&link).await {
+                tracker.spawn(async move {
+                    // 有序发送要求每个permit都必须发送，取消时也要发送一条错误
+                    let start = Instant::now();
+                    let doc = tokio::select! {
+                        x = fetch(&spider_id, &link) => x,
+                        _ = cancel.cancelled() => {
+                            tx.send(Err(CrawlError::Cancelled)).await;
+                            return;
+                        }
+                    };
+                    let doc = match doc {
                         Ok(x) => x,
                         Err(e) => {
-                            tx.send(Err(CrawlError::Disconnect {
-                                seq: Some(seq as i32),
-                                reason: e,
-                            }))
-                            .await;
+                            tx.send(Err(CrawlError::fetch(Some(seq as i32), e))).await;
                             return;
                         }
                     };
 
                     let page = WrapDocument::parse(&doc);
-                    let content = page.select(SELECT_NOVEL_CONTENT).text();
+                    let content = page.select(&site.profile.selectors.novel_content).text();
 
                     match content {
                         Some(doc) => {
                             tx.send(Ok(Section {
                                 seq: seq as u32,
                                 novel_id: id,
-                                name: info.0,
+                                name,
                                 update_at: None,
                                 text: doc,
                             }))
                             .await;
                         }
                         None => {
-                            tx.send(Err(CrawlError::MissSectionContent(seq as i32)))
-                                .await;
+                            let e = CrawlError::MissSectionContent(seq as i32);
+                            events::crawl_failed(&spider_id, Some(&link), &e, start.elapsed());
+                            tx.send(Err(e)).await;
                         }
                     }
 
@@ -668,54 +915,139 @@ impl Spider for DDSpider {
             }
         };
 
-        let doc = get(&novel.raw_link)
+        let site = self.site();
+        let raw_link = site.rebase(&novel.raw_link);
+        let doc = fetch(&self.id, &raw_link)
             .await
-            .map_err(|e| CrawlError::Disconnect {
-                seq: None,
-                reason: e,
-            })?;
+            .map_err(|e| CrawlError::fetch(None, e))?;
 
         let page = WrapDocument::parse(&doc);
 
-        let (cover, updated_at, last_section, state, intro) = Self::parse_detail_novel2(&page);
+        let detail = Self::parse_detail_novel2(&page, &raw_link, &site.profile);
 
         Ok(Novel {
             id: novel.id.into(),
             name: novel.name,
-            cover,
+            cover: detail.cover,
             author: novel.author,
-            intro,
-            last_updated_at: updated_at,
-            last_updated_section_name: last_section,
-            state,
+            intro: detail.intro,
+            last_updated_at: detail.updated_at.map(|x| x.at),
+            last_updated_section_name: detail.last_section,
+            state: detail.state,
+            word_count: detail.word_count,
+            tags: detail.tags,
+            category: detail.category,
+            aliases: detail.aliases,
+            source_url: Some(raw_link),
         })
     }
 
     async fn search(&self, name: &str) -> spider::Result<Vec<Novel>> {
-        let doc = CLIENT
-            .post(SEARCH_URL)
-            .form(&vec![("searchkey", name.trim())])
-            .send()
+        let kind = self.site().profile.search.by_name.clone();
+        self.search_by(&kind, name).await
+    }
+
+    async fn search_author(&self, author: &str) -> spider::Result<Vec<Novel>> {
+        let author = author.trim();
+        let kind = self.site().profile.search.by_author.clone();
+
+        // 网站的作者搜索是模糊匹配，只保留作者名完全相同的小说
+        Ok(self
+            .search_by(&kind, author)
+            .await?
+            .into_iter()
+            .filter(|x| x.author.trim() == author)
+            .collect())
+    }
+
+    async fn wait(&self) {
+        self.tracker.wait().await
+    }
+
+    async fn probe(&self, kind: ProbeKind, url: &str) -> spider::Result<Probe> {
+        let doc = fetch(&self.id, url)
             .await
-            .map_err(|e| CrawlError::Disconnect {
-                seq: None,
-                reason: e,
-            })?
-            .text()
+            .map_err(|e| CrawlError::fetch(None, e))?;
+        let page = WrapDocument::parse(&doc);
+        let site = self.site();
+        let profile = &site.profile;
+
+        let mut fields = BTreeMap::new();
+        let mut field = |name: &str, ok: bool| {
+            *fields.entry(String::from(name)).or_default() += ok as usize;
+        };
+
+        let (items, bytes) = match kind {
+            ProbeKind::Sort => {
+                // 第一行为表头
+                let rows = page.select(&profile.selectors.novel_table).iter().count();
+                for (_, _, last_section, section_link, author, last_updated_at, state) in
+                    Self::novels_from_page(&page, profile)
+                {
+                    field("name", true);
+                    field("last_section", last_section.is_some());
+                    field("section_link", section_link.is_some());
+                    field("author", author != "unknown");
+                    field("last_updated_at", last_updated_at.is_some());
+                    field("state", state.is_some());
+                }
+                (rows.saturating_sub(1), 0)
+            }
+            ProbeKind::Novel => {
+                let x = Self::parse_detail_novel2(&page, url, profile);
+                field("cover", x.cover.is_some());
+                field("last_updated_at", x.updated_at.is_some());
+                field("intro", x.intro.is_some());
+                field("last_section", x.last_section.is_some());
+                field("state", x.state.is_some());
+                field("category", x.category.is_some());
+                field("word_count", x.word_count.is_some());
+                field("tags", !x.tags.is_empty());
+                field("aliases", !x.aliases.is_empty());
+                (1, 0)
+            }
+            ProbeKind::Chapter => {
+                let content = page.select(&profile.selectors.novel_content).text();
+                field("content", content.is_some());
+                (1, content.map(|x| x.trim().len()).unwrap_or(0))
+            }
+        };
+
+        Ok(Probe {
+            items,
+            fields,
+            bytes,
+        })
+    }
+
+    fn permits(&self) -> Option<(usize, usize)> {
+        let site = self.site();
+        let total = site.profile.concurrency;
+
+        Some((total.saturating_sub(site.smp.available_permits()), total))
+    }
+}
+
+impl DDSpider {
+    // 使用网站的搜索功能，kind为搜索类型
+    async fn search_by(&self, kind: &str, key: &str) -> spider::Result<Vec<Novel>> {
+        let site = self.site();
+        let form = &site.profile.search;
+        let req = CLIENT.post(site.profile.search_url()).form(&vec![
+            (form.type_field.as_str(), kind),
+            (form.key_field.as_str(), key.trim()),
+        ]);
+        let (_, doc) = send(&self.id, req)
             .await
-            .map_err(|e| CrawlError::Disconnect {
-                seq: None,
-                reason: e,
-            })?;
+            .map_err(|e| CrawlError::fetch(None, e))?;
 
         let page = WrapDocument::parse(&doc);
 
         Ok(self
-            .parse_novels_from_page(&page)
+            .parse_novels_from_page(&page, &CancellationToken::new())
             .await
             .into_iter()
-            .filter(|x| x.is_ok())
-            .map(|x| x.unwrap())
+            .flatten()
             .collect())
     }
 }
@@ -3,19 +3,29 @@ use sea_orm::prelude::*;
 use sea_orm::ActiveValue::Set;
 use sea_orm::{Condition, ConnectionTrait, QuerySelect};
 
-use crate::common::snowid::id;
-
+use crate::common::snowid::IdGen;
 use crate::spider::{NovelID, SortID};
 
 pub mod novel;
 pub mod sort;
 
-pub async fn add_or_recover<T: ConnectionTrait>(db: &T, name: &str, link: &str) -> Result<i64> {
+// site为所属站点的爬虫id
+pub async fn add_or_recover<T: ConnectionTrait>(
+    db: &T,
+    ids: &IdGen,
+    site: &str,
+    name: &str,
+    link: &str,
+) -> Result<i64> {
     // 查询时候存在相同名字的分类
     let selector: Select<_> = sort::Entity::find();
     let x: Option<sort::Model> = selector
         .column(sort::Column::Id)
-        .filter(sort::Column::Name.eq(name))
+        .filter(
+            Condition::all()
+                .add(sort::Column::Site.eq(site))
+                .add(sort::Column::Name.eq(name)),
+        )
         .one(db)
         .await?;
 
@@ -32,12 +42,13 @@ pub async fn add_or_recover<T: ConnectionTrait>(db: &T, name: &str, link: &str)
             id
         }
         None => {
-            let id = id().await;
+            let id = ids.generate();
 
             let x: sort::ActiveModel = sort::Model {
                 id,
                 name: String::from(name),
                 link: String::from(link),
+                site: String::from(site),
             }
             .into();
 
@@ -60,21 +71,27 @@ pub async fn sort_by_id(db: &DbConn, id: &SortID) -> Result<Option<sort::Model>>
     Ok(x)
 }
 
-pub async fn clear_sort<T: ConnectionTrait>(db: &T) -> Result<()> {
-    let _ = sort::Entity::delete_many().exec(db).await?;
+pub async fn clear_sort<T: ConnectionTrait>(db: &T, site: &str) -> Result<()> {
+    let _ = sort::Entity::delete_many()
+        .filter(sort::Column::Site.eq(site))
+        .exec(db)
+        .await?;
 
     Ok(())
 }
 
-pub async fn sorts<T: ConnectionTrait>(db: &T) -> Result<Vec<sort::Model>> {
+pub async fn sorts<T: ConnectionTrait>(db: &T, site: &str) -> Result<Vec<sort::Model>> {
     let selector: Select<_> = sort::Entity::find();
-    let x = selector.all(db).await?;
+    let x = selector.filter(sort::Column::Site.eq(site)).all(db).await?;
 
     Ok(x)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn add_or_recover_novel(
     db: &DbConn,
+    ids: &IdGen,
+    site: &str,
     name: &str,
     link: &str,
     section_link: &str,
@@ -86,6 +103,7 @@ pub async fn add_or_recover_novel(
     let novel: Option<novel::Model> = selector
         .filter(
             Condition::all()
+                .add(novel::Column::Site.eq(site))
                 .add(novel::Column::Name.eq(name))
                 .add(novel::Column::Author.eq(author)),
         )
@@ -108,10 +126,11 @@ pub async fn add_or_recover_novel(
             id
         }
         None => {
-            let id = id().await;
+            let id = ids.generate();
 
             let x: novel::ActiveModel = novel::Model {
                 id,
+                site: String::from(site),
                 raw_id: String::from(raw_id),
                 name: String::from(name),
                 author: String::from(author),
@@ -5,6 +5,8 @@ use sea_orm::prelude::*;
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
     pub id: i64,
+    // 所属站点的爬虫id
+    pub site: String,
     pub raw_id: String,
     pub name: String,
     pub author: String,
@@ -8,6 +8,8 @@ pub struct Model {
     #[sea_orm(primary_key)]
     pub name: String,
     pub link: String,
+    // 所属站点的爬虫id
+    pub site: String,
 }
 
 #[derive(Debug, Copy, Clone, EnumIter)]
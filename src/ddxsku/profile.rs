@@ -0,0 +1,399 @@
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{FixedOffset, NaiveDate, NaiveDateTime};
+use log::warn;
+use reqwest::Url;
+use serde::Deserialize;
+
+use crate::common::date::{parse_offset, DateParser, ParsedDate, Precision};
+use crate::spider::{NovelState, SpiderConfig};
+
+// 内置的ddxsku站点，同时作为其他站点配置的默认值
+pub const DDXSKU_ID: &str = "ddxsku";
+
+// 使用jieqi模板的站点描述，相同模板的站点只需要修改差异的部分
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    // 爬虫id，用于区分不同站点的数据，设置后不能修改；站点描述文件中必须设置
+    pub id: String,
+    pub name: String,
+    // 网站地址
+    pub base_url: String,
+    // 搜索地址，相对路径基于base_url
+    pub search_path: String,
+    // 最大并发请求数
+    pub concurrency: usize,
+    pub search: SearchForm,
+    pub selectors: Selectors,
+    pub info_labels: InfoLabels,
+    pub states: StateLabels,
+    pub dates: DateFormats,
+}
+
+// 搜索表单的字段
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchForm {
+    pub type_field: String,
+    pub key_field: String,
+    // 按小说名搜索
+    pub by_name: String,
+    // 按作者搜索
+    pub by_author: String,
+}
+
+// 页面解析使用的css选择器
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Selectors {
+    // 获取最后一条分页
+    pub last_page: String,
+    // 获取小说列表
+    pub novel_table: String,
+    // 获取列表中的小说条目
+    pub novel_item: String,
+    // 获取小说封面链接
+    pub novel_cover: String,
+    // 获取小说最近更新时间
+    pub novel_last_updated_at: String,
+    // 获取小说简介
+    pub novel_intro: String,
+    // 获取最新章节
+    pub novel_last_section: String,
+    // 获取小说状态
+    pub novel_state: String,
+    // 获取小说信息表格中的单元格
+    pub novel_info_cell: String,
+    // 获取小说章节
+    pub novel_sections: String,
+    // 获取小说内容
+    pub novel_content: String,
+}
+
+// 小说信息表格中的标签
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InfoLabels {
+    pub category: String,
+    pub word_count: String,
+    // 标签与别名，多个值以 / 、 , 分隔；网站没有时设置为空
+    pub tags: String,
+    pub aliases: String,
+}
+
+// 小说状态的文字，都不匹配的状态视为连载中
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StateLabels {
+    pub updating: Vec<String>,
+    pub finished: Vec<String>,
+}
+
+// 时间格式，按顺序尝试，都不匹配时使用通用的中文时间解析
+// 只有日期的格式视为当天零点
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DateFormats {
+    // 网站时区，格式为 +08:00
+    pub timezone: String,
+    // 列表页中的更新时间
+    pub list: Vec<String>,
+    // 详情页中的更新时间
+    pub detail: Vec<String>,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            id: String::from(DDXSKU_ID),
+            name: String::from("ddxsku"),
+            base_url: String::from("http://www.ddxsku.com"),
+            search_path: String::from("/search/"),
+            concurrency: 100,
+            search: SearchForm::default(),
+            selectors: Selectors::default(),
+            info_labels: InfoLabels::default(),
+            states: StateLabels::default(),
+            dates: DateFormats::default(),
+        }
+    }
+}
+
+impl Default for SearchForm {
+    fn default() -> Self {
+        Self {
+            type_field: String::from("searchtype"),
+            key_field: String::from("searchkey"),
+            by_name: String::from("articlename"),
+            by_author: String::from("author"),
+        }
+    }
+}
+
+impl Default for Selectors {
+    fn default() -> Self {
+        Self {
+            last_page: String::from(r#"a.last"#),
+            novel_table: String::from(r#"tbody > tr"#),
+            novel_item: String::from(r#"td"#),
+            novel_cover: String::from(r#"dl div.fl:first-of-type img"#),
+            novel_last_updated_at: String::from(
+                r#"div.fl:last-of-type > table > tbody > tr:nth-of-type(2) > td:last-of-type"#,
+            ),
+            novel_intro: String::from(r#"dl#content > dd:last-of-type > p:nth-of-type(2)"#),
+            novel_last_section: String::from(r"dl#content > dd:last-of-type > p > a"),
+            novel_state: String::from(
+                "dl#content > dd:nth-of-type(2) > div > table > tbody > tr:first-of-type > td:last-of-type",
+            ),
+            novel_info_cell: String::from(
+                "dl#content > dd:nth-of-type(2) > div > table > tbody > tr > td",
+            ),
+            novel_sections: String::from(r#"table#at > tbody > tr > td > a"#),
+            novel_content: String::from(r#"dd#contents"#),
+        }
+    }
+}
+
+impl Default for InfoLabels {
+    fn default() -> Self {
+        Self {
+            category: String::from("类别"),
+            word_count: String::from("全文长度"),
+            tags: String::from("标签"),
+            aliases: String::from("别名"),
+        }
+    }
+}
+
+impl Default for StateLabels {
+    fn default() -> Self {
+        Self {
+            updating: vec![String::from("连载中")],
+            finished: vec![String::from("完本")],
+        }
+    }
+}
+
+impl Default for DateFormats {
+    fn default() -> Self {
+        Self {
+            timezone: String::from("+08:00"),
+            list: Vec::new(),
+            detail: Vec::new(),
+        }
+    }
+}
+
+impl Selectors {
+    // 按名称覆盖选择器，名称与字段名相同
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        let x = match name {
+            "last_page" => &mut self.last_page,
+            "novel_table" => &mut self.novel_table,
+            "novel_item" => &mut self.novel_item,
+            "novel_cover" => &mut self.novel_cover,
+            "novel_last_updated_at" => &mut self.novel_last_updated_at,
+            "novel_intro" => &mut self.novel_intro,
+            "novel_last_section" => &mut self.novel_last_section,
+            "novel_state" => &mut self.novel_state,
+            "novel_info_cell" => &mut self.novel_info_cell,
+            "novel_sections" => &mut self.novel_sections,
+            "novel_content" => &mut self.novel_content,
+            _ => bail!("未知的选择器: {name}"),
+        };
+        *x = String::from(value);
+
+        Ok(())
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&'static str, &str)> {
+        [
+            ("last_page", &self.last_page),
+            ("novel_table", &self.novel_table),
+            ("novel_item", &self.novel_item),
+            ("novel_cover", &self.novel_cover),
+            ("novel_last_updated_at", &self.novel_last_updated_at),
+            ("novel_intro", &self.novel_intro),
+            ("novel_last_section", &self.novel_last_section),
+            ("novel_state", &self.novel_state),
+            ("novel_info_cell", &self.novel_info_cell),
+            ("novel_sections", &self.novel_sections),
+            ("novel_content", &self.novel_content),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k, v.as_str()))
+    }
+}
+
+impl Profile {
+    // 根据扩展名解析 toml 或 yaml 格式的站点描述
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read_to_string(path)
+            .with_context(|| format!("读取站点描述失败; path={}", path.display()))?;
+
+        let ext = path
+            .extension()
+            .and_then(|x| x.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        Self::parse(&data, &ext)
+            .with_context(|| format!("解析站点描述失败; path={}", path.display()))
+    }
+
+    // 站点描述中必须设置id，未出现的其他项使用ddxsku的值
+    pub fn parse(data: &str, format: &str) -> Result<Self> {
+        let value: toml::Value = match format {
+            "toml" => toml::from_str(data)?,
+            "yaml" | "yml" => serde_yaml::from_str(data)?,
+            x => bail!("不支持的站点描述格式: {x}"),
+        };
+        // 不同站点的数据通过id区分，不能使用ddxsku的默认值，也不能随base_url变化
+        if value.get("id").is_none() {
+            bail!("站点描述必须设置 id");
+        }
+
+        let profile: Profile = value.try_into()?;
+        profile.validate()?;
+
+        Ok(profile)
+    }
+
+    // 根据爬虫配置得到站点描述：先加载profile文件或内置描述，再应用覆盖项
+    pub fn from_config(config: &SpiderConfig) -> Result<Self> {
+        config.validate()?;
+
+        let mut profile = match &config.profile {
+            Some(path) => Self::load(path)?,
+            None => Self::default(),
+        };
+
+        if let Some(x) = &config.id {
+            profile.id = x.clone();
+        }
+        if let Some(x) = &config.base_url {
+            profile.base_url = x.clone();
+        }
+        if let Some(x) = &config.search_url {
+            profile.search_path = x.clone();
+        }
+        if let Some(x) = config.concurrency {
+            profile.concurrency = x;
+        }
+        for (name, x) in &config.selectors {
+            profile.selectors.set(name, x)?;
+        }
+
+        profile.validate()?;
+
+        Ok(profile)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.id.trim().is_empty() {
+            bail!("id 不能为空");
+        }
+
+        let base =
+            Url::parse(&self.base_url).with_context(|| format!("base_url={}", self.base_url))?;
+        if !matches!(base.scheme(), "http" | "https") {
+            bail!("base_url 只支持 http 与 https: {}", self.base_url);
+        }
+        base.join(&self.search_path)
+            .with_context(|| format!("search_path={}", self.search_path))?;
+
+        if self.concurrency == 0 {
+            bail!("concurrency 必须大于0");
+        }
+
+        for (name, x) in self.selectors.iter() {
+            if x.trim().is_empty() {
+                bail!("selectors.{name} 不能为空");
+            }
+        }
+
+        self.timezone()?;
+
+        Ok(())
+    }
+
+    pub fn search_url(&self) -> String {
+        Url::parse(&self.base_url)
+            .and_then(|x| x.join(&self.search_path))
+            .map(|x| x.to_string())
+            .unwrap_or_else(|_| self.search_path.clone())
+    }
+
+    pub fn timezone(&self) -> Result<FixedOffset> {
+        parse_offset(&self.dates.timezone)
+            .ok_or_else(|| anyhow!("dates.timezone 格式错误: {}", self.dates.timezone))
+    }
+
+    // 未配置的状态文字视为连载中，记录日志以便补充站点描述
+    pub fn state(&self, label: &str) -> NovelState {
+        let label = label.trim();
+        if self.states.finished.iter().any(|x| x == label) {
+            NovelState::Finished
+        } else {
+            if !self.states.updating.iter().any(|x| x == label) {
+                warn!(
+                    "未知的小说状态，视为连载中; site={}, state={label}",
+                    self.id
+                );
+            }
+            NovelState::Updating
+        }
+    }
+
+    pub fn date_parser(&self) -> DateParser {
+        DateParser::new(
+            self.timezone()
+                .unwrap_or_else(|_| FixedOffset::east(8 * 3600)),
+        )
+    }
+
+    pub fn parse_list_date(&self, x: &str) -> Option<ParsedDate> {
+        self.parse_date(x, &self.dates.list)
+    }
+
+    pub fn parse_detail_date(&self, x: &str) -> Option<ParsedDate> {
+        self.parse_date(x, &self.dates.detail)
+    }
+
+    fn parse_date(&self, x: &str, formats: &[String]) -> Option<ParsedDate> {
+        let x = x.trim();
+        let parser = self.date_parser();
+
+        formats
+            .iter()
+            .find_map(|f| {
+                let (t, precision) = match NaiveDateTime::parse_from_str(x, f) {
+                    Ok(t) => (t, format_precision(f)),
+                    Err(_) => {
+                        let t = NaiveDate::parse_from_str(x, f).ok()?.and_hms(0, 0, 0);
+                        (t, Precision::Day)
+                    }
+                };
+
+                Some(ParsedDate {
+                    at: parser.to_utc(&t)?,
+                    precision,
+                })
+            })
+            .or_else(|| parser.parse(x))
+    }
+}
+
+fn format_precision(f: &str) -> Precision {
+    if f.contains("%S") || f.contains("%T") {
+        Precision::Second
+    } else if f.contains("%M") || f.contains("%R") {
+        Precision::Minute
+    } else if f.contains("%H") {
+        Precision::Hour
+    } else {
+        Precision::Day
+    }
+}
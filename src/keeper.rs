@@ -1,58 +1,240 @@
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+use anyhow::{anyhow, bail, Context, Result};
 use chrono::Duration;
+use futures::future::join_all;
+use sea_orm::DbConn;
+use serde::Deserialize;
 
-use crate::spider::{Spider, SpiderMetadata, Support};
+use crate::common::migrate::{Migration, Migrator};
+use crate::common::shutdown::CancellationToken;
+use crate::common::snowid::{self, IdGen};
+use crate::config::parse_duration;
+use crate::keeper::content::StoredSections;
+use crate::keeper::cover::CoverStore;
+use crate::keeper::notify::Notifier;
+use crate::keeper::queue::SectionSink;
+use crate::migration;
+use crate::spider::{Capabilities, Spider, SpiderInfo, SpiderMetadata};
 
+pub mod author;
+pub mod canary;
+pub mod content;
+pub mod cookies;
+pub mod cover;
 pub mod data;
+pub mod follow;
+pub mod fulltext;
+pub mod metrics;
+pub mod notify;
+pub mod plan;
+pub mod queue;
+pub mod registry;
+pub mod search;
+
+// keeper自身迁移的所有者名称
+pub const KEEPER_MIGRATION_OWNER: &str = "keeper";
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Policy {
     pub sort_update_interval: Duration,
+    // 没有任务时，worker再次检查任务队列的间隔
+    pub job_poll_interval: Duration,
+    // 任务最多执行的次数，超过后标记为失败
+    pub job_max_attempts: i32,
+    // 单个爬虫搜索的超时时间
+    pub search_timeout: Duration,
 }
 
 impl Default for Policy {
     fn default() -> Self {
         Self {
             sort_update_interval: Duration::days(7),
+            job_poll_interval: Duration::seconds(5),
+            job_max_attempts: 5,
+            search_timeout: Duration::seconds(10),
         }
     }
 }
 
 struct PropertySpider {
-    id: &'static str,
-    supported: &'static Support,
-    inner: Box<dyn Spider>,
+    id: Arc<str>,
+    info: SpiderInfo,
+    // 停用的爬虫不参与搜索，也不领取任务
+    enabled: AtomicBool,
+    // 来源评分，合并搜索结果时评分高的来源排在前面
+    score: AtomicI32,
+    // 健康检查未通过的原因，不为空时暂停该爬虫的任务
+    degraded: RwLock<Option<String>>,
+    // 正在执行的任务数
+    running: AtomicUsize,
+    // keeper在多个任务之间共享，爬虫需要能够跨线程移动
+    inner: Box<dyn Spider + Send + Sync>,
 }
 
 impl PropertySpider {
-    fn new(id: &'static str, supported: &'static Support, inner: Box<dyn Spider>) -> Self {
+    fn new(id: Arc<str>, info: SpiderInfo, inner: Box<dyn Spider + Send + Sync>) -> Self {
         Self {
             id,
-            supported,
+            info,
+            enabled: AtomicBool::new(true),
+            score: AtomicI32::new(0),
+            degraded: RwLock::new(None),
+            running: AtomicUsize::new(0),
             inner,
         }
     }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities().unwrap_or(self.info.capabilities)
+    }
 }
 
-#[derive(Default)]
 pub struct Keeper {
+    db: Arc<DbConn>,
+    // 新记录的id服务
+    ids: IdGen,
     spiders: Vec<PropertySpider>,
-    policy: Policy,
+    policy: RwLock<Policy>,
+    cancel: CancellationToken,
+    section_sink: Option<Arc<dyn SectionSink>>,
+    sections: Option<Arc<StoredSections>>,
+    covers: Option<CoverStore>,
+    notifier: Option<Arc<dyn Notifier>>,
+    migrator: Migrator,
+}
+
+// keeper自身数据的迁移
+pub fn migrations() -> Vec<Migration> {
+    vec![
+        migration!("20220801093000_crawl_jobs"),
+        migration!("20220805101500_authors"),
+        migration!("20220812090000_keeper_novels"),
+        migration!("20220820143000_sections"),
+        migration!("20220825100000_fulltext"),
+        migration!("20220905100000_follows"),
+        migration!("20220910090000_cookie_jars"),
+    ]
 }
 
 impl Keeper {
-    pub fn new() -> Self {
-        Keeper::default()
+    pub fn new(db: Arc<DbConn>) -> Self {
+        Self::with_policy(db, Policy::default())
+    }
+
+    pub fn with_policy(db: Arc<DbConn>, policy: Policy) -> Self {
+        let mut migrator = Migrator::new();
+        migrator.register(KEEPER_MIGRATION_OWNER, migrations());
+
+        Self {
+            db,
+            ids: snowid::global(),
+            spiders: Vec::new(),
+            policy: RwLock::new(policy),
+            cancel: CancellationToken::new(),
+            section_sink: None,
+            sections: None,
+            covers: None,
+            notifier: None,
+            migrator,
+        }
+    }
+
+    pub fn add_spider<T>(&mut self, spider: T) -> Result<()>
+    where
+        T: SpiderMetadata + Spider + Send + 'static,
+    {
+        self.add_spider_as(T::id(), spider)
     }
 
-    pub fn add_spider<T>(&mut self, spider: T)
+    // 以指定的id添加爬虫，用于同一类型的爬虫抓取多个站点，迁移仍然按类型注册一次
+    pub fn add_spider_as<T>(&mut self, id: &str, spider: T) -> Result<()>
     where
-        T: SpiderMetadata + Spider + 'static,
+        T: SpiderMetadata + Spider + Send + 'static,
     {
-        self.spiders.push(PropertySpider::new(
-            T::id(),
-            &T::SUPPORTED,
-            Box::new(spider),
-        ))
+        let info = SpiderInfo {
+            id: String::from(id),
+            ..T::info()
+        };
+        self.add_spider_info(info, spider)
+    }
+
+    // 以指定的描述添加爬虫，迁移按类型注册一次
+    pub fn add_spider_info<T>(&mut self, info: SpiderInfo, spider: T) -> Result<()>
+    where
+        T: SpiderMetadata + Spider + Send + 'static,
+    {
+        self.migrator.register(T::id(), T::migrations());
+        self.register(info, Box::new(spider))
+    }
+
+    pub fn db(&self) -> &DbConn {
+        &self.db
+    }
+
+    pub fn id_gen(&self) -> &IdGen {
+        &self.ids
+    }
+
+    // 替换id服务，需要在 set_content_store 之前调用
+    pub fn set_id_gen(&mut self, ids: IdGen) {
+        self.ids = ids;
+    }
+
+    pub fn migrator(&self) -> &Migrator {
+        &self.migrator
+    }
+
+    // 执行keeper与爬虫注册的所有未执行的迁移
+    pub async fn migrate(&self) -> Result<()> {
+        let _ = self.migrator.up(&self.db).await?;
+
+        Ok(())
+    }
+
+    pub fn policy(&self) -> Policy {
+        self.policy
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    // 替换策略，运行中的worker在下一次检查任务时使用新的策略
+    pub fn set_policy(&self, policy: Policy) {
+        *self.policy.write().unwrap_or_else(|e| e.into_inner()) = policy;
+    }
+
+    // 设置爬虫的来源评分
+    pub fn set_spider_score(&self, id: &str, score: i32) -> Result<()> {
+        let x = self
+            .spider(id)
+            .ok_or_else(|| anyhow!("爬虫不存在; spider_id={id}"))?;
+        x.score.store(score, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    fn spider(&self, id: &str) -> Option<&PropertySpider> {
+        self.spiders.iter().find(|x| &*x.id == id)
+    }
+
+    // 获取一个取消令牌，传递给爬虫调用，keeper关闭时会一并取消
+    pub fn cancel_token(&self) -> CancellationToken {
+        self.cancel.child_token()
+    }
+
+    // 取消所有正在执行的爬取，并在期限内等待爬虫的任务结束
+    pub async fn shutdown(&self, deadline: std::time::Duration) -> Result<()> {
+        self.cancel.cancel();
+
+        let wait = join_all(self.spiders.iter().map(|x| x.inner.wait()));
+        let r = tokio::time::timeout(deadline, wait).await;
+        // 超时也保存已经获得的cookie
+        self.save_all_cookies().await;
+        r.map_err(|_| anyhow!("等待爬虫任务结束超时; deadline={deadline:?}"))?;
+
+        Ok(())
     }
 
     // pub async fn run(&mut self) {
@@ -105,3 +287,48 @@ impl Keeper {
     //     }
     // }
 }
+
+// 时间间隔使用 "30s", "5m", "2h", "7d" 的格式，纯数字表示秒
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PolicyConfig {
+    pub sort_update_interval: String,
+    pub job_poll_interval: String,
+    pub job_max_attempts: i32,
+    pub search_timeout: String,
+}
+
+impl Default for PolicyConfig {
+    fn default() -> Self {
+        Self {
+            sort_update_interval: String::from("7d"),
+            job_poll_interval: String::from("5s"),
+            job_max_attempts: 5,
+            search_timeout: String::from("10s"),
+        }
+    }
+}
+
+impl PolicyConfig {
+    pub fn to_policy(&self) -> Result<Policy> {
+        let positive = |name: &str, x: &str| -> Result<Duration> {
+            let d = parse_duration(x).with_context(|| format!("{name}={x}"))?;
+            if d <= Duration::zero() {
+                bail!("{name} 必须大于0");
+            }
+
+            Ok(d)
+        };
+
+        if self.job_max_attempts < 1 {
+            bail!("job_max_attempts 必须大于0");
+        }
+
+        Ok(Policy {
+            sort_update_interval: positive("sort_update_interval", &self.sort_update_interval)?,
+            job_poll_interval: positive("job_poll_interval", &self.job_poll_interval)?,
+            job_max_attempts: self.job_max_attempts,
+            search_timeout: positive("search_timeout", &self.search_timeout)?,
+        })
+    }
+}
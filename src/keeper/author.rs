@@ -0,0 +1,99 @@
+use std::sync::atomic::Ordering;
+
+use anyhow::Result;
+use futures::future::join_all;
+use log::warn;
+
+use crate::keeper::data::{author, novel};
+use crate::keeper::Keeper;
+use crate::spider::{CrawlError, NovelID};
+
+// 作者索引中的一本小说，sources为关联的爬虫以及小说在爬虫中的id
+#[derive(Debug)]
+pub struct AuthorNovel {
+    pub novel_id: i64,
+    pub name: String,
+    pub sources: Vec<(String, NovelID)>,
+}
+
+#[derive(Debug)]
+pub struct AuthorResult {
+    pub author_id: Option<i64>,
+    pub novels: Vec<AuthorNovel>,
+    // 搜索失败的爬虫
+    pub failed: Vec<(String, CrawlError)>,
+}
+
+impl Keeper {
+    // 在所有支持作者搜索的爬虫中搜索作者，并将结果写入作者索引
+    pub async fn search_author(&self, name: &str) -> Result<AuthorResult> {
+        let name = name.trim();
+        let timeout = self
+            .policy()
+            .search_timeout
+            .to_std()
+            .unwrap_or(std::time::Duration::from_secs(10));
+
+        let tasks = self
+            .spiders
+            .iter()
+            .filter(|x| x.capabilities().search_author && x.enabled.load(Ordering::Relaxed))
+            .map(|x| async move {
+                let r = match tokio::time::timeout(timeout, x.inner.search_author(name)).await {
+                    Ok(r) => r,
+                    Err(_) => Err(CrawlError::Timeout),
+                };
+
+                (x, r)
+            });
+
+        let mut failed = Vec::new();
+        for (spider, r) in join_all(tasks).await {
+            let novels = match r {
+                Ok(x) => x,
+                Err(e) => {
+                    warn!(
+                        "搜索作者失败; spider_id={}, author={name}, err={e}",
+                        spider.id
+                    );
+                    failed.push((spider.id.to_string(), e));
+                    continue;
+                }
+            };
+
+            for x in novels.iter().filter(|x| x.author.trim() == name) {
+                novel::add_or_recover(&self.db, &self.ids, &spider.id, x).await?;
+            }
+        }
+
+        author::add_or_recover(&self.db, &self.ids, name, true).await?;
+
+        let mut r = self.author_novels(name).await?;
+        r.failed = failed;
+
+        Ok(r)
+    }
+
+    // 从作者索引中获取作者的全部小说，不会访问爬虫
+    pub async fn author_novels(&self, name: &str) -> Result<AuthorResult> {
+        let author_id = author::author_by_name(&self.db, name).await?.map(|x| x.id);
+        let novels = author::novels(&self.db, name)
+            .await?
+            .into_iter()
+            .map(|(x, relations)| AuthorNovel {
+                novel_id: x.id,
+                name: x.name,
+                sources: relations
+                    .into_iter()
+                    .map(|x| (x.spider_kind_id, x.spider_novel_id.into()))
+                    .collect(),
+            })
+            .collect();
+
+        Ok(AuthorResult {
+            author_id,
+            novels,
+            failed: Vec::new(),
+        })
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::Duration;
+use log::{error, info, warn};
+use reqwest::Url;
+use serde::Deserialize;
+
+use crate::config::parse_duration;
+
+use crate::keeper::Keeper;
+use crate::spider::{CrawlError, Probe, ProbeKind};
+
+// 爬虫的解析健康检查，使用已知的页面检查网站页面结构是否变化
+#[derive(Debug, Clone, PartialEq)]
+pub struct Canary {
+    pub interval: Duration,
+    pub pages: Vec<CanaryPage>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CanaryPage {
+    pub kind: ProbeKind,
+    pub url: String,
+    // 页面中至少需要的条目数
+    pub min_items: usize,
+    // 正文至少需要的字节数，章节页使用
+    pub min_bytes: usize,
+    // 字段名与至少需要成功解析的条目比例
+    pub fields: BTreeMap<String, f64>,
+}
+
+impl CanaryPage {
+    // 返回不满足要求的项，为空时检查通过
+    pub fn check(&self, probe: &Probe) -> Vec<String> {
+        let mut v = Vec::new();
+        let kind = self.kind.as_str();
+
+        if probe.items < self.min_items {
+            v.push(format!(
+                "{kind} 页面条目数 {} 少于 {}; url={}",
+                probe.items, self.min_items, self.url
+            ));
+        }
+        if probe.bytes < self.min_bytes {
+            v.push(format!(
+                "{kind} 页面正文 {} 字节少于 {}; url={}",
+                probe.bytes, self.min_bytes, self.url
+            ));
+        }
+
+        for (name, threshold) in &self.fields {
+            let n = probe.fields.get(name).copied().unwrap_or(0);
+            let ratio = if probe.items == 0 {
+                0.0
+            } else {
+                n as f64 / probe.items as f64
+            };
+            if ratio < *threshold {
+                v.push(format!(
+                    "{kind} 页面字段 {name} 解析比例 {ratio:.2} 低于 {threshold}; url={}",
+                    self.url
+                ));
+            }
+        }
+
+        v
+    }
+}
+
+impl Keeper {
+    // 标记爬虫为异常，异常的爬虫不再领取任务，正在执行的任务在下一页时暂停
+    // reason为None时恢复
+    pub fn set_degraded(&self, id: &str, reason: Option<String>) -> Result<()> {
+        let x = self
+            .spider(id)
+            .ok_or_else(|| anyhow!("爬虫不存在; spider_id={id}"))?;
+        *x.degraded.write().unwrap_or_else(|e| e.into_inner()) = reason;
+
+        Ok(())
+    }
+
+    // 爬虫异常的原因，正常时返回None
+    pub fn degraded(&self, id: &str) -> Option<String> {
+        self.spider(id)
+            .and_then(|x| x.degraded.read().unwrap_or_else(|e| e.into_inner()).clone())
+    }
+
+    // 异常或停用的爬虫暂停任务
+    pub(crate) fn is_paused(&self, id: &str) -> bool {
+        !self.is_enabled(id) || self.degraded(id).is_some()
+    }
+
+    // 执行一次健康检查，不通过时标记爬虫为异常，通过时恢复
+    // 网络错误无法判断页面结构，不改变爬虫状态
+    // 爬虫不支持检查页面时属于配置错误，返回 CrawlError::Unsupported，同样不改变爬虫状态
+    pub async fn run_canary(&self, id: &str, canary: &Canary) -> Result<Vec<String>> {
+        let spider = self
+            .spider(id)
+            .ok_or_else(|| anyhow!("爬虫不存在; spider_id={id}"))?;
+
+        let mut failures = Vec::new();
+        for page in &canary.pages {
+            match spider.inner.probe(page.kind, &page.url).await {
+                Ok(x) => failures.extend(page.check(&x)),
+                Err(e @ (CrawlError::Disconnect { .. } | CrawlError::Timeout)) => {
+                    return Err(anyhow!("获取健康检查页面失败; url={}, err={e}", page.url));
+                }
+                Err(e @ CrawlError::Unsupported) => {
+                    return Err(anyhow::Error::new(e).context(format!(
+                        "爬虫不支持健康检查，请检查canary配置; spider_id={id}, kind={}",
+                        page.kind.as_str()
+                    )));
+                }
+                Err(e) => failures.push(format!(
+                    "{} 页面解析失败; url={}, err={e}",
+                    page.kind.as_str(),
+                    page.url
+                )),
+            }
+        }
+
+        if failures.is_empty() {
+            if self.degraded(id).is_some() {
+                info!("爬虫健康检查通过，恢复任务; spider_id={id}");
+            }
+            self.set_degraded(id, None)?;
+        } else {
+            warn!(
+                "爬虫健康检查未通过，暂停任务; spider_id={id}, failures={}",
+                failures.join("; ")
+            );
+            self.set_degraded(id, Some(failures.join("; ")))?;
+        }
+
+        Ok(failures)
+    }
+
+    // 按各自的间隔定期执行健康检查，直到keeper被关闭
+    // 不支持健康检查的爬虫只记录一次错误，之后跳过
+    pub async fn run_canaries(&self, canaries: Vec<(String, Canary)>) {
+        if canaries.is_empty() {
+            return;
+        }
+
+        // 启动时立即检查一次
+        let now = std::time::Instant::now();
+        let mut due: Vec<_> = canaries.iter().map(|_| Some(now)).collect();
+
+        while !self.cancel.is_cancelled() {
+            let now = std::time::Instant::now();
+            for (i, (id, canary)) in canaries.iter().enumerate() {
+                match due[i] {
+                    Some(x) if x <= now => {}
+                    _ => continue,
+                }
+
+                match self.run_canary(id, canary).await {
+                    Ok(_) => {}
+                    Err(e) if matches!(e.downcast_ref(), Some(CrawlError::Unsupported)) => {
+                        error!("跳过爬虫健康检查; spider_id={id}, err={e:#}");
+                        due[i] = None;
+                        continue;
+                    }
+                    Err(e) => error!("爬虫健康检查失败; spider_id={id}, err={e:#}"),
+                }
+                let interval = canary
+                    .interval
+                    .to_std()
+                    .unwrap_or(std::time::Duration::from_secs(3600));
+                due[i] = Some(std::time::Instant::now() + interval);
+            }
+
+            let next = match due.iter().flatten().min() {
+                Some(x) => *x,
+                None => return,
+            };
+            tokio::select! {
+                _ = tokio::time::sleep_until(next.into()) => {},
+                _ = self.cancel.cancelled() => {},
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CanaryConfig {
+    // 检查间隔
+    pub interval: String,
+    pub pages: Vec<CanaryPageConfig>,
+}
+
+impl Default for CanaryConfig {
+    fn default() -> Self {
+        Self {
+            interval: String::from("1h"),
+            pages: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CanaryPageConfig {
+    // sort、novel 或 chapter
+    pub kind: String,
+    pub url: String,
+    #[serde(default = "default_min_items")]
+    pub min_items: usize,
+    #[serde(default)]
+    pub min_bytes: usize,
+    // 字段名与至少需要成功解析的比例，0到1之间
+    #[serde(default)]
+    pub fields: BTreeMap<String, f64>,
+}
+
+fn default_min_items() -> usize {
+    1
+}
+
+impl CanaryConfig {
+    pub fn to_canary(&self) -> Result<Canary> {
+        let interval = parse_duration(&self.interval)
+            .with_context(|| format!("interval={}", self.interval))?;
+        if interval <= Duration::zero() {
+            bail!("interval 必须大于0");
+        }
+
+        let mut pages = Vec::with_capacity(self.pages.len());
+        for x in &self.pages {
+            let kind =
+                ProbeKind::parse(&x.kind).ok_or_else(|| anyhow!("未知的页面类型: {}", x.kind))?;
+            let url = Url::parse(&x.url).with_context(|| format!("url={}", x.url))?;
+            if !matches!(url.scheme(), "http" | "https") {
+                bail!("url 只支持 http 与 https: {}", x.url);
+            }
+            for (name, ratio) in &x.fields {
+                if !(0.0..=1.0).contains(ratio) {
+                    bail!("fields.{name} 必须在0到1之间");
+                }
+            }
+
+            pages.push(CanaryPage {
+                kind,
+                url: x.url.clone(),
+                min_items: x.min_items,
+                min_bytes: x.min_bytes,
+                fields: x.fields.clone(),
+            });
+        }
+
+        Ok(Canary { interval, pages })
+    }
+}
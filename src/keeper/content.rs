@@ -0,0 +1,239 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use reqwest::Url;
+use sea_orm::DbConn;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::common::snowid::IdGen;
+use crate::keeper::content::fs::FsStore;
+use crate::keeper::content::inline::InlineStore;
+use crate::keeper::content::s3::{S3Config, S3Store};
+use crate::keeper::cover::CoverStore;
+use crate::keeper::data::entity::section::Model as SectionModel;
+use crate::keeper::data::{fulltext, novel, section};
+use crate::keeper::fulltext::{tokenize, unigrams};
+use crate::keeper::queue::SectionSink;
+use crate::keeper::Keeper;
+use crate::spider::Section;
+
+pub mod fs;
+pub mod inline;
+pub mod s3;
+
+// 章节内容的存储，章节记录中只保存内容的key与哈希
+#[async_trait]
+pub trait ContentStore: Send + Sync {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<()>;
+
+    // 内容不存在时返回None
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    async fn delete(&self, key: &str) -> Result<()>;
+}
+
+pub fn content_hash(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+// 将章节记录写入数据库，内容写入内容存储
+pub struct StoredSections {
+    db: Arc<DbConn>,
+    ids: IdGen,
+    store: Arc<dyn ContentStore>,
+}
+
+impl StoredSections {
+    pub fn new(db: Arc<DbConn>, ids: IdGen, store: Arc<dyn ContentStore>) -> Self {
+        Self { db, ids, store }
+    }
+
+    // 读取章节内容，并校验内容的哈希
+    pub async fn text(&self, x: &SectionModel) -> Result<Option<String>> {
+        let data = match self.store.get(&x.content_key).await? {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+
+        if content_hash(&data) != x.content_hash {
+            return Err(anyhow!(
+                "章节内容校验失败; id={}, key={}",
+                x.id,
+                x.content_key
+            ));
+        }
+
+        Ok(Some(String::from_utf8(data)?))
+    }
+}
+
+#[async_trait]
+impl SectionSink for StoredSections {
+    async fn save(&self, spider_id: &str, data: &Section) -> Result<()> {
+        let spider_novel_id: i64 = data.novel_id.into();
+        let seq = data.seq as i32;
+        let novel_id = novel::relation_novel_id(&self.db, spider_id, spider_novel_id).await?;
+
+        let content = data.text.as_bytes();
+        let hash = content_hash(content);
+
+        let exist = section::find(&self.db, spider_id, spider_novel_id, seq).await?;
+        let id = match exist {
+            // 内容没有变化时不重复写入
+            Some(x) if x.content_hash == hash => return Ok(()),
+            Some(x) => x.id,
+            // 在写入记录之前生成id，用于确定内容的key
+            None => self.ids.generate(),
+        };
+
+        let key = format!("sections/{id}");
+        self.store.put(&key, content).await?;
+
+        let info = section::SectionInfo {
+            spider_id,
+            spider_novel_id,
+            novel_id,
+            seq,
+            name: &data.name,
+            update_at: data.update_at,
+        };
+        section::save(&self.db, id, &info, &key, &hash, content.len()).await?;
+
+        fulltext::index_section(
+            &self.db,
+            id,
+            novel_id,
+            seq,
+            &tokenize(&data.text),
+            &unigrams(&data.text),
+        )
+        .await
+    }
+}
+
+impl Keeper {
+    // 使用内容存储保存任务爬取到的章节
+    pub fn set_content_store(&mut self, store: Arc<dyn ContentStore>) {
+        let sections = Arc::new(StoredSections::new(
+            self.db.clone(),
+            self.ids.clone(),
+            store,
+        ));
+        self.sections = Some(sections.clone());
+        self.section_sink = Some(sections);
+    }
+
+    // 读取章节内容
+    pub async fn section_text(&self, section_id: i64) -> Result<Option<String>> {
+        let sections = self
+            .sections
+            .as_ref()
+            .ok_or_else(|| anyhow!("没有设置内容存储"))?;
+        let x = match section::section_by_id(&self.db, section_id).await? {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+
+        sections.text(&x).await
+    }
+}
+
+// 章节内容与封面的保存位置
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContentConfig {
+    // inline 压缩后保存在数据库中，fs 保存在 dir 目录中，s3 保存在 [content.s3] 的存储中
+    pub store: String,
+    pub dir: PathBuf,
+    pub s3: Option<S3ContentConfig>,
+    // 封面的保存目录，未设置时不下载封面
+    pub covers: Option<PathBuf>,
+}
+
+impl Default for ContentConfig {
+    fn default() -> Self {
+        Self {
+            store: String::from(CONTENT_INLINE),
+            dir: PathBuf::from("sections"),
+            s3: None,
+            covers: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct S3ContentConfig {
+    // 例如 http://127.0.0.1:9000，使用 path-style 地址
+    pub endpoint: String,
+    pub bucket: String,
+    #[serde(default = "default_region")]
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+    // 对象key的前缀
+    #[serde(default)]
+    pub prefix: String,
+}
+
+fn default_region() -> String {
+    String::from("us-east-1")
+}
+
+pub const CONTENT_INLINE: &str = "inline";
+pub const CONTENT_FS: &str = "fs";
+pub const CONTENT_S3: &str = "s3";
+
+impl ContentConfig {
+    pub fn validate(&self) -> Result<()> {
+        match self.store.as_str() {
+            CONTENT_INLINE | CONTENT_FS => {}
+            CONTENT_S3 => {
+                let x = self
+                    .s3
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("store = \"s3\" 时需要设置 [content.s3]"))?;
+                let url = Url::parse(&x.endpoint)
+                    .with_context(|| format!("s3.endpoint={}", x.endpoint))?;
+                if !matches!(url.scheme(), "http" | "https") {
+                    bail!("s3.endpoint 只支持 http 与 https: {}", x.endpoint);
+                }
+            }
+            x => bail!("不支持的内容存储: {x}"),
+        }
+
+        Ok(())
+    }
+
+    // 根据配置创建章节内容的存储
+    pub fn to_store(&self, db: Arc<DbConn>) -> Result<Arc<dyn ContentStore>> {
+        self.validate()?;
+        let store: Arc<dyn ContentStore> = match (self.store.as_str(), &self.s3) {
+            (CONTENT_FS, _) => Arc::new(FsStore::new(&self.dir)),
+            (CONTENT_S3, Some(x)) => Arc::new(S3Store::new(S3Config {
+                endpoint: x.endpoint.clone(),
+                bucket: x.bucket.clone(),
+                region: x.region.clone(),
+                access_key: x.access_key.clone(),
+                secret_key: x.secret_key.clone(),
+                prefix: x.prefix.clone(),
+            })),
+            _ => Arc::new(InlineStore::new(db)),
+        };
+
+        Ok(store)
+    }
+
+    // 设置keeper保存章节内容与封面的位置，需要在 set_id_gen 之后调用
+    pub fn install(&self, keeper: &mut Keeper, db: Arc<DbConn>) -> Result<()> {
+        keeper.set_content_store(self.to_store(db)?);
+        if let Some(dir) = &self.covers {
+            keeper.set_cover_store(CoverStore::new(dir));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use tokio::fs;
+
+use crate::keeper::content::ContentStore;
+
+// 将内容保存为目录树中的文件，key中的 / 对应目录层级
+pub struct FsStore {
+    dir: PathBuf,
+}
+
+impl FsStore {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        // 不允许key跳出存储目录
+        if key.is_empty()
+            || key.starts_with('/')
+            || key
+                .split('/')
+                .any(|x| x.is_empty() || x == "." || x == "..")
+        {
+            return Err(anyhow!("非法的内容key; key={key}"));
+        }
+
+        Ok(self.dir.join(format!("{key}.txt")))
+    }
+}
+
+#[async_trait]
+impl ContentStore for FsStore {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        let path = self.path(key)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).await?;
+        }
+
+        // 先写入临时文件再重命名，避免读到不完整的内容
+        let tmp = path.with_extension("txt.tmp");
+        fs::write(&tmp, data).await?;
+        fs::rename(&tmp, &path).await?;
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match fs::read(self.path(key)?).await {
+            Ok(x) => Ok(Some(x)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        match fs::remove_file(self.path(key)?).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use sea_orm::prelude::*;
+use sea_orm::ActiveValue::Set;
+
+use crate::keeper::content::ContentStore;
+use crate::keeper::data::entity::section_content;
+
+// zstd默认压缩等级
+const DEFAULT_LEVEL: i32 = 3;
+
+// 将内容压缩后保存在数据库的 section_contents 表中
+pub struct InlineStore {
+    db: Arc<DbConn>,
+    level: i32,
+}
+
+impl InlineStore {
+    pub fn new(db: Arc<DbConn>) -> Self {
+        Self {
+            db,
+            level: DEFAULT_LEVEL,
+        }
+    }
+
+    pub fn level(mut self, level: i32) -> Self {
+        self.level = level;
+        self
+    }
+}
+
+#[async_trait]
+impl ContentStore for InlineStore {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        let data = zstd::encode_all(data, self.level)?;
+
+        match section_content::Entity::find_by_id(String::from(key))
+            .one(self.db.as_ref())
+            .await?
+        {
+            Some(x) => {
+                let mut x: section_content::ActiveModel = x.into();
+                x.data = Set(data);
+
+                let _ = x.update(self.db.as_ref()).await?;
+            }
+            None => {
+                let x = section_content::ActiveModel {
+                    key: Set(String::from(key)),
+                    data: Set(data),
+                };
+
+                let _ = section_content::Entity::insert(x)
+                    .exec(self.db.as_ref())
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let x = section_content::Entity::find_by_id(String::from(key))
+            .one(self.db.as_ref())
+            .await?;
+
+        match x {
+            Some(x) => Ok(Some(zstd::decode_all(x.data.as_slice())?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let _ = section_content::Entity::delete_by_id(String::from(key))
+            .exec(self.db.as_ref())
+            .await?;
+
+        Ok(())
+    }
+}
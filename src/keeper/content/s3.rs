@@ -0,0 +1,186 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::{Client, Method, StatusCode, Url};
+use sha2::{Digest, Sha256};
+
+use crate::keeper::content::ContentStore;
+
+// S3兼容存储的配置，endpoint例如 http://127.0.0.1:9000
+#[derive(Debug, Clone)]
+pub struct S3Config {
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+    // 对象key的前缀
+    pub prefix: String,
+}
+
+// 使用S3兼容的接口保存内容，请求使用 path-style 地址与 AWS Signature V4 签名
+pub struct S3Store {
+    config: S3Config,
+    client: Client,
+}
+
+impl S3Store {
+    pub fn new(config: S3Config) -> Self {
+        Self {
+            config,
+            client: Client::new(),
+        }
+    }
+
+    async fn request(&self, method: Method, key: &str, body: &[u8]) -> Result<reqwest::Response> {
+        let path = format!(
+            "/{}/{}",
+            uri_encode(&self.config.bucket, false),
+            uri_encode(&format!("{}{key}", self.config.prefix), false)
+        );
+        let url = Url::parse(&self.config.endpoint)?.join(&path)?;
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{host}:{port}"),
+            (Some(host), None) => String::from(host),
+            _ => return Err(anyhow!("非法的endpoint; endpoint={}", self.config.endpoint)),
+        };
+
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let payload_hash = hex::encode(Sha256::digest(body));
+
+        let headers = [
+            ("host", host.as_str()),
+            ("x-amz-content-sha256", payload_hash.as_str()),
+            ("x-amz-date", amz_date.as_str()),
+        ];
+        let signature = signature(
+            &self.config.secret_key,
+            &self.config.region,
+            method.as_str(),
+            url.path(),
+            &headers,
+            &payload_hash,
+        );
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}/{}/s3/aws4_request, SignedHeaders={}, Signature={signature}",
+            self.config.access_key,
+            &amz_date[..8],
+            self.config.region,
+            signed_headers(&headers)
+        );
+
+        let resp = self
+            .client
+            .request(method, url)
+            .header("x-amz-date", amz_date)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("authorization", authorization)
+            .body(body.to_vec())
+            .send()
+            .await?;
+
+        Ok(resp)
+    }
+}
+
+#[async_trait]
+impl ContentStore for S3Store {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        let resp = self.request(Method::PUT, key, data).await?;
+        if !resp.status().is_success() {
+            return Err(anyhow!("保存内容失败; key={key}, status={}", resp.status()));
+        }
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let resp = self.request(Method::GET, key, &[]).await?;
+        match resp.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            x if x.is_success() => Ok(Some(resp.bytes().await?.to_vec())),
+            x => Err(anyhow!("读取内容失败; key={key}, status={x}")),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let resp = self.request(Method::DELETE, key, &[]).await?;
+        if !resp.status().is_success() && resp.status() != StatusCode::NOT_FOUND {
+            return Err(anyhow!("删除内容失败; key={key}, status={}", resp.status()));
+        }
+
+        Ok(())
+    }
+}
+
+// 计算AWS Signature V4签名，path为编码后的路径，不支持查询参数
+// headers为参与签名的请求头，名称为小写并按名称排序，必须包含x-amz-date
+pub fn signature(
+    secret_key: &str,
+    region: &str,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    payload_hash: &str,
+) -> String {
+    let amz_date = headers
+        .iter()
+        .find(|(name, _)| *name == "x-amz-date")
+        .map(|(_, x)| *x)
+        .unwrap_or_default();
+    let date = amz_date.get(..8).unwrap_or_default();
+
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{name}:{}\n", value.trim()))
+        .collect();
+    let canonical_request = format!(
+        "{method}\n{path}\n\n{canonical_headers}\n{}\n{payload_hash}",
+        signed_headers(headers)
+    );
+    let scope = format!("{date}/{region}/s3/aws4_request");
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let key = hmac(format!("AWS4{secret_key}").as_bytes(), date.as_bytes());
+    let key = hmac(&key, region.as_bytes());
+    let key = hmac(&key, b"s3");
+    let key = hmac(&key, b"aws4_request");
+
+    hex::encode(hmac(&key, string_to_sign.as_bytes()))
+}
+
+fn signed_headers(headers: &[(&str, &str)]) -> String {
+    headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("hmac accepts any key length");
+    mac.update(data);
+
+    mac.finalize().into_bytes().to_vec()
+}
+
+// 按照S3的要求编码路径，encode_slash为false时保留 /
+fn uri_encode(x: &str, encode_slash: bool) -> String {
+    let mut s = String::with_capacity(x.len());
+    for b in x.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                s.push(b as char)
+            }
+            b'/' if !encode_slash => s.push('/'),
+            _ => s.push_str(&format!("%{b:02X}")),
+        }
+    }
+
+    s
+}
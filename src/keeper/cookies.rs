@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use log::{error, info};
+
+use crate::common::cookiejar::{self, Cookie, CookieJar};
+use crate::keeper::data::cookie_jar;
+use crate::keeper::Keeper;
+
+impl Keeper {
+    // 为爬虫启用cookie并恢复上次保存的，爬虫的并发任务共享同一个jar
+    pub async fn enable_cookies(&self, id: &str) -> Result<Arc<CookieJar>> {
+        if let Some(x) = cookiejar::jar(id) {
+            return Ok(x);
+        }
+
+        let cookies = cookie_jar::load(&self.db, id).await?.unwrap_or_default();
+        info!("启用cookie; spider_id={id}, count={}", cookies.len());
+        let jar = Arc::new(CookieJar::with_cookies(cookies));
+        cookiejar::register(id, jar.clone());
+
+        Ok(jar)
+    }
+
+    // 保存有修改的cookie，返回是否写入了数据库
+    pub async fn save_cookies(&self, id: &str) -> Result<bool> {
+        let jar = match cookiejar::jar(id) {
+            Some(x) => x,
+            None => return Ok(false),
+        };
+        if !jar.take_dirty() {
+            return Ok(false);
+        }
+
+        if let Err(e) = cookie_jar::save(&self.db, id, &jar.cookies()).await {
+            // 下次再尝试保存
+            jar.mark_dirty();
+            return Err(e);
+        }
+
+        Ok(true)
+    }
+
+    pub(crate) async fn save_all_cookies(&self) {
+        for id in cookiejar::spider_ids() {
+            if let Err(e) = self.save_cookies(&id).await {
+                error!("保存cookie失败; spider_id={id}, err={e}");
+            }
+        }
+    }
+
+    // 全部爬虫的cookie，正在使用的jar优先于数据库中保存的
+    pub async fn cookies(&self) -> Result<Vec<(String, Vec<Cookie>)>> {
+        let mut v = cookie_jar::list(&self.db).await?;
+        for id in cookiejar::spider_ids() {
+            let cookies = match cookiejar::jar(&id) {
+                Some(x) => x.cookies(),
+                None => continue,
+            };
+            match v.iter_mut().find(|x| x.0 == id) {
+                Some(x) => x.1 = cookies,
+                None => v.push((id, cookies)),
+            }
+        }
+        v.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Ok(v)
+    }
+
+    // 清除爬虫的cookie，返回是否存在
+    pub async fn clear_cookies(&self, id: &str) -> Result<bool> {
+        let mut found = false;
+        if let Some(x) = cookiejar::jar(id) {
+            found = !x.cookies().is_empty();
+            x.clear();
+            x.take_dirty();
+        }
+        found |= cookie_jar::remove(&self.db, id).await?;
+        info!("清除cookie; spider_id={id}");
+
+        Ok(found)
+    }
+}
@@ -0,0 +1,205 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use reqwest::header;
+use sha2::{Digest, Sha256};
+use tokio::fs;
+
+use crate::common::httputils;
+use crate::keeper::data::novel;
+use crate::keeper::Keeper;
+
+// 默认允许的封面最大字节数
+const DEFAULT_MAX_SIZE: usize = 5 * 1024 * 1024;
+
+// 按内容哈希存储封面图片，相同的图片只保存一份
+// 路径为 dir/哈希前两位/哈希.扩展名
+pub struct CoverStore {
+    dir: PathBuf,
+    max_size: usize,
+    // 缩略图的最大宽高，为None时不生成缩略图
+    thumbnail: Option<(u32, u32)>,
+}
+
+#[derive(Debug)]
+pub struct StoredCover {
+    pub hash: String,
+    pub path: PathBuf,
+    pub mime: &'static str,
+    pub size: usize,
+    pub thumbnail: Option<PathBuf>,
+}
+
+impl CoverStore {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            max_size: DEFAULT_MAX_SIZE,
+            thumbnail: None,
+        }
+    }
+
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    // 生成缩略图需要开启thumbnail特性
+    pub fn thumbnail(mut self, width: u32, height: u32) -> Self {
+        self.thumbnail = Some((width, height));
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // 通过爬虫的cookie、反爬虫验证与代理下载封面，校验类型与大小后保存
+    pub async fn fetch(&self, spider_id: &str, url: &str) -> Result<StoredCover> {
+        let x = httputils::download(spider_id, url, self.max_size).await?;
+        if !(200..300).contains(&x.status) {
+            return Err(anyhow!("下载封面失败; url={url}, status={}", x.status));
+        }
+
+        // 先根据响应头做检查，不保存不需要的内容
+        if let Some(v) = x.headers.get(header::CONTENT_TYPE) {
+            let v = v.to_str().unwrap_or_default();
+            if !v.trim().starts_with("image/") {
+                return Err(anyhow!("封面不是图片; url={url}, content-type={v}"));
+            }
+        }
+        let size = x
+            .headers
+            .get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok()?.parse().ok())
+            .unwrap_or(x.data.len());
+        if size > self.max_size || x.data.len() > self.max_size {
+            return Err(anyhow!("封面过大; url={url}, size={size}"));
+        }
+
+        self.store(&x.data).await
+    }
+
+    // 保存图片数据，返回保存的位置
+    pub async fn store(&self, data: &[u8]) -> Result<StoredCover> {
+        if data.len() > self.max_size {
+            return Err(anyhow!("封面过大; size={}", data.len()));
+        }
+
+        // 以文件内容判断图片类型，不信任响应头
+        let (mime, ext) = sniff_image(data).ok_or_else(|| anyhow!("无法识别的图片格式"))?;
+
+        let hash = hex::encode(Sha256::digest(data));
+        let dir = self.dir.join(&hash[..2]);
+        let path = dir.join(format!("{hash}.{ext}"));
+
+        if fs::metadata(&path).await.is_err() {
+            fs::create_dir_all(&dir).await?;
+
+            // 先写入临时文件再重命名，避免留下不完整的文件
+            let tmp = dir.join(format!("{hash}.{ext}.tmp"));
+            fs::write(&tmp, data).await?;
+            fs::rename(&tmp, &path).await?;
+        }
+
+        let thumbnail = match self.thumbnail {
+            Some((width, height)) => {
+                let thumb = dir.join(format!("{hash}_{width}x{height}.jpg"));
+                match make_thumbnail(&path, &thumb, width, height).await {
+                    Ok(()) => Some(thumb),
+                    Err(e) => {
+                        warn!("生成缩略图失败; hash={hash}, err={e}");
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+
+        Ok(StoredCover {
+            hash,
+            path,
+            mime,
+            size: data.len(),
+            thumbnail,
+        })
+    }
+}
+
+// 根据文件头识别图片类型
+pub fn sniff_image(data: &[u8]) -> Option<(&'static str, &'static str)> {
+    if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some(("image/jpeg", "jpg"))
+    } else if data.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+        Some(("image/png", "png"))
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some(("image/gif", "gif"))
+    } else if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        Some(("image/webp", "webp"))
+    } else if data.starts_with(b"BM") {
+        Some(("image/bmp", "bmp"))
+    } else {
+        None
+    }
+}
+
+#[cfg(feature = "thumbnail")]
+async fn make_thumbnail(src: &Path, dst: &Path, width: u32, height: u32) -> Result<()> {
+    if fs::metadata(dst).await.is_ok() {
+        return Ok(());
+    }
+
+    let src = src.to_path_buf();
+    let dst = dst.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let img = image::open(&src)?.thumbnail(width, height);
+        img.to_rgb8()
+            .save_with_format(&dst, image::ImageFormat::Jpeg)?;
+
+        Ok::<(), anyhow::Error>(())
+    })
+    .await?
+}
+
+#[cfg(not(feature = "thumbnail"))]
+async fn make_thumbnail(_: &Path, _: &Path, _: u32, _: u32) -> Result<()> {
+    Err(anyhow!("没有开启thumbnail特性"))
+}
+
+impl Keeper {
+    pub fn set_cover_store(&mut self, store: CoverStore) {
+        self.covers = Some(store);
+    }
+
+    // 下载小说封面并记录本地路径，已经保存过的封面不会重复下载
+    pub async fn store_cover(&self, spider_id: &str, novel_id: i64) -> Result<Option<PathBuf>> {
+        let store = self
+            .covers
+            .as_ref()
+            .ok_or_else(|| anyhow!("没有设置封面的保存位置"))?;
+        let x = novel::novel_by_id(&self.db, novel_id)
+            .await?
+            .ok_or_else(|| anyhow!("小说不存在; id={novel_id}"))?;
+
+        if let Some(path) = x.cover_path.as_ref().map(PathBuf::from) {
+            if fs::metadata(&path).await.is_ok() {
+                return Ok(Some(path));
+            }
+        }
+
+        let url = match x.cover {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+
+        let cover = store.fetch(spider_id, &url).await?;
+        info!(
+            "保存封面; novel_id={novel_id}, hash={}, size={}",
+            cover.hash, cover.size
+        );
+        novel::set_cover_path(&self.db, novel_id, &cover.path.to_string_lossy()).await?;
+
+        Ok(Some(cover.path))
+    }
+}
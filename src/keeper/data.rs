@@ -1,3 +1,9 @@
+pub mod author;
+pub mod cookie_jar;
 pub mod entity;
+pub mod follow;
+pub mod fulltext;
+pub mod job;
 pub mod novel;
+pub mod section;
 pub mod sort;
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use sea_orm::prelude::*;
+use sea_orm::sea_query::Expr;
+use sea_orm::ActiveValue::Set;
+use sea_orm::QueryOrder;
+
+use crate::common::db::insert_or_ignore;
+use crate::common::snowid::IdGen;
+use crate::keeper::data::entity::{author, novel, novel_relation};
+
+// 记录作者，searched为true时更新搜索时间
+// 多个任务可能同时记录同一个作者，先插入，名字已经存在时使用已有的记录
+pub async fn add_or_recover(db: &DbConn, ids: &IdGen, name: &str, searched: bool) -> Result<i64> {
+    let name = name.trim();
+    let now = Utc::now();
+    let id = ids.generate();
+    let x = author::ActiveModel {
+        id: Set(id),
+        name: Set(String::from(name)),
+        created_at: Set(now),
+        searched_at: Set(if searched { Some(now) } else { None }),
+    };
+    insert_or_ignore(db, x, &[author::Column::Name]).await?;
+
+    let x = author::Entity::find()
+        .filter(author::Column::Name.eq(name))
+        .one(db)
+        .await?
+        .ok_or_else(|| anyhow!("保存作者失败; name={name}"))?;
+    if x.id != id && searched {
+        let _ = author::Entity::update_many()
+            .col_expr(author::Column::SearchedAt, Expr::value(now))
+            .filter(author::Column::Id.eq(x.id))
+            .exec(db)
+            .await?;
+    }
+
+    Ok(x.id)
+}
+
+pub async fn author_by_name(db: &DbConn, name: &str) -> Result<Option<author::Model>> {
+    let x = author::Entity::find()
+        .filter(author::Column::Name.eq(name.trim()))
+        .one(db)
+        .await?;
+
+    Ok(x)
+}
+
+// 获取作者的全部小说，以及每本小说关联的爬虫
+pub async fn novels(
+    db: &DbConn,
+    name: &str,
+) -> Result<Vec<(novel::Model, Vec<novel_relation::Model>)>> {
+    let novels = novel::Entity::find()
+        .filter(novel::Column::Author.eq(name.trim()))
+        .order_by_asc(novel::Column::CreatedAt)
+        .all(db)
+        .await?;
+
+    let ids: Vec<i64> = novels.iter().map(|x| x.id).collect();
+    let mut relations: HashMap<i64, Vec<novel_relation::Model>> = HashMap::new();
+    for x in novel_relation::Entity::find()
+        .filter(novel_relation::Column::NovelId.is_in(ids))
+        .all(db)
+        .await?
+    {
+        relations.entry(x.novel_id).or_default().push(x);
+    }
+
+    Ok(novels
+        .into_iter()
+        .map(|x| {
+            let r = relations.remove(&x.id).unwrap_or_default();
+
+            (x, r)
+        })
+        .collect())
+}
@@ -0,0 +1,70 @@
+use anyhow::Result;
+use chrono::Utc;
+use sea_orm::prelude::*;
+use sea_orm::ActiveValue::Set;
+use sea_orm::QueryOrder;
+
+use crate::common::cookiejar::Cookie;
+use crate::keeper::data::entity::cookie_jar;
+
+// 爬虫保存的cookie，没有保存过时返回None
+pub async fn load(db: &DbConn, spider_id: &str) -> Result<Option<Vec<Cookie>>> {
+    let x = cookie_jar::Entity::find_by_id(String::from(spider_id))
+        .one(db)
+        .await?;
+
+    match x {
+        Some(x) => Ok(Some(serde_json::from_str(&x.cookies)?)),
+        None => Ok(None),
+    }
+}
+
+// 保存爬虫的全部cookie，替换原来保存的
+pub async fn save(db: &DbConn, spider_id: &str, cookies: &[Cookie]) -> Result<()> {
+    let data = serde_json::to_string(cookies)?;
+    let now = Utc::now();
+    match cookie_jar::Entity::find_by_id(String::from(spider_id))
+        .one(db)
+        .await?
+    {
+        Some(x) => {
+            let mut x: cookie_jar::ActiveModel = x.into();
+            x.cookies = Set(data);
+            x.updated_at = Set(now);
+
+            let _ = x.update(db).await?;
+        }
+        None => {
+            let x = cookie_jar::ActiveModel {
+                spider_id: Set(String::from(spider_id)),
+                cookies: Set(data),
+                updated_at: Set(now),
+            };
+
+            let _ = cookie_jar::Entity::insert(x).exec(db).await?;
+        }
+    }
+
+    Ok(())
+}
+
+// 全部保存的cookie，以爬虫id排序
+pub async fn list(db: &DbConn) -> Result<Vec<(String, Vec<Cookie>)>> {
+    let x = cookie_jar::Entity::find()
+        .order_by_asc(cookie_jar::Column::SpiderId)
+        .all(db)
+        .await?;
+
+    x.into_iter()
+        .map(|x| Ok((x.spider_id, serde_json::from_str(&x.cookies)?)))
+        .collect()
+}
+
+// 删除爬虫保存的cookie，返回是否存在
+pub async fn remove(db: &DbConn, spider_id: &str) -> Result<bool> {
+    let r = cookie_jar::Entity::delete_by_id(String::from(spider_id))
+        .exec(db)
+        .await?;
+
+    Ok(r.rows_affected > 0)
+}
@@ -0,0 +1,25 @@
+use sea_orm::prelude::*;
+
+#[derive(Debug, PartialEq, Clone, DeriveEntityModel)]
+#[sea_orm(table_name = "authors")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: i64,
+    // 作者名
+    pub name: String,
+    // 记录创建时间
+    pub created_at: DateTimeUtc,
+    // 最近一次从爬虫中搜索该作者的时间
+    pub searched_at: Option<DateTimeUtc>,
+}
+
+#[derive(Debug, Copy, Clone, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        match *self {}
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
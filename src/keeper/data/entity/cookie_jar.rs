@@ -0,0 +1,23 @@
+use sea_orm::prelude::*;
+
+#[derive(Debug, PartialEq, Clone, DeriveEntityModel)]
+#[sea_orm(table_name = "cookie_jars")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub spider_id: String,
+    // 全部cookie，json数组
+    pub cookies: String,
+    // 最后保存的时间
+    pub updated_at: DateTimeUtc,
+}
+
+#[derive(Debug, Copy, Clone, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        match *self {}
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
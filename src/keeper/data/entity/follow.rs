@@ -0,0 +1,25 @@
+use sea_orm::prelude::*;
+
+#[derive(Debug, PartialEq, Clone, DeriveEntityModel)]
+#[sea_orm(table_name = "follows")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: i64,
+    // 关注的小说
+    pub novel_id: i64,
+    // 关注者标识，例如 user:1 或 mailto:a@b.com
+    pub subscriber: String,
+    // 记录创建时间
+    pub created_at: DateTimeUtc,
+}
+
+#[derive(Debug, Copy, Clone, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        match *self {}
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
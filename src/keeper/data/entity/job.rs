@@ -0,0 +1,37 @@
+use sea_orm::prelude::*;
+
+#[derive(Debug, PartialEq, Clone, DeriveEntityModel)]
+#[sea_orm(table_name = "crawl_jobs")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: i64,
+    // 任务类型
+    pub kind: String,
+    // 执行任务的爬虫id
+    pub spider_id: String,
+    // 爬取目标的id，分类id或者小说id
+    pub target_id: i64,
+    // 已经完成的页数或者章节数
+    pub cursor: i32,
+    // 任务状态
+    pub state: String,
+    // 执行次数
+    pub attempts: i32,
+    // 最近一次失败的原因
+    pub last_error: Option<String>,
+    // 记录创建时间
+    pub created_at: DateTimeUtc,
+    // 记录更新时间
+    pub updated_at: DateTimeUtc,
+}
+
+#[derive(Debug, Copy, Clone, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        match *self {}
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
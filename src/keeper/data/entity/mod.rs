@@ -2,6 +2,13 @@
 
 pub mod prelude;
 
+pub mod author;
+pub mod cookie_jar;
+pub mod follow;
+pub mod job;
 pub mod novel;
 pub mod novel_relation;
+pub mod novel_watch;
+pub mod section;
+pub mod section_content;
 pub mod sort;
@@ -13,12 +13,26 @@ pub struct Model {
     pub updated_at: DateTimeUtc,
     // 封面地址
     pub cover: Option<String>,
+    // 封面在本地的保存路径
+    pub cover_path: Option<String>,
     // 作者名
     pub author: String,
+    // 简介
+    pub intro: Option<String>,
     // 小说上次更新时间
     pub last_updated_at: Option<DateTimeUtc>,
     // 最近更新的小说章节
     pub last_section: Option<i64>,
+    // 最近更新的章节名
+    pub last_section_name: Option<String>,
+    // 字数
+    pub word_count: Option<i64>,
+    // 标签，字符串数组
+    pub tags: Option<Json>,
+    // 来源网站上的原始分类
+    pub category: Option<String>,
+    // 别名，字符串数组
+    pub aliases: Option<Json>,
 }
 
 #[derive(Debug, Copy, Clone, EnumIter)]
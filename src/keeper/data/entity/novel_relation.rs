@@ -11,6 +11,8 @@ pub struct Model {
     pub spider_novel_id: i64,
     #[sea_orm(default_value = 0)]
     pub score: i32,
+    // 小说在该来源网站上的地址
+    pub source_url: Option<String>,
 }
 
 #[derive(Debug, Copy, Clone, EnumIter)]
@@ -0,0 +1,27 @@
+use sea_orm::prelude::*;
+
+#[derive(Debug, PartialEq, Clone, DeriveEntityModel)]
+#[sea_orm(table_name = "novel_watches")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub novel_id: i64,
+    // 上次检查时的最新章节名
+    pub last_section_name: Option<String>,
+    // 上次检查时的更新时间
+    pub last_updated_at: Option<DateTimeUtc>,
+    // 上次检查时已保存的章节数
+    pub section_count: i64,
+    // 上次检查的时间
+    pub checked_at: DateTimeUtc,
+}
+
+#[derive(Debug, Copy, Clone, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        match *self {}
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,41 @@
+use sea_orm::prelude::*;
+
+#[derive(Debug, PartialEq, Clone, DeriveEntityModel)]
+#[sea_orm(table_name = "sections")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: i64,
+    // 爬虫id
+    pub spider_id: String,
+    // 小说在爬虫中的id
+    pub spider_novel_id: i64,
+    // 关联的小说id，爬虫中的小说没有关联时为空
+    pub novel_id: Option<i64>,
+    // 章节序号
+    pub seq: i32,
+    // 章节名
+    pub name: String,
+    // 章节更新时间
+    pub update_at: Option<DateTimeUtc>,
+    // 内容在内容存储中的key
+    pub content_key: String,
+    // 内容的sha256
+    pub content_hash: String,
+    // 内容的字节数
+    pub size: i64,
+    // 记录创建时间
+    pub created_at: DateTimeUtc,
+    // 记录更新时间
+    pub updated_at: DateTimeUtc,
+}
+
+#[derive(Debug, Copy, Clone, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        match *self {}
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
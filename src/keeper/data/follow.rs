@@ -0,0 +1,111 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sea_orm::prelude::*;
+use sea_orm::ActiveValue::Set;
+use sea_orm::{Condition, QueryOrder};
+
+use crate::common::snowid::IdGen;
+use crate::keeper::data::entity::{follow, novel_watch};
+
+// 添加关注，已经关注时返回原来的记录id
+pub async fn add(db: &DbConn, ids: &IdGen, novel_id: i64, subscriber: &str) -> Result<i64> {
+    let x = follow::Entity::find()
+        .filter(
+            Condition::all()
+                .add(follow::Column::NovelId.eq(novel_id))
+                .add(follow::Column::Subscriber.eq(subscriber)),
+        )
+        .one(db)
+        .await?;
+    if let Some(x) = x {
+        return Ok(x.id);
+    }
+
+    let id = ids.generate();
+    let x = follow::ActiveModel {
+        id: Set(id),
+        novel_id: Set(novel_id),
+        subscriber: Set(String::from(subscriber)),
+        created_at: Set(Utc::now()),
+    };
+    let _ = follow::Entity::insert(x).exec(db).await?;
+
+    Ok(id)
+}
+
+// 取消关注，返回是否存在该关注
+pub async fn remove(db: &DbConn, novel_id: i64, subscriber: &str) -> Result<bool> {
+    let r = follow::Entity::delete_many()
+        .filter(
+            Condition::all()
+                .add(follow::Column::NovelId.eq(novel_id))
+                .add(follow::Column::Subscriber.eq(subscriber)),
+        )
+        .exec(db)
+        .await?;
+
+    Ok(r.rows_affected > 0)
+}
+
+// 小说的全部关注者
+pub async fn subscribers(db: &DbConn, novel_id: i64) -> Result<Vec<String>> {
+    let x = follow::Entity::find()
+        .filter(follow::Column::NovelId.eq(novel_id))
+        .order_by_asc(follow::Column::CreatedAt)
+        .all(db)
+        .await?;
+
+    Ok(x.into_iter().map(|x| x.subscriber).collect())
+}
+
+// 关注者关注的全部小说
+pub async fn novels(db: &DbConn, subscriber: &str) -> Result<Vec<i64>> {
+    let x = follow::Entity::find()
+        .filter(follow::Column::Subscriber.eq(subscriber))
+        .order_by_asc(follow::Column::CreatedAt)
+        .all(db)
+        .await?;
+
+    Ok(x.into_iter().map(|x| x.novel_id).collect())
+}
+
+pub async fn watch(db: &DbConn, novel_id: i64) -> Result<Option<novel_watch::Model>> {
+    let x = novel_watch::Entity::find_by_id(novel_id).one(db).await?;
+
+    Ok(x)
+}
+
+// 记录检查时小说的状态
+pub async fn save_watch(
+    db: &DbConn,
+    novel_id: i64,
+    last_section_name: Option<&str>,
+    last_updated_at: Option<DateTime<Utc>>,
+    section_count: i64,
+) -> Result<()> {
+    let now = Utc::now();
+    match novel_watch::Entity::find_by_id(novel_id).one(db).await? {
+        Some(x) => {
+            let mut x: novel_watch::ActiveModel = x.into();
+            x.last_section_name = Set(last_section_name.map(String::from));
+            x.last_updated_at = Set(last_updated_at);
+            x.section_count = Set(section_count);
+            x.checked_at = Set(now);
+
+            let _ = x.update(db).await?;
+        }
+        None => {
+            let x = novel_watch::ActiveModel {
+                novel_id: Set(novel_id),
+                last_section_name: Set(last_section_name.map(String::from)),
+                last_updated_at: Set(last_updated_at),
+                section_count: Set(section_count),
+                checked_at: Set(now),
+            };
+
+            let _ = novel_watch::Entity::insert(x).exec(db).await?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,261 @@
+use anyhow::Result;
+use sea_orm::{ConnectionTrait, DbBackend, DbConn, Statement, Value};
+
+use crate::common::db::{placeholder, placeholders};
+use crate::keeper::fulltext::is_cjk;
+
+// 全文索引中匹配到的小说
+#[derive(Debug)]
+pub struct NovelMatch {
+    pub novel_id: i64,
+    pub name: String,
+    pub intro: Option<String>,
+}
+
+// 全文索引中匹配到的章节
+#[derive(Debug)]
+pub struct SectionMatch {
+    pub section_id: i64,
+    pub novel_id: Option<i64>,
+    pub seq: i32,
+}
+
+// 索引小说名与简介，tokens为分词后的内容，chars为其中的中文单字
+pub async fn index_novel(
+    db: &DbConn,
+    novel_id: i64,
+    name: &str,
+    intro: Option<&str>,
+    tokens: &str,
+    chars: &str,
+) -> Result<()> {
+    replace(
+        db,
+        "fts_novels",
+        "novel_id",
+        &["novel_id", "name", "intro", "tokens", "chars"],
+        vec![
+            novel_id.into(),
+            name.into(),
+            intro.map(String::from).into(),
+            tokens.into(),
+            chars.into(),
+        ],
+    )
+    .await
+}
+
+// 索引章节内容，tokens为分词后的内容，chars为其中的中文单字
+pub async fn index_section(
+    db: &DbConn,
+    section_id: i64,
+    novel_id: Option<i64>,
+    seq: i32,
+    tokens: &str,
+    chars: &str,
+) -> Result<()> {
+    replace(
+        db,
+        "fts_sections",
+        "section_id",
+        &["section_id", "novel_id", "seq", "tokens", "chars"],
+        vec![
+            section_id.into(),
+            novel_id.into(),
+            seq.into(),
+            tokens.into(),
+            chars.into(),
+        ],
+    )
+    .await
+}
+
+// 删除旧的索引后写入新的索引，values的第一个值为key
+// sqlite的fts5表中其他列都没有索引，使用与key相同的rowid查找；其他数据库的key为主键
+async fn replace(
+    db: &DbConn,
+    table: &str,
+    key: &str,
+    columns: &[&str],
+    mut values: Vec<Value>,
+) -> Result<()> {
+    let backend = db.get_database_backend();
+    let mut columns = columns.to_vec();
+    let key = match backend {
+        DbBackend::Sqlite => {
+            columns.insert(0, "rowid");
+            values.insert(0, values[0].clone());
+            "rowid"
+        }
+        _ => key,
+    };
+
+    exec(
+        db,
+        format!(
+            "delete from {table} where {key} = {}",
+            placeholder(backend, 1)
+        ),
+        vec![values[0].clone()],
+    )
+    .await?;
+    exec(
+        db,
+        format!(
+            "insert into {table} ({}) values ({})",
+            columns.join(", "),
+            placeholders(backend, values.len())
+        ),
+        values,
+    )
+    .await
+}
+
+// 搜索小说，phrase为分词后的搜索词
+// sqlite使用fts5，postgres使用tsvector，mysql使用ngram全文索引匹配并按相关度排序
+pub async fn search_novels(db: &DbConn, phrase: &str, limit: u64) -> Result<Vec<NovelMatch>> {
+    let backend = db.get_database_backend();
+    let (cond, value) = match_condition(backend, "fts_novels", phrase);
+    let mut values = vec![value];
+    let order = rank(backend, phrase, &mut values);
+    values.push((limit as i64).into());
+    let sql = format!(
+        "select novel_id, name, intro from fts_novels where {cond} order by {order}, novel_id limit {}",
+        placeholder(backend, values.len())
+    );
+
+    let rows = db
+        .query_all(Statement::from_sql_and_values(backend, &sql, values))
+        .await?;
+
+    let mut x = Vec::with_capacity(rows.len());
+    for row in rows {
+        x.push(NovelMatch {
+            novel_id: row.try_get("", "novel_id")?,
+            name: row.try_get("", "name")?,
+            intro: row.try_get("", "intro")?,
+        });
+    }
+
+    Ok(x)
+}
+
+// 搜索章节，novel_id不为空时只搜索该小说的章节
+pub async fn search_sections(
+    db: &DbConn,
+    phrase: &str,
+    novel_id: Option<i64>,
+    limit: u64,
+) -> Result<Vec<SectionMatch>> {
+    let backend = db.get_database_backend();
+    let (cond, value) = match_condition(backend, "fts_sections", phrase);
+    let mut values = vec![value];
+    let mut sql = format!("select section_id, novel_id, seq from fts_sections where {cond}");
+    if let Some(x) = novel_id {
+        values.push(x.into());
+        sql.push_str(&format!(
+            " and novel_id = {}",
+            placeholder(backend, values.len())
+        ));
+    }
+    let order = rank(backend, phrase, &mut values);
+    values.push((limit as i64).into());
+    sql.push_str(&format!(
+        " order by {order}, novel_id, seq limit {}",
+        placeholder(backend, values.len())
+    ));
+
+    let rows = db
+        .query_all(Statement::from_sql_and_values(backend, &sql, values))
+        .await?;
+
+    let mut x = Vec::with_capacity(rows.len());
+    for row in rows {
+        x.push(SectionMatch {
+            section_id: row.try_get("", "section_id")?,
+            novel_id: row.try_get("", "novel_id")?,
+            seq: row.try_get("", "seq")?,
+        });
+    }
+
+    Ok(x)
+}
+
+// 生成匹配条件，参数固定为第一个占位符
+// 中文单字可能是二元组的第二个字，在单字列中匹配
+fn match_condition(backend: DbBackend, table: &str, phrase: &str) -> (String, Value) {
+    let p = placeholder(backend, 1);
+    match backend {
+        DbBackend::Sqlite => (format!("{table} match {p}"), fts5_query(phrase).into()),
+        DbBackend::Postgres if single_cjk(phrase) => (
+            format!("to_tsvector('simple', chars) @@ plainto_tsquery('simple', {p})"),
+            phrase.into(),
+        ),
+        _ if single_cjk(phrase) => (format!("chars like {p}"), format!("% {phrase} %").into()),
+        // 字母数字的单字匹配以其开头的词
+        _ if phrase.chars().count() == 1 => {
+            (format!("tokens like {p}"), format!("% {phrase}%").into())
+        }
+        DbBackend::Postgres => (
+            format!("to_tsvector('simple', tokens) @@ phraseto_tsquery('simple', {p})"),
+            phrase.into(),
+        ),
+        DbBackend::MySql => (
+            format!("match (tokens) against ({p} in boolean mode)"),
+            mysql_query(phrase).into(),
+        ),
+    }
+}
+
+// 按相关度排序的表达式，需要的参数追加到values
+// postgres可以重复使用第一个参数，mysql的参数只能按顺序出现
+fn rank(backend: DbBackend, phrase: &str, values: &mut Vec<Value>) -> String {
+    match backend {
+        DbBackend::Sqlite => String::from("rank"),
+        _ if phrase.chars().count() == 1 => String::from("novel_id"),
+        DbBackend::Postgres => format!(
+            "ts_rank(to_tsvector('simple', tokens), phraseto_tsquery('simple', {})) desc",
+            placeholder(backend, 1)
+        ),
+        DbBackend::MySql => {
+            values.push(mysql_query(phrase).into());
+            format!(
+                "match (tokens) against ({} in boolean mode) desc",
+                placeholder(backend, values.len())
+            )
+        }
+    }
+}
+
+fn single_cjk(phrase: &str) -> bool {
+    let mut chars = phrase.chars();
+    matches!((chars.next(), chars.next()), (Some(c), None) if is_cjk(c))
+}
+
+// mysql布尔模式的短语查询
+fn mysql_query(phrase: &str) -> String {
+    format!("\"{}\"", phrase.replace('"', " "))
+}
+
+// fts5的查询语句，中文单字在单字列中查找，其他单字使用前缀查询
+fn fts5_query(phrase: &str) -> String {
+    let phrase = phrase.replace('"', "\"\"");
+    if single_cjk(&phrase) {
+        format!("chars : \"{phrase}\"")
+    } else if phrase.chars().count() == 1 {
+        format!("tokens : \"{phrase}\" *")
+    } else {
+        format!("tokens : \"{phrase}\"")
+    }
+}
+
+async fn exec(db: &DbConn, sql: String, values: Vec<Value>) -> Result<()> {
+    db.execute(Statement::from_sql_and_values(
+        db.get_database_backend(),
+        &sql,
+        values,
+    ))
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,214 @@
+use anyhow::Result;
+use chrono::Utc;
+use sea_orm::prelude::*;
+use sea_orm::sea_query::Expr;
+use sea_orm::ActiveValue::Set;
+use sea_orm::{Condition, ConnectionTrait, QueryOrder, Statement};
+
+use crate::common::db::placeholders;
+use crate::common::snowid::IdGen;
+use crate::keeper::data::entity::job;
+
+// 任务状态
+pub const STATE_PENDING: &str = "pending";
+pub const STATE_RUNNING: &str = "running";
+pub const STATE_FINISHED: &str = "finished";
+pub const STATE_FAILED: &str = "failed";
+
+pub async fn add(
+    db: &DbConn,
+    ids: &IdGen,
+    kind: &str,
+    spider_id: &str,
+    target_id: i64,
+) -> Result<i64> {
+    // 相同的任务尚未完成时不重复添加
+    let exist = job::Entity::find()
+        .filter(
+            Condition::all()
+                .add(job::Column::Kind.eq(kind))
+                .add(job::Column::SpiderId.eq(spider_id))
+                .add(job::Column::TargetId.eq(target_id))
+                .add(job::Column::State.is_in([STATE_PENDING, STATE_RUNNING])),
+        )
+        .one(db)
+        .await?;
+    if let Some(x) = exist {
+        return Ok(x.id);
+    }
+
+    let id = ids.generate();
+    let now = Utc::now();
+    let x = job::ActiveModel {
+        id: Set(id),
+        kind: Set(String::from(kind)),
+        spider_id: Set(String::from(spider_id)),
+        target_id: Set(target_id),
+        cursor: Set(0),
+        state: Set(String::from(STATE_PENDING)),
+        attempts: Set(0),
+        last_error: Set(None),
+        created_at: Set(now),
+        updated_at: Set(now),
+    };
+
+    let _ = job::Entity::insert(x).exec(db).await?;
+
+    Ok(id)
+}
+
+// 领取一个等待中的任务，并将其标记为执行中
+pub async fn acquire(db: &DbConn) -> Result<Option<job::Model>> {
+    acquire_except(db, &[]).await
+}
+
+// 领取任务时跳过指定爬虫的任务
+pub async fn acquire_except(db: &DbConn, paused: &[String]) -> Result<Option<job::Model>> {
+    let mut cond = Condition::all().add(job::Column::State.eq(STATE_PENDING));
+    if !paused.is_empty() {
+        cond = cond.add(job::Column::SpiderId.is_not_in(paused.iter().cloned()));
+    }
+
+    loop {
+        let x = job::Entity::find()
+            .filter(cond.clone())
+            .order_by_asc(job::Column::UpdatedAt)
+            .one(db)
+            .await?;
+
+        let mut x = match x {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+
+        // 只有状态仍然是等待中才能领取成功，否则已经被其他worker领取
+        let now = Utc::now();
+        let r = job::Entity::update_many()
+            .col_expr(job::Column::State, Expr::value(STATE_RUNNING))
+            .col_expr(job::Column::Attempts, Expr::value(x.attempts + 1))
+            .col_expr(job::Column::UpdatedAt, Expr::value(now))
+            .filter(
+                Condition::all()
+                    .add(job::Column::Id.eq(x.id))
+                    .add(job::Column::State.eq(STATE_PENDING)),
+            )
+            .exec(db)
+            .await?;
+
+        if r.rows_affected == 1 {
+            x.state = String::from(STATE_RUNNING);
+            x.attempts += 1;
+            x.updated_at = now;
+
+            return Ok(Some(x));
+        }
+    }
+}
+
+// 记录任务进度
+pub async fn checkpoint(db: &DbConn, id: i64, cursor: i32) -> Result<()> {
+    let _ = job::Entity::update_many()
+        .col_expr(job::Column::Cursor, Expr::value(cursor))
+        .col_expr(job::Column::UpdatedAt, Expr::value(Utc::now()))
+        .filter(job::Column::Id.eq(id))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn finish(db: &DbConn, id: i64) -> Result<()> {
+    set_state(db, id, STATE_FINISHED, None).await
+}
+
+// 将执行中的任务放回队列，不计入失败
+pub async fn release(db: &DbConn, id: i64) -> Result<()> {
+    let _ = job::Entity::update_many()
+        .col_expr(job::Column::State, Expr::value(STATE_PENDING))
+        .col_expr(
+            job::Column::Attempts,
+            Expr::col(job::Column::Attempts).sub(1),
+        )
+        .col_expr(job::Column::UpdatedAt, Expr::value(Utc::now()))
+        .filter(job::Column::Id.eq(id))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+// 记录任务失败，未超过最大次数时重新放回队列
+pub async fn fail(db: &DbConn, x: &job::Model, reason: &str, max_attempts: i32) -> Result<()> {
+    let state = if x.attempts < max_attempts {
+        STATE_PENDING
+    } else {
+        STATE_FAILED
+    };
+
+    set_state(db, x.id, state, Some(reason)).await
+}
+
+// 进程重启后，将上次未执行完的任务恢复为等待状态
+pub async fn recover(db: &DbConn) -> Result<u64> {
+    let r = job::Entity::update_many()
+        .col_expr(job::Column::State, Expr::value(STATE_PENDING))
+        .col_expr(job::Column::UpdatedAt, Expr::value(Utc::now()))
+        .filter(job::Column::State.eq(STATE_RUNNING))
+        .exec(db)
+        .await?;
+
+    Ok(r.rows_affected)
+}
+
+pub async fn list(db: &DbConn, state: Option<&str>) -> Result<Vec<job::Model>> {
+    let mut selector = job::Entity::find();
+    if let Some(x) = state {
+        selector = selector.filter(job::Column::State.eq(x));
+    }
+
+    let x = selector
+        .order_by_asc(job::Column::CreatedAt)
+        .all(db)
+        .await?;
+
+    Ok(x)
+}
+
+// 每个爬虫未完成的任务数，返回 (spider_id, state, count)
+pub async fn depth(db: &DbConn) -> Result<Vec<(String, String, i64)>> {
+    let backend = db.get_database_backend();
+    let rows = db
+        .query_all(Statement::from_sql_and_values(
+            backend,
+            &format!(
+                "select spider_id, state, count(*) as n from crawl_jobs where state in ({}) group by spider_id, state",
+                placeholders(backend, 2)
+            ),
+            vec![STATE_PENDING.into(), STATE_RUNNING.into()],
+        ))
+        .await?;
+
+    let mut v = Vec::with_capacity(rows.len());
+    for x in rows {
+        v.push((
+            x.try_get("", "spider_id")?,
+            x.try_get("", "state")?,
+            x.try_get("", "n")?,
+        ));
+    }
+
+    Ok(v)
+}
+
+async fn set_state(db: &DbConn, id: i64, state: &str, reason: Option<&str>) -> Result<()> {
+    let mut update = job::Entity::update_many()
+        .col_expr(job::Column::State, Expr::value(state))
+        .col_expr(job::Column::UpdatedAt, Expr::value(Utc::now()));
+    if let Some(x) = reason {
+        update = update.col_expr(job::Column::LastError, Expr::value(x));
+    }
+
+    let _ = update.filter(job::Column::Id.eq(id)).exec(db).await?;
+
+    Ok(())
+}
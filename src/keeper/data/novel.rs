@@ -1 +1,178 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use sea_orm::prelude::*;
+use sea_orm::sea_query::Expr;
+use sea_orm::ActiveValue::Set;
+use sea_orm::Condition;
+use serde_json::json;
 
+use crate::common::db::insert_or_ignore;
+use crate::common::snowid::IdGen;
+use crate::keeper::data::entity::{novel, novel_relation};
+use crate::spider::Novel;
+
+// 保存爬虫获取到的小说，相同名字与作者的小说只保留一条记录，并关联到爬虫中的小说
+// 多个任务可能同时保存同一本小说，先插入，已经存在时再合并新的数据
+pub async fn add_or_recover(
+    db: &DbConn,
+    ids: &IdGen,
+    spider_id: &str,
+    data: &Novel,
+) -> Result<i64> {
+    let now = Utc::now();
+    let name = data.name.trim();
+    let author = data.author.trim();
+    let new_id = ids.generate();
+    let x = novel::ActiveModel {
+        id: Set(new_id),
+        name: Set(String::from(name)),
+        created_at: Set(now),
+        updated_at: Set(now),
+        cover: Set(data.cover.clone()),
+        cover_path: Set(None),
+        author: Set(String::from(author)),
+        intro: Set(data.intro.clone()),
+        last_updated_at: Set(data.last_updated_at),
+        last_section: Set(None),
+        last_section_name: Set(data.last_updated_section_name.clone()),
+        word_count: Set(data.word_count.map(|x| x as i64)),
+        tags: Set(Some(json!(data.tags))),
+        category: Set(data.category.clone()),
+        aliases: Set(Some(json!(data.aliases))),
+    };
+    insert_or_ignore(db, x, &[novel::Column::Name, novel::Column::Author]).await?;
+
+    let x: novel::Model = novel::Entity::find()
+        .filter(
+            Condition::all()
+                .add(novel::Column::Name.eq(name))
+                .add(novel::Column::Author.eq(author)),
+        )
+        .one(db)
+        .await?
+        .ok_or_else(|| anyhow!("保存小说失败; name={name}, author={author}"))?;
+    let id = x.id;
+
+    if id != new_id {
+        // 标签与别名合并所有来源的数据
+        let tags = merge_strings(x.tags.as_ref(), &data.tags);
+        let aliases = merge_strings(x.aliases.as_ref(), &data.aliases);
+
+        let mut x: novel::ActiveModel = x.into();
+        if data.cover.is_some() {
+            x.cover = Set(data.cover.clone());
+        }
+        if data.intro.is_some() {
+            x.intro = Set(data.intro.clone());
+        }
+        if data.last_updated_at.is_some() {
+            x.last_updated_at = Set(data.last_updated_at);
+        }
+        if data.last_updated_section_name.is_some() {
+            x.last_section_name = Set(data.last_updated_section_name.clone());
+        }
+        if let Some(n) = data.word_count {
+            x.word_count = Set(Some(n as i64));
+        }
+        if data.category.is_some() {
+            x.category = Set(data.category.clone());
+        }
+        x.tags = Set(Some(json!(tags)));
+        x.aliases = Set(Some(json!(aliases)));
+        x.updated_at = Set(now);
+
+        let _ = x.update(db).await?;
+    }
+
+    // 关联爬虫中的小说，来源网站上的地址保存在关联上
+    let spider_novel_id: i64 = data.id.into();
+    let x = novel_relation::ActiveModel {
+        spider_kind_id: Set(String::from(spider_id)),
+        novel_id: Set(id),
+        spider_novel_id: Set(spider_novel_id),
+        score: Set(0),
+        source_url: Set(data.source_url.clone()),
+    };
+    insert_or_ignore(
+        db,
+        x,
+        &[
+            novel_relation::Column::SpiderKindId,
+            novel_relation::Column::NovelId,
+            novel_relation::Column::SpiderNovelId,
+        ],
+    )
+    .await?;
+    if let Some(url) = &data.source_url {
+        let _ = novel_relation::Entity::update_many()
+            .col_expr(novel_relation::Column::SourceUrl, Expr::value(url.as_str()))
+            .filter(
+                Condition::all()
+                    .add(novel_relation::Column::SpiderKindId.eq(spider_id))
+                    .add(novel_relation::Column::NovelId.eq(id))
+                    .add(novel_relation::Column::SpiderNovelId.eq(spider_novel_id)),
+            )
+            .exec(db)
+            .await?;
+    }
+
+    Ok(id)
+}
+
+pub async fn set_cover_path(db: &DbConn, id: i64, path: &str) -> Result<()> {
+    let _ = novel::Entity::update_many()
+        .col_expr(novel::Column::CoverPath, Expr::value(path))
+        .col_expr(novel::Column::UpdatedAt, Expr::value(Utc::now()))
+        .filter(novel::Column::Id.eq(id))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+// 通过爬虫中的小说id获取关联的小说id
+pub async fn relation_novel_id(
+    db: &DbConn,
+    spider_id: &str,
+    spider_novel_id: i64,
+) -> Result<Option<i64>> {
+    let x = novel_relation::Entity::find()
+        .filter(
+            Condition::all()
+                .add(novel_relation::Column::SpiderKindId.eq(spider_id))
+                .add(novel_relation::Column::SpiderNovelId.eq(spider_novel_id)),
+        )
+        .one(db)
+        .await?;
+
+    Ok(x.map(|x| x.novel_id))
+}
+
+pub async fn novel_by_id(db: &DbConn, id: i64) -> Result<Option<novel::Model>> {
+    let x = novel::Entity::find_by_id(id).one(db).await?;
+
+    Ok(x)
+}
+
+// 读取json中的字符串数组
+pub fn strings(x: Option<&Json>) -> Vec<String> {
+    x.and_then(|x| x.as_array())
+        .map(|x| {
+            x.iter()
+                .filter_map(|x| x.as_str())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn merge_strings(old: Option<&Json>, new: &[String]) -> Vec<String> {
+    let mut x = strings(old);
+    for v in new {
+        if !x.contains(v) {
+            x.push(v.clone());
+        }
+    }
+
+    x
+}
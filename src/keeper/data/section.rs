@@ -0,0 +1,120 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sea_orm::prelude::*;
+use sea_orm::ActiveValue::Set;
+use sea_orm::{Condition, ConnectionTrait, QueryOrder, Statement};
+
+use crate::common::db::placeholder;
+use crate::keeper::data::entity::section;
+
+// 章节内容以外的信息
+pub struct SectionInfo<'a> {
+    pub spider_id: &'a str,
+    pub spider_novel_id: i64,
+    pub novel_id: Option<i64>,
+    pub seq: i32,
+    pub name: &'a str,
+    pub update_at: Option<DateTime<Utc>>,
+}
+
+pub async fn find(
+    db: &DbConn,
+    spider_id: &str,
+    spider_novel_id: i64,
+    seq: i32,
+) -> Result<Option<section::Model>> {
+    let x = section::Entity::find()
+        .filter(
+            Condition::all()
+                .add(section::Column::SpiderId.eq(spider_id))
+                .add(section::Column::SpiderNovelId.eq(spider_novel_id))
+                .add(section::Column::Seq.eq(seq)),
+        )
+        .one(db)
+        .await?;
+
+    Ok(x)
+}
+
+// 写入章节记录，id已经存在时更新记录
+pub async fn save(
+    db: &DbConn,
+    id: i64,
+    info: &SectionInfo<'_>,
+    content_key: &str,
+    content_hash: &str,
+    size: usize,
+) -> Result<()> {
+    let now = Utc::now();
+    match section::Entity::find_by_id(id).one(db).await? {
+        Some(x) => {
+            let mut x: section::ActiveModel = x.into();
+            x.novel_id = Set(info.novel_id);
+            x.name = Set(String::from(info.name));
+            x.update_at = Set(info.update_at);
+            x.content_key = Set(String::from(content_key));
+            x.content_hash = Set(String::from(content_hash));
+            x.size = Set(size as i64);
+            x.updated_at = Set(now);
+
+            let _ = x.update(db).await?;
+        }
+        None => {
+            let x = section::ActiveModel {
+                id: Set(id),
+                spider_id: Set(String::from(info.spider_id)),
+                spider_novel_id: Set(info.spider_novel_id),
+                novel_id: Set(info.novel_id),
+                seq: Set(info.seq),
+                name: Set(String::from(info.name)),
+                update_at: Set(info.update_at),
+                content_key: Set(String::from(content_key)),
+                content_hash: Set(String::from(content_hash)),
+                size: Set(size as i64),
+                created_at: Set(now),
+                updated_at: Set(now),
+            };
+
+            let _ = section::Entity::insert(x).exec(db).await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn section_by_id(db: &DbConn, id: i64) -> Result<Option<section::Model>> {
+    let x = section::Entity::find_by_id(id).one(db).await?;
+
+    Ok(x)
+}
+
+// 获取小说的全部章节，按序号排序
+pub async fn sections_by_novel_id(db: &DbConn, novel_id: i64) -> Result<Vec<section::Model>> {
+    let x = section::Entity::find()
+        .filter(section::Column::NovelId.eq(novel_id))
+        .order_by_asc(section::Column::Seq)
+        .all(db)
+        .await?;
+
+    Ok(x)
+}
+
+// 小说已保存的章节数，多个来源的相同序号只计算一次
+pub async fn count_by_novel_id(db: &DbConn, novel_id: i64) -> Result<i64> {
+    let backend = db.get_database_backend();
+    let x = db
+        .query_one(Statement::from_sql_and_values(
+            backend,
+            &format!(
+                "select count(distinct seq) as n from sections where novel_id = {}",
+                placeholder(backend, 1)
+            ),
+            vec![novel_id.into()],
+        ))
+        .await?;
+
+    Ok(match x {
+        Some(x) => x.try_get("", "n")?,
+        None => 0,
+    })
+}
@@ -4,9 +4,9 @@ use sea_orm::prelude::*;
 use sea_orm::ActiveValue::Set;
 use sea_orm::{Condition, DatabaseConnection, TransactionTrait};
 
+use crate::common::snowid::IdGen;
 use crate::keeper::data::entity::sort;
 use crate::spider::Sort;
-use crate::GEN;
 
 pub struct ListOpt<'a> {
     pub created_at_less_than: Option<&'a DateTime<Utc>>,
@@ -15,17 +15,17 @@ pub struct ListOpt<'a> {
 
 pub async fn add_or_recover<'a>(
     db: &'a DatabaseConnection,
+    ids: &IdGen,
     id: &'a str,
-    data: &Vec<Sort>,
+    data: &[Sort],
 ) -> Result<()> {
     let data: Vec<sort::ActiveModel> = data
         .iter()
         .map(|x| {
-            let mut gen = GEN.write();
             let now = Utc::now();
             sort::ActiveModel {
-                id: Set(gen.generate()),
-                created_at: Set(now.clone()),
+                id: Set(ids.generate()),
+                created_at: Set(now),
                 updated_at: Set(None),
                 name: Set(x.name.clone()),
                 relation_kind_id: Set(Some(id.into())),
@@ -36,19 +36,18 @@ pub async fn add_or_recover<'a>(
 
     // TODO 使用引用有生命周期的问题
     let id = String::from(id);
-    let _ = db
-        .transaction(|tx| {
-            Box::pin(async move {
-                let _ = sort::Entity::delete_many()
-                    .filter(sort::Column::RelationKindId.eq(id))
-                    .exec(tx)
-                    .await?;
-                let _ = sort::Entity::insert_many(data).exec(tx).await?;
+    db.transaction(|tx| {
+        Box::pin(async move {
+            let _ = sort::Entity::delete_many()
+                .filter(sort::Column::RelationKindId.eq(id))
+                .exec(tx)
+                .await?;
+            let _ = sort::Entity::insert_many(data).exec(tx).await?;
 
-                Ok::<(), DbErr>(())
-            })
+            Ok::<(), DbErr>(())
         })
-        .await?;
+    })
+    .await?;
 
     Ok(())
 }
@@ -60,7 +59,7 @@ pub async fn list(db: &DatabaseConnection, opts: Option<ListOpt<'_>>) -> Result<
 
             if let Some(opts) = opts {
                 if let Some(x) = opts.created_at_less_than {
-                    condition = condition.add(sort::Column::CreatedAt.lt((*x).clone()));
+                    condition = condition.add(sort::Column::CreatedAt.lt(*x));
                 }
 
                 if let Some(x) = opts.relation_spider_id {
@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::error;
+
+use crate::keeper::data::{follow, novel, section};
+use crate::keeper::notify::{Notifier, NovelUpdated};
+use crate::keeper::Keeper;
+
+impl Keeper {
+    // 设置更新通知方式，未设置时只记录状态不发送通知
+    pub fn set_notifier(&mut self, notifier: Arc<dyn Notifier>) {
+        self.notifier = Some(notifier);
+    }
+
+    // 关注小说，subscriber由调用方定义，例如用户id或 mailto:地址
+    pub async fn follow(&self, novel_id: i64, subscriber: &str) -> Result<i64> {
+        follow::add(&self.db, &self.ids, novel_id, subscriber).await
+    }
+
+    pub async fn unfollow(&self, novel_id: i64, subscriber: &str) -> Result<bool> {
+        follow::remove(&self.db, novel_id, subscriber).await
+    }
+
+    pub async fn followers(&self, novel_id: i64) -> Result<Vec<String>> {
+        follow::subscribers(&self.db, novel_id).await
+    }
+
+    // 对比小说的最新章节、更新时间与已保存的章节数，有变化时发送通知
+    // 最新章节与更新时间为None时使用上次记录的值，只对比章节数
+    // 只检查被关注的小说，第一次检查只记录状态
+    pub async fn check_update(
+        &self,
+        novel_id: i64,
+        last_section_name: Option<&str>,
+        last_updated_at: Option<DateTime<Utc>>,
+    ) -> Result<Option<NovelUpdated>> {
+        let subscribers = follow::subscribers(&self.db, novel_id).await?;
+        if subscribers.is_empty() {
+            return Ok(None);
+        }
+
+        let count = section::count_by_novel_id(&self.db, novel_id).await?;
+        let old = follow::watch(&self.db, novel_id).await?;
+
+        let name = last_section_name
+            .map(String::from)
+            .or_else(|| old.as_ref().and_then(|x| x.last_section_name.clone()));
+        let updated_at = last_updated_at.or_else(|| old.as_ref().and_then(|x| x.last_updated_at));
+        follow::save_watch(&self.db, novel_id, name.as_deref(), updated_at, count).await?;
+
+        let old = match old {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+
+        let changed = name.is_some() && name != old.last_section_name
+            || matches!((updated_at, old.last_updated_at), (Some(a), Some(b)) if a > b)
+            || (updated_at.is_some() && old.last_updated_at.is_none())
+            || count > old.section_count;
+        if !changed {
+            return Ok(None);
+        }
+
+        let x = novel::novel_by_id(&self.db, novel_id).await?;
+        let event = NovelUpdated {
+            novel_id,
+            name: x.as_ref().map(|x| x.name.clone()).unwrap_or_default(),
+            author: x.map(|x| x.author).unwrap_or_default(),
+            last_section_name: name,
+            last_updated_at: updated_at,
+            new_sections: (count - old.section_count).max(0),
+            section_count: count,
+            subscribers,
+        };
+
+        // 通知失败不影响爬取
+        if let Some(notifier) = &self.notifier {
+            if let Err(e) = notifier.notify(&event).await {
+                error!("发送更新通知失败; novel_id={novel_id}, err={e:#}");
+            }
+        }
+
+        Ok(Some(event))
+    }
+}
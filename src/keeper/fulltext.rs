@@ -0,0 +1,218 @@
+use anyhow::{anyhow, Result};
+
+use crate::keeper::data::{fulltext, section};
+use crate::keeper::Keeper;
+
+// 片段中关键词前后保留的字数
+const SNIPPET_CONTEXT: usize = 30;
+
+// 全文搜索的结果，section_id为空时表示匹配的是小说名或简介
+#[derive(Debug)]
+pub struct TextHit {
+    pub novel_id: Option<i64>,
+    pub section_id: Option<i64>,
+    // 章节序号
+    pub seq: Option<i32>,
+    // 小说名或章节名
+    pub name: String,
+    // 包含关键词的片段
+    pub snippet: String,
+    // 关键词在内容中的字符位置，没有找到原文时为None
+    pub offset: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct TextQuery<'a> {
+    pub keyword: &'a str,
+    // 只搜索指定小说的章节
+    pub novel_id: Option<i64>,
+    // 是否搜索章节内容
+    pub sections: bool,
+    pub limit: u64,
+}
+
+impl<'a> TextQuery<'a> {
+    pub fn new(keyword: &'a str) -> Self {
+        Self {
+            keyword,
+            novel_id: None,
+            sections: true,
+            limit: 20,
+        }
+    }
+}
+
+// 将文本切分为以空格分隔的词，中文按二元组切分，其他连续的字母数字作为一个词
+pub fn tokenize(text: &str) -> String {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut cjk: Vec<char> = Vec::new();
+    let mut word = String::new();
+
+    fn flush_cjk(cjk: &mut Vec<char>, tokens: &mut Vec<String>) {
+        match cjk.len() {
+            0 => {}
+            1 => tokens.push(cjk[0].to_string()),
+            _ => tokens.extend(cjk.windows(2).map(|x| x.iter().collect())),
+        }
+        cjk.clear();
+    }
+
+    for c in text.chars() {
+        if is_cjk(c) {
+            if !word.is_empty() {
+                tokens.push(std::mem::take(&mut word));
+            }
+            cjk.push(c);
+        } else if c.is_alphanumeric() {
+            flush_cjk(&mut cjk, &mut tokens);
+            word.extend(c.to_lowercase());
+        } else {
+            flush_cjk(&mut cjk, &mut tokens);
+            if !word.is_empty() {
+                tokens.push(std::mem::take(&mut word));
+            }
+        }
+    }
+    flush_cjk(&mut cjk, &mut tokens);
+    if !word.is_empty() {
+        tokens.push(word);
+    }
+
+    // 前后的空格用于like匹配完整的词
+    format!(" {} ", tokens.join(" "))
+}
+
+// 文本中不重复的中文单字，以空格分隔
+pub fn unigrams(text: &str) -> String {
+    let mut chars: Vec<char> = text.chars().filter(|x| is_cjk(*x)).collect();
+    chars.sort_unstable();
+    chars.dedup();
+
+    let v: Vec<String> = chars.iter().map(|x| x.to_string()).collect();
+    format!(" {} ", v.join(" "))
+}
+
+pub fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0x20000..=0x2FFFF
+        | 0x3040..=0x30FF | 0xAC00..=0xD7AF)
+}
+
+// 截取关键词附近的片段，返回片段与关键词的字符位置
+pub fn snippet(text: &str, keyword: &str) -> (String, Option<usize>) {
+    let chars: Vec<char> = text.chars().collect();
+    let lower: Vec<char> = text.chars().flat_map(|x| x.to_lowercase()).collect();
+    let key: Vec<char> = keyword
+        .trim()
+        .chars()
+        .flat_map(|x| x.to_lowercase())
+        .collect();
+
+    // 大小写转换改变了长度时无法对应位置，只取开头
+    let offset = if key.is_empty() || lower.len() != chars.len() {
+        None
+    } else {
+        lower.windows(key.len()).position(|x| x == key.as_slice())
+    };
+
+    let (start, end) = match offset {
+        Some(x) => (
+            x.saturating_sub(SNIPPET_CONTEXT),
+            (x + key.len() + SNIPPET_CONTEXT).min(chars.len()),
+        ),
+        None => (0, (SNIPPET_CONTEXT * 2).min(chars.len())),
+    };
+
+    let mut s: String = chars[start..end]
+        .iter()
+        .map(|x| if x.is_whitespace() { ' ' } else { *x })
+        .collect();
+    if start > 0 {
+        s.insert(0, '…');
+    }
+    if end < chars.len() {
+        s.push('…');
+    }
+
+    (s, offset)
+}
+
+impl Keeper {
+    // 索引小说名与简介
+    pub async fn index_novel_text(
+        &self,
+        novel_id: i64,
+        name: &str,
+        intro: Option<&str>,
+    ) -> Result<()> {
+        let text = format!("{name} {}", intro.unwrap_or_default());
+
+        fulltext::index_novel(
+            &self.db,
+            novel_id,
+            name,
+            intro,
+            &tokenize(&text),
+            &unigrams(&text),
+        )
+        .await
+    }
+
+    // 在小说名、简介以及章节内容中搜索
+    pub async fn search_text(&self, query: &TextQuery<'_>) -> Result<Vec<TextHit>> {
+        let phrase = tokenize(query.keyword);
+        let phrase = phrase.trim();
+        if phrase.is_empty() {
+            return Err(anyhow!("搜索词不能为空"));
+        }
+
+        let mut hits = Vec::new();
+        if query.novel_id.is_none() {
+            for x in fulltext::search_novels(&self.db, phrase, query.limit).await? {
+                let (snippet, offset) = match x.intro.as_deref() {
+                    Some(intro) => snippet(intro, query.keyword),
+                    None => (String::new(), None),
+                };
+
+                hits.push(TextHit {
+                    novel_id: Some(x.novel_id),
+                    section_id: None,
+                    seq: None,
+                    name: x.name,
+                    snippet,
+                    offset,
+                });
+            }
+        }
+
+        if !query.sections {
+            return Ok(hits);
+        }
+
+        let sections = self
+            .sections
+            .as_ref()
+            .ok_or_else(|| anyhow!("没有设置内容存储"))?;
+        for x in fulltext::search_sections(&self.db, phrase, query.novel_id, query.limit).await? {
+            let row = match section::section_by_id(&self.db, x.section_id).await? {
+                Some(x) => x,
+                None => continue,
+            };
+            let (snippet, offset) = match sections.text(&row).await? {
+                Some(text) => snippet(&text, query.keyword),
+                None => (String::new(), None),
+            };
+
+            hits.push(TextHit {
+                novel_id: x.novel_id,
+                section_id: Some(x.section_id),
+                seq: Some(x.seq),
+                name: row.name,
+                snippet,
+                offset,
+            });
+        }
+
+        Ok(hits)
+    }
+}
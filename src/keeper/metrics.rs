@@ -0,0 +1,528 @@
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use log::{info, warn};
+use reqwest::Url;
+use serde::Deserialize;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+
+use crate::common::events;
+use crate::common::events::Event;
+use crate::common::shutdown::CancellationToken;
+use crate::keeper::data::job;
+use crate::keeper::Keeper;
+
+// 请求耗时的分桶，单位为秒
+const LATENCY_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+// 解析失败的错误类型，网站页面结构变化时会大量出现
+const PARSE_FAILURE_KINDS: &[&str] = &["parse_failed", "miss_section_content", "miss_section_link"];
+
+// 订阅事件总线得到的监控指标，以Prometheus文本格式输出
+#[derive(Default)]
+pub struct Metrics {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    // (spider, host, status)
+    requests: BTreeMap<(String, String, u16), u64>,
+    // (spider, host)
+    bytes: BTreeMap<(String, String), u64>,
+    latency: BTreeMap<(String, String), Histogram>,
+    // (spider, host, kind)
+    failures: BTreeMap<(String, String, String), u64>,
+    // (spider, kind)
+    parse_failures: BTreeMap<(String, String), u64>,
+    novels: BTreeMap<String, u64>,
+    sections: BTreeMap<String, u64>,
+    section_bytes: BTreeMap<String, u64>,
+    // (spider, kind, outcome)
+    jobs: BTreeMap<(String, String, String), u64>,
+    // (spider, kind)
+    retries: BTreeMap<(String, String), u64>,
+}
+
+#[derive(Clone)]
+struct Histogram {
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            counts: vec![0; LATENCY_BUCKETS.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+}
+
+impl Histogram {
+    fn observe(&mut self, d: Duration) {
+        let x = d.as_secs_f64();
+        for (i, le) in LATENCY_BUCKETS.iter().enumerate() {
+            if x <= *le {
+                self.counts[i] += 1;
+            }
+        }
+        self.sum += x;
+        self.count += 1;
+    }
+}
+
+// 采集时读取的瞬时值
+#[derive(Debug, Default)]
+pub struct Gauges {
+    // (spider, state, count)
+    pub queue: Vec<(String, String, i64)>,
+    // (spider, in_use, limit)
+    pub permits: Vec<(String, usize, usize)>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn observe(&self, event: &Event) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match event {
+            Event::PageFetched(x) => {
+                let host = host(&x.url);
+                *state
+                    .requests
+                    .entry((x.spider_id.clone(), host.clone(), x.status))
+                    .or_default() += 1;
+                *state
+                    .bytes
+                    .entry((x.spider_id.clone(), host.clone()))
+                    .or_default() += x.bytes;
+                state
+                    .latency
+                    .entry((x.spider_id.clone(), host))
+                    .or_default()
+                    .observe(x.elapsed);
+            }
+            Event::NovelParsed(x) => {
+                *state.novels.entry(x.spider_id.clone()).or_default() += 1;
+            }
+            Event::SectionStored(x) => {
+                *state.sections.entry(x.spider_id.clone()).or_default() += 1;
+                *state.section_bytes.entry(x.spider_id.clone()).or_default() += x.bytes;
+            }
+            Event::CrawlFailed(x) => {
+                let host = x.url.as_deref().map(host).unwrap_or_default();
+                *state
+                    .failures
+                    .entry((x.spider_id.clone(), host, x.kind.clone()))
+                    .or_default() += 1;
+                if PARSE_FAILURE_KINDS.contains(&x.kind.as_str()) {
+                    *state
+                        .parse_failures
+                        .entry((x.spider_id.clone(), x.kind.clone()))
+                        .or_default() += 1;
+                }
+            }
+            Event::JobFinished(x) => {
+                *state
+                    .jobs
+                    .entry((x.spider_id.clone(), x.kind.clone(), x.outcome.clone()))
+                    .or_default() += 1;
+                if x.attempt > 1 {
+                    *state
+                        .retries
+                        .entry((x.spider_id.clone(), x.kind.clone()))
+                        .or_default() += 1;
+                }
+            }
+        }
+    }
+
+    // 订阅事件总线，直到取消
+    pub fn spawn(self: &Arc<Self>, cancel: CancellationToken) -> JoinHandle<()> {
+        let mut rx = events::subscribe();
+        let metrics = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let event = tokio::select! {
+                    x = rx.recv() => x,
+                    _ = cancel.cancelled() => return,
+                };
+
+                match event {
+                    Ok(x) => metrics.observe(&x),
+                    Err(RecvError::Lagged(n)) => warn!("监控指标处理不及时，丢弃了 {n} 个事件"),
+                    Err(RecvError::Closed) => return,
+                }
+            }
+        })
+    }
+
+    pub fn render(&self, gauges: &Gauges) -> String {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "spider_http_requests_total",
+            "counter",
+            "按状态码统计的页面请求数",
+        );
+        for ((spider, host, status), n) in &state.requests {
+            let status = status.to_string();
+            sample(
+                &mut out,
+                "spider_http_requests_total",
+                &[
+                    ("spider", spider.as_str()),
+                    ("host", host.as_str()),
+                    ("status", status.as_str()),
+                ],
+                *n as f64,
+            );
+        }
+
+        header(
+            &mut out,
+            "spider_http_response_bytes_total",
+            "counter",
+            "响应体字节数",
+        );
+        for ((spider, host), n) in &state.bytes {
+            sample(
+                &mut out,
+                "spider_http_response_bytes_total",
+                &[("spider", spider.as_str()), ("host", host.as_str())],
+                *n as f64,
+            );
+        }
+
+        header(
+            &mut out,
+            "spider_http_request_duration_seconds",
+            "histogram",
+            "页面请求耗时",
+        );
+        for ((spider, host), h) in &state.latency {
+            let labels = [("spider", spider.as_str()), ("host", host.as_str())];
+            for (le, n) in LATENCY_BUCKETS.iter().zip(&h.counts) {
+                let le = le.to_string();
+                sample(
+                    &mut out,
+                    "spider_http_request_duration_seconds_bucket",
+                    &[labels[0], labels[1], ("le", le.as_str())],
+                    *n as f64,
+                );
+            }
+            sample(
+                &mut out,
+                "spider_http_request_duration_seconds_bucket",
+                &[labels[0], labels[1], ("le", "+Inf")],
+                h.count as f64,
+            );
+            sample(
+                &mut out,
+                "spider_http_request_duration_seconds_sum",
+                &labels,
+                h.sum,
+            );
+            sample(
+                &mut out,
+                "spider_http_request_duration_seconds_count",
+                &labels,
+                h.count as f64,
+            );
+        }
+
+        header(
+            &mut out,
+            "spider_crawl_failures_total",
+            "counter",
+            "按错误类型统计的爬取失败数",
+        );
+        for ((spider, host, kind), n) in &state.failures {
+            sample(
+                &mut out,
+                "spider_crawl_failures_total",
+                &[
+                    ("spider", spider.as_str()),
+                    ("host", host.as_str()),
+                    ("kind", kind.as_str()),
+                ],
+                *n as f64,
+            );
+        }
+
+        header(
+            &mut out,
+            "spider_parse_failures_total",
+            "counter",
+            "页面解析失败数",
+        );
+        for ((spider, kind), n) in &state.parse_failures {
+            sample(
+                &mut out,
+                "spider_parse_failures_total",
+                &[("spider", spider.as_str()), ("kind", kind.as_str())],
+                *n as f64,
+            );
+        }
+
+        header(
+            &mut out,
+            "spider_novels_parsed_total",
+            "counter",
+            "解析到的小说数",
+        );
+        for (spider, n) in &state.novels {
+            sample(
+                &mut out,
+                "spider_novels_parsed_total",
+                &[("spider", spider.as_str())],
+                *n as f64,
+            );
+        }
+
+        header(
+            &mut out,
+            "spider_sections_stored_total",
+            "counter",
+            "保存的章节数",
+        );
+        for (spider, n) in &state.sections {
+            sample(
+                &mut out,
+                "spider_sections_stored_total",
+                &[("spider", spider.as_str())],
+                *n as f64,
+            );
+        }
+
+        header(
+            &mut out,
+            "spider_section_bytes_total",
+            "counter",
+            "保存的章节字节数",
+        );
+        for (spider, n) in &state.section_bytes {
+            sample(
+                &mut out,
+                "spider_section_bytes_total",
+                &[("spider", spider.as_str())],
+                *n as f64,
+            );
+        }
+
+        header(
+            &mut out,
+            "spider_jobs_finished_total",
+            "counter",
+            "按结果统计的任务执行次数",
+        );
+        for ((spider, kind, outcome), n) in &state.jobs {
+            sample(
+                &mut out,
+                "spider_jobs_finished_total",
+                &[
+                    ("spider", spider.as_str()),
+                    ("kind", kind.as_str()),
+                    ("outcome", outcome.as_str()),
+                ],
+                *n as f64,
+            );
+        }
+
+        header(
+            &mut out,
+            "spider_job_retries_total",
+            "counter",
+            "任务重试次数",
+        );
+        for ((spider, kind), n) in &state.retries {
+            sample(
+                &mut out,
+                "spider_job_retries_total",
+                &[("spider", spider.as_str()), ("kind", kind.as_str())],
+                *n as f64,
+            );
+        }
+
+        header(
+            &mut out,
+            "spider_job_queue_depth",
+            "gauge",
+            "未完成的任务数",
+        );
+        for (spider, state, n) in &gauges.queue {
+            sample(
+                &mut out,
+                "spider_job_queue_depth",
+                &[("spider", spider.as_str()), ("state", state.as_str())],
+                *n as f64,
+            );
+        }
+
+        header(
+            &mut out,
+            "spider_concurrency_in_use",
+            "gauge",
+            "正在使用的并发数",
+        );
+        for (spider, used, _) in &gauges.permits {
+            sample(
+                &mut out,
+                "spider_concurrency_in_use",
+                &[("spider", spider.as_str())],
+                *used as f64,
+            );
+        }
+        header(&mut out, "spider_concurrency_limit", "gauge", "最大并发数");
+        for (spider, _, limit) in &gauges.permits {
+            sample(
+                &mut out,
+                "spider_concurrency_limit",
+                &[("spider", spider.as_str())],
+                *limit as f64,
+            );
+        }
+        header(
+            &mut out,
+            "spider_concurrency_saturation",
+            "gauge",
+            "并发使用率，为1时请求需要排队",
+        );
+        for (spider, used, limit) in &gauges.permits {
+            let x = if *limit == 0 {
+                0.0
+            } else {
+                *used as f64 / *limit as f64
+            };
+            sample(
+                &mut out,
+                "spider_concurrency_saturation",
+                &[("spider", spider.as_str())],
+                x,
+            );
+        }
+
+        out
+    }
+}
+
+impl Keeper {
+    // 读取任务队列与爬虫并发的瞬时值
+    pub async fn gauges(&self) -> Result<Gauges> {
+        let queue = job::depth(&self.db).await?;
+        let permits = self
+            .spiders
+            .iter()
+            .filter_map(|x| {
+                x.inner
+                    .permits()
+                    .map(|(used, limit)| (x.id.to_string(), used, limit))
+            })
+            .collect();
+
+        Ok(Gauges { queue, permits })
+    }
+
+    // 读取瞬时值失败时只输出计数器
+    pub async fn metrics_text(&self, metrics: &Metrics) -> String {
+        let gauges = match self.gauges().await {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("读取任务队列失败: {e}");
+                Gauges::default()
+            }
+        };
+
+        metrics.render(&gauges)
+    }
+}
+
+// 在 /metrics 提供监控指标，直到取消
+pub async fn serve<F, Fut>(addr: SocketAddr, cancel: CancellationToken, render: F) -> Result<()>
+where
+    F: Fn() -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = String> + Send,
+{
+    let make = make_service_fn(move |_| {
+        let render = render.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                let render = render.clone();
+                async move {
+                    let resp = if req.method() == Method::GET && req.uri().path() == "/metrics" {
+                        Response::builder()
+                            .header("content-type", "text/plain; version=0.0.4; charset=utf-8")
+                            .body(Body::from(render().await))
+                            .unwrap()
+                    } else {
+                        Response::builder()
+                            .status(StatusCode::NOT_FOUND)
+                            .body(Body::empty())
+                            .unwrap()
+                    };
+
+                    Ok::<_, Infallible>(resp)
+                }
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&addr)?.serve(make);
+    info!("监控指标地址 http://{}/metrics", server.local_addr());
+    server
+        .with_graceful_shutdown(async move { cancel.cancelled().await })
+        .await?;
+
+    Ok(())
+}
+
+fn host(url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|x| x.host_str().map(String::from))
+        .unwrap_or_default()
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: f64) {
+    let labels = labels
+        .iter()
+        .map(|(k, v)| format!("{k}=\"{}\"", escape(v)))
+        .collect::<Vec<_>>()
+        .join(",");
+    let _ = writeln!(out, "{name}{{{labels}}} {value}");
+}
+
+fn escape(x: &str) -> String {
+    x.replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    // 监控指标的监听地址，例如 127.0.0.1:9100，未设置时不提供
+    pub listen: Option<String>,
+}
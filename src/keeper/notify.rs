@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use log::error;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::config::parse_duration;
+use crate::keeper::notify::smtp::SmtpNotifier;
+use crate::keeper::notify::stdout::StdoutNotifier;
+use crate::keeper::notify::webhook::WebhookNotifier;
+
+pub mod smtp;
+pub mod stdout;
+pub mod webhook;
+
+// 关注的小说有更新
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NovelUpdated {
+    pub novel_id: i64,
+    pub name: String,
+    pub author: String,
+    pub last_section_name: Option<String>,
+    pub last_updated_at: Option<DateTime<Utc>>,
+    // 新增的章节数，只检测到最新章节变化时为0
+    pub new_sections: i64,
+    // 已保存的章节数
+    pub section_count: i64,
+    pub subscribers: Vec<String>,
+}
+
+impl NovelUpdated {
+    // 合并同一本小说之后的更新，保留最新的状态并累计新增章节
+    pub fn merge(&mut self, newer: NovelUpdated) {
+        self.new_sections += newer.new_sections;
+        self.name = newer.name;
+        self.author = newer.author;
+        if newer.last_section_name.is_some() {
+            self.last_section_name = newer.last_section_name;
+        }
+        if newer.last_updated_at.is_some() {
+            self.last_updated_at = newer.last_updated_at;
+        }
+        self.section_count = self.section_count.max(newer.section_count);
+        for x in newer.subscribers {
+            if !self.subscribers.contains(&x) {
+                self.subscribers.push(x);
+            }
+        }
+    }
+}
+
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn notify(&self, event: &NovelUpdated) -> Result<()>;
+}
+
+// 依次通知所有的通知方式，某个失败不影响其他
+pub struct Fanout {
+    inner: Vec<Arc<dyn Notifier>>,
+}
+
+impl Fanout {
+    pub fn new(inner: Vec<Arc<dyn Notifier>>) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl Notifier for Fanout {
+    async fn notify(&self, event: &NovelUpdated) -> Result<()> {
+        let mut failed = 0;
+        for x in &self.inner {
+            if let Err(e) = x.notify(event).await {
+                error!("发送更新通知失败; novel_id={}, err={e:#}", event.novel_id);
+                failed += 1;
+            }
+        }
+
+        if failed > 0 {
+            return Err(anyhow!("{failed} 个通知发送失败"));
+        }
+
+        Ok(())
+    }
+}
+
+// 防抖：同一本小说在窗口内的多次更新合并为一条通知，在第一次更新后window时间发送
+pub struct Debounced {
+    inner: Arc<dyn Notifier>,
+    window: Duration,
+    pending: Arc<Mutex<HashMap<i64, NovelUpdated>>>,
+}
+
+impl Debounced {
+    pub fn new(inner: Arc<dyn Notifier>, window: Duration) -> Self {
+        Self {
+            inner,
+            window,
+            pending: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // 立即发送所有等待中的通知，关闭时调用
+    pub async fn flush(&self) {
+        let events: Vec<_> = self.pending.lock().await.drain().map(|x| x.1).collect();
+        for x in events {
+            if let Err(e) = self.inner.notify(&x).await {
+                error!("发送更新通知失败; novel_id={}, err={e:#}", x.novel_id);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for Debounced {
+    async fn notify(&self, event: &NovelUpdated) -> Result<()> {
+        let mut pending = self.pending.lock().await;
+        if let Some(x) = pending.get_mut(&event.novel_id) {
+            x.merge(event.clone());
+            return Ok(());
+        }
+        pending.insert(event.novel_id, event.clone());
+
+        let novel_id = event.novel_id;
+        let window = self.window;
+        let pending = self.pending.clone();
+        let inner = self.inner.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(window).await;
+
+            // 已经被flush时不再发送
+            let event = match pending.lock().await.remove(&novel_id) {
+                Some(x) => x,
+                None => return,
+            };
+            if let Err(e) = inner.notify(&event).await {
+                error!("发送更新通知失败; novel_id={novel_id}, err={e:#}");
+            }
+        });
+
+        Ok(())
+    }
+}
+
+// 关注小说的更新通知，所有通知方式都未设置时不发送通知
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifyConfig {
+    // 同一本小说在该时间内的多次更新合并为一条通知
+    pub debounce: String,
+    // 以json输出到标准输出
+    pub stdout: bool,
+    pub webhook: Option<WebhookConfig>,
+    pub smtp: Option<SmtpConfig>,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            debounce: String::from("10m"),
+            stdout: false,
+            webhook: None,
+            smtp: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub url: String,
+    // 设置后对请求体签名
+    pub secret: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SmtpConfig {
+    // 格式为 host:port
+    pub addr: String,
+    pub from: String,
+    // 固定的收件人，以 mailto: 关注的地址也会收到邮件
+    #[serde(default)]
+    pub to: Vec<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl NotifyConfig {
+    pub fn validate(&self) -> Result<()> {
+        let d = parse_duration(&self.debounce)
+            .with_context(|| format!("debounce={}", self.debounce))?;
+        if d < chrono::Duration::zero() {
+            bail!("debounce 不能小于0");
+        }
+
+        if let Some(x) = &self.webhook {
+            let url = Url::parse(&x.url).with_context(|| format!("webhook.url={}", x.url))?;
+            if !matches!(url.scheme(), "http" | "https") {
+                bail!("webhook.url 只支持 http 与 https: {}", x.url);
+            }
+        }
+
+        if let Some(x) = &self.smtp {
+            if !x.addr.contains(':') {
+                bail!("smtp.addr 格式错误，应为 host:port: {}", x.addr);
+            }
+            if x.username.is_some() != x.password.is_some() {
+                bail!("smtp.username 与 smtp.password 需要同时设置");
+            }
+        }
+
+        Ok(())
+    }
+
+    // 根据配置创建通知方式，都未设置时返回None
+    pub fn notifier(&self) -> Result<Option<Arc<Debounced>>> {
+        let mut v: Vec<Arc<dyn Notifier>> = Vec::new();
+        if self.stdout {
+            v.push(Arc::new(StdoutNotifier));
+        }
+        if let Some(x) = &self.webhook {
+            let mut n = WebhookNotifier::new(&x.url);
+            if let Some(secret) = &x.secret {
+                n = n.secret(secret);
+            }
+            v.push(Arc::new(n));
+        }
+        if let Some(x) = &self.smtp {
+            let mut n = SmtpNotifier::new(&x.addr, &x.from, x.to.clone());
+            if let (Some(username), Some(password)) = (&x.username, &x.password) {
+                n = n.auth(username, password);
+            }
+            v.push(Arc::new(n));
+        }
+
+        if v.is_empty() {
+            return Ok(None);
+        }
+
+        let window = parse_duration(&self.debounce)?.to_std()?;
+        Ok(Some(Arc::new(Debounced::new(
+            Arc::new(Fanout::new(v)),
+            window,
+        ))))
+    }
+}
@@ -0,0 +1,157 @@
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use crate::keeper::notify::{Notifier, NovelUpdated};
+
+// 关注者以 mailto: 开头时，该地址也会收到邮件
+pub const MAILTO_PREFIX: &str = "mailto:";
+
+// 最简单的SMTP客户端，不支持TLS，适用于本地或内网的邮件中继
+pub struct SmtpNotifier {
+    // 服务器地址，格式为 host:port
+    addr: String,
+    from: String,
+    to: Vec<String>,
+    // 用户名与密码，设置后使用 AUTH PLAIN
+    auth: Option<(String, String)>,
+}
+
+impl SmtpNotifier {
+    pub fn new(addr: &str, from: &str, to: Vec<String>) -> Self {
+        Self {
+            addr: String::from(addr),
+            from: String::from(from),
+            to,
+            auth: None,
+        }
+    }
+
+    pub fn auth(mut self, username: &str, password: &str) -> Self {
+        self.auth = Some((String::from(username), String::from(password)));
+        self
+    }
+
+    // 配置的收件人与以 mailto: 关注的地址
+    pub fn recipients(&self, event: &NovelUpdated) -> Vec<String> {
+        let mut v = self.to.clone();
+        for x in &event.subscribers {
+            if let Some(x) = x.strip_prefix(MAILTO_PREFIX) {
+                if !v.iter().any(|v| v == x) {
+                    v.push(String::from(x));
+                }
+            }
+        }
+
+        v
+    }
+
+    async fn send(&self, to: &[String], subject: &str, body: &str) -> Result<()> {
+        let stream = TcpStream::connect(&self.addr)
+            .await
+            .with_context(|| format!("连接SMTP服务器失败; addr={}", self.addr))?;
+        let (r, mut w) = stream.into_split();
+        let mut r = BufReader::new(r);
+
+        expect(&mut r, 220).await?;
+        command(&mut r, &mut w, "EHLO spider-novel", 250).await?;
+
+        if let Some((username, password)) = &self.auth {
+            let token = base64::encode(format!("\0{username}\0{password}"));
+            command(&mut r, &mut w, &format!("AUTH PLAIN {token}"), 235).await?;
+        }
+
+        command(&mut r, &mut w, &format!("MAIL FROM:<{}>", self.from), 250).await?;
+        for x in to {
+            command(&mut r, &mut w, &format!("RCPT TO:<{x}>"), 250).await?;
+        }
+        command(&mut r, &mut w, "DATA", 354).await?;
+
+        let message = format!(
+            "From: <{}>\r\nTo: {}\r\nSubject: =?UTF-8?B?{}?=\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=UTF-8\r\nContent-Transfer-Encoding: base64\r\n\r\n{}\r\n.",
+            self.from,
+            to.iter()
+                .map(|x| format!("<{x}>"))
+                .collect::<Vec<_>>()
+                .join(", "),
+            base64::encode(subject),
+            wrap(&base64::encode(body), 76),
+        );
+        command(&mut r, &mut w, &message, 250).await?;
+
+        // 邮件已经发送，QUIT失败不影响结果
+        let _ = command(&mut r, &mut w, "QUIT", 221).await;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for SmtpNotifier {
+    async fn notify(&self, event: &NovelUpdated) -> Result<()> {
+        let to = self.recipients(event);
+        if to.is_empty() {
+            return Ok(());
+        }
+
+        let subject = format!("《{}》更新了", event.name);
+        let mut body = format!("《{}》 作者：{}\n", event.name, event.author);
+        if let Some(x) = &event.last_section_name {
+            body.push_str(&format!("最新章节：{x}\n"));
+        }
+        if let Some(x) = event.last_updated_at {
+            body.push_str(&format!("更新时间：{}\n", x.to_rfc3339()));
+        }
+        if event.new_sections > 0 {
+            body.push_str(&format!("新增章节：{}\n", event.new_sections));
+        }
+
+        self.send(&to, &subject, &body).await
+    }
+}
+
+async fn command<R, W>(r: &mut R, w: &mut W, line: &str, code: u16) -> Result<()>
+where
+    R: AsyncBufReadExt + Unpin,
+    W: AsyncWriteExt + Unpin,
+{
+    w.write_all(line.as_bytes()).await?;
+    w.write_all(b"\r\n").await?;
+    w.flush().await?;
+
+    expect(r, code).await
+}
+
+// 读取一个响应，多行响应中间行的状态码后为 '-'
+async fn expect<R>(r: &mut R, code: u16) -> Result<()>
+where
+    R: AsyncBufReadExt + Unpin,
+{
+    loop {
+        let mut line = String::new();
+        if r.read_line(&mut line).await? == 0 {
+            bail!("SMTP连接已关闭");
+        }
+
+        let got: u16 = line
+            .get(..3)
+            .and_then(|x| x.parse().ok())
+            .with_context(|| format!("SMTP响应格式错误: {}", line.trim_end()))?;
+        if got != code {
+            bail!("SMTP响应错误; expect={code}, got={}", line.trim_end());
+        }
+
+        if line.as_bytes().get(3) != Some(&b'-') {
+            return Ok(());
+        }
+    }
+}
+
+fn wrap(x: &str, width: usize) -> String {
+    x.as_bytes()
+        .chunks(width)
+        .map(|x| std::str::from_utf8(x).unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\r\n")
+}
@@ -0,0 +1,17 @@
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::keeper::notify::{Notifier, NovelUpdated};
+
+// 将通知以json输出到标准输出，每条一行
+#[derive(Default)]
+pub struct StdoutNotifier;
+
+#[async_trait]
+impl Notifier for StdoutNotifier {
+    async fn notify(&self, event: &NovelUpdated) -> Result<()> {
+        println!("{}", serde_json::to_string(event)?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::common::httputils::CLIENT;
+use crate::keeper::notify::{Notifier, NovelUpdated};
+
+// 设置了密钥时，请求体的 HMAC-SHA256 签名放在该请求头中，格式为 sha256=<hex>
+pub const SIGNATURE_HEADER: &str = "X-Spider-Signature";
+
+// 以json POST到指定地址，非2xx的响应视为失败
+pub struct WebhookNotifier {
+    url: String,
+    secret: Option<String>,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: String::from(url),
+            secret: None,
+        }
+    }
+
+    pub fn secret(mut self, secret: &str) -> Self {
+        self.secret = Some(String::from(secret));
+        self
+    }
+}
+
+pub fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac接受任意长度的密钥");
+    mac.update(body);
+
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, event: &NovelUpdated) -> Result<()> {
+        let body = serde_json::to_vec(event)?;
+
+        let mut req = CLIENT
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json");
+        if let Some(secret) = &self.secret {
+            req = req.header(SIGNATURE_HEADER, signature(secret, &body));
+        }
+
+        let resp = req.body(body).send().await?;
+        if !resp.status().is_success() {
+            return Err(anyhow!(
+                "webhook响应错误; url={}, status={}",
+                self.url,
+                resp.status()
+            ));
+        }
+
+        Ok(())
+    }
+}
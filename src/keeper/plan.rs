@@ -0,0 +1,109 @@
+use std::sync::atomic::Ordering;
+
+use anyhow::{anyhow, bail, Result};
+use log::info;
+
+use crate::keeper::queue::JobKind;
+use crate::keeper::Keeper;
+use crate::spider::{Capabilities, Pagination};
+
+// 根据爬虫能力得到的任务计划
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobPlan {
+    pub capabilities: Capabilities,
+    // 可以添加的任务类型
+    pub kinds: Vec<JobKind>,
+    // 分类任务按页记录进度，不能直接访问指定页码时每次从第一页开始
+    pub resumable: bool,
+    // 网站限制请求频率时，同一时间只执行该爬虫的一个任务
+    pub exclusive: bool,
+}
+
+impl JobPlan {
+    pub fn new(capabilities: Capabilities) -> Self {
+        let mut kinds = Vec::new();
+        if capabilities.get_novel_from_sort {
+            kinds.push(JobKind::NovelsBySort);
+        }
+        kinds.push(JobKind::SectionsByNovel);
+
+        Self {
+            kinds,
+            resumable: capabilities.pagination == Pagination::Numbered,
+            exclusive: capabilities.rate_limit.is_some(),
+            capabilities,
+        }
+    }
+
+    pub fn allows(&self, kind: JobKind) -> bool {
+        self.kinds.contains(&kind)
+    }
+}
+
+impl Keeper {
+    // 爬虫当前的能力，规则爬虫与插件加载后可能与注册时不同
+    pub fn capabilities(&self, id: &str) -> Option<Capabilities> {
+        self.spider(id).map(|x| x.capabilities())
+    }
+
+    pub fn plan(&self, id: &str) -> Option<JobPlan> {
+        self.capabilities(id).map(JobPlan::new)
+    }
+
+    // 为爬虫的全部分类添加爬取任务，返回添加的任务数
+    pub async fn plan_sorts(&self, id: &str) -> Result<usize> {
+        let spider = self
+            .spider(id)
+            .ok_or_else(|| anyhow!("爬虫不存在; spider_id={id}"))?;
+        let plan = JobPlan::new(spider.capabilities());
+        if !plan.allows(JobKind::NovelsBySort) {
+            bail!("爬虫不支持从分类获取小说; spider_id={id}");
+        }
+
+        let sorts: Vec<i64> = spider.inner.sorts().iter().map(|x| x.id.into()).collect();
+        for x in &sorts {
+            self.enqueue(JobKind::NovelsBySort, id, *x).await?;
+        }
+        info!("添加分类任务; spider_id={id}, count={}", sorts.len());
+
+        Ok(sorts.len())
+    }
+
+    // 开始执行爬虫的任务，独占的爬虫已有任务在执行时返回false
+    pub(crate) fn try_start_job(&self, id: &str) -> bool {
+        let x = match self.spider(id) {
+            Some(x) => x,
+            None => return true,
+        };
+
+        if JobPlan::new(x.capabilities()).exclusive {
+            x.running
+                .compare_exchange(0, 1, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+        } else {
+            x.running.fetch_add(1, Ordering::SeqCst);
+            true
+        }
+    }
+
+    pub(crate) fn finish_job(&self, id: &str) {
+        if let Some(x) = self.spider(id) {
+            let _ = x
+                .running
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1));
+        }
+    }
+
+    // 暂停或正在执行独占任务的爬虫，不领取新的任务
+    pub(crate) fn busy_spiders(&self) -> Vec<String> {
+        self.spiders
+            .iter()
+            .filter(|x| {
+                self.is_paused(&x.id)
+                    || (JobPlan::new(x.capabilities()).exclusive
+                        && x.running.load(Ordering::SeqCst) > 0)
+            })
+            .map(|x| x.id.to_string())
+            .collect()
+    }
+}
@@ -0,0 +1,339 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures::future::join_all;
+use log::{error, info, warn};
+
+use crate::common::events;
+use crate::common::events::{Event, JobFinished, SectionStored};
+use crate::keeper::data::entity::job::Model as JobModel;
+use crate::keeper::data::{author, job, novel};
+use crate::keeper::plan::JobPlan;
+use crate::keeper::Keeper;
+use crate::spider::{CrawlError, Novel, NovelID, Position, Section, SortID};
+
+// 任务类型
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum JobKind {
+    // 逐页爬取分类下的全部小说，进度为已完成的页数
+    NovelsBySort,
+    // 爬取小说的全部章节，进度为已完成的章节数
+    SectionsByNovel,
+}
+
+impl JobKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobKind::NovelsBySort => "novels_by_sort",
+            JobKind::SectionsByNovel => "sections_by_novel",
+        }
+    }
+
+    pub fn parse(x: &str) -> Option<Self> {
+        match x {
+            "novels_by_sort" => Some(JobKind::NovelsBySort),
+            "sections_by_novel" => Some(JobKind::SectionsByNovel),
+            _ => None,
+        }
+    }
+}
+
+// 章节的保存位置，由keeper的使用者提供
+#[async_trait]
+pub trait SectionSink: Send + Sync {
+    async fn save(&self, spider_id: &str, section: &Section) -> Result<()>;
+}
+
+// 任务执行的结果
+enum Outcome {
+    Finished,
+    Cancelled,
+}
+
+// 任务处理的数据量，用于任务结束事件
+#[derive(Default)]
+struct JobStats {
+    items: u64,
+    bytes: u64,
+}
+
+impl Keeper {
+    // 添加一个爬取任务，相同的任务未完成时返回已有任务的id
+    pub async fn enqueue(&self, kind: JobKind, spider_id: &str, target_id: i64) -> Result<i64> {
+        let plan = self
+            .plan(spider_id)
+            .ok_or_else(|| anyhow!("爬虫不存在; spider_id={spider_id}"))?;
+        if !plan.allows(kind) {
+            return Err(anyhow!(
+                "爬虫不支持该任务; spider_id={spider_id}, kind={}",
+                kind.as_str()
+            ));
+        }
+
+        job::add(&self.db, &self.ids, kind.as_str(), spider_id, target_id).await
+    }
+
+    pub fn set_section_sink(&mut self, sink: Arc<dyn SectionSink>) {
+        self.section_sink = Some(sink);
+    }
+
+    // 执行数据迁移，恢复上次未完成的任务，并启动worker执行任务，直到keeper被关闭
+    pub async fn run_jobs(&self, workers: usize) -> Result<()> {
+        self.migrate().await?;
+
+        let n = job::recover(&self.db).await?;
+        if n > 0 {
+            info!("恢复未完成的任务; count={n}");
+        }
+
+        join_all((0..workers.max(1)).map(|_| self.job_worker())).await;
+
+        Ok(())
+    }
+
+    async fn job_worker(&self) {
+        while !self.cancel.is_cancelled() {
+            // 每次循环读取策略，配置重新加载后立即生效
+            let policy = self.policy();
+            let interval = policy
+                .job_poll_interval
+                .to_std()
+                .unwrap_or(std::time::Duration::from_secs(5));
+
+            let x = match job::acquire_except(&self.db, &self.busy_spiders()).await {
+                Ok(Some(x)) => x,
+                Ok(None) => {
+                    tokio::select! {
+                        _ = tokio::time::sleep(interval) => {},
+                        _ = self.cancel.cancelled() => {},
+                    }
+                    continue;
+                }
+                Err(e) => {
+                    error!("领取任务失败: {e}");
+                    tokio::select! {
+                        _ = tokio::time::sleep(interval) => {},
+                        _ = self.cancel.cancelled() => {},
+                    }
+                    continue;
+                }
+            };
+
+            // 领取后其他worker可能已经开始执行同一个独占爬虫的任务
+            if !self.try_start_job(&x.spider_id) {
+                if let Err(e) = job::release(&self.db, x.id).await {
+                    error!("释放任务失败; id={}, err={e}", x.id);
+                }
+                continue;
+            }
+
+            info!(
+                "开始执行任务; id={}, kind={}, spider_id={}, target_id={}, cursor={}",
+                x.id, x.kind, x.spider_id, x.target_id, x.cursor
+            );
+            let start = Instant::now();
+            let mut stats = JobStats::default();
+            let r = self.execute_job(&x, &mut stats).await;
+            self.finish_job(&x.spider_id);
+            if let Err(e) = self.save_cookies(&x.spider_id).await {
+                error!("保存cookie失败; spider_id={}, err={e}", x.spider_id);
+            }
+            let outcome = match &r {
+                Ok(Outcome::Finished) => "finished",
+                Ok(Outcome::Cancelled) => "cancelled",
+                Err(_) => "failed",
+            };
+            events::publish(Event::JobFinished(JobFinished {
+                job_id: x.id,
+                kind: x.kind.clone(),
+                spider_id: x.spider_id.clone(),
+                outcome: String::from(outcome),
+                attempt: x.attempts,
+                items: stats.items,
+                bytes: stats.bytes,
+                elapsed: start.elapsed(),
+            }));
+
+            let r = match r {
+                Ok(Outcome::Finished) => job::finish(&self.db, x.id).await,
+                Ok(Outcome::Cancelled) => job::release(&self.db, x.id).await,
+                Err(e) => {
+                    warn!(
+                        "任务执行失败; id={}, attempts={}, err={e}",
+                        x.id, x.attempts
+                    );
+                    job::fail(&self.db, &x, &e.to_string(), policy.job_max_attempts).await
+                }
+            };
+
+            if let Err(e) = r {
+                error!("更新任务状态失败; id={}, err={e}", x.id);
+            }
+        }
+    }
+
+    async fn execute_job(&self, x: &JobModel, stats: &mut JobStats) -> Result<Outcome> {
+        match JobKind::parse(&x.kind) {
+            Some(JobKind::NovelsBySort) => self.execute_novels_job(x, stats).await,
+            Some(JobKind::SectionsByNovel) => self.execute_sections_job(x, stats).await,
+            None => Err(anyhow!("未知的任务类型; kind={}", x.kind)),
+        }
+    }
+
+    async fn execute_novels_job(&self, x: &JobModel, stats: &mut JobStats) -> Result<Outcome> {
+        let spider = self
+            .spider(&x.spider_id)
+            .ok_or_else(|| anyhow!("爬虫不存在; spider_id={}", x.spider_id))?;
+        let id: SortID = x.target_id.into();
+        let cancel = self.cancel_token();
+        let resumable = JobPlan::new(spider.capabilities()).resumable;
+
+        let mut cursor = x.cursor;
+        // 上一页的小说，部分网站对超出范围的页码返回最后一页
+        let mut last_ids: Vec<NovelID> = Vec::new();
+        loop {
+            // 爬虫被停用或标记为异常时暂停，任务回到等待状态
+            if self.is_paused(&spider.id) {
+                return Ok(Outcome::Cancelled);
+            }
+
+            // 不能直接访问指定页码时一次获取全部页面，中断后从第一页重新开始
+            let page = cursor + 1;
+            let pos = if resumable {
+                Position::Specify(page)
+            } else {
+                Position::Full
+            };
+            let mut rx = spider.inner.novels_by_sort_id(&id, pos, &cancel).await?;
+
+            // 按页获取时先读取整页，与上一页相同时不重复保存
+            let mut novels = Vec::new();
+            while let Some(novel) = rx.recv().await {
+                match novel {
+                    Ok(novel) if resumable => novels.push(novel),
+                    Ok(novel) => self.save_sort_novel(&spider.id, &novel, stats).await?,
+                    Err(CrawlError::Cancelled) => return Ok(Outcome::Cancelled),
+                    Err(e) => return Err(e.into()),
+                }
+            }
+
+            // 被取消时当前页可能不完整，不记录进度
+            if cancel.is_cancelled() {
+                return Ok(Outcome::Cancelled);
+            }
+            if !resumable {
+                return Ok(Outcome::Finished);
+            }
+
+            // 没有数据或者与上一页相同，说明已经超过了最后一页
+            let ids: Vec<NovelID> = novels.iter().map(|x| x.id).collect();
+            if ids.is_empty() || ids.iter().all(|x| last_ids.contains(x)) {
+                info!(
+                    "分类已经没有更多页面; spider_id={}, sort={id}, page={page}",
+                    spider.id
+                );
+                return Ok(Outcome::Finished);
+            }
+
+            for novel in &novels {
+                self.save_sort_novel(&spider.id, novel, stats).await?;
+            }
+
+            last_ids = ids;
+            cursor = page;
+            job::checkpoint(&self.db, x.id, cursor).await?;
+        }
+    }
+
+    async fn save_sort_novel(
+        &self,
+        spider_id: &str,
+        novel: &Novel,
+        stats: &mut JobStats,
+    ) -> Result<()> {
+        let novel_id = novel::add_or_recover(&self.db, &self.ids, spider_id, novel).await?;
+        author::add_or_recover(&self.db, &self.ids, &novel.author, false).await?;
+        if let Err(e) = self
+            .check_update(
+                novel_id,
+                novel.last_updated_section_name.as_deref(),
+                novel.last_updated_at,
+            )
+            .await
+        {
+            warn!("检查小说更新失败; novel_id={novel_id}, err={e}");
+        }
+        self.index_novel_text(novel_id, &novel.name, novel.intro.as_deref())
+            .await?;
+
+        // 封面下载失败不影响任务
+        if self.covers.is_some() && novel.cover.is_some() {
+            if let Err(e) = self.store_cover(spider_id, novel_id).await {
+                warn!("保存封面失败; novel_id={novel_id}, err={e}");
+            }
+        }
+        stats.items += 1;
+
+        Ok(())
+    }
+
+    async fn execute_sections_job(&self, x: &JobModel, stats: &mut JobStats) -> Result<Outcome> {
+        let spider = self
+            .spider(&x.spider_id)
+            .ok_or_else(|| anyhow!("爬虫不存在; spider_id={}", x.spider_id))?;
+        let sink = self
+            .section_sink
+            .clone()
+            .ok_or_else(|| anyhow!("没有设置章节的保存位置"))?;
+        let id: NovelID = x.target_id.into();
+        let cancel = self.cancel_token();
+
+        if self.is_paused(&spider.id) {
+            return Ok(Outcome::Cancelled);
+        }
+
+        let mut rx = spider
+            .inner
+            .sections_by_novel_id(&id, Position::Range(x.cursor + 1..i32::MAX), &cancel)
+            .await?;
+
+        while let Some(section) = rx.recv().await {
+            match section {
+                Ok(section) => {
+                    let start = Instant::now();
+                    sink.save(&spider.id, &section).await?;
+
+                    let bytes = section.text.len() as u64;
+                    stats.items += 1;
+                    stats.bytes += bytes;
+                    events::publish(Event::SectionStored(SectionStored {
+                        spider_id: spider.id.to_string(),
+                        novel_id: section.novel_id.into(),
+                        seq: section.seq,
+                        bytes,
+                        elapsed: start.elapsed(),
+                    }));
+
+                    job::checkpoint(&self.db, x.id, section.seq as i32 + 1).await?;
+                }
+                Err(CrawlError::Cancelled) => return Ok(Outcome::Cancelled),
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        if cancel.is_cancelled() {
+            return Ok(Outcome::Cancelled);
+        }
+
+        // 章节保存完成后按章节数检查关注的小说是否更新
+        if let Some(novel_id) = novel::relation_novel_id(&self.db, &spider.id, x.target_id).await? {
+            if let Err(e) = self.check_update(novel_id, None, None).await {
+                warn!("检查小说更新失败; novel_id={novel_id}, err={e}");
+            }
+        }
+
+        Ok(Outcome::Finished)
+    }
+}
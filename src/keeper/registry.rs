@@ -0,0 +1,85 @@
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Result};
+use log::info;
+
+use crate::keeper::{Keeper, PropertySpider};
+use crate::spider::{Spider, SpiderInfo};
+
+// 注册的爬虫及其运行状态
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpiderStatus {
+    pub info: SpiderInfo,
+    pub enabled: bool,
+    // 健康检查未通过的原因
+    pub degraded: Option<String>,
+    pub score: i32,
+}
+
+impl Keeper {
+    // 注册爬虫，编译在程序中的爬虫、规则爬虫与插件都通过这里注册，id不能重复
+    // 爬虫自身的数据迁移需要另外注册
+    pub fn register(
+        &mut self,
+        info: SpiderInfo,
+        spider: Box<dyn Spider + Send + Sync>,
+    ) -> Result<()> {
+        if info.id.trim().is_empty() {
+            bail!("爬虫id不能为空");
+        }
+        if self.spider(&info.id).is_some() {
+            bail!("爬虫id重复; spider_id={}", info.id);
+        }
+
+        let id = Arc::from(info.id.as_str());
+        self.spiders.push(PropertySpider::new(id, info, spider));
+
+        Ok(())
+    }
+
+    // 全部已注册的爬虫，按注册顺序
+    pub fn registry(&self) -> Vec<SpiderStatus> {
+        self.spiders
+            .iter()
+            .map(|x| SpiderStatus {
+                info: SpiderInfo {
+                    capabilities: x.capabilities(),
+                    ..x.info.clone()
+                },
+                enabled: x.enabled.load(Ordering::Relaxed),
+                degraded: x.degraded.read().unwrap_or_else(|e| e.into_inner()).clone(),
+                score: x.score.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
+    // 爬虫的描述，能力为运行时的值
+    pub fn spider_info(&self, id: &str) -> Option<SpiderInfo> {
+        self.spider(id).map(|x| SpiderInfo {
+            capabilities: x.capabilities(),
+            ..x.info.clone()
+        })
+    }
+
+    // 启用或停用爬虫，停用后不参与搜索，已有的任务暂停直到重新启用
+    pub fn set_enabled(&self, id: &str, enabled: bool) -> Result<()> {
+        let x = self
+            .spider(id)
+            .ok_or_else(|| anyhow!("爬虫不存在; spider_id={id}"))?;
+        if x.enabled.swap(enabled, Ordering::Relaxed) != enabled {
+            info!(
+                "爬虫已{}; spider_id={id}",
+                if enabled { "启用" } else { "停用" }
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn is_enabled(&self, id: &str) -> bool {
+        self.spider(id)
+            .map(|x| x.enabled.load(Ordering::Relaxed))
+            .unwrap_or(false)
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+
+use futures::future::join_all;
+use log::warn;
+
+use crate::keeper::Keeper;
+use crate::spider::{CrawlError, Novel, NovelID};
+
+// 合并后的搜索结果
+#[derive(Debug)]
+pub struct SearchHit {
+    pub novel: Novel,
+    // 找到该小说的爬虫，以及小说在爬虫中的id
+    pub sources: Vec<(String, NovelID)>,
+    pub score: f64,
+    // 来源中最高的评分，总分相同时评分高的来源排在前面
+    pub source_score: i32,
+}
+
+#[derive(Debug, Default)]
+pub struct SearchResult {
+    pub hits: Vec<SearchHit>,
+    // 搜索失败的爬虫，其余爬虫的结果仍然会返回
+    pub failed: Vec<(String, CrawlError)>,
+}
+
+impl Keeper {
+    // 在所有支持搜索的爬虫中并发搜索，按名字与作者去重后排序
+    pub async fn search(&self, name: &str) -> SearchResult {
+        let name = name.trim();
+        let timeout = self
+            .policy()
+            .search_timeout
+            .to_std()
+            .unwrap_or(std::time::Duration::from_secs(10));
+
+        let tasks = self
+            .spiders
+            .iter()
+            .filter(|x| x.capabilities().search_novel && x.enabled.load(Ordering::Relaxed))
+            .map(|x| async move {
+                let r = match tokio::time::timeout(timeout, x.inner.search(name)).await {
+                    Ok(r) => r,
+                    Err(_) => Err(CrawlError::Timeout),
+                };
+
+                (x, r)
+            });
+
+        let mut result = SearchResult::default();
+        // key为小说名与作者
+        let mut merged: HashMap<(String, String), SearchHit> = HashMap::new();
+        for (spider, r) in join_all(tasks).await {
+            let novels = match r {
+                Ok(x) => x,
+                Err(e) => {
+                    warn!("搜索失败; spider_id={}, name={name}, err={e}", spider.id);
+                    result.failed.push((spider.id.to_string(), e));
+                    continue;
+                }
+            };
+
+            for novel in novels {
+                let novel_id = novel.id;
+                let key = (
+                    String::from(novel.name.trim()),
+                    String::from(novel.author.trim()),
+                );
+                let score = spider.score.load(Ordering::Relaxed);
+                let hit = merged.entry(key).or_insert_with(|| SearchHit {
+                    score: relevance(name, &novel.name) * 100.0,
+                    sources: Vec::new(),
+                    source_score: score,
+                    novel,
+                });
+
+                if hit.sources.iter().all(|(id, _)| **id != *spider.id) {
+                    hit.score += score as f64 + 1.0;
+                    hit.source_score = hit.source_score.max(score);
+                    hit.sources.push((spider.id.to_string(), novel_id));
+                }
+            }
+        }
+
+        // 总分相同时依次比较来源评分与来源中的id，保证结果的顺序固定
+        result.hits = merged.into_values().collect();
+        result.hits.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then(b.source_score.cmp(&a.source_score))
+                .then_with(|| a.sources.cmp(&b.sources))
+        });
+
+        result
+    }
+}
+
+// 计算搜索词与小说名的相关度，范围为0到1
+fn relevance(keyword: &str, name: &str) -> f64 {
+    let name = name.trim();
+    if keyword.is_empty() || name.is_empty() {
+        return 0.0;
+    }
+
+    if name == keyword {
+        return 1.0;
+    }
+    if name.starts_with(keyword) {
+        return 0.8;
+    }
+    if name.contains(keyword) {
+        return 0.6;
+    }
+
+    // 按字符计算重合的比例
+    let n = keyword.chars().filter(|x| name.contains(*x)).count();
+    0.5 * n as f64 / keyword.chars().count() as f64
+}
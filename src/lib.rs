@@ -1,10 +1,8 @@
-use static_init::dynamic;
-
 pub mod common;
+pub mod config;
 pub mod ddxsku;
 pub mod keeper;
+#[cfg(feature = "wasm")]
+pub mod plugin;
 pub mod spider;
 pub mod webook;
-
-#[dynamic]
-static mut GEN: snowflake::SnowflakeIdGenerator = snowflake::SnowflakeIdGenerator::new(1, 1);
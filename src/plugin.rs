@@ -0,0 +1,635 @@
+// WASM插件爬虫
+//
+// 插件是导出以下函数的wasm模块，只负责解析页面，网络请求由宿主完成：
+//   memory                              线性内存
+//   spider_alloc(len: i32) -> i32       分配len字节，返回地址
+//   spider_info() -> i64                插件描述 PluginInfo
+//   parse_sorts(ptr, len) -> i64        分类列表页，返回 Reply<Vec<SortItem>>
+//   parse_novels(ptr, len) -> i64       小说列表页与搜索结果页，返回 Reply<NovelList>
+//   parse_novel(ptr, len) -> i64        小说详情页，返回 Reply<NovelDetail>
+//   parse_chapter(ptr, len) -> i64      章节页，返回 Reply<Chapter>
+// 参数为JSON格式的 Page，返回值为JSON，高32位为地址，低32位为长度。
+// 插件可以导入 spider.log(level, ptr, len) 输出日志。
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use log::warn;
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+
+use crate::common::events;
+use crate::common::events::{Event, NovelParsed};
+use crate::common::shutdown::{CancellationToken, TaskTracker};
+use crate::plugin::abi::{
+    parse_time, Chapter, ChapterLink, NovelDetail, NovelList, Page, PluginInfo, Reply, SortItem,
+    FN_PARSE_CHAPTER, FN_PARSE_NOVEL, FN_PARSE_NOVELS, FN_PARSE_SORTS,
+};
+use crate::plugin::host::Host;
+use crate::plugin::runtime::{Limits, Runtime};
+use crate::spider;
+use crate::spider::{
+    Capabilities, CrawlError, Novel, NovelID, Pagination, Position, Probe, ProbeKind, RateLimit,
+    Section, Sort, SortID, SourceKind, Spider, SpiderConfig, SpiderInfo, SpiderMetadata, Support,
+};
+
+pub mod abi;
+pub mod host;
+pub mod runtime;
+
+// 插件未设置时的并发数
+const DEFAULT_CONCURRENCY: usize = 4;
+
+// 一个章节最多的分页数
+const MAX_CHAPTER_PAGES: usize = 20;
+
+struct Inner {
+    id: Arc<str>,
+    info: PluginInfo,
+    runtime: Runtime,
+    host: Host,
+}
+
+pub struct WasmSpider {
+    inner: Arc<Inner>,
+    sorts: Vec<Sort>,
+    tracker: TaskTracker,
+}
+
+impl WasmSpider {
+    pub fn new(runtime: Runtime, concurrency: Option<usize>) -> Result<Self> {
+        let info = runtime.info()?;
+        if info.id.trim().is_empty() {
+            bail!("插件id不能为空");
+        }
+        if !info.novel_url.contains("{id}") {
+            bail!("novel_url 需要包含 {{id}}: {}", info.novel_url);
+        }
+        let pagination = match info.pagination() {
+            Some(x) => x,
+            None => bail!("未知的分页方式: {:?}", info.pagination),
+        };
+        if let Some(x) = &info.sort_url {
+            if !x.contains("{id}") {
+                bail!("sort_url 需要包含 {{id}}: {x}");
+            }
+            if pagination == Pagination::Numbered && !x.contains("{page}") {
+                bail!("numbered 分页的 sort_url 需要包含 {{page}}: {x}");
+            }
+        }
+
+        let concurrency = concurrency
+            .or(info.concurrency)
+            .unwrap_or(DEFAULT_CONCURRENCY);
+        if concurrency == 0 {
+            bail!("concurrency 必须大于0");
+        }
+        let interval = Duration::from_millis(info.interval_ms.unwrap_or(0));
+
+        let id: Arc<str> = Arc::from(info.id.as_str());
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                host: Host::new(id.clone(), concurrency, interval),
+                id,
+                info,
+                runtime,
+            }),
+            sorts: Vec::new(),
+            tracker: TaskTracker::new(),
+        })
+    }
+
+    pub fn load(path: &Path, limits: Limits, concurrency: Option<usize>) -> Result<Self> {
+        Self::new(Runtime::from_file(path, limits)?, concurrency)
+            .with_context(|| format!("加载插件失败; path={}", path.display()))
+    }
+
+    pub fn with_config(config: &SpiderConfig) -> Result<Self> {
+        config.validate()?;
+        let path = match &config.plugin {
+            Some(x) => x,
+            None => bail!("未设置插件文件"),
+        };
+
+        let mut limits = Limits::default();
+        if let Some(x) = config.fuel {
+            limits.fuel = x;
+        }
+        if let Some(x) = config.memory_mb {
+            limits.memory = x << 20;
+        }
+
+        Self::load(path, limits, config.concurrency)
+    }
+
+    pub fn spider_id(&self) -> &str {
+        &self.inner.id
+    }
+
+    pub fn plugin_info(&self) -> &PluginInfo {
+        &self.inner.info
+    }
+
+    // 注册到keeper的描述
+    pub fn info(&self) -> SpiderInfo {
+        let x = &self.inner.info;
+        SpiderInfo {
+            id: x.id.clone(),
+            name: x.name.clone(),
+            version: x.version.clone(),
+            source: SourceKind::Wasm,
+            capabilities: self.inner.capabilities(),
+        }
+    }
+
+    // 获取并解析分类列表页，插件不支持时分类为空
+    pub async fn load_sorts(&mut self) -> spider::Result<()> {
+        let url = match &self.inner.info.sorts_url {
+            Some(x) => x.clone(),
+            None => return Ok(()),
+        };
+
+        let page = self.inner.page(&url, &CancellationToken::new()).await?;
+        let sorts: Vec<SortItem> = self.inner.parse(FN_PARSE_SORTS, page).await?;
+        self.sorts = sorts
+            .into_iter()
+            .map(|x| Sort {
+                id: x.id.into(),
+                name: x.name,
+            })
+            .collect();
+
+        Ok(())
+    }
+}
+
+impl Inner {
+    // 插件声明的能力，请求频率以宿主实际使用的设置为准
+    fn capabilities(&self) -> Capabilities {
+        let (_, concurrency) = self.host.permits();
+        let interval = self.host.interval();
+        Capabilities {
+            rate_limit: (!interval.is_zero()).then_some(RateLimit {
+                concurrency,
+                interval,
+            }),
+            ..self.info.capabilities()
+        }
+    }
+
+    async fn page(&self, url: &str, cancel: &CancellationToken) -> spider::Result<Page> {
+        Ok(Page {
+            url: String::from(url),
+            body: self.host.get(url, cancel).await?,
+        })
+    }
+
+    // 在阻塞线程中执行插件，解析失败时返回 ParseFailed
+    async fn parse<O>(self: &Arc<Self>, name: &'static str, page: Page) -> spider::Result<O>
+    where
+        O: DeserializeOwned + Send + 'static,
+    {
+        let url = page.url.clone();
+        let this = self.clone();
+        let r = tokio::task::spawn_blocking(move || this.runtime.call::<Page, O>(name, &page))
+            .await
+            .map_err(|e| CrawlError::SpiderInnerFailed(e.into()))?;
+
+        match r {
+            Ok(Reply::Ok(x)) => Ok(x),
+            Ok(Reply::Err(e)) => {
+                warn!(
+                    "插件解析失败; spider_id={}, fn={name}, url={url}, err={e}",
+                    self.id
+                );
+                let e = CrawlError::ParseFailed;
+                events::crawl_failed(&self.id, Some(&url), &e, Duration::ZERO);
+                Err(e)
+            }
+            Err(e) => Err(CrawlError::SpiderInnerFailed(e)),
+        }
+    }
+
+    fn sort_url(&self, id: &SortID, page: i32) -> spider::Result<String> {
+        let id: i64 = (*id).into();
+        match &self.info.sort_url {
+            Some(x) => Ok(x
+                .replace("{id}", &id.to_string())
+                .replace("{page}", &page.to_string())),
+            None => Err(CrawlError::Unsupported),
+        }
+    }
+
+    fn novel_url(&self, id: &NovelID) -> String {
+        let id: i64 = id.into();
+        self.info.novel_url.replace("{id}", &id.to_string())
+    }
+
+    async fn novels(
+        self: &Arc<Self>,
+        url: &str,
+        cancel: &CancellationToken,
+    ) -> spider::Result<(Vec<Novel>, Option<i32>, Option<String>)> {
+        let start = Instant::now();
+        let page = self.page(url, cancel).await?;
+        let bytes = page.body.len() as u64;
+        let list: NovelList = self.parse(FN_PARSE_NOVELS, page).await?;
+
+        let novels = list
+            .novels
+            .into_iter()
+            .map(|x| {
+                let url = self.novel_url(&x.id.into());
+                let novel = x.into_novel(Some(url));
+                events::publish(Event::NovelParsed(NovelParsed {
+                    spider_id: self.id.to_string(),
+                    novel_id: novel.id.into(),
+                    name: novel.name.clone(),
+                    bytes,
+                    elapsed: start.elapsed(),
+                }));
+                novel
+            })
+            .collect();
+        let next = list.next.map(|x| join(url, &x));
+
+        Ok((novels, list.pages, next))
+    }
+
+    async fn detail(
+        self: &Arc<Self>,
+        id: &NovelID,
+        cancel: &CancellationToken,
+    ) -> spider::Result<(String, NovelDetail)> {
+        let url = self.novel_url(id);
+        let page = self.page(&url, cancel).await?;
+        let detail = self.parse(FN_PARSE_NOVEL, page).await?;
+
+        Ok((url, detail))
+    }
+
+    // 获取章节内容，章节分页时依次获取后续页
+    async fn chapter(
+        self: &Arc<Self>,
+        url: &str,
+        cancel: &CancellationToken,
+    ) -> spider::Result<String> {
+        let mut text = String::new();
+        let mut url = String::from(url);
+        for _ in 0..MAX_CHAPTER_PAGES {
+            let page = self.page(&url, cancel).await?;
+            let chapter: Chapter = self.parse(FN_PARSE_CHAPTER, page).await?;
+            if !text.is_empty() {
+                text.push('\n');
+            }
+            text.push_str(&chapter.text);
+
+            match chapter.next {
+                Some(next) => url = join(&url, &next),
+                None => break,
+            }
+        }
+
+        Ok(text)
+    }
+
+    async fn send_novels(
+        self: Arc<Self>,
+        id: SortID,
+        tx: Sender<spider::Result<Novel>>,
+        pos: Position,
+        cancel: CancellationToken,
+    ) {
+        macro_rules! send_or_abort {
+            ($r:expr) => {
+                match $r {
+                    Ok(x) => x,
+                    Err(e) => {
+                        let _ = tx.send(Err(e)).await;
+                        return;
+                    }
+                }
+            };
+        }
+
+        macro_rules! send_page_or_abort {
+            ($page:expr) => {{
+                let url = send_or_abort!(self.sort_url(&id, $page));
+                let (novels, pages, _) = send_or_abort!(self.novels(&url, &cancel).await);
+                for x in novels {
+                    if tx.send(Ok(x)).await.is_err() {
+                        return;
+                    }
+                }
+                pages
+            }};
+        }
+
+        // 不能直接访问指定页码时，从第一页开始依次访问
+        let pagination = self.info.pagination().unwrap_or(Pagination::Numbered);
+        if pagination != Pagination::Numbered {
+            let mut url = Some(send_or_abort!(self.sort_url(&id, 1)));
+            let mut page = 1;
+            while let Some(x) = url {
+                if cancel.is_cancelled() {
+                    return;
+                }
+
+                let (novels, _, next) = send_or_abort!(self.novels(&x, &cancel).await);
+                let next = next.filter(|_| pagination == Pagination::Sequential);
+                let (send, more) = match &pos {
+                    Position::Full => (true, true),
+                    Position::First => (true, false),
+                    Position::Last => (next.is_none(), true),
+                    Position::Specify(n) => (page == *n, page < *n),
+                    Position::Range(r) => (r.contains(&page), page + 1 < r.end),
+                };
+                if send {
+                    for x in novels {
+                        if tx.send(Ok(x)).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+                if !more {
+                    return;
+                }
+
+                url = next;
+                page += 1;
+            }
+            return;
+        }
+
+        match pos {
+            Position::Full => {
+                let pages = send_page_or_abort!(1).unwrap_or(1);
+                for page in 2..=pages {
+                    if cancel.is_cancelled() {
+                        return;
+                    }
+                    send_page_or_abort!(page);
+                }
+            }
+            Position::First => {
+                send_page_or_abort!(1);
+            }
+            Position::Last => {
+                // 先获取第一页得到总页数
+                let url = send_or_abort!(self.sort_url(&id, 1));
+                let (novels, pages, _) = send_or_abort!(self.novels(&url, &cancel).await);
+                let pages = pages.unwrap_or(1);
+                if pages <= 1 {
+                    for x in novels {
+                        if tx.send(Ok(x)).await.is_err() {
+                            return;
+                        }
+                    }
+                    return;
+                }
+                send_page_or_abort!(pages);
+            }
+            Position::Specify(page) => {
+                send_page_or_abort!(page);
+            }
+            Position::Range(range) => {
+                for page in range {
+                    if cancel.is_cancelled() {
+                        return;
+                    }
+                    send_page_or_abort!(page);
+                }
+            }
+        }
+    }
+
+    async fn send_sections(
+        self: Arc<Self>,
+        id: NovelID,
+        base: String,
+        chapters: Vec<(usize, ChapterLink)>,
+        tx: Sender<spider::Result<Section>>,
+        cancel: CancellationToken,
+    ) {
+        for (seq, link) in chapters {
+            if cancel.is_cancelled() {
+                return;
+            }
+
+            let url = join(&base, &link.url);
+            let r = match self.chapter(&url, &cancel).await {
+                Ok(text) if text.trim().is_empty() => {
+                    let e = CrawlError::MissSectionContent(seq as i32);
+                    events::crawl_failed(&self.id, Some(&url), &e, Duration::ZERO);
+                    Err(e)
+                }
+                Ok(text) => Ok(Section {
+                    seq: seq as u32,
+                    novel_id: id,
+                    name: link.name,
+                    update_at: link.updated_at.as_deref().and_then(parse_time),
+                    text,
+                }),
+                Err(CrawlError::Cancelled) => return,
+                Err(e) => Err(e),
+            };
+
+            if tx.send(r).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+// 相对地址基于页面地址
+fn join(base: &str, link: &str) -> String {
+    Url::parse(base)
+        .and_then(|x| x.join(link))
+        .map(|x| x.to_string())
+        .unwrap_or_else(|_| String::from(link))
+}
+
+// 搜索词编码后填入地址
+fn encode(keyword: &str) -> String {
+    let mut s = String::new();
+    for b in keyword.trim().bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                s.push(b as char)
+            }
+            _ => s.push_str(&format!("%{b:02X}")),
+        }
+    }
+
+    s
+}
+
+impl SpiderMetadata for WasmSpider {
+    // 插件支持的功能在运行时确定，见 WasmSpider::info
+    const SUPPORTED: Support = Support {
+        get_sort: false,
+        get_novel_from_sort: false,
+        search_novel: false,
+        search_author: false,
+    };
+
+    fn id() -> &'static str {
+        "wasm"
+    }
+}
+
+#[async_trait]
+impl Spider for WasmSpider {
+    fn sorts(&self) -> &Vec<Sort> {
+        &self.sorts
+    }
+
+    async fn novels_by_sort_id(
+        &self,
+        id: &SortID,
+        pos: Position,
+        cancel: &CancellationToken,
+    ) -> spider::Result<Receiver<spider::Result<Novel>>> {
+        if self.inner.info.sort_url.is_none() {
+            return Err(CrawlError::Unsupported);
+        }
+
+        let (tx, rx) = channel(50);
+        let inner = self.inner.clone();
+        let id = *id;
+        let cancel = cancel.clone();
+        self.tracker
+            .spawn(async move { inner.send_novels(id, tx, pos, cancel).await });
+
+        Ok(rx)
+    }
+
+    async fn sections_by_novel_id(
+        &self,
+        id: &NovelID,
+        pos: Position,
+        cancel: &CancellationToken,
+    ) -> spider::Result<Receiver<spider::Result<Section>>> {
+        let (url, detail) = self.inner.detail(id, cancel).await?;
+
+        let mut chapters = detail.chapters.into_iter().enumerate();
+        let chapters: Vec<_> = match pos {
+            Position::Full => chapters.collect(),
+            Position::First => chapters.next().into_iter().collect(),
+            Position::Last => chapters.next_back().into_iter().collect(),
+            Position::Specify(x) => chapters
+                .nth((x as usize).saturating_sub(1))
+                .into_iter()
+                .collect(),
+            Position::Range(range) => chapters
+                .filter(|(seq, _)| range.contains(&((*seq as i32) + 1)))
+                .collect(),
+        };
+
+        let (tx, rx) = channel(50);
+        let inner = self.inner.clone();
+        let id = *id;
+        let cancel = cancel.clone();
+        self.tracker
+            .spawn(async move { inner.send_sections(id, url, chapters, tx, cancel).await });
+
+        Ok(rx)
+    }
+
+    async fn fetch_novel(&self, id: &NovelID) -> spider::Result<Novel> {
+        let (url, detail) = self.inner.detail(id, &CancellationToken::new()).await?;
+
+        Ok(detail.novel.into_novel(Some(url)))
+    }
+
+    async fn search(&self, name: &str) -> spider::Result<Vec<Novel>> {
+        let url = match &self.inner.info.search_url {
+            Some(x) => x.replace("{keyword}", &encode(name)),
+            None => return Err(CrawlError::Unsupported),
+        };
+
+        Ok(self.inner.novels(&url, &CancellationToken::new()).await?.0)
+    }
+
+    async fn search_author(&self, author: &str) -> spider::Result<Vec<Novel>> {
+        let author = author.trim();
+        let url = match &self.inner.info.author_search_url {
+            Some(x) => x.replace("{keyword}", &encode(author)),
+            None => return Err(CrawlError::Unsupported),
+        };
+
+        Ok(self
+            .inner
+            .novels(&url, &CancellationToken::new())
+            .await?
+            .0
+            .into_iter()
+            .filter(|x| x.author.trim() == author)
+            .collect())
+    }
+
+    async fn wait(&self) {
+        self.tracker.wait().await
+    }
+
+    async fn probe(&self, kind: ProbeKind, url: &str) -> spider::Result<Probe> {
+        let page = self.inner.page(url, &CancellationToken::new()).await?;
+
+        let mut fields = BTreeMap::new();
+        let mut field = |name: &str, ok: bool| {
+            *fields.entry(String::from(name)).or_default() += ok as usize;
+        };
+
+        let (items, bytes) = match kind {
+            ProbeKind::Sort => {
+                let list: NovelList = self.inner.parse(FN_PARSE_NOVELS, page).await?;
+                for x in &list.novels {
+                    field("name", !x.name.is_empty());
+                    field("author", !x.author.is_empty());
+                    field("last_section", x.last_updated_section_name.is_some());
+                    field("last_updated_at", x.last_updated_at.is_some());
+                    field("state", x.state.is_some());
+                }
+                (list.novels.len(), 0)
+            }
+            ProbeKind::Novel => {
+                let x: NovelDetail = self.inner.parse(FN_PARSE_NOVEL, page).await?;
+                field("cover", x.novel.cover.is_some());
+                field("last_updated_at", x.novel.last_updated_at.is_some());
+                field("intro", x.novel.intro.is_some());
+                field("last_section", x.novel.last_updated_section_name.is_some());
+                field("state", x.novel.state.is_some());
+                field("category", x.novel.category.is_some());
+                field("word_count", x.novel.word_count.is_some());
+                field("chapters", !x.chapters.is_empty());
+                (1, 0)
+            }
+            ProbeKind::Chapter => {
+                let x: Chapter = self.inner.parse(FN_PARSE_CHAPTER, page).await?;
+                let bytes = x.text.trim().len();
+                field("content", bytes > 0);
+                (1, bytes)
+            }
+        };
+
+        Ok(Probe {
+            items,
+            fields,
+            bytes,
+        })
+    }
+
+    fn permits(&self) -> Option<(usize, usize)> {
+        Some(self.inner.host.permits())
+    }
+
+    fn capabilities(&self) -> Option<Capabilities> {
+        Some(self.inner.capabilities())
+    }
+}
@@ -0,0 +1,196 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use std::time::Duration;
+
+use crate::spider::{Capabilities, Novel, NovelState, Pagination, RateLimit};
+
+// 插件中的函数名
+pub const FN_ALLOC: &str = "spider_alloc";
+pub const FN_INFO: &str = "spider_info";
+pub const FN_PARSE_SORTS: &str = "parse_sorts";
+pub const FN_PARSE_NOVELS: &str = "parse_novels";
+pub const FN_PARSE_NOVEL: &str = "parse_novel";
+pub const FN_PARSE_CHAPTER: &str = "parse_chapter";
+
+// 插件可以导入的宿主函数所在的模块名
+pub const HOST_MODULE: &str = "spider";
+
+// 插件的描述，由 spider_info 返回
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PluginInfo {
+    // 爬虫id，设置后不能修改
+    pub id: String,
+    pub name: String,
+    // 插件自身的版本
+    pub version: String,
+    // 分类列表页地址，未设置时不支持获取分类
+    #[serde(default)]
+    pub sorts_url: Option<String>,
+    // 分类下的小说列表页地址，{id} 为分类id，{page} 为从1开始的页码
+    #[serde(default)]
+    pub sort_url: Option<String>,
+    // 小说详情页地址，{id} 为小说id
+    pub novel_url: String,
+    // 搜索结果页地址，{keyword} 为搜索词
+    #[serde(default)]
+    pub search_url: Option<String>,
+    // 按作者搜索的结果页地址，{keyword} 为作者名
+    #[serde(default)]
+    pub author_search_url: Option<String>,
+    // 建议的最大并发请求数与请求间隔，可以被配置覆盖
+    #[serde(default)]
+    pub concurrency: Option<usize>,
+    #[serde(default)]
+    pub interval_ms: Option<u64>,
+    // 列表页的分页方式：single、numbered 或 sequential，默认 numbered
+    #[serde(default)]
+    pub pagination: Option<String>,
+    // 章节目录带有更新时间
+    #[serde(default)]
+    pub chapter_updated_at: bool,
+    #[serde(default)]
+    pub vip: bool,
+    #[serde(default)]
+    pub login_required: bool,
+    #[serde(default)]
+    pub json_api: bool,
+}
+
+impl PluginInfo {
+    pub fn pagination(&self) -> Option<Pagination> {
+        match self.pagination.as_deref() {
+            None | Some("numbered") => Some(Pagination::Numbered),
+            Some("single") => Some(Pagination::Single),
+            Some("sequential") => Some(Pagination::Sequential),
+            Some(_) => None,
+        }
+    }
+
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            get_sort: self.sorts_url.is_some(),
+            get_novel_from_sort: self.sort_url.is_some(),
+            search_novel: self.search_url.is_some(),
+            search_author: self.author_search_url.is_some(),
+            pagination: self.pagination().unwrap_or(Pagination::Numbered),
+            chapter_updated_at: self.chapter_updated_at,
+            vip: self.vip,
+            login_required: self.login_required,
+            json_api: self.json_api,
+            rate_limit: self.interval_ms.map(|x| RateLimit {
+                concurrency: self.concurrency.unwrap_or(1),
+                interval: Duration::from_millis(x),
+            }),
+        }
+    }
+}
+
+// 宿主获取的页面，作为各解析函数的参数
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Page {
+    pub url: String,
+    pub body: String,
+}
+
+// 解析函数的返回值，{"ok": ...} 或 {"err": "..."}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Reply<T> {
+    Ok(T),
+    Err(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SortItem {
+    pub id: i64,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NovelItem {
+    pub id: i64,
+    pub name: String,
+    pub author: String,
+    pub cover: Option<String>,
+    pub intro: Option<String>,
+    // RFC 3339 格式的时间
+    pub last_updated_at: Option<String>,
+    pub last_updated_section_name: Option<String>,
+    // updating 或 finished
+    pub state: Option<String>,
+    pub word_count: Option<u64>,
+    pub tags: Vec<String>,
+    pub category: Option<String>,
+    pub aliases: Vec<String>,
+}
+
+// 解析RFC 3339格式的时间，格式错误时忽略
+pub fn parse_time(x: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(x)
+        .ok()
+        .map(|x| x.with_timezone(&Utc))
+}
+
+impl NovelItem {
+    pub fn into_novel(self, source_url: Option<String>) -> Novel {
+        Novel {
+            id: self.id.into(),
+            name: self.name,
+            cover: self.cover,
+            author: self.author,
+            intro: self.intro,
+            last_updated_at: self.last_updated_at.as_deref().and_then(parse_time),
+            last_updated_section_name: self.last_updated_section_name,
+            state: match self.state.as_deref() {
+                Some("updating") => Some(NovelState::Updating),
+                Some("finished") => Some(NovelState::Finished),
+                _ => None,
+            },
+            word_count: self.word_count,
+            tags: self.tags,
+            category: self.category,
+            aliases: self.aliases,
+            source_url,
+        }
+    }
+}
+
+// 分类下的小说列表页与搜索结果页
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NovelList {
+    pub novels: Vec<NovelItem>,
+    // 总页数，未知时只获取当前页
+    pub pages: Option<i32>,
+    // sequential 分页时下一页的地址，相对地址基于当前页地址
+    pub next: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChapterLink {
+    pub name: String,
+    // 相对地址基于详情页地址
+    pub url: String,
+    // RFC 3339 格式的更新时间
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+// 小说详情页，包含章节目录
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NovelDetail {
+    pub novel: NovelItem,
+    #[serde(default)]
+    pub chapters: Vec<ChapterLink>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Chapter {
+    pub text: String,
+    // 章节分为多页时下一页的地址
+    pub next: Option<String>,
+}
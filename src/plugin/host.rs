@@ -0,0 +1,66 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::Semaphore;
+use tokio::time::Instant;
+
+use crate::common::httputils::fetch;
+use crate::common::shutdown::CancellationToken;
+use crate::spider::{CrawlError, Result};
+
+// 插件不能直接访问网络，由宿主获取页面，并限制并发数与请求间隔
+pub struct Host {
+    spider_id: Arc<str>,
+    smp: Arc<Semaphore>,
+    concurrency: usize,
+    interval: Duration,
+    // 下一个请求最早可以发出的时间
+    next: Mutex<Instant>,
+}
+
+impl Host {
+    pub fn new(spider_id: Arc<str>, concurrency: usize, interval: Duration) -> Self {
+        Self {
+            spider_id,
+            smp: Arc::new(Semaphore::new(concurrency)),
+            concurrency,
+            interval,
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    // cancel被取消时不再发起请求
+    pub async fn get(&self, url: &str, cancel: &CancellationToken) -> Result<String> {
+        let _permit = tokio::select! {
+            permit = self.smp.acquire() => permit.unwrap(),
+            _ = cancel.cancelled() => return Err(CrawlError::Cancelled),
+        };
+
+        let at = {
+            let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+            let at = (*next).max(Instant::now());
+            *next = at + self.interval;
+            at
+        };
+        tokio::select! {
+            _ = tokio::time::sleep_until(at) => {},
+            _ = cancel.cancelled() => return Err(CrawlError::Cancelled),
+        }
+
+        fetch(&self.spider_id, url)
+            .await
+            .map_err(|e| CrawlError::fetch(None, e))
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn permits(&self) -> (usize, usize) {
+        (
+            self.concurrency
+                .saturating_sub(self.smp.available_permits()),
+            self.concurrency,
+        )
+    }
+}
@@ -0,0 +1,169 @@
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use log::{debug, error, info, warn};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use wasmtime::{
+    Caller, Config, Engine, Instance, Linker, Memory, Module, Store, StoreLimits,
+    StoreLimitsBuilder,
+};
+
+use crate::plugin::abi::{PluginInfo, Reply, FN_ALLOC, FN_INFO, HOST_MODULE};
+
+// 插件每次调用可以使用的资源
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Limits {
+    // 可以执行的指令数，用完后调用失败
+    pub fuel: u64,
+    // 线性内存的最大字节数
+    pub memory: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            fuel: 500_000_000,
+            memory: 64 << 20,
+        }
+    }
+}
+
+struct State {
+    limits: StoreLimits,
+}
+
+// 编译后的插件，每次调用都在新的实例中执行，调用之间不共享状态
+pub struct Runtime {
+    engine: Engine,
+    module: Module,
+    limits: Limits,
+}
+
+impl Runtime {
+    // bytes为wasm二进制或文本格式
+    pub fn new(bytes: &[u8], limits: Limits) -> Result<Self> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config)?;
+        let module = Module::new(&engine, bytes).context("编译插件失败")?;
+
+        Ok(Self {
+            engine,
+            module,
+            limits,
+        })
+    }
+
+    pub fn from_file(path: &Path, limits: Limits) -> Result<Self> {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("读取插件失败; path={}", path.display()))?;
+        Self::new(&bytes, limits).with_context(|| format!("path={}", path.display()))
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    fn instantiate(&self) -> Result<(Store<State>, Instance, Memory)> {
+        let mut store = Store::new(
+            &self.engine,
+            State {
+                limits: StoreLimitsBuilder::new()
+                    .memory_size(self.limits.memory)
+                    .instances(1)
+                    .build(),
+            },
+        );
+        store.limiter(|x| &mut x.limits);
+        store.add_fuel(self.limits.fuel)?;
+
+        let mut linker = Linker::new(&self.engine);
+        // log(level, ptr, len)，level: 0 error, 1 warn, 2 info, 其他 debug
+        linker.func_wrap(
+            HOST_MODULE,
+            "log",
+            |mut caller: Caller<'_, State>, level: i32, ptr: i32, len: i32| {
+                let memory = match caller.get_export("memory").and_then(|x| x.into_memory()) {
+                    Some(x) => x,
+                    None => return,
+                };
+                let text = match read(memory.data(&caller), ptr as u32, len as u32) {
+                    Ok(x) => String::from_utf8_lossy(x).into_owned(),
+                    Err(_) => return,
+                };
+                match level {
+                    0 => error!("插件: {text}"),
+                    1 => warn!("插件: {text}"),
+                    2 => info!("插件: {text}"),
+                    _ => debug!("插件: {text}"),
+                }
+            },
+        )?;
+
+        let instance = linker
+            .instantiate(&mut store, &self.module)
+            .context("实例化插件失败")?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| anyhow!("插件没有导出memory"))?;
+
+        Ok((store, instance, memory))
+    }
+
+    // 读取插件的描述
+    pub fn info(&self) -> Result<PluginInfo> {
+        let (mut store, instance, memory) = self.instantiate()?;
+        let f = instance.get_typed_func::<(), i64, _>(&mut store, FN_INFO)?;
+        let packed = f
+            .call(&mut store, ())
+            .context("调用插件失败; fn=spider_info")?;
+
+        let bytes = read_packed(memory.data(&store), packed)?;
+        serde_json::from_slice(bytes).context("插件描述格式错误")
+    }
+
+    // 调用解析函数，参数与返回值都是JSON
+    pub fn call<I, O>(&self, name: &str, input: &I) -> Result<Reply<O>>
+    where
+        I: Serialize,
+        O: DeserializeOwned,
+    {
+        let input = serde_json::to_vec(input)?;
+        let len = i32::try_from(input.len()).context("参数过大")?;
+
+        let (mut store, instance, memory) = self.instantiate()?;
+        let alloc = instance.get_typed_func::<i32, i32, _>(&mut store, FN_ALLOC)?;
+        let ptr = alloc
+            .call(&mut store, len)
+            .context("调用插件失败; fn=spider_alloc")?;
+        memory
+            .write(&mut store, ptr as u32 as usize, &input)
+            .context("写入插件内存失败")?;
+
+        let f = instance.get_typed_func::<(i32, i32), i64, _>(&mut store, name)?;
+        let packed = f
+            .call(&mut store, (ptr, len))
+            .with_context(|| format!("调用插件失败; fn={name}"))?;
+
+        let bytes = read_packed(memory.data(&store), packed)?;
+        serde_json::from_slice(bytes).with_context(|| format!("插件返回值格式错误; fn={name}"))
+    }
+}
+
+// 返回值的高32位为地址，低32位为长度
+fn read_packed(data: &[u8], packed: i64) -> Result<&[u8]> {
+    let ptr = (packed as u64 >> 32) as u32;
+    let len = packed as u64 as u32;
+    read(data, ptr, len)
+}
+
+fn read(data: &[u8], ptr: u32, len: u32) -> Result<&[u8]> {
+    let start = ptr as usize;
+    let end = start + len as usize;
+    if end > data.len() {
+        bail!("插件内存越界; ptr={ptr}, len={len}");
+    }
+
+    Ok(&data[start..end])
+}
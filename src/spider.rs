@@ -1,19 +1,29 @@
+use std::collections::BTreeMap;
 use std::fmt::{Display, Formatter};
 use std::ops::Range;
+use std::path::PathBuf;
 
+use anyhow::{bail, Context};
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
+use reqwest::Url;
+use serde::Deserialize;
 use thiserror::Error;
 use tokio::sync::mpsc::Receiver;
 
+use crate::common::httputils::FetchError;
+use crate::common::migrate::Migration;
+use crate::common::shutdown::CancellationToken;
+use crate::keeper::canary::CanaryConfig;
 use crate::keeper::data::entity::sort::Model as SortModel;
+use crate::webook::session::LoginConfig;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct SortID(i64);
 
-impl Into<i64> for SortID {
-    fn into(self) -> i64 {
-        self.0
+impl From<SortID> for i64 {
+    fn from(id: SortID) -> Self {
+        id.0
     }
 }
 
@@ -53,15 +63,15 @@ impl From<SortModel> for Sort {
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Ord, Eq)]
 pub struct NovelID(i64);
 
-impl Into<i64> for NovelID {
-    fn into(self) -> i64 {
-        self.0
+impl From<NovelID> for i64 {
+    fn from(id: NovelID) -> Self {
+        id.0
     }
 }
 
-impl Into<i64> for &NovelID {
-    fn into(self) -> i64 {
-        self.0
+impl From<&NovelID> for i64 {
+    fn from(id: &NovelID) -> Self {
+        id.0
     }
 }
 
@@ -93,6 +103,16 @@ pub struct Novel {
     pub last_updated_at: Option<DateTime<Utc>>,
     pub last_updated_section_name: Option<String>,
     pub state: Option<NovelState>,
+    // 字数
+    pub word_count: Option<u64>,
+    // 标签
+    pub tags: Vec<String>,
+    // 网站上的原始分类
+    pub category: Option<String>,
+    // 别名
+    pub aliases: Vec<String>,
+    // 小说在网站上的地址
+    pub source_url: Option<String>,
 }
 
 // pub enum SectionName {
@@ -127,7 +147,7 @@ pub struct Section {
     pub text: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Support {
     // 是否支持获取分类
     pub get_sort: bool,
@@ -135,6 +155,95 @@ pub struct Support {
     pub get_novel_from_sort: bool,
     // 是否支持搜索小说
     pub search_novel: bool,
+    // 是否支持按作者搜索小说
+    pub search_author: bool,
+}
+
+// 列表页的分页方式
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Pagination {
+    // 只有一页
+    Single,
+    // 可以直接访问指定页码
+    Numbered,
+    // 只能从第一页开始依次访问下一页
+    Sequential,
+}
+
+impl Pagination {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Pagination::Single => "single",
+            Pagination::Numbered => "numbered",
+            Pagination::Sequential => "sequential",
+        }
+    }
+}
+
+// 网站对请求频率的要求
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RateLimit {
+    // 最大并发请求数
+    pub concurrency: usize,
+    // 两次请求的最小间隔
+    pub interval: std::time::Duration,
+}
+
+// 爬虫支持的功能，编译在程序中的爬虫由 Support 得到，规则爬虫与插件在加载后才能确定
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    pub get_sort: bool,
+    pub get_novel_from_sort: bool,
+    pub search_novel: bool,
+    pub search_author: bool,
+    pub pagination: Pagination,
+    // 章节带有更新时间
+    pub chapter_updated_at: bool,
+    // 能识别VIP章节
+    pub vip: bool,
+    // 需要登录后才能访问
+    pub login_required: bool,
+    // 使用网站的JSON接口，而不是解析网页
+    pub json_api: bool,
+    pub rate_limit: Option<RateLimit>,
+}
+
+impl From<Support> for Capabilities {
+    fn from(x: Support) -> Self {
+        Self {
+            get_sort: x.get_sort,
+            get_novel_from_sort: x.get_novel_from_sort,
+            search_novel: x.search_novel,
+            search_author: x.search_author,
+            pagination: Pagination::Numbered,
+            chapter_updated_at: false,
+            vip: false,
+            login_required: false,
+            json_api: false,
+            rate_limit: None,
+        }
+    }
+}
+
+impl Capabilities {
+    // 支持的功能名称，用于显示
+    pub fn names(&self) -> Vec<&'static str> {
+        [
+            (self.get_sort, "sort"),
+            (self.get_novel_from_sort, "novels"),
+            (self.search_novel, "search"),
+            (self.search_author, "author"),
+            (self.chapter_updated_at, "chapter_time"),
+            (self.vip, "vip"),
+            (self.login_required, "login"),
+            (self.json_api, "json"),
+            (self.rate_limit.is_some(), "rate_limit"),
+        ]
+        .into_iter()
+        .filter(|(x, _)| *x)
+        .map(|(_, name)| name)
+        .collect()
+    }
 }
 
 pub enum Position {
@@ -150,6 +259,81 @@ pub enum Position {
     Range(Range<i32>),
 }
 
+// 爬虫的来源
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SourceKind {
+    // 编译在程序中的爬虫
+    Builtin,
+    // 从文件加载规则的爬虫，例如jieqi模板的站点描述
+    Rule,
+    // WASM插件
+    Wasm,
+}
+
+impl SourceKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SourceKind::Builtin => "builtin",
+            SourceKind::Rule => "rule",
+            SourceKind::Wasm => "wasm",
+        }
+    }
+}
+
+// 注册到keeper的爬虫描述
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpiderInfo {
+    // 稳定的爬虫id，同时用于区分各爬虫的数据，设置后不能修改
+    pub id: String,
+    // 显示的名称
+    pub name: String,
+    pub version: String,
+    pub source: SourceKind,
+    // 注册时的能力，运行时以 Spider::capabilities 为准
+    pub capabilities: Capabilities,
+}
+
+// 解析健康检查的页面类型
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ProbeKind {
+    // 分类下的小说列表页
+    Sort,
+    // 小说详情页
+    Novel,
+    // 章节内容页
+    Chapter,
+}
+
+impl ProbeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProbeKind::Sort => "sort",
+            ProbeKind::Novel => "novel",
+            ProbeKind::Chapter => "chapter",
+        }
+    }
+
+    pub fn parse(x: &str) -> Option<Self> {
+        match x {
+            "sort" => Some(ProbeKind::Sort),
+            "novel" => Some(ProbeKind::Novel),
+            "chapter" => Some(ProbeKind::Chapter),
+            _ => None,
+        }
+    }
+}
+
+// 解析一个页面的结果，用于检查网站页面结构是否变化
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Probe {
+    // 页面中的条目数，列表页为行数，详情页与章节页为1
+    pub items: usize,
+    // 每个字段成功解析的条目数
+    pub fields: BTreeMap<String, usize>,
+    // 解析出的正文字节数
+    pub bytes: usize,
+}
+
 #[derive(Error, Debug)]
 pub enum CrawlError {
     #[error("network disconnect")]
@@ -167,6 +351,52 @@ pub enum CrawlError {
     MissSectionLink(i32),
     #[error("section missing content: {0}")]
     MissSectionContent(i32),
+    #[error("crawl cancelled")]
+    Cancelled,
+    #[error("crawl timeout")]
+    Timeout,
+    #[error("operation not supported by spider")]
+    Unsupported,
+    #[error("login failed")]
+    LoginFailed,
+    #[error("anti-bot challenge not solved: {0}")]
+    Challenge(String),
+    #[error("banned by site: {0}")]
+    Banned(String),
+    #[error("no proxy available: {0}")]
+    NoProxy(String),
+}
+
+impl CrawlError {
+    // 错误类型的名称，用于事件与监控指标
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CrawlError::Disconnect { .. } => "disconnect",
+            CrawlError::ResourceNotFound => "resource_not_found",
+            CrawlError::ParseFailed => "parse_failed",
+            CrawlError::SpiderInnerFailed(_) => "spider_inner_failed",
+            CrawlError::MissSectionLink(_) => "miss_section_link",
+            CrawlError::MissSectionContent(_) => "miss_section_content",
+            CrawlError::Cancelled => "cancelled",
+            CrawlError::Timeout => "timeout",
+            CrawlError::Unsupported => "unsupported",
+            CrawlError::LoginFailed => "login_failed",
+            CrawlError::Challenge(_) => "challenge",
+            CrawlError::Banned(_) => "banned",
+            CrawlError::NoProxy(_) => "no_proxy",
+        }
+    }
+
+    // 获取页面失败，seq为页码或章节序号
+    pub fn fetch(seq: Option<i32>, e: FetchError) -> Self {
+        match e {
+            FetchError::Http(reason) => CrawlError::Disconnect { seq, reason },
+            FetchError::Challenge(x) => CrawlError::Challenge(x),
+            FetchError::Login(_) => CrawlError::LoginFailed,
+            FetchError::Banned(x) => CrawlError::Banned(x),
+            FetchError::NoProxy(x) => CrawlError::NoProxy(x),
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, CrawlError>;
@@ -175,6 +405,31 @@ pub trait SpiderMetadata {
     const SUPPORTED: Support;
     // 获取一个网站爬虫的id
     fn id() -> &'static str;
+
+    // 显示的名称，默认与id相同
+    fn name() -> &'static str {
+        Self::id()
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    // 编译在程序中的爬虫的描述
+    fn info() -> SpiderInfo {
+        SpiderInfo {
+            id: String::from(Self::id()),
+            name: String::from(Self::name()),
+            version: String::from(Self::version()),
+            source: SourceKind::Builtin,
+            capabilities: Self::SUPPORTED.into(),
+        }
+    }
+
+    // 爬虫自身数据的迁移，由keeper在启动时执行
+    fn migrations() -> Vec<Migration> {
+        Vec::new()
+    }
 }
 
 #[async_trait]
@@ -182,18 +437,20 @@ pub trait Spider: Sync {
     // 获取分类
     fn sorts(&self) -> &Vec<Sort>;
 
-    // 通过分类id获取小说元信息
+    // 通过分类id获取小说元信息，cancel被取消后不再发起新的请求
     async fn novels_by_sort_id(
         &self,
         id: &SortID,
         pos: Position,
+        cancel: &CancellationToken,
     ) -> Result<Receiver<Result<Novel>>>;
 
-    // 通过小说id获取章节和内容
+    // 通过小说id获取章节和内容，cancel被取消后不再发起新的请求
     async fn sections_by_novel_id(
         &self,
         id: &NovelID,
         pos: Position,
+        cancel: &CancellationToken,
     ) -> Result<Receiver<Result<Section>>>;
 
     // 获取小说元信息
@@ -210,4 +467,146 @@ pub trait Spider: Sync {
             .into_iter()
             .find(|x| x.author == author && x.name.trim() == name))
     }
+
+    // 通过作者名搜索小说，返回该作者的全部小说
+    async fn search_author(&self, _author: &str) -> Result<Vec<Novel>> {
+        Err(CrawlError::Unsupported)
+    }
+
+    // 等待爬虫内部正在执行的任务结束
+    async fn wait(&self) {}
+
+    // 获取并解析指定页面，返回各字段的解析情况
+    async fn probe(&self, _kind: ProbeKind, _url: &str) -> Result<Probe> {
+        Err(CrawlError::Unsupported)
+    }
+
+    // 并发限制的使用情况，返回 (正在使用的数量, 最大并发数)，没有限制时返回None
+    fn permits(&self) -> Option<(usize, usize)> {
+        None
+    }
+
+    // 运行时确定的能力，返回None时使用注册时的描述
+    fn capabilities(&self) -> Option<Capabilities> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpiderConfig {
+    pub enabled: bool,
+    // 爬虫模板，jieqi 或 wasm；未设置时按名称使用内置爬虫
+    pub template: Option<String>,
+    // 爬虫id，未设置时使用站点描述中的id；没有站点描述的镜像站需要设置，否则与ddxsku重复
+    pub id: Option<String>,
+    // 站点描述文件，未设置时使用内置的站点描述
+    pub profile: Option<PathBuf>,
+    // 网站地址，网站更换域名时修改
+    pub base_url: Option<String>,
+    pub search_url: Option<String>,
+    // 最大并发请求数
+    pub concurrency: Option<usize>,
+    // 来源评分
+    pub score: Option<i32>,
+    // 覆盖爬虫内置的css选择器，键为选择器名称
+    pub selectors: BTreeMap<String, String>,
+    // 解析健康检查，未设置时不检查
+    pub canary: Option<CanaryConfig>,
+    // wasm模板的插件文件
+    pub plugin: Option<PathBuf>,
+    // 插件每次调用可以执行的指令数与最大内存，未设置时使用默认值
+    pub fuel: Option<u64>,
+    pub memory_mb: Option<usize>,
+    // 保存网站设置的cookie并在之后的请求中发送，重启后恢复
+    pub cookies: bool,
+    // 需要登录的网站，获取页面时携带登录状态，显示未登录时自动重新登录
+    pub login: Option<LoginConfig>,
+}
+
+impl Default for SpiderConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            template: None,
+            id: None,
+            profile: None,
+            base_url: None,
+            search_url: None,
+            concurrency: None,
+            score: None,
+            selectors: BTreeMap::new(),
+            canary: None,
+            plugin: None,
+            fuel: None,
+            memory_mb: None,
+            cookies: false,
+            login: None,
+        }
+    }
+}
+
+// jieqi模板，ddxsku及其同模板站点
+pub const TEMPLATE_JIEQI: &str = "jieqi";
+
+// wasm插件
+pub const TEMPLATE_WASM: &str = "wasm";
+
+impl SpiderConfig {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if let Some(x) = &self.template {
+            if x != TEMPLATE_JIEQI && x != TEMPLATE_WASM {
+                bail!("未知的爬虫模板: {x}");
+            }
+        }
+        if (self.template.as_deref() == Some(TEMPLATE_WASM)) != self.plugin.is_some() {
+            bail!("wasm 模板需要且只有 wasm 模板可以设置 plugin");
+        }
+        // 插件的id由插件声明
+        if let Some(x) = &self.id {
+            if self.plugin.is_some() {
+                bail!("wasm 模板不能设置 id");
+            }
+            if x.trim().is_empty() {
+                bail!("id 不能为空");
+            }
+        }
+        if self.fuel == Some(0) {
+            bail!("fuel 必须大于0");
+        }
+        if self.memory_mb == Some(0) {
+            bail!("memory_mb 必须大于0");
+        }
+
+        for (name, x) in [
+            ("base_url", &self.base_url),
+            ("search_url", &self.search_url),
+        ] {
+            if let Some(x) = x {
+                let url = Url::parse(x).with_context(|| format!("{name}={x}"))?;
+                if !matches!(url.scheme(), "http" | "https") {
+                    bail!("{name} 只支持 http 与 https: {x}");
+                }
+            }
+        }
+
+        if self.concurrency == Some(0) {
+            bail!("concurrency 必须大于0");
+        }
+
+        for (name, x) in &self.selectors {
+            if x.trim().is_empty() {
+                bail!("selectors.{name} 不能为空");
+            }
+        }
+
+        if let Some(x) = &self.canary {
+            x.to_canary().context("canary 配置错误")?;
+        }
+        if let Some(x) = &self.login {
+            x.to_source().context("login 配置错误")?;
+        }
+
+        Ok(())
+    }
 }
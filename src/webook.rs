@@ -1,3 +1,6 @@
+pub mod credential;
+pub mod session;
+
 #[derive(Debug, Clone, Copy)]
 pub enum SourceType {
     Text,
@@ -7,6 +10,7 @@ pub enum SourceType {
     File,
 }
 
+#[allow(dead_code)]
 pub struct BookSource {
     /// 地址，包括http/https
     url: String,
@@ -52,6 +56,69 @@ pub struct BookSource {
     rule_content: Option<ContentRule>,
 }
 
+impl BookSource {
+    pub fn new(url: &str, name: &str) -> Self {
+        Self {
+            url: String::from(url),
+            name: String::from(name),
+            group: None,
+            r_type: SourceType::Text,
+            url_pattern: None,
+            enabled: true,
+            enabled_cookiejar: false,
+            concurrent_rate: None,
+            header: None,
+            login_url: None,
+            login_ui: None,
+            login_checkjs: None,
+            comment: None,
+            iable_comment: None,
+            explore_url: None,
+            rule_explore: ExploreRule::default(),
+            search_url: None,
+            rule_search: None,
+            rule_book_info: None,
+            rule_toc: None,
+            rule_content: None,
+        }
+    }
+
+    /// 设置登录地址或js、登录UI与登录检测js
+    pub fn with_login(
+        mut self,
+        url: Option<String>,
+        ui: Option<String>,
+        checkjs: Option<String>,
+    ) -> Self {
+        self.login_url = url;
+        self.login_ui = ui;
+        self.login_checkjs = checkjs;
+        self
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn login_url(&self) -> Option<&str> {
+        self.login_url.as_deref()
+    }
+
+    pub fn login_ui(&self) -> Option<&str> {
+        self.login_ui.as_deref()
+    }
+
+    pub fn login_checkjs(&self) -> Option<&str> {
+        self.login_checkjs.as_deref()
+    }
+}
+
+#[derive(Default)]
+#[allow(dead_code)]
 pub struct ExploreRule {
     book_list: Option<String>,
     name: Option<String>,
@@ -65,6 +132,8 @@ pub struct ExploreRule {
     word_count: Option<String>,
 }
 
+#[derive(Default)]
+#[allow(dead_code)]
 pub struct SearchRule {
     /// 校验关键字
     check_keyword: Option<String>,
@@ -80,6 +149,8 @@ pub struct SearchRule {
     word_count: Option<String>,
 }
 
+#[derive(Default)]
+#[allow(dead_code)]
 pub struct InfoRule {
     init: Option<String>,
     name: Option<String>,
@@ -95,6 +166,8 @@ pub struct InfoRule {
     download_urls: Option<String>,
 }
 
+#[derive(Default)]
+#[allow(dead_code)]
 pub struct TocRule {
     pre_update_js: Option<String>,
     chapter_list: Option<String>,
@@ -107,6 +180,8 @@ pub struct TocRule {
     next_toc_url: Option<String>,
 }
 
+#[derive(Default)]
+#[allow(dead_code)]
 pub struct ContentRule {
     content: Option<String>,
     next_content_url: Option<String>,
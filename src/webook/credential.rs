@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hmac::Hmac;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+// 提供凭据文件密码的环境变量
+pub const ENV_CREDENTIAL_SECRET: &str = "SPIDER_CREDENTIAL_SECRET";
+
+const FILE_VERSION: u32 = 1;
+const KDF_ROUNDS: u32 = 100_000;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+// 一个书源的登录信息，键为登录UI中的字段名，例如 username、password
+pub type Credentials = BTreeMap<String, String>;
+
+#[derive(Serialize, Deserialize)]
+struct EncryptedFile {
+    version: u32,
+    salt: String,
+    nonce: String,
+    data: String,
+}
+
+// 加密保存在本地文件中的登录信息，以书源地址区分
+pub struct CredentialStore {
+    path: PathBuf,
+    secret: String,
+    sources: BTreeMap<String, Credentials>,
+}
+
+impl CredentialStore {
+    // 打开凭据文件，文件不存在时为空，密码错误或文件被修改时返回错误
+    pub fn open(path: &Path, secret: &str) -> Result<Self> {
+        if secret.is_empty() {
+            bail!("凭据文件密码不能为空");
+        }
+
+        let sources = match std::fs::read(path) {
+            Ok(x) => decrypt(&x, secret)
+                .with_context(|| format!("读取凭据文件失败; path={}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e).with_context(|| format!("path={}", path.display())),
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            secret: String::from(secret),
+            sources,
+        })
+    }
+
+    // 使用环境变量中的密码打开凭据文件
+    pub fn open_env(path: &Path) -> Result<Self> {
+        let secret = std::env::var(ENV_CREDENTIAL_SECRET)
+            .map_err(|_| anyhow!("需要设置环境变量 {ENV_CREDENTIAL_SECRET}"))?;
+        Self::open(path, &secret)
+    }
+
+    pub fn get(&self, source: &str) -> Option<&Credentials> {
+        self.sources.get(source)
+    }
+
+    pub fn sources(&self) -> Vec<&str> {
+        self.sources.keys().map(|x| x.as_str()).collect()
+    }
+
+    // 保存书源的登录信息并写入文件
+    pub fn set(&mut self, source: &str, credentials: Credentials) -> Result<()> {
+        self.sources.insert(String::from(source), credentials);
+        self.save()
+    }
+
+    pub fn remove(&mut self, source: &str) -> Result<bool> {
+        let removed = self.sources.remove(source).is_some();
+        if removed {
+            self.save()?;
+        }
+
+        Ok(removed)
+    }
+
+    fn save(&self) -> Result<()> {
+        let data = encrypt(&self.sources, &self.secret)?;
+
+        // 先写入临时文件再替换，避免写入中断时损坏原文件
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, data).with_context(|| format!("path={}", tmp.display()))?;
+        restrict(&tmp)?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("path={}", self.path.display()))?;
+
+        Ok(())
+    }
+}
+
+// 只允许文件所有者读写
+#[cfg(unix)]
+fn restrict(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn restrict(_: &Path) -> Result<()> {
+    Ok(())
+}
+
+fn cipher(secret: &str, salt: &[u8]) -> ChaCha20Poly1305 {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(secret.as_bytes(), salt, KDF_ROUNDS, &mut key);
+    ChaCha20Poly1305::new(Key::from_slice(&key))
+}
+
+fn encrypt(sources: &BTreeMap<String, Credentials>, secret: &str) -> Result<Vec<u8>> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill(&mut salt);
+    rand::thread_rng().fill(&mut nonce);
+
+    let plain = serde_json::to_vec(sources)?;
+    let data = cipher(secret, &salt)
+        .encrypt(Nonce::from_slice(&nonce), plain.as_slice())
+        .map_err(|_| anyhow!("加密凭据失败"))?;
+
+    let file = EncryptedFile {
+        version: FILE_VERSION,
+        salt: base64::encode(salt),
+        nonce: base64::encode(nonce),
+        data: base64::encode(data),
+    };
+    Ok(serde_json::to_vec_pretty(&file)?)
+}
+
+fn decrypt(bytes: &[u8], secret: &str) -> Result<BTreeMap<String, Credentials>> {
+    let file: EncryptedFile = serde_json::from_slice(bytes).context("凭据文件格式错误")?;
+    if file.version != FILE_VERSION {
+        bail!("不支持的凭据文件版本: {}", file.version);
+    }
+
+    let salt = base64::decode(&file.salt)?;
+    let nonce = base64::decode(&file.nonce)?;
+    if nonce.len() != NONCE_LEN {
+        bail!("凭据文件格式错误");
+    }
+    let data = base64::decode(&file.data)?;
+
+    let plain = cipher(secret, &salt)
+        .decrypt(Nonce::from_slice(&nonce), data.as_slice())
+        .map_err(|_| anyhow!("密码错误或凭据文件已损坏"))?;
+
+    Ok(serde_json::from_slice(&plain)?)
+}
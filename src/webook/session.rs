@@ -0,0 +1,342 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use log::{info, warn};
+use reqwest::header::HeaderName;
+use reqwest::{Method, Url};
+use serde::Deserialize;
+use static_init::dynamic;
+
+use crate::common::challenge::{Sandbox, SCRIPT_MEMORY, SCRIPT_TIMEOUT};
+use crate::common::cookiejar;
+use crate::common::httputils::{self, FetchError, CLIENT};
+use crate::spider::CrawlError;
+use crate::webook::credential::Credentials;
+use crate::webook::BookSource;
+
+// 需要登录的爬虫的会话，通过httputils获取页面时使用
+#[dynamic]
+static SESSIONS: RwLock<HashMap<String, Arc<Session>>> = RwLock::new(HashMap::new());
+
+// 登录js返回的请求，js中定义 login() 函数，可以使用 source 与 credentials 变量
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct LoginRequest {
+    pub url: String,
+    // GET 或 POST
+    pub method: String,
+    // 表单字段，POST时作为请求体
+    pub form: BTreeMap<String, String>,
+    pub headers: BTreeMap<String, String>,
+}
+
+impl Default for LoginRequest {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            method: String::from("POST"),
+            form: BTreeMap::new(),
+            headers: BTreeMap::new(),
+        }
+    }
+}
+
+// 书源的登录状态，请求发现已登出时自动重新登录
+// 登录得到的cookie保存在爬虫的cookie中，页面与爬虫的其他请求一样使用代理池
+pub struct Session {
+    spider_id: Arc<str>,
+    source: Arc<BookSource>,
+    credentials: Option<Credentials>,
+    // 每次登录后加一，并发请求同时发现登出时只重新登录一次
+    generation: Mutex<u64>,
+    login: tokio::sync::Mutex<()>,
+}
+
+impl Session {
+    pub fn new(spider_id: &str, source: Arc<BookSource>, credentials: Option<Credentials>) -> Self {
+        Self {
+            spider_id: Arc::from(spider_id),
+            source,
+            credentials,
+            generation: Mutex::new(0),
+            login: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub fn spider_id(&self) -> &str {
+        &self.spider_id
+    }
+
+    pub fn source(&self) -> &BookSource {
+        &self.source
+    }
+
+    // 爬虫当前的cookie
+    pub fn cookies(&self) -> BTreeMap<String, String> {
+        cookiejar::jar(&self.spider_id)
+            .map(|x| x.cookies())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|x| (x.name, x.value))
+            .collect()
+    }
+
+    // 清除爬虫的cookie，下一次请求时重新登录
+    pub fn logout(&self) {
+        if let Some(x) = cookiejar::jar(&self.spider_id) {
+            x.clear();
+        }
+    }
+
+    fn generation(&self) -> std::sync::MutexGuard<'_, u64> {
+        self.generation.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // 使用保存的登录信息登录
+    pub async fn login(&self) -> Result<()> {
+        let generation = *self.generation();
+        self.relogin(generation).await
+    }
+
+    // 登录状态在generation之后已经更新时不再重复登录
+    async fn relogin(&self, generation: u64) -> Result<()> {
+        let _lock = self.login.lock().await;
+        if *self.generation() != generation {
+            return Ok(());
+        }
+
+        let credentials = self
+            .credentials
+            .as_ref()
+            .ok_or_else(|| anyhow!("没有书源的登录信息; source={}", self.source.url()))?;
+        let login = self
+            .source
+            .login_url()
+            .ok_or_else(|| anyhow!("书源没有登录地址; source={}", self.source.url()))?;
+
+        let req = match script(login) {
+            Some(js) => {
+                // 登录js由书源提供，与验证脚本一样限制执行时间与内存，不阻塞异步任务
+                let (js, source, credentials) =
+                    (String::from(js), self.source.clone(), credentials.clone());
+                tokio::task::spawn_blocking(move || login_js(&js, &source, &credentials)).await??
+            }
+            None => LoginRequest {
+                url: String::from(login),
+                form: credentials.clone(),
+                ..LoginRequest::default()
+            },
+        };
+        let url = join(self.source.url(), &req.url)?;
+
+        let method = Method::from_bytes(req.method.to_uppercase().as_bytes())
+            .with_context(|| format!("登录请求方法错误: {}", req.method))?;
+        let mut builder = CLIENT.request(method.clone(), url.clone());
+        for (k, v) in &req.headers {
+            builder = builder.header(HeaderName::from_bytes(k.as_bytes())?, v.as_str());
+        }
+        builder = if method == Method::GET {
+            builder.query(&req.form)
+        } else {
+            builder.form(&req.form)
+        };
+
+        // 与爬虫的其他请求一样使用代理池，重定向中设置的cookie都保存在爬虫的cookie中
+        // 没有启用cookie的爬虫也需要保存登录状态
+        let jar = cookiejar::jar_or_register(&self.spider_id);
+        let before = jar.cookies();
+        let (status, _) = httputils::send(&self.spider_id, builder).await?;
+        if status >= 400 {
+            bail!("登录失败; source={}, status={status}", self.source.url());
+        }
+        if jar.cookies() == before {
+            warn!(
+                "登录响应中没有cookie; source={}, url={url}",
+                self.source.url()
+            );
+        }
+
+        *self.generation() += 1;
+        info!(
+            "书源登录成功; spider_id={}, source={}",
+            self.spider_id,
+            self.source.url()
+        );
+
+        Ok(())
+    }
+
+    // 携带登录状态获取页面，页面显示未登录时重新登录后再获取一次
+    pub async fn get(&self, url: &str) -> crate::spider::Result<String> {
+        self.fetch(url)
+            .await
+            .map(|(_, text)| text)
+            .map_err(|e| CrawlError::fetch(None, e))
+    }
+
+    // 与get相同，返回状态码，供httputils为需要登录的爬虫获取页面
+    pub(crate) async fn fetch(&self, url: &str) -> Result<(u16, String), FetchError> {
+        for retry in [false, true] {
+            let generation = *self.generation();
+            let (status, body) = httputils::fetch_page(&self.spider_id, url).await?;
+
+            let logged_in = self
+                .check(url, status, &body)
+                .await
+                .map_err(|e| FetchError::Login(e.to_string()))?;
+            if logged_in {
+                return Ok((status, body));
+            }
+            if retry {
+                break;
+            }
+
+            warn!(
+                "页面显示未登录，重新登录; spider_id={}, url={url}",
+                self.spider_id
+            );
+            if let Err(e) = self.relogin(generation).await {
+                warn!("重新登录失败; spider_id={}, err={e:#}", self.spider_id);
+                return Err(FetchError::Login(format!("{e:#}")));
+            }
+        }
+
+        Err(FetchError::Login(format!(
+            "重新登录后仍然未登录; url={url}"
+        )))
+    }
+
+    // 检查页面是否处于登录状态，书源设置了登录检测js时由js判断
+    pub async fn check(&self, url: &str, status: u16, body: &str) -> crate::spider::Result<bool> {
+        let js = match self.source.login_checkjs() {
+            Some(js) => String::from(script(js).unwrap_or(js)),
+            None => return Ok(status != 401 && status != 403),
+        };
+
+        let (url, body) = (String::from(url), String::from(body));
+        tokio::task::spawn_blocking(move || check_js(&js, &url, status, &body))
+            .await
+            .map_err(|e| CrawlError::SpiderInnerFailed(e.into()))?
+            .map_err(CrawlError::SpiderInnerFailed)
+    }
+}
+
+// 为需要登录的爬虫注册会话，之后通过httputils获取的页面都携带登录状态
+pub fn register(session: Arc<Session>) {
+    SESSIONS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(String::from(session.spider_id()), session);
+}
+
+pub fn unregister(spider_id: &str) -> Option<Arc<Session>> {
+    SESSIONS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(spider_id)
+}
+
+pub fn session(spider_id: &str) -> Option<Arc<Session>> {
+    SESSIONS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(spider_id)
+        .cloned()
+}
+
+// 以 @js: 开头或者包含在 <js></js> 中的内容为js
+fn script(x: &str) -> Option<&str> {
+    let x = x.trim();
+    if let Some(js) = x.strip_prefix("@js:") {
+        return Some(js);
+    }
+
+    x.strip_prefix("<js>").and_then(|x| x.strip_suffix("</js>"))
+}
+
+fn join(base: &str, link: &str) -> Result<Url> {
+    Url::parse(base)
+        .and_then(|x| x.join(link))
+        .with_context(|| format!("地址格式错误; base={base}, link={link}"))
+}
+
+// 脚本会阻塞线程，需要在 spawn_blocking 中调用
+fn login_js(js: &str, source: &BookSource, credentials: &Credentials) -> Result<LoginRequest> {
+    let ctx = Sandbox::new(SCRIPT_TIMEOUT, SCRIPT_MEMORY)?;
+    ctx.exec(&format!(
+        "var source = {}; var credentials = {};",
+        serde_json::json!({"url": source.url(), "name": source.name()}),
+        serde_json::to_string(credentials)?
+    ))?;
+    ctx.exec(js).context("执行登录js失败")?;
+
+    let x = ctx
+        .eval_string("JSON.stringify(login())")
+        .context("登录js需要定义返回请求的 login() 函数")?;
+    serde_json::from_str(&x).context("登录js返回的请求格式错误")
+}
+
+// js中可以使用 result、url、status 变量，返回值为true时表示已登录
+// 脚本会阻塞线程，需要在 spawn_blocking 中调用
+fn check_js(js: &str, url: &str, status: u16, body: &str) -> Result<bool> {
+    let ctx = Sandbox::new(SCRIPT_TIMEOUT, SCRIPT_MEMORY)?;
+    ctx.exec(&format!(
+        "var result = {}; var url = {}; var status = {status};",
+        serde_json::to_string(body)?,
+        serde_json::to_string(url)?
+    ))?;
+
+    // 可以是表达式，也可以是使用return返回结果的语句
+    ctx.eval_string(&format!("!!({js})"))
+        .or_else(|_| ctx.eval_string(&format!("!!(function() {{ {js} \n}})()")))
+        .map(|x| x == "true")
+        .context("执行登录检测js失败")
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionConfig {
+    // 加密保存书源登录信息的文件，密码使用 SPIDER_CREDENTIAL_SECRET 环境变量
+    pub credentials: PathBuf,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            credentials: PathBuf::from("credentials.enc"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LoginConfig {
+    // 书源地址，登录信息以此区分，相对的登录地址基于此地址
+    pub source: String,
+    // 登录页面地址，或者以 @js: 开头返回登录请求的js
+    pub url: String,
+    // 判断页面是否已登录的js，未设置时按状态码判断
+    #[serde(default)]
+    pub check: Option<String>,
+}
+
+impl LoginConfig {
+    pub fn to_source(&self) -> Result<BookSource> {
+        let url = Url::parse(&self.source).with_context(|| format!("source={}", self.source))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            bail!("source 只支持 http 与 https: {}", self.source);
+        }
+        if self.url.trim().is_empty() {
+            bail!("url 不能为空");
+        }
+
+        let x = BookSource::new(&self.source, &self.source).with_login(
+            Some(self.url.clone()),
+            None,
+            self.check.clone(),
+        );
+        Ok(x)
+    }
+}
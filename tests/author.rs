@@ -0,0 +1,170 @@
+use std::sync::Arc;
+
+use tokio::test;
+
+use spider_novel::common::snowid::IdGen;
+use spider_novel::keeper::data::author;
+use spider_novel::keeper::Keeper;
+use spider_novel::spider::{CrawlError, Novel, NovelID, Result, Support};
+
+use common::{novel, FakeSpider};
+
+mod common;
+
+// 只支持按作者搜索的爬虫
+fn author_spider(id: &str, search: fn() -> Result<Vec<Novel>>) -> FakeSpider {
+    FakeSpider::new()
+        .with_id(id)
+        .support(Support {
+            get_sort: false,
+            get_novel_from_sort: false,
+            search_novel: false,
+            search_author: true,
+        })
+        .search_author(move |_| async move { search() })
+}
+
+#[test]
+async fn author_rows() {
+    let db = common::migrated_db("author-rows").await;
+    let ids = IdGen::sequential(1);
+
+    // 同名作者只保留一条记录
+    let id = author::add_or_recover(&db, &ids, "辰东", false)
+        .await
+        .unwrap();
+    assert_eq!(
+        author::add_or_recover(&db, &ids, " 辰东 ", false)
+            .await
+            .unwrap(),
+        id
+    );
+    let x = author::author_by_name(&db, "辰东").await.unwrap().unwrap();
+    assert_eq!(x.id, id);
+    assert!(x.searched_at.is_none());
+
+    // 搜索后更新搜索时间
+    assert_eq!(
+        author::add_or_recover(&db, &ids, "辰东", true)
+            .await
+            .unwrap(),
+        id
+    );
+    let x = author::author_by_name(&db, " 辰东").await.unwrap().unwrap();
+    assert!(x.searched_at.is_some());
+    let searched_at = x.searched_at;
+    assert_eq!(
+        author::add_or_recover(&db, &ids, "辰东", false)
+            .await
+            .unwrap(),
+        id
+    );
+    let x = author::author_by_name(&db, "辰东").await.unwrap().unwrap();
+    assert_eq!(x.searched_at, searched_at);
+
+    let other = author::add_or_recover(&db, &ids, "天蚕土豆", false)
+        .await
+        .unwrap();
+    assert_ne!(other, id);
+    assert!(author::author_by_name(&db, "唐家三少")
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_author_rows() {
+    let db = Arc::new(common::migrated_db("author-concurrent").await);
+    let ids = IdGen::sequential(1);
+
+    // 多个任务同时遇到同一个新作者时都返回同一条记录
+    let tasks: Vec<_> = (0..8)
+        .map(|x| {
+            let db = db.clone();
+            let ids = ids.clone();
+            tokio::spawn(async move { author::add_or_recover(&db, &ids, "辰东", x % 2 == 0).await })
+        })
+        .collect();
+    let mut v = Vec::new();
+    for x in tasks {
+        v.push(x.await.unwrap().unwrap());
+    }
+    v.dedup();
+    assert_eq!(v.len(), 1);
+    assert_eq!(
+        author::author_by_name(&db, "辰东")
+            .await
+            .unwrap()
+            .unwrap()
+            .id,
+        v[0]
+    );
+}
+
+#[test]
+async fn search_author_index() {
+    let db = Arc::new(common::temp_db("author-index").await);
+    let mut keeper = Keeper::new(db.clone());
+    author_spider("author-a", || {
+        Ok(vec![
+            novel(1, "遮天", "辰东"),
+            novel(2, "完美世界", "辰东"),
+            // 网站的模糊搜索返回了其他作者的小说
+            novel(3, "斗破苍穹", "天蚕土豆"),
+        ])
+    })
+    .add_to(&mut keeper)
+    .unwrap();
+    author_spider("author-b", || Ok(vec![novel(7, " 遮天 ", " 辰东 ")]))
+        .add_to(&mut keeper)
+        .unwrap();
+    author_spider("author-c", || Err(CrawlError::ParseFailed))
+        .add_to(&mut keeper)
+        .unwrap();
+    keeper.migrate().await.unwrap();
+
+    // 搜索前索引中没有该作者
+    let r = keeper.author_novels("辰东").await.unwrap();
+    assert!(r.author_id.is_none());
+    assert!(r.novels.is_empty());
+
+    let r = keeper.search_author(" 辰东 ").await.unwrap();
+    let author_id = r.author_id.expect("没有记录作者");
+    let failed: Vec<_> = r.failed.iter().map(|x| x.0.as_str()).collect();
+    assert_eq!(failed, vec!["author-c"]);
+
+    // 不同爬虫中的同一本小说合并为一条，其他作者的小说不写入索引
+    let mut names: Vec<_> = r.novels.iter().map(|x| x.name.as_str()).collect();
+    names.sort();
+    assert_eq!(names, vec!["完美世界", "遮天"]);
+    let x = r.novels.iter().find(|x| x.name == "遮天").unwrap();
+    let mut sources = x.sources.clone();
+    sources.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        sources,
+        vec![
+            (String::from("author-a"), NovelID::from(1)),
+            (String::from("author-b"), NovelID::from(7)),
+        ]
+    );
+    assert!(author::author_by_name(&db, "天蚕土豆")
+        .await
+        .unwrap()
+        .is_none());
+
+    // 再次搜索时更新已有的记录
+    let first = author::author_by_name(&db, "辰东").await.unwrap().unwrap();
+    let r = keeper.search_author("辰东").await.unwrap();
+    assert_eq!(r.author_id, Some(author_id));
+    assert_eq!(r.novels.len(), 2);
+    let sources: usize = r.novels.iter().map(|x| x.sources.len()).sum();
+    assert_eq!(sources, 3);
+    let x = author::author_by_name(&db, "辰东").await.unwrap().unwrap();
+    assert!(x.searched_at >= first.searched_at);
+
+    // 只读取索引，不访问爬虫
+    let r = keeper.author_novels("辰东").await.unwrap();
+    assert_eq!(r.author_id, Some(author_id));
+    assert_eq!(r.novels.len(), 2);
+    assert!(r.failed.is_empty());
+}
@@ -0,0 +1,205 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use hyper::{Body, Request, Response};
+use tokio::test;
+
+use spider_novel::ddxsku::profile::Profile;
+use spider_novel::ddxsku::DDSpider;
+use spider_novel::keeper::canary::{Canary, CanaryPage};
+use spider_novel::keeper::data::job;
+use spider_novel::keeper::Keeper;
+use spider_novel::spider::{CrawlError, Probe, ProbeKind, Spider, Support};
+
+use common::{temp_db, FakeSpider};
+
+mod common;
+
+fn probe(items: usize, fields: &[(&str, usize)]) -> Probe {
+    Probe {
+        items,
+        fields: fields.iter().map(|(k, v)| (String::from(*k), *v)).collect(),
+        bytes: 0,
+    }
+}
+
+fn sort_page(url: &str) -> CanaryPage {
+    CanaryPage {
+        kind: ProbeKind::Sort,
+        url: String::from(url),
+        min_items: 10,
+        min_bytes: 0,
+        fields: BTreeMap::from([(String::from("name"), 1.0), (String::from("author"), 0.8)]),
+    }
+}
+
+#[test]
+async fn check_thresholds() {
+    let page = sort_page("http://localhost/sort");
+
+    assert!(page
+        .check(&probe(20, &[("name", 20), ("author", 17)]))
+        .is_empty());
+    // 行存在但解析不出小说，页面结构已经变化
+    assert_eq!(
+        page.check(&probe(20, &[("name", 0), ("author", 0)])).len(),
+        2
+    );
+    assert_eq!(
+        page.check(&probe(20, &[("name", 20), ("author", 10)]))
+            .len(),
+        1
+    );
+    // 列表选择器失效
+    assert_eq!(page.check(&probe(0, &[])).len(), 3);
+
+    let chapter = CanaryPage {
+        kind: ProbeKind::Chapter,
+        url: String::from("http://localhost/chapter"),
+        min_items: 1,
+        min_bytes: 100,
+        fields: BTreeMap::from([(String::from("content"), 1.0)]),
+    };
+    let mut x = probe(1, &[("content", 1)]);
+    x.bytes = 50;
+    assert_eq!(chapter.check(&x).len(), 1);
+    x.bytes = 500;
+    assert!(chapter.check(&x).is_empty());
+}
+
+const SUPPORT: Support = Support {
+    get_sort: false,
+    get_novel_from_sort: true,
+    search_novel: false,
+    search_author: false,
+};
+
+#[test]
+async fn degrade_pauses_jobs() {
+    let db = Arc::new(temp_db("canary-degrade").await);
+    let state = Arc::new(Mutex::new(Some(probe(20, &[("name", 20), ("author", 20)]))));
+
+    let mut keeper = Keeper::new(db.clone());
+    let current = state.clone();
+    FakeSpider::new()
+        .with_id("canary")
+        .support(SUPPORT)
+        .probe(move || {
+            current
+                .lock()
+                .unwrap()
+                .clone()
+                .ok_or(CrawlError::Unsupported)
+        })
+        .add_to(&mut keeper)
+        .unwrap();
+    keeper.migrate().await.unwrap();
+
+    let canary = Canary {
+        interval: chrono::Duration::hours(1),
+        pages: vec![sort_page("http://localhost/sort")],
+    };
+
+    assert!(keeper
+        .run_canary("canary", &canary)
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(keeper.degraded("canary").is_none());
+
+    *state.lock().unwrap() = Some(probe(20, &[("name", 0), ("author", 0)]));
+    assert_eq!(keeper.run_canary("canary", &canary).await.unwrap().len(), 2);
+    assert!(keeper.degraded("canary").is_some());
+
+    // 异常爬虫的任务不会被领取
+    job::add(&db, keeper.id_gen(), "novels_by_sort", "canary", 1)
+        .await
+        .unwrap();
+    job::add(&db, keeper.id_gen(), "novels_by_sort", "other", 1)
+        .await
+        .unwrap();
+    let paused = vec![String::from("canary")];
+    let x = job::acquire_except(&db, &paused).await.unwrap().unwrap();
+    assert_eq!(x.spider_id, "other");
+    assert!(job::acquire_except(&db, &paused).await.unwrap().is_none());
+
+    // 检查通过后恢复
+    *state.lock().unwrap() = Some(probe(20, &[("name", 20), ("author", 18)]));
+    assert!(keeper
+        .run_canary("canary", &canary)
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(keeper.degraded("canary").is_none());
+    let x = job::acquire_except(&db, &[]).await.unwrap().unwrap();
+    assert_eq!(x.spider_id, "canary");
+}
+
+#[test]
+async fn unsupported_probe_skips_canary() {
+    let db = Arc::new(temp_db("canary-unsupported").await);
+    let mut keeper = Keeper::new(db);
+    // 与没有实现页面检查的爬虫相同
+    FakeSpider::new()
+        .with_id("canary")
+        .support(SUPPORT)
+        .add_to(&mut keeper)
+        .unwrap();
+    keeper.migrate().await.unwrap();
+
+    let canary = Canary {
+        interval: chrono::Duration::seconds(1),
+        pages: vec![sort_page("http://localhost/sort")],
+    };
+
+    // 配置错误，不标记爬虫为异常
+    let e = keeper.run_canary("canary", &canary).await.unwrap_err();
+    assert!(matches!(e.downcast_ref(), Some(CrawlError::Unsupported)));
+    assert!(keeper.degraded("canary").is_none());
+
+    // 没有可执行的检查时直接返回，不会一直重试
+    tokio::time::timeout(
+        Duration::from_secs(5),
+        keeper.run_canaries(vec![(String::from("canary"), canary)]),
+    )
+    .await
+    .expect("不支持的健康检查没有被跳过");
+    assert!(keeper.degraded("canary").is_none());
+}
+
+#[test]
+async fn ddxsku_probe() {
+    let addr = common::serve(|req: Request<Body>| async move {
+        let body = match req.uri().path() {
+            "/chapter.html" => {
+                "<html><body><dl><dd id=\"contents\">第一章 正文内容</dd></dl></body></html>"
+            }
+            // 改版后的列表页，没有原来的表格
+            _ => "<html><body><ul class=\"list\"><li>遮天</li></ul></body></html>",
+        };
+        Response::new(Body::from(body))
+    });
+
+    let db = Arc::new(temp_db("canary-ddxsku").await);
+    let profile = Profile {
+        base_url: format!("http://{addr}"),
+        ..Profile::default()
+    };
+    let spider = DDSpider::with_profile(db, profile).unwrap();
+
+    let x = spider
+        .probe(ProbeKind::Chapter, &format!("http://{addr}/chapter.html"))
+        .await
+        .unwrap();
+    assert_eq!(x.items, 1);
+    assert_eq!(x.fields.get("content"), Some(&1));
+    assert_eq!(x.bytes, "第一章 正文内容".len());
+
+    let x = spider
+        .probe(ProbeKind::Sort, &format!("http://{addr}/sort.html"))
+        .await
+        .unwrap();
+    assert_eq!(x.items, 0);
+    assert!(!sort_page("http://localhost/sort").check(&x).is_empty());
+}
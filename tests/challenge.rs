@@ -0,0 +1,215 @@
+use std::time::Duration;
+
+use hyper::header::COOKIE;
+use hyper::{Body, Request, Response, StatusCode};
+use reqwest::Url;
+use tokio::test;
+
+use spider_novel::common::challenge::{self, Challenge};
+use spider_novel::common::cookiejar;
+use spider_novel::common::httputils::{fetch, FetchError};
+use spider_novel::spider::CrawlError;
+
+mod common;
+
+// 与常见的5秒盾类似，脚本计算出cookie后刷新页面
+const SCRIPT_PAGE: &str = r#"<html><head><title>Just a moment...</title></head><body>
+<script>
+var a = [111, 107].map(function(c) { return String.fromCharCode(c); }).join("");
+document.cookie = "pass=" + a + "; path=/";
+location.reload();
+</script>
+</body></html>"#;
+
+const REFRESH_PAGE: &str = r#"<html><head>
+<meta http-equiv="Refresh" content="0; url=/chapter?from=refresh">
+<script>document.cookie = "pass=" + "o" + "k";</script>
+</head><body></body></html>"#;
+
+// 验证脚本不会结束
+const LOOP_PAGE: &str = r#"<html><body>
+<script>document.cookie = "pass=ok"; while (true) {} location.reload();</script>
+</body></html>"#;
+
+const CAPTCHA_PAGE: &str = r#"<html><body>
+<form id="challenge-form"><img src="/captcha.png"><input name="code"></form>
+</body></html>"#;
+
+#[test]
+async fn detect() {
+    assert_eq!(
+        challenge::detect(200, "<html><body>第一章 正文</body></html>"),
+        None
+    );
+
+    match challenge::detect(503, SCRIPT_PAGE) {
+        Some(Challenge::Script { script }) => assert!(script.contains("document.cookie")),
+        x => panic!("{x:?}"),
+    }
+    match challenge::detect(200, REFRESH_PAGE) {
+        Some(Challenge::MetaRefresh { url, delay, script }) => {
+            assert_eq!(url.as_deref(), Some("/chapter?from=refresh"));
+            assert_eq!(delay, 0);
+            assert!(script.is_some());
+        }
+        x => panic!("{x:?}"),
+    }
+    assert!(matches!(
+        challenge::detect(503, CAPTCHA_PAGE),
+        Some(Challenge::Blocked { .. })
+    ));
+
+    // 外部脚本与大页面不作为验证
+    let x = r#"<html><script src="/app.js"></script><body>目录</body></html>"#;
+    assert_eq!(challenge::detect(200, x), None);
+    let x = format!(
+        "<html><script>document.cookie = 'a=1';</script><body>{}</body></html>",
+        "正文".repeat(20000)
+    );
+    assert_eq!(challenge::detect(200, &x), None);
+
+    // 正常状态码的页面只有没有内容、由脚本设置cookie后刷新时才是验证页面
+    let x = format!(
+        "<html><script>document.cookie = 'a=1'; location.reload();</script><body>{}</body></html>",
+        "第一章 正文内容".repeat(50)
+    );
+    assert_eq!(challenge::detect(200, &x), None);
+    let x = "<html><script>document.cookie = 'visited=1';</script><body>目录</body></html>";
+    assert_eq!(challenge::detect(200, x), None);
+    let x = r#"<html><meta http-equiv="refresh" content="0; url=/new"><body></body></html>"#;
+    assert_eq!(challenge::detect(200, x), None);
+    let x = "<html><body>书中的人机验证与captcha只是文字</body></html>";
+    assert_eq!(challenge::detect(200, x), None);
+    assert_eq!(challenge::detect(200, CAPTCHA_PAGE), None);
+
+    // 验证状态码的页面
+    assert!(matches!(
+        challenge::detect(403, CAPTCHA_PAGE),
+        Some(Challenge::Blocked { .. })
+    ));
+    assert!(matches!(
+        challenge::detect(200, SCRIPT_PAGE),
+        Some(Challenge::Script { .. })
+    ));
+}
+
+#[test]
+async fn solve() {
+    let url = Url::parse("http://www.a.com/book/1.html").unwrap();
+
+    let x = challenge::detect(503, SCRIPT_PAGE).unwrap();
+    let solution = challenge::solve(&x, &url).unwrap();
+    assert_eq!(solution.cookies, vec!["pass=ok; path=/"]);
+    assert_eq!(solution.redirect, None);
+
+    let x = challenge::detect(200, REFRESH_PAGE).unwrap();
+    let solution = challenge::solve(&x, &url).unwrap();
+    assert_eq!(solution.cookies, vec!["pass=ok"]);
+    assert_eq!(
+        solution.redirect.as_deref(),
+        Some("http://www.a.com/chapter?from=refresh")
+    );
+
+    // 脚本修改页面地址
+    let x = Challenge::Script {
+        script: String::from("document.cookie = 'k=v'; location.href = '/next';"),
+    };
+    let solution = challenge::solve(&x, &url).unwrap();
+    assert_eq!(solution.redirect.as_deref(), Some("http://www.a.com/next"));
+
+    let x = challenge::detect(503, CAPTCHA_PAGE).unwrap();
+    assert!(challenge::solve(&x, &url).is_err());
+    let x = Challenge::Script {
+        script: String::from("document.cookie = undefinedFunction();"),
+    };
+    assert!(challenge::solve(&x, &url).is_err());
+}
+
+#[test]
+async fn solve_limits() {
+    let url = Url::parse("http://www.a.com/book/1.html").unwrap();
+
+    // 不会结束的脚本在超时后中断
+    let start = std::time::Instant::now();
+    let x = challenge::detect(503, LOOP_PAGE).unwrap();
+    let e = challenge::solve(&x, &url).unwrap_err();
+    assert!(format!("{e:#}").contains("超时"), "{e:#}");
+    assert!(start.elapsed() < Duration::from_secs(10));
+
+    // 超出内存上限
+    let x = Challenge::Script {
+        script: String::from(
+            "document.cookie = 'a=1'; var a = []; while (true) { a.push(new Array(1024).join('x')); }",
+        ),
+    };
+    assert!(challenge::solve(&x, &url).is_err());
+    assert!(start.elapsed() < Duration::from_secs(20));
+}
+
+#[test]
+async fn fetch_with_challenge() {
+    let addr = common::serve(|req: Request<Body>| async move {
+        let passed = req
+            .headers()
+            .get(COOKIE)
+            .and_then(|x| x.to_str().ok())
+            .is_some_and(|x| x.contains("pass=ok"));
+        let (status, body) = match req.uri().path() {
+            "/captcha" => (StatusCode::SERVICE_UNAVAILABLE, CAPTCHA_PAGE),
+            // 验证脚本设置的cookie总是不被接受
+            "/broken" => (StatusCode::SERVICE_UNAVAILABLE, SCRIPT_PAGE),
+            "/loop" => (StatusCode::SERVICE_UNAVAILABLE, LOOP_PAGE),
+            "/refresh" if !passed => (StatusCode::OK, REFRESH_PAGE),
+            _ if !passed => (StatusCode::SERVICE_UNAVAILABLE, SCRIPT_PAGE),
+            _ => (StatusCode::OK, "<html><body>第一章 正文内容</body></html>"),
+        };
+        let mut resp = Response::new(Body::from(body));
+        *resp.status_mut() = status;
+        resp
+    });
+
+    // 解决验证后保存cookie并重试
+    let id = "challenge-script";
+    let x = fetch(id, &format!("http://{addr}/chapter")).await.unwrap();
+    assert!(x.contains("正文内容"));
+    let jar = cookiejar::jar(id).unwrap();
+    assert_eq!(jar.cookies()[0].name, "pass");
+    // 之后的请求直接携带cookie
+    let x = fetch(id, &format!("http://{addr}/other")).await.unwrap();
+    assert!(x.contains("正文内容"));
+
+    let x = fetch("challenge-refresh", &format!("http://{addr}/refresh"))
+        .await
+        .unwrap();
+    assert!(x.contains("正文内容"));
+
+    // 无法解决的验证
+    let e = fetch("challenge-captcha", &format!("http://{addr}/captcha"))
+        .await
+        .unwrap_err();
+    assert!(matches!(e, FetchError::Challenge(_)));
+    assert_eq!(e.kind(), "challenge");
+    assert!(matches!(
+        CrawlError::fetch(None, e),
+        CrawlError::Challenge(_)
+    ));
+
+    let e = tokio::time::timeout(
+        Duration::from_secs(10),
+        fetch("challenge-broken", &format!("http://{addr}/broken")),
+    )
+    .await
+    .unwrap()
+    .unwrap_err();
+    assert!(matches!(e, FetchError::Challenge(_)));
+
+    // 验证脚本超出执行时间
+    let e = tokio::time::timeout(
+        Duration::from_secs(10),
+        fetch("challenge-loop", &format!("http://{addr}/loop")),
+    )
+    .await
+    .unwrap()
+    .unwrap_err();
+    assert!(matches!(e, FetchError::Challenge(_)));
+}
@@ -0,0 +1,281 @@
+// 各集成测试共用的数据库、HTTP服务与爬虫替身，每个测试文件只使用其中一部分
+#![allow(dead_code)]
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server};
+use sea_orm::{Database, DbConn};
+use tokio::sync::mpsc::{channel, Receiver};
+
+use spider_novel::common::migrate::Migrator;
+use spider_novel::common::shutdown::{CancellationToken, TaskTracker};
+use spider_novel::keeper;
+use spider_novel::keeper::Keeper;
+use spider_novel::spider::{
+    Capabilities, CrawlError, Novel, NovelID, Position, Probe, ProbeKind, Result, Section, Sort,
+    SortID, Spider, SpiderInfo, SpiderMetadata, Support,
+};
+
+// 临时目录中的sqlite数据库，每次打开前删除上次测试留下的文件
+pub async fn temp_db(name: &str) -> DbConn {
+    let path = std::env::temp_dir().join(format!("spider-novel-{name}.db"));
+    let _ = std::fs::remove_file(&path);
+
+    Database::connect(format!("sqlite://{}?mode=rwc", path.display()))
+        .await
+        .expect("连接数据库失败")
+}
+
+// 已经创建了keeper全部表的临时数据库
+pub async fn migrated_db(name: &str) -> DbConn {
+    let db = temp_db(name).await;
+
+    let mut migrator = Migrator::new();
+    migrator.register(keeper::KEEPER_MIGRATION_OWNER, keeper::migrations());
+    migrator.up(&db).await.expect("创建表失败");
+
+    db
+}
+
+// 在随机端口启动本地HTTP服务，每个请求交给handler处理
+pub fn serve<F, R>(handler: F) -> SocketAddr
+where
+    F: Fn(Request<Body>) -> R + Clone + Send + Sync + 'static,
+    R: Future<Output = Response<Body>> + Send + 'static,
+{
+    let make = make_service_fn(move |_| {
+        let handler = handler.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                let resp = handler(req);
+                async move { Ok::<_, Infallible>(resp.await) }
+            }))
+        }
+    });
+    let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make);
+    let addr = server.local_addr();
+    tokio::spawn(server);
+
+    addr
+}
+
+pub fn novel(id: i64, name: &str, author: &str) -> Novel {
+    Novel {
+        id: id.into(),
+        name: String::from(name),
+        cover: None,
+        author: String::from(author),
+        intro: None,
+        last_updated_at: None,
+        last_updated_section_name: None,
+        state: None,
+        word_count: None,
+        tags: vec![],
+        category: None,
+        aliases: vec![],
+        source_url: None,
+    }
+}
+
+type Novels = Arc<dyn Fn(Position) -> Result<Vec<Novel>> + Send + Sync>;
+type Sections = Arc<dyn Fn(&NovelID) -> Result<Vec<Section>> + Send + Sync>;
+type Search = Arc<dyn Fn(String) -> BoxFuture<'static, Result<Vec<Novel>>> + Send + Sync>;
+type Probing = Arc<dyn Fn() -> Result<Probe> + Send + Sync>;
+
+// 可配置的爬虫替身，未设置的操作返回 Unsupported
+// 按类型注册时id为 fake，需要其他id或能力时通过 add_to 注册
+pub struct FakeSpider {
+    id: String,
+    support: Support,
+    sorts: Vec<Sort>,
+    capabilities: Option<Capabilities>,
+    novels: Option<Novels>,
+    sections: Option<Sections>,
+    search: Option<Search>,
+    search_author: Option<Search>,
+    probe: Option<Probing>,
+    tracker: Option<TaskTracker>,
+}
+
+impl FakeSpider {
+    pub fn new() -> Self {
+        Self {
+            id: String::from(Self::id()),
+            support: Self::SUPPORTED,
+            sorts: vec![],
+            capabilities: None,
+            novels: None,
+            sections: None,
+            search: None,
+            search_author: None,
+            probe: None,
+            tracker: None,
+        }
+    }
+
+    pub fn with_id(mut self, id: &str) -> Self {
+        self.id = String::from(id);
+        self
+    }
+
+    // 注册时声明的能力
+    pub fn support(mut self, support: Support) -> Self {
+        self.support = support;
+        self
+    }
+
+    pub fn sorts(mut self, sorts: Vec<Sort>) -> Self {
+        self.sorts = sorts;
+        self
+    }
+
+    // 运行时确定的能力
+    pub fn capabilities(mut self, x: Capabilities) -> Self {
+        self.capabilities = Some(x);
+        self
+    }
+
+    pub fn novels<F>(mut self, f: F) -> Self
+    where
+        F: Fn(Position) -> Result<Vec<Novel>> + Send + Sync + 'static,
+    {
+        self.novels = Some(Arc::new(f));
+        self
+    }
+
+    pub fn sections<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&NovelID) -> Result<Vec<Section>> + Send + Sync + 'static,
+    {
+        self.sections = Some(Arc::new(f));
+        self
+    }
+
+    pub fn search<F, R>(mut self, f: F) -> Self
+    where
+        F: Fn(String) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<Vec<Novel>>> + Send + 'static,
+    {
+        self.search = Some(Arc::new(move |x| Box::pin(f(x))));
+        self
+    }
+
+    pub fn search_author<F, R>(mut self, f: F) -> Self
+    where
+        F: Fn(String) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<Vec<Novel>>> + Send + 'static,
+    {
+        self.search_author = Some(Arc::new(move |x| Box::pin(f(x))));
+        self
+    }
+
+    pub fn probe<F>(mut self, f: F) -> Self
+    where
+        F: Fn() -> Result<Probe> + Send + Sync + 'static,
+    {
+        self.probe = Some(Arc::new(f));
+        self
+    }
+
+    // 关闭时等待tracker上的任务结束
+    pub fn tracker(mut self, tracker: TaskTracker) -> Self {
+        self.tracker = Some(tracker);
+        self
+    }
+
+    pub fn spider_info(&self) -> SpiderInfo {
+        SpiderInfo {
+            id: self.id.clone(),
+            capabilities: self.support.into(),
+            ..Self::info()
+        }
+    }
+
+    pub fn add_to(self, keeper: &mut Keeper) -> anyhow::Result<()> {
+        keeper.add_spider_info(self.spider_info(), self)
+    }
+}
+
+impl SpiderMetadata for FakeSpider {
+    const SUPPORTED: Support = Support {
+        get_sort: false,
+        get_novel_from_sort: false,
+        search_novel: false,
+        search_author: false,
+    };
+
+    fn id() -> &'static str {
+        "fake"
+    }
+}
+
+#[async_trait]
+impl Spider for FakeSpider {
+    fn sorts(&self) -> &Vec<Sort> {
+        &self.sorts
+    }
+
+    async fn novels_by_sort_id(
+        &self,
+        _: &SortID,
+        pos: Position,
+        _: &CancellationToken,
+    ) -> Result<Receiver<Result<Novel>>> {
+        let f = self.novels.as_ref().ok_or(CrawlError::Unsupported)?;
+        Ok(send_all(f(pos)?).await)
+    }
+
+    async fn sections_by_novel_id(
+        &self,
+        id: &NovelID,
+        _: Position,
+        _: &CancellationToken,
+    ) -> Result<Receiver<Result<Section>>> {
+        let f = self.sections.as_ref().ok_or(CrawlError::Unsupported)?;
+        Ok(send_all(f(id)?).await)
+    }
+
+    async fn fetch_novel(&self, _: &NovelID) -> Result<Novel> {
+        Err(CrawlError::Unsupported)
+    }
+
+    async fn search(&self, name: &str) -> Result<Vec<Novel>> {
+        let f = self.search.as_ref().ok_or(CrawlError::Unsupported)?;
+        f(String::from(name)).await
+    }
+
+    async fn search_author(&self, author: &str) -> Result<Vec<Novel>> {
+        let f = self.search_author.as_ref().ok_or(CrawlError::Unsupported)?;
+        f(String::from(author)).await
+    }
+
+    async fn wait(&self) {
+        if let Some(x) = &self.tracker {
+            x.wait().await
+        }
+    }
+
+    async fn probe(&self, _: ProbeKind, _: &str) -> Result<Probe> {
+        let f = self.probe.as_ref().ok_or(CrawlError::Unsupported)?;
+        f()
+    }
+
+    fn capabilities(&self) -> Option<Capabilities> {
+        self.capabilities
+    }
+}
+
+async fn send_all<T: Send + 'static>(v: Vec<T>) -> Receiver<Result<T>> {
+    let (tx, rx) = channel(v.len().max(1));
+    for x in v {
+        let _ = tx.send(Ok(x)).await;
+    }
+
+    rx
+}
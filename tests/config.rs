@@ -0,0 +1,259 @@
+use std::time::Duration;
+
+use chrono::Duration as ChronoDuration;
+use tokio::test;
+
+use spider_novel::common::shutdown::CancellationToken;
+use spider_novel::config::{self, parse_duration, Config, SpiderConfig};
+use spider_novel::ddxsku::Site;
+
+const TOML: &str = r#"
+[database]
+url = "sqlite://data.db"
+
+[snowflake]
+machine_id = 2
+node_id = 3
+
+[policy]
+job_poll_interval = "1s"
+search_timeout = "30s"
+
+[spiders.ddxsku]
+base_url = "https://www.ddxsku.net"
+concurrency = 8
+
+[spiders.ddxsku.selectors]
+novel_content = "div#content"
+"#;
+
+const YAML: &str = r#"
+database:
+  url: "sqlite://data.db"
+snowflake:
+  machine_id: 2
+  node_id: 3
+policy:
+  job_poll_interval: "1s"
+  search_timeout: "30s"
+spiders:
+  ddxsku:
+    base_url: "https://www.ddxsku.net"
+    concurrency: 8
+    selectors:
+      novel_content: "div#content"
+"#;
+
+#[test]
+async fn parse_toml_and_yaml() {
+    let a = Config::parse(TOML, "toml").unwrap();
+    let b = Config::parse(YAML, "yaml").unwrap();
+    assert_eq!(a, b);
+    a.validate().unwrap();
+
+    let policy = a.policy.to_policy().unwrap();
+    assert_eq!(policy.job_poll_interval, ChronoDuration::seconds(1));
+    assert_eq!(policy.search_timeout, ChronoDuration::seconds(30));
+    // 未设置的项使用默认值
+    assert_eq!(policy.sort_update_interval, ChronoDuration::days(7));
+    assert_eq!(policy.job_max_attempts, 5);
+
+    let x = a.spider("ddxsku");
+    assert!(x.enabled);
+    assert_eq!(x.concurrency, Some(8));
+    // 未配置的爬虫默认启用
+    assert!(a.spider("other").enabled);
+}
+
+#[test]
+async fn reject_invalid() {
+    // 未知字段
+    assert!(Config::parse("[policy]\nfoo = 1", "toml").is_err());
+
+    let invalid = [
+        "[snowflake]\nmachine_id = 32",
+        "[policy]\njob_poll_interval = \"5x\"",
+        "[policy]\njob_poll_interval = \"99999999999999999d\"",
+        "[policy]\njob_max_attempts = 0",
+        "[spiders.ddxsku]\nbase_url = \"ftp://example.com\"",
+        "[spiders.ddxsku]\nconcurrency = 0",
+        "[database]\nurl = \"data.db\"",
+        "[notify]\ndebounce = \"10x\"",
+        "[notify.webhook]\nurl = \"ftp://example.com\"",
+        "[notify.smtp]\naddr = \"localhost\"\nfrom = \"a@localhost\"",
+        "[notify.smtp]\naddr = \"localhost:25\"\nfrom = \"a@localhost\"\nusername = \"a\"",
+        "[metrics]\nlisten = \"localhost\"",
+        "[content]\nstore = \"redis\"",
+        "[content]\nstore = \"s3\"",
+        "[content]\nstore = \"s3\"\n[content.s3]\nendpoint = \"ftp://example.com\"\nbucket = \"a\"\naccess_key = \"a\"\nsecret_key = \"b\"",
+    ];
+    for x in invalid {
+        let config = Config::parse(x, "toml").unwrap();
+        assert!(config.validate().is_err(), "{x}");
+    }
+}
+
+#[test]
+async fn snowflake_nodes() {
+    let x = Config::parse(TOML, "toml").unwrap();
+    assert_eq!((x.id_gen().unwrap().generate() >> 12) & 0x3FF, 2 << 5 | 3);
+
+    // 没有设置节点时需要声明单节点
+    let x = Config::parse("[snowflake]\nnode_id = 3", "toml").unwrap();
+    x.validate().unwrap();
+    assert!(x.id_gen().is_err());
+    let x = Config::parse("[snowflake]\nsingle_node = true", "toml").unwrap();
+    assert_eq!((x.id_gen().unwrap().generate() >> 12) & 0x3FF, 1 << 5 | 1);
+}
+
+#[test]
+async fn durations() {
+    assert_eq!(parse_duration("30").unwrap(), ChronoDuration::seconds(30));
+    assert_eq!(
+        parse_duration("500ms").unwrap(),
+        ChronoDuration::milliseconds(500)
+    );
+    assert_eq!(parse_duration("5m").unwrap(), ChronoDuration::minutes(5));
+    assert_eq!(parse_duration("2h").unwrap(), ChronoDuration::hours(2));
+    assert_eq!(parse_duration("7d").unwrap(), ChronoDuration::days(7));
+    assert!(parse_duration("d").is_err());
+    assert!(parse_duration("1w").is_err());
+    assert!(parse_duration("99999999999999999d").is_err());
+    assert!(parse_duration("9223372036854775807s").is_err());
+    assert!(parse_duration("99999999999999999999").is_err());
+}
+
+#[test]
+async fn site_from_config() {
+    let config = Config::parse(TOML, "toml").unwrap().spider("ddxsku");
+    let site = Site::from_config(&config).unwrap();
+
+    assert_eq!(site.profile.base_url, "https://www.ddxsku.net");
+    // 搜索地址跟随网站地址
+    assert_eq!(site.profile.search_url(), "https://www.ddxsku.net/search/");
+    assert_eq!(site.profile.concurrency, 8);
+    assert_eq!(site.profile.selectors.novel_content, "div#content");
+    // 修改域名不影响爬虫id
+    assert_eq!(site.profile.id, "ddxsku");
+
+    // 数据库中的旧链接替换为新域名
+    assert_eq!(
+        site.rebase("http://www.ddxsku.com/xiaoshuo/1.html"),
+        "https://www.ddxsku.net/xiaoshuo/1.html"
+    );
+
+    let mut config = SpiderConfig::default();
+    config
+        .selectors
+        .insert(String::from("no_such_selector"), String::from("a"));
+    assert!(Site::from_config(&config).is_err());
+}
+
+#[test]
+async fn hot_reload() {
+    let path = std::env::temp_dir().join("spider-novel-hot-reload.toml");
+    std::fs::write(&path, "[policy]\njob_max_attempts = 3\n").unwrap();
+
+    let initial = Config::load(&path).unwrap();
+    let cancel = CancellationToken::new();
+    let mut rx = config::watch(
+        path.clone(),
+        initial,
+        Duration::from_millis(50),
+        cancel.clone(),
+    );
+    assert_eq!(rx.borrow().policy.job_max_attempts, 3);
+
+    // 保证修改时间不同
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+
+    // 校验失败的配置不会生效
+    std::fs::write(&path, "[policy]\njob_max_attempts = 0\n").unwrap();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(rx.borrow().policy.job_max_attempts, 3);
+
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    std::fs::write(&path, "[policy]\njob_max_attempts = 7\n").unwrap();
+    tokio::time::timeout(Duration::from_secs(5), rx.changed())
+        .await
+        .expect("没有重新加载配置")
+        .unwrap();
+    assert_eq!(rx.borrow().policy.job_max_attempts, 7);
+
+    cancel.cancel();
+}
+
+#[test]
+async fn discover_spider_files() {
+    let dir = std::env::temp_dir().join("spider-novel-discovery");
+    let _ = std::fs::remove_dir_all(&dir);
+    let (rules, plugins) = (dir.join("rules"), dir.join("plugins"));
+    std::fs::create_dir_all(&rules).unwrap();
+    std::fs::create_dir_all(&plugins).unwrap();
+    std::fs::write(
+        rules.join("mirror.toml"),
+        "id = \"mirror\"\nbase_url = \"https://www.mirror.test\"\n",
+    )
+    .unwrap();
+    std::fs::write(
+        rules.join("other.yaml"),
+        "id: \"other\"\nbase_url: \"https://www.other.test\"\n",
+    )
+    .unwrap();
+    // 扩展名不匹配的文件被忽略
+    std::fs::write(rules.join("README.md"), "").unwrap();
+    std::fs::write(plugins.join("partner.wasm"), "").unwrap();
+
+    let mut config = Config::parse(
+        &format!(
+            "[discovery]\nrules = {:?}\nplugins = {:?}\n\n[spiders.mirror]\nenabled = false\nscore = 3\n",
+            rules.display().to_string(),
+            plugins.display().to_string()
+        ),
+        "toml",
+    )
+    .unwrap();
+    config.discover_spiders().unwrap();
+    config.validate().unwrap();
+
+    let jieqi: Vec<_> = config
+        .jieqi_spiders()
+        .into_iter()
+        .map(|(name, x)| (name, x.profile))
+        .collect();
+    assert_eq!(
+        jieqi,
+        vec![
+            (String::from("mirror"), Some(rules.join("mirror.toml"))),
+            (String::from("other"), Some(rules.join("other.yaml"))),
+            (String::from("ddxsku"), None),
+        ]
+    );
+    // 与同名的配置合并
+    let mirror = config.spider("mirror");
+    assert!(!mirror.enabled);
+    assert_eq!(mirror.score, Some(3));
+
+    let wasm = config.wasm_spiders();
+    assert_eq!(wasm.len(), 1);
+    assert_eq!(wasm[0].0, "partner");
+    assert_eq!(wasm[0].1.plugin, Some(plugins.join("partner.wasm")));
+
+    // 同一名称不能同时是站点描述与插件
+    std::fs::write(plugins.join("mirror.wasm"), "").unwrap();
+    let mut config = Config::parse(
+        &format!(
+            "[discovery]\nrules = {:?}\nplugins = {:?}\n",
+            rules.display().to_string(),
+            plugins.display().to_string()
+        ),
+        "toml",
+    )
+    .unwrap();
+    assert!(config.discover_spiders().is_err());
+
+    // 目录不存在时报错
+    let mut config = Config::default();
+    config.discovery.rules = Some(dir.join("missing"));
+    assert!(config.discover_spiders().is_err());
+}
@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+
+use hyper::{Body, Method, Request, Response, StatusCode};
+use sha2::{Digest, Sha256};
+use tokio::test;
+
+use spider_novel::common::snowid::IdGen;
+use spider_novel::config::Config;
+use spider_novel::keeper::content::fs::FsStore;
+use spider_novel::keeper::content::inline::InlineStore;
+use spider_novel::keeper::content::s3::{signature, S3Config, S3Store};
+use spider_novel::keeper::content::{ContentStore, StoredSections};
+use spider_novel::keeper::data::{job, section};
+use spider_novel::keeper::queue::{JobKind, SectionSink};
+use spider_novel::keeper::{Keeper, Policy};
+use spider_novel::spider::Section;
+
+use common::FakeSpider;
+
+mod common;
+
+const TEXT: &str = "第一章 山边小村\n\n二愣子睁大着双眼，直直望着茅草和烂泥糊成的黑屋顶。";
+
+async fn roundtrip(store: &dyn ContentStore) {
+    store.put("sections/1", TEXT.as_bytes()).await.unwrap();
+    assert_eq!(
+        store.get("sections/1").await.unwrap().as_deref(),
+        Some(TEXT.as_bytes())
+    );
+
+    store.put("sections/1", b"new").await.unwrap();
+    assert_eq!(
+        store.get("sections/1").await.unwrap().as_deref(),
+        Some(&b"new"[..])
+    );
+
+    store.delete("sections/1").await.unwrap();
+    assert!(store.get("sections/1").await.unwrap().is_none());
+}
+
+#[test]
+async fn fs_store() {
+    let dir = env::temp_dir().join("spider-novel-content");
+    let _ = tokio::fs::remove_dir_all(&dir).await;
+    let store = FsStore::new(&dir);
+
+    roundtrip(&store).await;
+    assert!(store.put("../escape", b"x").await.is_err());
+}
+
+#[test]
+async fn inline_store_and_sections() {
+    let db = Arc::new(common::migrated_db("content").await);
+
+    let store = Arc::new(InlineStore::new(db.clone()));
+    roundtrip(store.as_ref()).await;
+
+    let sections = StoredSections::new(db.clone(), IdGen::sequential(1), store);
+    let data = Section {
+        seq: 0,
+        novel_id: 42.into(),
+        name: String::from("第一章 山边小村"),
+        update_at: None,
+        text: String::from(TEXT),
+    };
+    sections.save("ddxsku", &data).await.unwrap();
+    sections.save("ddxsku", &data).await.unwrap();
+
+    let x = section::find(&db, "ddxsku", 42, 0)
+        .await
+        .unwrap()
+        .expect("章节没有保存");
+    assert_eq!(x.size as usize, TEXT.len());
+    assert_eq!(sections.text(&x).await.unwrap().as_deref(), Some(TEXT));
+}
+
+// AWS文档中的GET Object签名示例
+#[test]
+async fn s3_signature_vector() {
+    let x = signature(
+        "wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY",
+        "us-east-1",
+        "GET",
+        "/test.txt",
+        &[
+            ("host", "examplebucket.s3.amazonaws.com"),
+            ("range", "bytes=0-9"),
+            (
+                "x-amz-content-sha256",
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            ),
+            ("x-amz-date", "20130524T000000Z"),
+        ],
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+    );
+    assert_eq!(
+        x,
+        "f0e8bdb87c964420e857bd35b5d6ed310bd44f0170aba48dd91039c6036bdb41"
+    );
+}
+
+// 按请求中的签名头重新计算签名，与Authorization中的签名一致时返回请求体
+async fn verify(req: Request<Body>) -> Option<(Method, String, Vec<u8>)> {
+    let auth = req
+        .headers()
+        .get("authorization")?
+        .to_str()
+        .ok()?
+        .to_string();
+    let auth = auth.strip_prefix("AWS4-HMAC-SHA256 ")?;
+    let mut fields = HashMap::new();
+    for x in auth.split(", ") {
+        let (k, v) = x.split_once('=')?;
+        fields.insert(k, v);
+    }
+    let credential: Vec<_> = fields.get("Credential")?.split('/').collect();
+    if credential.len() != 5
+        || credential[0] != "minio"
+        || credential[2..] != ["us-east-1", "s3", "aws4_request"]
+    {
+        return None;
+    }
+
+    let mut headers = Vec::new();
+    for name in fields.get("SignedHeaders")?.split(';') {
+        let value = req.headers().get(name)?.to_str().ok()?.to_string();
+        headers.push((name.to_string(), value));
+    }
+    let headers: Vec<_> = headers
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .collect();
+    if !headers.iter().any(|(k, _)| *k == "host") {
+        return None;
+    }
+
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let payload_hash = req
+        .headers()
+        .get("x-amz-content-sha256")?
+        .to_str()
+        .ok()?
+        .to_string();
+    let expect = signature(
+        "minio123",
+        "us-east-1",
+        method.as_str(),
+        &path,
+        &headers,
+        &payload_hash,
+    );
+    if fields.get("Signature")? != &expect {
+        return None;
+    }
+
+    // 签名中的内容哈希必须与请求体一致
+    let body = hyper::body::to_bytes(req.into_body()).await.ok()?;
+    if hex::encode(Sha256::digest(&body)) != payload_hash {
+        return None;
+    }
+
+    Some((method, path, body.to_vec()))
+}
+
+// 本地的S3替身，只保存对象，校验请求的签名
+async fn s3_stub() -> String {
+    let objects: Arc<Mutex<HashMap<String, Vec<u8>>>> = Arc::default();
+    let addr = common::serve(move |req: Request<Body>| {
+        let objects = objects.clone();
+        async move {
+            let (method, path, body) = match verify(req).await {
+                Some(x) => x,
+                None => {
+                    return Response::builder()
+                        .status(StatusCode::FORBIDDEN)
+                        .body(Body::empty())
+                        .unwrap()
+                }
+            };
+
+            match method {
+                Method::PUT => {
+                    objects.lock().unwrap().insert(path, body);
+                    Response::new(Body::empty())
+                }
+                Method::GET => match objects.lock().unwrap().get(&path) {
+                    Some(x) => Response::new(Body::from(x.clone())),
+                    None => Response::builder()
+                        .status(StatusCode::NOT_FOUND)
+                        .body(Body::empty())
+                        .unwrap(),
+                },
+                Method::DELETE => {
+                    objects.lock().unwrap().remove(&path);
+                    Response::builder()
+                        .status(StatusCode::NO_CONTENT)
+                        .body(Body::empty())
+                        .unwrap()
+                }
+                _ => Response::builder()
+                    .status(StatusCode::METHOD_NOT_ALLOWED)
+                    .body(Body::empty())
+                    .unwrap(),
+            }
+        }
+    });
+
+    format!("http://{addr}")
+}
+
+#[test]
+async fn s3_store() {
+    // 设置了 TEST_S3_ENDPOINT 时使用真实的S3兼容服务，例如本地的minio
+    let endpoint = match env::var("TEST_S3_ENDPOINT") {
+        Ok(x) => x,
+        Err(_) => s3_stub().await,
+    };
+
+    let store = S3Store::new(S3Config {
+        endpoint,
+        bucket: env::var("TEST_S3_BUCKET").unwrap_or(String::from("spider")),
+        region: String::from("us-east-1"),
+        access_key: env::var("TEST_S3_ACCESS_KEY").unwrap_or(String::from("minio")),
+        secret_key: env::var("TEST_S3_SECRET_KEY").unwrap_or(String::from("minio123")),
+        prefix: String::from("test/"),
+    });
+
+    roundtrip(&store).await;
+}
+
+#[test]
+async fn s3_bad_signature() {
+    let endpoint = s3_stub().await;
+    let store = S3Store::new(S3Config {
+        endpoint,
+        bucket: String::from("spider"),
+        region: String::from("us-east-1"),
+        access_key: String::from("minio"),
+        secret_key: String::from("wrong"),
+        prefix: String::from("test/"),
+    });
+
+    assert!(store.put("sections/1", b"x").await.is_err());
+}
+
+// 与 spider run 一样按 [content] 配置内容存储，章节任务的内容写入配置的目录
+#[test]
+async fn sections_job_with_config() {
+    let dir = env::temp_dir().join("spider-novel-content-job");
+    let _ = tokio::fs::remove_dir_all(&dir).await;
+    let config = Config::parse(
+        &format!(
+            "[content]\nstore = \"fs\"\ndir = {:?}",
+            dir.display().to_string()
+        ),
+        "toml",
+    )
+    .unwrap();
+    config.validate().unwrap();
+
+    let db = Arc::new(common::migrated_db("content-job").await);
+    let mut keeper = Keeper::with_policy(
+        db.clone(),
+        Policy {
+            job_poll_interval: chrono::Duration::milliseconds(50),
+            ..Policy::default()
+        },
+    );
+    keeper.set_id_gen(IdGen::sequential(1));
+    config.content.install(&mut keeper, db.clone()).unwrap();
+    // 每本小说只有两章
+    FakeSpider::new()
+        .with_id("sections")
+        .sections(|id| {
+            let v = (1..=2)
+                .map(|seq| Section {
+                    seq,
+                    novel_id: *id,
+                    name: format!("第{seq}章"),
+                    update_at: None,
+                    text: format!("{TEXT}{seq}"),
+                })
+                .collect();
+            Ok(v)
+        })
+        .add_to(&mut keeper)
+        .unwrap();
+    let id = keeper
+        .enqueue(JobKind::SectionsByNovel, "sections", 42)
+        .await
+        .unwrap();
+
+    let keeper = Arc::new(keeper);
+    let runner = keeper.clone();
+    let jobs = tokio::spawn(async move { runner.run_jobs(1).await });
+    tokio::time::timeout(std::time::Duration::from_secs(10), async {
+        loop {
+            let x = job::list(&db, Some(job::STATE_FINISHED)).await.unwrap();
+            if x.iter().any(|x| x.id == id) {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("任务没有结束");
+    keeper
+        .shutdown(std::time::Duration::from_secs(5))
+        .await
+        .unwrap();
+    jobs.await.unwrap().unwrap();
+
+    let x = section::find(&db, "sections", 42, 2)
+        .await
+        .unwrap()
+        .expect("章节没有保存");
+    let text = format!("{TEXT}2");
+    assert_eq!(
+        keeper.section_text(x.id).await.unwrap().as_deref(),
+        Some(text.as_str())
+    );
+    let file = dir.join(format!("{}.txt", x.content_key));
+    assert_eq!(tokio::fs::read_to_string(file).await.unwrap(), text);
+}
@@ -0,0 +1,198 @@
+use std::sync::Arc;
+
+use hyper::header::{COOKIE, LOCATION, SET_COOKIE};
+use hyper::{Body, Request, Response};
+use reqwest::header::HeaderValue;
+use reqwest::Url;
+use tokio::test;
+
+use spider_novel::common::cookiejar::{self, Cookie, CookieJar};
+use spider_novel::common::httputils::fetch;
+use spider_novel::keeper::Keeper;
+
+use common::temp_db;
+
+mod common;
+
+fn url(x: &str) -> Url {
+    Url::parse(x).unwrap()
+}
+
+#[test]
+async fn parse_and_match() {
+    let page = url("http://www.a.com/book/1/index.html");
+
+    let x = Cookie::parse("sid=abc; Path=/; HttpOnly", &page).unwrap();
+    assert_eq!(x.domain, "www.a.com");
+    assert!(x.host_only);
+    assert_eq!(x.path, "/");
+    assert!(x.expires.is_none());
+
+    // 默认路径为页面所在目录
+    let x = Cookie::parse("page=2", &page).unwrap();
+    assert_eq!(x.path, "/book/1");
+
+    let x = Cookie::parse("uid=7; Domain=.a.com; Max-Age=3600; Secure", &page).unwrap();
+    assert_eq!(x.domain, "a.com");
+    assert!(!x.host_only);
+    assert!(x.secure);
+    assert!(x.expires.is_some());
+
+    // 超出时间范围的Max-Age视为没有过期时间，不会溢出
+    let x = Cookie::parse("big=1; Max-Age=9223372036854775807", &page).unwrap();
+    assert!(x.expires.is_none());
+    assert!(!x.is_expired());
+    let x = Cookie::parse("big=1; Max-Age=9223372036854775", &page).unwrap();
+    assert!(x.expires.is_none());
+    let x = Cookie::parse("old=1; Max-Age=-9223372036854775808", &page).unwrap();
+    assert!(x.is_expired());
+    let x = Cookie::parse("old=1; Max-Age=0", &page).unwrap();
+    assert!(x.is_expired());
+
+    // 不能为其他网站设置cookie
+    assert!(Cookie::parse("x=1; Domain=b.com", &page).is_none());
+    assert!(Cookie::parse("invalid", &page).is_none());
+
+    let jar = CookieJar::new();
+    jar.store(
+        &page,
+        [
+            &HeaderValue::from_static("sid=abc; Path=/"),
+            &HeaderValue::from_static("page=2"),
+            &HeaderValue::from_static("uid=7; Domain=a.com; Secure"),
+        ],
+    );
+    assert!(jar.take_dirty());
+    assert!(!jar.take_dirty());
+
+    assert_eq!(
+        jar.header(&url("http://www.a.com/book/1/2.html")).unwrap(),
+        "page=2; sid=abc"
+    );
+    assert_eq!(jar.header(&url("http://www.a.com/")).unwrap(), "sid=abc");
+    // 没有设置Path的cookie只发送给页面所在目录
+    assert_eq!(
+        jar.header(&url("https://m.a.com/book/1/3.html")).unwrap(),
+        "uid=7"
+    );
+    assert!(jar.header(&url("https://m.a.com/")).is_none());
+    assert!(jar.header(&url("http://m.a.com/book/1/3.html")).is_none());
+    assert!(jar.header(&url("http://www.b.com/")).is_none());
+
+    // 过期的cookie删除原来的值
+    jar.store(
+        &page,
+        [&HeaderValue::from_static("sid=; Path=/; Max-Age=0")],
+    );
+    assert_eq!(jar.header(&url("http://www.a.com/")), None);
+    assert_eq!(jar.cookies().len(), 2);
+}
+
+#[test]
+async fn shared_and_persisted() {
+    // 首页设置cookie之后才能访问章节
+    let addr = common::serve(|req: Request<Body>| async move {
+        let cookie = req
+            .headers()
+            .get(COOKIE)
+            .and_then(|x| x.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        match req.uri().path() {
+            "/" => Response::builder()
+                .header(SET_COOKIE, "visit=1; Path=/")
+                .body(Body::from("首页"))
+                .unwrap(),
+            _ if cookie.contains("visit=1") => Response::new(Body::from("正文内容")),
+            _ => Response::new(Body::from("请从首页进入")),
+        }
+    });
+
+    let db = Arc::new(temp_db("cookies").await);
+    let keeper = Keeper::new(db.clone());
+    keeper.migrate().await.unwrap();
+
+    let id = "cookie-site";
+    let chapter = format!("http://{addr}/chapter/1.html");
+    // 未启用cookie时不保存
+    fetch(id, &format!("http://{addr}/")).await.unwrap();
+    assert_eq!(fetch(id, &chapter).await.unwrap(), "请从首页进入");
+
+    let jar = keeper.enable_cookies(id).await.unwrap();
+    assert!(jar.cookies().is_empty());
+    fetch(id, &format!("http://{addr}/")).await.unwrap();
+
+    // 并发的请求使用同一个jar
+    let tasks: Vec<_> = (0..8)
+        .map(|_| {
+            let chapter = chapter.clone();
+            tokio::spawn(async move { fetch(id, &chapter).await })
+        })
+        .collect();
+    for x in tasks {
+        assert_eq!(x.await.unwrap().unwrap(), "正文内容");
+    }
+
+    assert!(keeper.save_cookies(id).await.unwrap());
+    // 没有修改时不重复保存
+    assert!(!keeper.save_cookies(id).await.unwrap());
+
+    // 重启后恢复保存的cookie
+    cookiejar::unregister(id);
+    let keeper = Keeper::new(db.clone());
+    let x = keeper.cookies().await.unwrap();
+    assert_eq!(x.len(), 1);
+    assert_eq!(x[0].0, id);
+    assert_eq!(x[0].1[0].name, "visit");
+
+    keeper.enable_cookies(id).await.unwrap();
+    assert_eq!(fetch(id, &chapter).await.unwrap(), "正文内容");
+
+    assert!(keeper.clear_cookies(id).await.unwrap());
+    assert!(!keeper.clear_cookies(id).await.unwrap());
+    assert!(keeper.cookies().await.unwrap()[0].1.is_empty());
+    assert_eq!(fetch(id, &chapter).await.unwrap(), "请从首页进入");
+    cookiejar::unregister(id);
+    assert!(keeper.cookies().await.unwrap().is_empty());
+}
+
+#[test]
+async fn redirect_cookies() {
+    // 每次跳转都设置cookie，最后的页面需要全部的cookie
+    let addr = common::serve(|req: Request<Body>| async move {
+        let cookie = req
+            .headers()
+            .get(COOKIE)
+            .and_then(|x| x.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let redirect = |to: &str, cookie: &str| {
+            Response::builder()
+                .status(302)
+                .header(LOCATION, to)
+                .header(SET_COOKIE, cookie)
+                .body(Body::empty())
+                .unwrap()
+        };
+        match req.uri().path() {
+            "/login" => redirect("/check", "sid=1; Path=/"),
+            "/check" if cookie.contains("sid=1") => redirect("/home", "step=2; Path=/"),
+            "/home" if cookie.contains("sid=1") && cookie.contains("step=2") => {
+                Response::new(Body::from("欢迎"))
+            }
+            _ => Response::new(Body::from("未登录")),
+        }
+    });
+
+    let id = "cookie-redirect";
+    cookiejar::register(id, Arc::new(CookieJar::new()));
+    assert_eq!(
+        fetch(id, &format!("http://{addr}/login")).await.unwrap(),
+        "欢迎"
+    );
+
+    let jar = cookiejar::unregister(id).unwrap();
+    let mut names: Vec<String> = jar.cookies().into_iter().map(|x| x.name).collect();
+    names.sort();
+    assert_eq!(names, vec!["sid", "step"]);
+}
@@ -0,0 +1,124 @@
+use std::sync::Arc;
+
+use hyper::{Body, Request, Response};
+use tokio::test;
+
+use spider_novel::common::snowid::IdGen;
+use spider_novel::keeper::cover::{sniff_image, CoverStore};
+use spider_novel::keeper::data::novel;
+use spider_novel::keeper::Keeper;
+use spider_novel::spider::Novel;
+
+mod common;
+
+const PNG: &[u8] = &[
+    0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52,
+];
+
+#[test]
+async fn sniff() {
+    assert_eq!(sniff_image(PNG), Some(("image/png", "png")));
+    assert_eq!(
+        sniff_image(&[0xFF, 0xD8, 0xFF, 0xE0]),
+        Some(("image/jpeg", "jpg"))
+    );
+    assert_eq!(sniff_image(b"<html></html>"), None);
+}
+
+#[test]
+async fn store_dedupe() {
+    let dir = std::env::temp_dir().join("spider-novel-covers");
+    let _ = tokio::fs::remove_dir_all(&dir).await;
+    let store = CoverStore::new(&dir);
+
+    let a = store.store(PNG).await.unwrap();
+    let b = store.store(PNG).await.unwrap();
+    assert_eq!(a.hash, b.hash);
+    assert_eq!(a.path, b.path);
+    assert!(a.path.starts_with(dir.join(&a.hash[..2])));
+    assert_eq!(tokio::fs::read(&a.path).await.unwrap(), PNG);
+
+    assert!(store.store(b"<html></html>").await.is_err());
+    assert!(CoverStore::new(&dir).max_size(4).store(PNG).await.is_err());
+}
+
+// 按路径返回不同内容的封面服务
+fn serve_covers() -> String {
+    let addr = common::serve(|req: Request<Body>| async move {
+        let resp = Response::builder();
+        match req.uri().path() {
+            "/cover.png" => resp
+                .header("content-type", "image/png")
+                .body(Body::from(PNG)),
+            "/page.png" => resp
+                .header("content-type", "text/html")
+                .body(Body::from("<html></html>")),
+            _ => resp
+                .header("content-type", "image/png")
+                .body(Body::from(vec![0x89; 1024])),
+        }
+        .unwrap()
+    });
+
+    format!("http://{addr}")
+}
+
+#[test]
+async fn fetch_checks() {
+    let base = serve_covers();
+    let dir = std::env::temp_dir().join("spider-novel-covers-fetch");
+    let _ = tokio::fs::remove_dir_all(&dir).await;
+    let store = CoverStore::new(&dir).max_size(512);
+
+    let x = store
+        .fetch("cover", &format!("{base}/cover.png"))
+        .await
+        .unwrap();
+    assert_eq!(x.mime, "image/png");
+
+    // 响应头声明的类型不是图片
+    let e = store.fetch("cover", &format!("{base}/page.png")).await;
+    assert!(e.unwrap_err().to_string().contains("不是图片"));
+
+    // 超出大小限制
+    let e = store.fetch("cover", &format!("{base}/large.png")).await;
+    assert!(e.unwrap_err().to_string().contains("过大"));
+}
+
+#[test]
+async fn keeper_store_cover() {
+    let base = serve_covers();
+    let db = Arc::new(common::migrated_db("cover-keeper").await);
+    let dir = std::env::temp_dir().join("spider-novel-covers-keeper");
+    let _ = tokio::fs::remove_dir_all(&dir).await;
+
+    let data = Novel {
+        id: 1.into(),
+        name: String::from("遮天"),
+        cover: Some(format!("{base}/cover.png")),
+        author: String::from("辰东"),
+        intro: None,
+        last_updated_at: None,
+        last_updated_section_name: None,
+        state: None,
+        word_count: None,
+        tags: vec![],
+        category: None,
+        aliases: vec![],
+        source_url: None,
+    };
+    let id = novel::add_or_recover(&db, &IdGen::sequential(1), "cover", &data)
+        .await
+        .unwrap();
+
+    let mut keeper = Keeper::new(db.clone());
+    keeper.set_cover_store(CoverStore::new(&dir));
+    let path = keeper.store_cover("cover", id).await.unwrap().unwrap();
+    assert!(path.starts_with(&dir));
+
+    let x = novel::novel_by_id(&db, id).await.unwrap().unwrap();
+    assert_eq!(
+        x.cover_path.as_deref(),
+        Some(path.to_string_lossy().as_ref())
+    );
+}
@@ -1,8 +1,7 @@
 use sea_orm::Database;
-use tokio::sync::oneshot;
-use tokio::sync::oneshot::error::RecvError;
 use tokio::test;
 
+use spider_novel::common::snowid;
 use spider_novel::keeper::data::sort::{add_or_recover, list};
 use spider_novel::spider::{Sort, SortID};
 
@@ -14,16 +13,16 @@ async fn add() {
 
     let data: Vec<Sort> = vec![
         Sort {
-            id: SortID::from(20 as i64),
+            id: SortID::from(20),
             name: "xx2x3a".to_string(),
         },
         Sort {
-            id: SortID::from(10 as i64),
+            id: SortID::from(10),
             name: "xx123".to_string(),
         },
     ];
 
-    add_or_recover(&db, "https://aa.com", &data)
+    add_or_recover(&db, &snowid::global(), "https://aa.com", &data)
         .await
         .expect("插入数据失败");
 }
@@ -0,0 +1,147 @@
+use std::sync::Arc;
+
+use hyper::{Body, Request, Response};
+use sea_orm::Database;
+use tokio::test;
+
+use spider_novel::common::events::{self, Event};
+use spider_novel::common::migrate::Migrator;
+use spider_novel::common::shutdown::CancellationToken;
+use spider_novel::ddxsku::parse_word_count;
+use spider_novel::ddxsku::profile::Profile;
+use spider_novel::ddxsku::{DDSpider, SortEntity};
+use spider_novel::spider::{Position, ProbeKind, Spider, SpiderMetadata};
+
+mod common;
+
+// 小说信息表格，标签与值交替出现
+const DETAIL: &str = r#"<html><body><dl id="content"><dd></dd><dd><div><table><tbody>
+<tr><td>类别：</td><td>仙侠修真</td></tr>
+<tr><td>全文长度：</td><td>12.5万字</td></tr>
+<tr><td>标签：</td><td>热血 / 升级、 系统</td></tr>
+<tr><td>别名：</td><td>遮天传</td></tr>
+</tbody></table></div></dd></dl></body></html>"#;
+
+#[test]
+async fn word_count() {
+    assert_eq!(parse_word_count("123456字"), Some(123456));
+    assert_eq!(parse_word_count(" 1,234,567 字 "), Some(1234567));
+    assert_eq!(parse_word_count("12.5万字"), Some(125000));
+    assert_eq!(parse_word_count("300万"), Some(3000000));
+    assert_eq!(parse_word_count("未知"), None);
+    assert_eq!(parse_word_count("NaN字"), None);
+    assert_eq!(parse_word_count("inf万字"), None);
+    assert_eq!(parse_word_count("-1字"), None);
+}
+
+#[test]
+async fn tags_and_aliases() {
+    let addr = common::serve(|req: Request<Body>| async move {
+        let body = match req.uri().path() {
+            "/detail.html" => DETAIL,
+            _ => "<html><body></body></html>",
+        };
+        Response::new(Body::from(body))
+    });
+
+    let db = Arc::new(Database::connect("sqlite::memory:").await.unwrap());
+    let profile = Profile {
+        base_url: format!("http://{addr}"),
+        ..Profile::default()
+    };
+    let spider = DDSpider::with_profile(db, profile).unwrap();
+
+    let x = spider
+        .probe(ProbeKind::Novel, &format!("http://{addr}/detail.html"))
+        .await
+        .unwrap();
+    assert_eq!(x.fields.get("category"), Some(&1));
+    assert_eq!(x.fields.get("word_count"), Some(&1));
+    assert_eq!(x.fields.get("tags"), Some(&1));
+    assert_eq!(x.fields.get("aliases"), Some(&1));
+
+    // 页面中没有标签与别名时不使用分类代替
+    let x = spider
+        .probe(ProbeKind::Novel, &format!("http://{addr}/empty.html"))
+        .await
+        .unwrap();
+    assert_eq!(x.fields.get("tags"), Some(&0));
+    assert_eq!(x.fields.get("aliases"), Some(&0));
+}
+
+// 第二行的详情页无法连接，第三行没有章节链接
+const SORT_PAGE: &str = r#"<html><body><table><tbody>
+<tr><th>书名</th><th>最新章节</th><th>作者</th><th>字数</th><th>更新</th><th>状态</th></tr>
+<tr><td><a href="{base}/book/1.html">遮天</a></td><td><a href="{base}/book/1/">第一章</a></td><td>辰东</td><td>1</td><td>22-07-08</td><td>连载中</td></tr>
+<tr><td><a href="http://127.0.0.1:1/book/2.html">完美世界</a></td><td><a href="{base}/book/2/">第一章</a></td><td>辰东</td><td>1</td><td>22-07-08</td><td>连载中</td></tr>
+<tr><td><a href="{base}/book/3.html">圣墟</a></td><td>第一章</td><td>辰东</td><td>1</td><td>22-07-08</td><td>连载中</td></tr>
+</tbody></table><a class="last">1</a></body></html>"#;
+
+#[test]
+async fn skip_broken_rows() {
+    let addr = common::serve(|req: Request<Body>| async move {
+        let body = match req.uri().path() {
+            "/book/1.html" => String::from(DETAIL),
+            _ => {
+                let host = req.headers()["host"].to_str().unwrap();
+                SORT_PAGE.replace("{base}", &format!("http://{host}"))
+            }
+        };
+        Response::new(Body::from(body))
+    });
+
+    let db = common::temp_db("ddxsku-broken-rows").await;
+    let mut migrator = Migrator::new();
+    migrator.register(DDSpider::id(), DDSpider::migrations());
+    migrator.up(&db).await.unwrap();
+
+    let profile = Profile {
+        id: String::from("broken-rows"),
+        base_url: format!("http://{addr}"),
+        ..Profile::default()
+    };
+    let mut spider = DDSpider::with_profile(Arc::new(db), profile).unwrap();
+    spider
+        .set_sort(&vec![SortEntity {
+            name: String::from("全部"),
+            link: format!("http://{addr}/sort/{{{{page}}}}.html"),
+        }])
+        .await
+        .unwrap();
+    let sort = spider.sorts()[0].id;
+
+    let mut events = events::subscribe();
+    let mut rx = spider
+        .novels_by_sort_id(&sort, Position::First, &CancellationToken::new())
+        .await
+        .unwrap();
+
+    // 没有章节链接的行被跳过，详情页获取失败的行只保存列表页中的信息
+    let mut novels = Vec::new();
+    while let Some(x) = rx.recv().await {
+        novels.push(x.unwrap());
+    }
+    novels.sort_by(|a, b| a.name.cmp(&b.name));
+    let names: Vec<_> = novels.iter().map(|x| x.name.as_str()).collect();
+    assert_eq!(names, vec!["完美世界", "遮天"]);
+    assert_eq!(novels[0].category, None);
+    assert_eq!(novels[1].category.as_deref(), Some("仙侠修真"));
+
+    // 两行都记为解析失败
+    let mut failed = Vec::new();
+    while let Ok(x) = events.try_recv() {
+        if let Event::CrawlFailed(x) = x {
+            if x.spider_id == "broken-rows" && x.kind == "parse_failed" {
+                failed.push(x.url.unwrap());
+            }
+        }
+    }
+    failed.sort();
+    assert_eq!(
+        failed,
+        vec![
+            String::from("http://127.0.0.1:1/book/2.html"),
+            format!("http://{addr}/book/3.html"),
+        ]
+    );
+}
@@ -0,0 +1,169 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result as AnyResult;
+use async_trait::async_trait;
+use hyper::{Body, Request, Response};
+use tokio::sync::broadcast;
+use tokio::test;
+
+use spider_novel::common::events;
+use spider_novel::common::events::Event;
+use spider_novel::common::httputils::fetch;
+use spider_novel::keeper::queue::{JobKind, SectionSink};
+use spider_novel::keeper::{Keeper, Policy};
+use spider_novel::spider::Section;
+
+use common::FakeSpider;
+
+mod common;
+
+struct DiscardSink;
+
+#[async_trait]
+impl SectionSink for DiscardSink {
+    async fn save(&self, _: &str, _: &Section) -> AnyResult<()> {
+        Ok(())
+    }
+}
+
+// 收集指定爬虫的事件，直到满足条件或超时
+async fn collect(
+    rx: &mut broadcast::Receiver<Event>,
+    spider_id: &str,
+    until: impl Fn(&Event) -> bool,
+) -> Vec<Event> {
+    let mut v = Vec::new();
+    let _ = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let x = match rx.recv().await {
+                Ok(x) => x,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(_) => return,
+            };
+            if x.spider_id() != spider_id {
+                continue;
+            }
+            let done = until(&x);
+            v.push(x);
+            if done {
+                return;
+            }
+        }
+    })
+    .await;
+
+    v
+}
+
+#[test]
+async fn job_events() {
+    let db = common::temp_db("events").await;
+
+    let mut keeper = Keeper::with_policy(
+        Arc::new(db),
+        Policy {
+            job_poll_interval: chrono::Duration::milliseconds(50),
+            ..Policy::default()
+        },
+    );
+    FakeSpider::new()
+        .with_id("events")
+        .sections(|id| {
+            let v = ["第一章内容", "第二章"]
+                .into_iter()
+                .enumerate()
+                .map(|(seq, text)| Section {
+                    seq: seq as u32,
+                    novel_id: *id,
+                    name: format!("第{}章", seq + 1),
+                    update_at: None,
+                    text: String::from(text),
+                })
+                .collect();
+            Ok(v)
+        })
+        .add_to(&mut keeper)
+        .unwrap();
+    keeper.set_section_sink(Arc::new(DiscardSink));
+    keeper.migrate().await.unwrap();
+    let keeper = Arc::new(keeper);
+
+    let mut rx = events::subscribe();
+    keeper
+        .enqueue(JobKind::SectionsByNovel, "events", 7)
+        .await
+        .unwrap();
+    let runner = keeper.clone();
+    let jobs = tokio::spawn(async move { runner.run_jobs(1).await });
+
+    let v = collect(&mut rx, "events", |x| matches!(x, Event::JobFinished(_))).await;
+    keeper.shutdown(Duration::from_secs(5)).await.unwrap();
+    jobs.await.unwrap().unwrap();
+
+    let stored: Vec<_> = v
+        .iter()
+        .filter_map(|x| match x {
+            Event::SectionStored(x) => Some((x.novel_id, x.seq, x.bytes)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        stored,
+        vec![
+            (7, 0, "第一章内容".len() as u64),
+            (7, 1, "第二章".len() as u64)
+        ]
+    );
+
+    match v.last() {
+        Some(Event::JobFinished(x)) => {
+            assert_eq!(x.kind, "sections_by_novel");
+            assert_eq!(x.outcome, "finished");
+            assert_eq!(x.items, 2);
+            assert_eq!(x.bytes, ("第一章内容".len() + "第二章".len()) as u64);
+        }
+        x => panic!("没有收到任务结束事件: {x:?}"),
+    }
+
+    let x = serde_json::to_value(v.last().unwrap()).unwrap();
+    assert_eq!(x["type"], "JobFinished");
+    assert!(x["elapsed"].is_u64());
+}
+
+#[test]
+async fn fetch_events() {
+    let addr = common::serve(|req: Request<Body>| async move {
+        match req.uri().path() {
+            "/ok" => Response::new(Body::from("hello")),
+            _ => Response::builder()
+                .status(404)
+                .body(Body::from("missing"))
+                .unwrap(),
+        }
+    });
+
+    let mut rx = events::subscribe();
+    fetch("fetch-events", &format!("http://{addr}/ok"))
+        .await
+        .unwrap();
+    fetch("fetch-events", &format!("http://{addr}/missing"))
+        .await
+        .unwrap();
+    assert!(fetch("fetch-events", "http://127.0.0.1:1/").await.is_err());
+
+    let v = collect(&mut rx, "fetch-events", |x| {
+        matches!(x, Event::CrawlFailed(_))
+    })
+    .await;
+    assert_eq!(v.len(), 3);
+    match (&v[0], &v[1], &v[2]) {
+        (Event::PageFetched(a), Event::PageFetched(b), Event::CrawlFailed(c)) => {
+            assert_eq!((a.status, a.bytes), (200, 5));
+            assert_eq!((b.status, b.bytes), (404, 7));
+            assert_eq!(c.kind, "disconnect");
+            assert_eq!(c.url.as_deref(), Some("http://127.0.0.1:1/"));
+        }
+        x => panic!("事件不符合预期: {x:?}"),
+    }
+}
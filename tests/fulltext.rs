@@ -0,0 +1,128 @@
+use std::sync::Arc;
+
+use tokio::test;
+
+use spider_novel::keeper::content::inline::InlineStore;
+use spider_novel::keeper::content::StoredSections;
+use spider_novel::keeper::fulltext::{snippet, tokenize, unigrams, TextQuery};
+use spider_novel::keeper::queue::SectionSink;
+use spider_novel::keeper::Keeper;
+use spider_novel::spider::Section;
+
+mod common;
+
+#[test]
+async fn tokenize_cjk() {
+    assert_eq!(tokenize("山边小村"), " 山边 边小 小村 ");
+    assert_eq!(tokenize("韩立 Lv10级！"), " 韩立 lv10 级 ");
+    assert_eq!(tokenize(""), "  ");
+    assert_eq!(unigrams("遮天 天道 Lv10"), " 天 道 遮 ");
+}
+
+#[test]
+async fn snippet_position() {
+    let (s, offset) = snippet("二愣子睁大着双眼，直直望着茅草和烂泥糊成的黑屋顶。", "茅草");
+    assert_eq!(offset, Some(13));
+    assert!(s.contains("茅草"));
+
+    let (_, offset) = snippet("Hello World", "world");
+    assert_eq!(offset, Some(6));
+}
+
+#[test]
+async fn search_sections() {
+    let db = Arc::new(common::temp_db("fulltext").await);
+
+    let store = Arc::new(InlineStore::new(db.clone()));
+    let mut keeper = Keeper::new(db.clone());
+    keeper.migrate().await.unwrap();
+    keeper.set_content_store(store.clone());
+
+    keeper
+        .index_novel_text(
+            1,
+            "凡人修仙传",
+            Some("一个普通山村小子，偶然下进入到当地江湖小门派"),
+        )
+        .await
+        .unwrap();
+
+    let sections = StoredSections::new(db.clone(), keeper.id_gen().clone(), store);
+    for (seq, text) in [
+        "二愣子睁大着双眼，直直望着茅草和烂泥糊成的黑屋顶。",
+        "韩立被墨大夫收为记名弟子，开始修炼长春功。",
+    ]
+    .iter()
+    .enumerate()
+    {
+        sections
+            .save(
+                "ddxsku",
+                &Section {
+                    seq: seq as u32,
+                    novel_id: 7.into(),
+                    name: format!("第{}章", seq + 1),
+                    update_at: None,
+                    text: String::from(*text),
+                },
+            )
+            .await
+            .unwrap();
+    }
+
+    let hits = keeper.search_text(&TextQuery::new("长春功")).await.unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].seq, Some(1));
+    assert_eq!(hits[0].offset, Some(17));
+    assert!(hits[0].snippet.contains("长春功"));
+
+    let hits = keeper.search_text(&TextQuery::new("山村")).await.unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].novel_id, Some(1));
+    assert_eq!(hits[0].section_id, None);
+
+    // 单字只是二元组的第二个字时同样匹配，例如 长春功 中的 功
+    let hits = keeper.search_text(&TextQuery::new("功")).await.unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].seq, Some(1));
+    assert_eq!(hits[0].offset, Some(19));
+    // 单字作为二元组的第一个字
+    let hits = keeper.search_text(&TextQuery::new("墨")).await.unwrap();
+    assert_eq!(hits[0].seq, Some(1));
+
+    // 重新保存时替换原来的索引
+    sections
+        .save(
+            "ddxsku",
+            &Section {
+                seq: 1,
+                novel_id: 7.into(),
+                name: String::from("第2章"),
+                update_at: None,
+                text: String::from("韩立修炼长春功，墨大夫另有图谋。"),
+            },
+        )
+        .await
+        .unwrap();
+    keeper
+        .index_novel_text(1, "凡人修仙传", Some("山村小子韩立"))
+        .await
+        .unwrap();
+
+    let hits = keeper.search_text(&TextQuery::new("长春功")).await.unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].offset, Some(4));
+    assert!(keeper
+        .search_text(&TextQuery::new("记名弟子"))
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        keeper
+            .search_text(&TextQuery::new("山村"))
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+}
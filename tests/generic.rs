@@ -1,7 +1,107 @@
 use std::collections::HashMap;
 
+use chrono::{FixedOffset, TimeZone, Utc};
+
+use spider_novel::common::date::{parse_offset, DateParser, Precision};
+
 #[test]
-fn parse_date() {}
+fn parse_date() {
+    // 网站时间 2022-07-08 15:00:00 +08:00
+    let now = Utc.ymd(2022, 7, 8).and_hms(7, 0, 0);
+    let parser = DateParser::new(parse_offset("+08:00").unwrap()).with_now(now);
+    let at = |y, m, d, h, min, s| {
+        FixedOffset::east(8 * 3600)
+            .ymd(y, m, d)
+            .and_hms(h, min, s)
+            .with_timezone(&Utc)
+    };
+
+    let cases = [
+        ("2022-07-08", at(2022, 7, 8, 0, 0, 0), Precision::Day),
+        ("22-07-08", at(2022, 7, 8, 0, 0, 0), Precision::Day),
+        (
+            "2022/07/08 12:30:45",
+            at(2022, 7, 8, 12, 30, 45),
+            Precision::Second,
+        ),
+        ("07-08 12:30", at(2022, 7, 8, 12, 30, 0), Precision::Minute),
+        // 省略年份且在未来时使用上一年
+        ("12-31 08:00", at(2021, 12, 31, 8, 0, 0), Precision::Minute),
+        ("今天 12:30", at(2022, 7, 8, 12, 30, 0), Precision::Minute),
+        ("昨天", at(2022, 7, 7, 0, 0, 0), Precision::Day),
+        (
+            "前天 23:59:59",
+            at(2022, 7, 6, 23, 59, 59),
+            Precision::Second,
+        ),
+        ("3小时前", at(2022, 7, 8, 12, 0, 0), Precision::Hour),
+        ("5分钟前", at(2022, 7, 8, 14, 55, 0), Precision::Minute),
+        ("2天前", at(2022, 7, 6, 15, 0, 0), Precision::Day),
+        ("刚刚", at(2022, 7, 8, 15, 0, 0), Precision::Second),
+        ("2022年7月8日", at(2022, 7, 8, 0, 0, 0), Precision::Day),
+        (
+            "2022年7月8日 12:30",
+            at(2022, 7, 8, 12, 30, 0),
+            Precision::Minute,
+        ),
+        (
+            "7月8日12时30分",
+            at(2022, 7, 8, 12, 30, 0),
+            Precision::Minute,
+        ),
+        (
+            "更新时间：2022-07-08 12:30:45",
+            at(2022, 7, 8, 12, 30, 45),
+            Precision::Second,
+        ),
+        (
+            "2022-07-08T12:30:45",
+            at(2022, 7, 8, 12, 30, 45),
+            Precision::Second,
+        ),
+        (
+            "1657263600",
+            Utc.ymd(2022, 7, 8).and_hms(7, 0, 0),
+            Precision::Second,
+        ),
+        (
+            "1657263600123",
+            Utc.ymd(2022, 7, 8).and_hms_milli(7, 0, 0, 123),
+            Precision::Second,
+        ),
+    ];
+    for (x, expect, precision) in cases {
+        let r = parser.parse(x).unwrap_or_else(|| panic!("解析失败: {x}"));
+        assert_eq!(r.at, expect, "{x}");
+        assert_eq!(r.precision, precision, "{x}");
+    }
+
+    for x in ["", "未知", "2022-13-01", "25:00", "3年前", "20220708"] {
+        assert!(parser.parse(x).is_none(), "{x}");
+    }
+
+    // 超出时间范围的相对时间不会panic
+    for x in [
+        "99999999999999天前",
+        "9223372036854775807秒前",
+        "999999999999周前",
+        "100000000天前",
+    ] {
+        assert!(parser.parse(x).is_none(), "{x}");
+    }
+
+    // 时区只影响没有时区信息的时间
+    let utc = DateParser::new(parse_offset("+00:00").unwrap()).with_now(now);
+    assert_eq!(
+        utc.parse("2022-07-08").unwrap().at,
+        Utc.ymd(2022, 7, 8).and_hms(0, 0, 0)
+    );
+    assert_eq!(utc.parse("1657263600").unwrap().at, now);
+    assert!(parse_offset("CST").is_none());
+    // 多字节字符不在字符边界上切分
+    assert!(parse_offset("+0八").is_none());
+    assert!(parse_offset("-时0").is_none());
+}
 
 #[test]
 fn map() {
@@ -1,14 +1,12 @@
-use skyscraper::html;
-use spider_novel::common::doc;
 use spider_novel::common::doc::WrapDocument;
 use spider_novel::common::httputils::get;
-use std::borrow::BorrowMut;
 use tokio::test;
 
 #[test]
 async fn tget() {
     let x = get("http://www.ddxsku.com/").await.unwrap();
-    let doc = doc::parse(&x).unwrap();
+    let doc = WrapDocument::parse(&x);
+    assert!(doc.select("body").text().is_some());
 }
 
 // #[test]
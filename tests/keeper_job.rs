@@ -0,0 +1,130 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::test;
+
+use spider_novel::common::snowid::IdGen;
+use spider_novel::keeper::data::job;
+use spider_novel::keeper::queue::JobKind;
+use spider_novel::keeper::{Keeper, Policy};
+use spider_novel::spider::{CrawlError, Position, Support};
+
+use common::{migrated_db, novel, FakeSpider};
+
+mod common;
+
+#[test]
+async fn acquire_and_resume() {
+    let db = migrated_db("acquire_and_resume").await;
+    let ids = IdGen::sequential(1);
+
+    let id = job::add(&db, &ids, "novels_by_sort", "ddxsku", 10)
+        .await
+        .unwrap();
+    // 未完成的相同任务不会重复添加
+    let id2 = job::add(&db, &ids, "novels_by_sort", "ddxsku", 10)
+        .await
+        .unwrap();
+    assert_eq!(id, id2);
+
+    let x = job::acquire(&db).await.unwrap().expect("没有领取到任务");
+    assert_eq!(x.id, id);
+    assert_eq!(x.attempts, 1);
+    assert!(job::acquire(&db).await.unwrap().is_none());
+
+    job::checkpoint(&db, id, 42).await.unwrap();
+
+    // 模拟进程重启
+    assert_eq!(job::recover(&db).await.unwrap(), 1);
+    let x = job::acquire(&db).await.unwrap().expect("没有领取到任务");
+    assert_eq!(x.cursor, 42);
+
+    job::finish(&db, id).await.unwrap();
+    assert!(job::acquire(&db).await.unwrap().is_none());
+}
+
+#[test]
+async fn fail_until_max_attempts() {
+    let db = migrated_db("fail_until_max_attempts").await;
+    let ids = IdGen::sequential(1);
+
+    let id = job::add(&db, &ids, "sections_by_novel", "ddxsku", 7)
+        .await
+        .unwrap();
+
+    for _ in 0..2 {
+        let x = job::acquire(&db).await.unwrap().expect("没有领取到任务");
+        job::fail(&db, &x, "network disconnect", 2).await.unwrap();
+    }
+
+    assert!(job::acquire(&db).await.unwrap().is_none());
+    let x = job::list(&db, Some(job::STATE_FAILED)).await.unwrap();
+    assert_eq!(x.len(), 1);
+    assert_eq!(x[0].id, id);
+    assert_eq!(x[0].last_error.as_deref(), Some("network disconnect"));
+}
+
+#[test]
+async fn stop_at_repeated_page() {
+    let db = Arc::new(common::temp_db("repeated_page").await);
+    let pages = Arc::new(Mutex::new(Vec::new()));
+    let mut keeper = Keeper::with_policy(
+        db.clone(),
+        Policy {
+            job_poll_interval: chrono::Duration::milliseconds(50),
+            ..Policy::default()
+        },
+    );
+    // 只有两页的分类，超出范围的页码返回最后一页
+    let requested = pages.clone();
+    FakeSpider::new()
+        .with_id("repeat")
+        .support(Support {
+            get_sort: true,
+            get_novel_from_sort: true,
+            search_novel: false,
+            search_author: false,
+        })
+        .novels(move |pos| {
+            let page = match pos {
+                Position::Specify(x) => x,
+                _ => return Err(CrawlError::Unsupported),
+            };
+            requested.lock().unwrap().push(page);
+
+            let ids = if page == 1 { [1, 2] } else { [3, 4] };
+            Ok(ids
+                .into_iter()
+                .map(|id| novel(id, &format!("小说{id}"), "辰东"))
+                .collect())
+        })
+        .add_to(&mut keeper)
+        .unwrap();
+    keeper.migrate().await.unwrap();
+    let id = keeper
+        .enqueue(JobKind::NovelsBySort, "repeat", 1)
+        .await
+        .unwrap();
+
+    let keeper = Arc::new(keeper);
+    let runner = keeper.clone();
+    let jobs = tokio::spawn(async move { runner.run_jobs(1).await });
+
+    let finished = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let x = job::list(&db, Some(job::STATE_FINISHED)).await.unwrap();
+            if x.iter().any(|x| x.id == id) {
+                return x;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("任务没有结束");
+    keeper.shutdown(Duration::from_secs(5)).await.unwrap();
+    jobs.await.unwrap().unwrap();
+
+    // 第三页与第二页相同，不再继续，进度停在最后一页
+    assert_eq!(*pages.lock().unwrap(), vec![1, 2, 3]);
+    assert_eq!(finished[0].cursor, 2);
+}
@@ -0,0 +1,99 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::test;
+
+use spider_novel::keeper::{Keeper, Policy};
+use spider_novel::spider::{CrawlError, Novel, Result, Support};
+
+use common::{novel, FakeSpider};
+
+mod common;
+
+// 只支持搜索的爬虫，search 返回搜索结果
+fn add_spider<F, R>(keeper: &mut Keeper, id: &str, search: F)
+where
+    F: Fn(String) -> R + Send + Sync + 'static,
+    R: Future<Output = Result<Vec<Novel>>> + Send + 'static,
+{
+    FakeSpider::new()
+        .with_id(id)
+        .support(Support {
+            get_sort: false,
+            get_novel_from_sort: false,
+            search_novel: true,
+            search_author: false,
+        })
+        .search(search)
+        .add_to(keeper)
+        .unwrap();
+}
+
+#[test]
+async fn merge_search() {
+    let db = common::temp_db("keeper-search").await;
+
+    let mut keeper = Keeper::with_policy(
+        Arc::new(db),
+        Policy {
+            search_timeout: chrono::Duration::milliseconds(200),
+            ..Policy::default()
+        },
+    );
+    add_spider(&mut keeper, "a", |_| async {
+        Ok(vec![novel(1, "遮天外传", "辰东"), novel(2, "遮天", "辰东")])
+    });
+    add_spider(&mut keeper, "b", |_| async {
+        Ok(vec![novel(3, " 遮天 ", "辰东")])
+    });
+    add_spider(&mut keeper, "c", |_| async { Err(CrawlError::ParseFailed) });
+    add_spider(&mut keeper, "d", |_| async {
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        Ok(vec![novel(4, "遮天", "辰东")])
+    });
+
+    let r = keeper.search("遮天").await;
+
+    assert_eq!(r.hits.len(), 2);
+    assert_eq!(r.hits[0].novel.name.trim(), "遮天");
+    assert_eq!(r.hits[0].sources.len(), 2);
+    assert_eq!(r.hits[1].novel.name, "遮天外传");
+
+    let mut failed: Vec<&str> = r.failed.iter().map(|x| x.0.as_str()).collect();
+    failed.sort();
+    assert_eq!(failed, vec!["c", "d"]);
+    assert!(matches!(
+        r.failed.iter().find(|x| x.0 == "d").unwrap().1,
+        CrawlError::Timeout
+    ));
+}
+
+#[test]
+async fn equal_scores() {
+    let db = common::temp_db("keeper-search-ties").await;
+    let mut keeper = Keeper::new(Arc::new(db));
+    add_spider(&mut keeper, "e", |_| async {
+        Ok(vec![
+            novel(12, "遮天乙", "佚名"),
+            novel(11, "遮天甲", "佚名"),
+        ])
+    });
+    add_spider(&mut keeper, "f", |_| async {
+        Ok(vec![novel(21, "遮天丙", "佚名")])
+    });
+    add_spider(&mut keeper, "g", |_| async {
+        Ok(vec![novel(31, "遮天丙", "佚名")])
+    });
+    keeper.set_spider_score("e", 2).unwrap();
+    keeper.set_spider_score("g", 1).unwrap();
+
+    // 三本书的总分相同，依次按来源评分与来源中的id排序
+    for _ in 0..10 {
+        let r = keeper.search("遮天").await;
+        let names: Vec<_> = r.hits.iter().map(|x| x.novel.name.as_str()).collect();
+        assert_eq!(names, vec!["遮天甲", "遮天乙", "遮天丙"]);
+        assert!(r.hits.iter().all(|x| x.score == r.hits[0].score));
+        assert_eq!(r.hits[2].source_score, 1);
+    }
+}
@@ -0,0 +1,123 @@
+use std::time::Duration;
+
+use tokio::test;
+
+use spider_novel::common::events::{CrawlFailed, Event, JobFinished, PageFetched, SectionStored};
+use spider_novel::common::shutdown::CancellationToken;
+use spider_novel::keeper::metrics::{serve, Gauges, Metrics};
+
+fn page(status: u16, ms: u64) -> Event {
+    Event::PageFetched(PageFetched {
+        spider_id: String::from("ddxsku"),
+        url: String::from("http://www.ddxsku.com/a/1.html"),
+        status,
+        bytes: 100,
+        elapsed: Duration::from_millis(ms),
+    })
+}
+
+fn failed(kind: &str) -> Event {
+    Event::CrawlFailed(CrawlFailed {
+        spider_id: String::from("ddxsku"),
+        url: Some(String::from("http://www.ddxsku.com/a/1.html")),
+        kind: String::from(kind),
+        error: String::from("x"),
+        elapsed: Duration::ZERO,
+    })
+}
+
+#[test]
+async fn render() {
+    let m = Metrics::new();
+    m.observe(&page(200, 30));
+    m.observe(&page(200, 700));
+    m.observe(&page(503, 100));
+    m.observe(&failed("parse_failed"));
+    m.observe(&failed("miss_section_content"));
+    m.observe(&failed("miss_section_content"));
+    m.observe(&failed("disconnect"));
+    m.observe(&Event::SectionStored(SectionStored {
+        spider_id: String::from("ddxsku"),
+        novel_id: 1,
+        seq: 0,
+        bytes: 42,
+        elapsed: Duration::ZERO,
+    }));
+    for attempt in [1, 2] {
+        m.observe(&Event::JobFinished(JobFinished {
+            job_id: 1,
+            kind: String::from("sections_by_novel"),
+            spider_id: String::from("ddxsku"),
+            outcome: String::from("failed"),
+            attempt,
+            items: 0,
+            bytes: 0,
+            elapsed: Duration::ZERO,
+        }));
+    }
+
+    let text = m.render(&Gauges {
+        queue: vec![(String::from("ddxsku"), String::from("pending"), 3)],
+        permits: vec![(String::from("ddxsku"), 25, 100)],
+    });
+    let has = |x: &str| assert!(text.lines().any(|l| l == x), "缺少 {x}\n{text}");
+
+    has("# TYPE spider_http_requests_total counter");
+    has(r#"spider_http_requests_total{spider="ddxsku",host="www.ddxsku.com",status="200"} 2"#);
+    has(r#"spider_http_requests_total{spider="ddxsku",host="www.ddxsku.com",status="503"} 1"#);
+    has(r#"spider_http_response_bytes_total{spider="ddxsku",host="www.ddxsku.com"} 300"#);
+    has(
+        r#"spider_http_request_duration_seconds_bucket{spider="ddxsku",host="www.ddxsku.com",le="0.05"} 1"#,
+    );
+    has(
+        r#"spider_http_request_duration_seconds_bucket{spider="ddxsku",host="www.ddxsku.com",le="0.5"} 2"#,
+    );
+    has(
+        r#"spider_http_request_duration_seconds_bucket{spider="ddxsku",host="www.ddxsku.com",le="+Inf"} 3"#,
+    );
+    has(r#"spider_http_request_duration_seconds_count{spider="ddxsku",host="www.ddxsku.com"} 3"#);
+    has(r#"spider_parse_failures_total{spider="ddxsku",kind="miss_section_content"} 2"#);
+    has(r#"spider_parse_failures_total{spider="ddxsku",kind="parse_failed"} 1"#);
+    has(
+        r#"spider_crawl_failures_total{spider="ddxsku",host="www.ddxsku.com",kind="disconnect"} 1"#,
+    );
+    has(r#"spider_section_bytes_total{spider="ddxsku"} 42"#);
+    has(
+        r#"spider_jobs_finished_total{spider="ddxsku",kind="sections_by_novel",outcome="failed"} 2"#,
+    );
+    has(r#"spider_job_retries_total{spider="ddxsku",kind="sections_by_novel"} 1"#);
+    has(r#"spider_job_queue_depth{spider="ddxsku",state="pending"} 3"#);
+    has(r#"spider_concurrency_saturation{spider="ddxsku"} 0.25"#);
+}
+
+#[test]
+async fn endpoint() {
+    let addr = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let cancel = CancellationToken::new();
+    let server = tokio::spawn(serve(addr, cancel.clone(), || async {
+        String::from("spider_up 1\n")
+    }));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let resp = reqwest::get(format!("http://{addr}/metrics"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert!(resp
+        .headers()
+        .get("content-type")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain"));
+    assert_eq!(resp.text().await.unwrap(), "spider_up 1\n");
+
+    let resp = reqwest::get(format!("http://{addr}/other")).await.unwrap();
+    assert_eq!(resp.status(), 404);
+
+    cancel.cancel();
+    server.await.unwrap().unwrap();
+}
@@ -0,0 +1,163 @@
+use sea_orm::{ConnectionTrait, DbBackend, Statement};
+use tokio::test;
+
+use spider_novel::common::migrate::{statements, Migration, Migrator, Script};
+use spider_novel::ddxsku::profile::DDXSKU_ID;
+use spider_novel::ddxsku::{self, DDSpider};
+use spider_novel::keeper;
+use spider_novel::spider::SpiderMetadata;
+
+mod common;
+
+#[test]
+async fn up_status_down() {
+    let db = common::temp_db("migrate").await;
+
+    let mut migrator = Migrator::new();
+    migrator.register(keeper::KEEPER_MIGRATION_OWNER, keeper::migrations());
+    migrator.register(DDSpider::id(), DDSpider::migrations());
+
+    let total = keeper::migrations().len() + DDSpider::migrations().len();
+    assert_eq!(migrator.up(&db).await.unwrap().len(), total);
+    // 再次执行不会重复迁移
+    assert!(migrator.up(&db).await.unwrap().is_empty());
+    assert!(migrator
+        .status(&db)
+        .await
+        .unwrap()
+        .iter()
+        .all(|x| x.applied_at.is_some()));
+
+    let done = migrator.down(&db, Some(DDSpider::id()), 1).await.unwrap();
+    assert_eq!(done.len(), 1);
+    assert_eq!(done[0].1, "20220901090000_jieqi_sites");
+
+    let pending: Vec<_> = migrator
+        .status(&db)
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|x| x.applied_at.is_none())
+        .collect();
+    assert_eq!(pending.len(), 1);
+
+    assert_eq!(migrator.up(&db).await.unwrap().len(), 1);
+}
+
+#[test]
+async fn existing_rows_belong_to_ddxsku() {
+    let db = common::temp_db("migrate-jieqi-sites").await;
+
+    // 增加site列之前写入的数据都属于ddxsku
+    let mut old = Migrator::new();
+    old.register(
+        DDSpider::id(),
+        DDSpider::migrations()
+            .into_iter()
+            .filter(|x| x.id != "20220901090000_jieqi_sites")
+            .collect(),
+    );
+    old.up(&db).await.unwrap();
+    db.execute(Statement::from_string(
+        DbBackend::Sqlite,
+        String::from(
+            "insert into ddxsku_spider_sorts (id, name, link) values (1, '玄幻', '/xiaoshuo/1_{{ page }}.html')",
+        ),
+    ))
+    .await
+    .unwrap();
+
+    let mut migrator = Migrator::new();
+    migrator.register(DDSpider::id(), DDSpider::migrations());
+    migrator.up(&db).await.unwrap();
+
+    let x = ddxsku::data::sorts(&db, DDXSKU_ID).await.unwrap();
+    assert_eq!(x.len(), 1);
+    assert_eq!(x[0].name, "玄幻");
+}
+
+const TRIGGER_UP: &str = r#"
+create table notes (id integer primary key, body text not null, seen integer not null default 0);
+create table note_logs (note_id integer not null, body text not null);
+-- 触发器中的分号不能拆分语句
+create trigger notes_log after insert on notes
+begin
+    insert into note_logs (note_id, body) values (new.id, 'a;b');
+    update notes set seen = 1 where id = new.id;
+end;
+insert into notes (id, body) values (1, 'x; y; ''z''');
+"#;
+
+#[test]
+async fn script_with_trigger_and_strings() {
+    let db = common::temp_db("migrate-trigger").await;
+
+    let script = Script {
+        up: TRIGGER_UP,
+        down: "drop trigger notes_log; drop table note_logs; drop table notes;",
+    };
+    let mut migrator = Migrator::new();
+    migrator.register(
+        "trigger",
+        vec![Migration {
+            id: "20221001000000_trigger",
+            sqlite: script.clone(),
+            postgres: script.clone(),
+            mysql: script,
+        }],
+    );
+    assert_eq!(migrator.up(&db).await.unwrap().len(), 1);
+
+    let row = db
+        .query_one(Statement::from_string(
+            DbBackend::Sqlite,
+            String::from(
+                "select n.body as body, n.seen as seen, l.body as log \
+                 from notes n join note_logs l on l.note_id = n.id",
+            ),
+        ))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(row.try_get::<String>("", "body").unwrap(), "x; y; 'z'");
+    assert_eq!(row.try_get::<i32>("", "seen").unwrap(), 1);
+    assert_eq!(row.try_get::<String>("", "log").unwrap(), "a;b");
+
+    assert_eq!(migrator.down(&db, None, 1).await.unwrap().len(), 1);
+}
+
+#[test]
+async fn split_statements() {
+    let sql = r#"
+create function touch() returns trigger as $body$
+begin
+    new.updated_at := now();
+    return new;
+end;
+$body$ language plpgsql;
+-- 只有注释的语句会被忽略;
+create trigger t before update on novels for each row execute function touch();
+insert into "a;b" values ('it''s; fine', $$;$$, $1);
+"#;
+    let x = statements(DbBackend::Postgres, sql);
+    assert_eq!(x.len(), 3);
+    assert!(x[0].starts_with("create function") && x[0].ends_with("language plpgsql"));
+    assert!(x[2].ends_with("$1)"));
+
+    let sql = r#"
+create trigger t before insert on novels for each row
+begin
+    if new.name = 'a\';' then
+        set new.name = 'b';
+    end if;
+end;
+/* 注释; */ drop table `x;y`;
+"#;
+    let x = statements(DbBackend::MySql, sql);
+    assert_eq!(x.len(), 2);
+    assert!(x[0].ends_with("end"));
+    assert!(x[1].ends_with("`x;y`"));
+
+    let x = statements(DbBackend::Postgres, "begin; select 1; commit;");
+    assert_eq!(x, vec!["begin", "select 1", "commit"]);
+}
@@ -0,0 +1,272 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use hyper::{Body, Request, Response};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::test;
+
+use spider_novel::keeper::data::novel;
+use spider_novel::keeper::notify::smtp::SmtpNotifier;
+use spider_novel::keeper::notify::webhook::{signature, WebhookNotifier, SIGNATURE_HEADER};
+use spider_novel::keeper::notify::{Debounced, Notifier, NovelUpdated};
+use spider_novel::keeper::Keeper;
+use spider_novel::spider::Novel;
+
+mod common;
+
+fn event(novel_id: i64, section: &str, new_sections: i64) -> NovelUpdated {
+    NovelUpdated {
+        novel_id,
+        name: String::from("遮天"),
+        author: String::from("辰东"),
+        last_section_name: Some(String::from(section)),
+        last_updated_at: None,
+        new_sections,
+        section_count: 0,
+        subscribers: vec![String::from("u1")],
+    }
+}
+
+// 记录收到的通知
+#[derive(Default)]
+struct Recorder(Mutex<Vec<NovelUpdated>>);
+
+#[async_trait]
+impl Notifier for Recorder {
+    async fn notify(&self, event: &NovelUpdated) -> Result<()> {
+        self.0.lock().unwrap().push(event.clone());
+        Ok(())
+    }
+}
+
+impl Recorder {
+    fn take(&self) -> Vec<NovelUpdated> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+#[test]
+async fn follow_and_check_update() {
+    let db = Arc::new(common::migrated_db("follow_and_check_update").await);
+    let mut keeper = Keeper::new(db.clone());
+    let recorder = Arc::new(Recorder::default());
+    keeper.set_notifier(recorder.clone());
+
+    let data = Novel {
+        id: 10.into(),
+        name: String::from("遮天"),
+        cover: None,
+        author: String::from("辰东"),
+        intro: None,
+        last_updated_at: None,
+        last_updated_section_name: Some(String::from("第一章")),
+        state: None,
+        word_count: None,
+        tags: vec![],
+        category: None,
+        aliases: vec![],
+        source_url: None,
+    };
+    let id = novel::add_or_recover(&db, keeper.id_gen(), "test", &data)
+        .await
+        .unwrap();
+
+    // 没有关注时不记录
+    assert!(keeper
+        .check_update(id, Some("第一章"), None)
+        .await
+        .unwrap()
+        .is_none());
+
+    keeper.follow(id, "u1").await.unwrap();
+    keeper.follow(id, "mailto:a@localhost").await.unwrap();
+    keeper.follow(id, "u1").await.unwrap();
+    assert_eq!(
+        keeper.followers(id).await.unwrap(),
+        vec!["u1", "mailto:a@localhost"]
+    );
+
+    // 第一次检查只记录状态
+    assert!(keeper
+        .check_update(id, Some("第一章"), None)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(keeper
+        .check_update(id, Some("第一章"), None)
+        .await
+        .unwrap()
+        .is_none());
+    // 只对比章节数时沿用上次的最新章节
+    assert!(keeper.check_update(id, None, None).await.unwrap().is_none());
+
+    let x = keeper
+        .check_update(id, Some("第二章"), None)
+        .await
+        .unwrap()
+        .expect("没有检测到更新");
+    assert_eq!(x.name, "遮天");
+    assert_eq!(x.author, "辰东");
+    assert_eq!(x.last_section_name.as_deref(), Some("第二章"));
+    assert_eq!(x.subscribers.len(), 2);
+    assert_eq!(recorder.take(), vec![x]);
+
+    assert!(keeper.unfollow(id, "u1").await.unwrap());
+    assert!(keeper.unfollow(id, "mailto:a@localhost").await.unwrap());
+    assert!(!keeper.unfollow(id, "u1").await.unwrap());
+    assert!(keeper
+        .check_update(id, Some("第三章"), None)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(recorder.take().is_empty());
+}
+
+#[test]
+async fn debounce_merges_updates() {
+    let recorder = Arc::new(Recorder::default());
+    let debounced = Debounced::new(recorder.clone(), Duration::from_millis(200));
+
+    debounced.notify(&event(1, "第二章", 1)).await.unwrap();
+    let mut e = event(1, "第三章", 1);
+    e.subscribers = vec![String::from("u2")];
+    debounced.notify(&e).await.unwrap();
+    debounced.notify(&event(2, "第一章", 0)).await.unwrap();
+    assert!(recorder.take().is_empty());
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let mut v = recorder.take();
+    v.sort_by_key(|x| x.novel_id);
+    assert_eq!(v.len(), 2);
+    assert_eq!(v[0].last_section_name.as_deref(), Some("第三章"));
+    assert_eq!(v[0].new_sections, 2);
+    assert_eq!(v[0].subscribers, vec!["u1", "u2"]);
+
+    // flush立即发送，之后定时器不再重复发送
+    debounced.notify(&event(1, "第四章", 1)).await.unwrap();
+    debounced.flush().await;
+    assert_eq!(recorder.take().len(), 1);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(recorder.take().is_empty());
+}
+
+// webhook收到的签名与请求体
+type Received = Arc<Mutex<Vec<(Option<String>, Vec<u8>)>>>;
+
+#[test]
+async fn webhook() {
+    let received: Received = Arc::default();
+    let shared = received.clone();
+    let addr = common::serve(move |req: Request<Body>| {
+        let received = shared.clone();
+        async move {
+            let sign = req
+                .headers()
+                .get(SIGNATURE_HEADER)
+                .and_then(|x| x.to_str().ok())
+                .map(String::from);
+            let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+            received.lock().unwrap().push((sign, body.to_vec()));
+            Response::new(Body::empty())
+        }
+    });
+
+    let n = WebhookNotifier::new(&format!("http://{addr}/hook")).secret("key");
+    n.notify(&event(1, "第二章", 1)).await.unwrap();
+
+    let (sign, body) = received.lock().unwrap().pop().unwrap();
+    assert_eq!(sign, Some(signature("key", &body)));
+    let x: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(x["novel_id"], 1);
+    assert_eq!(x["last_section_name"], "第二章");
+
+    // 服务不可用时返回错误
+    let n = WebhookNotifier::new("http://127.0.0.1:1/hook");
+    assert!(n.notify(&event(1, "第二章", 1)).await.is_err());
+}
+
+// 本地的SMTP替身，记录收到的命令
+async fn smtp_stub() -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let lines: Arc<Mutex<Vec<String>>> = Arc::default();
+
+    let received = lines.clone();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (r, mut w) = stream.into_split();
+        let mut r = BufReader::new(r);
+        w.write_all(b"220 localhost\r\n").await.unwrap();
+
+        let mut data = false;
+        loop {
+            let mut line = String::new();
+            if r.read_line(&mut line).await.unwrap() == 0 {
+                return;
+            }
+            let line = line.trim_end().to_string();
+            received.lock().unwrap().push(line.clone());
+
+            let resp: &[u8] = if data {
+                if line != "." {
+                    continue;
+                }
+                data = false;
+                b"250 queued\r\n"
+            } else if line.starts_with("EHLO") {
+                b"250-localhost\r\n250 AUTH PLAIN\r\n"
+            } else if line.starts_with("AUTH") {
+                b"235 ok\r\n"
+            } else if line == "DATA" {
+                data = true;
+                b"354 go ahead\r\n"
+            } else if line == "QUIT" {
+                w.write_all(b"221 bye\r\n").await.unwrap();
+                return;
+            } else {
+                b"250 ok\r\n"
+            };
+            w.write_all(resp).await.unwrap();
+        }
+    });
+
+    (addr, lines)
+}
+
+#[test]
+async fn smtp() {
+    let (addr, lines) = smtp_stub().await;
+
+    let n = SmtpNotifier::new(
+        &addr,
+        "spider@localhost",
+        vec![String::from("me@localhost")],
+    )
+    .auth("spider", "secret");
+    let mut e = event(1, "第二章", 1);
+    e.subscribers.push(String::from("mailto:a@localhost"));
+    n.notify(&e).await.unwrap();
+
+    let lines = lines.lock().unwrap().clone();
+    assert_eq!(lines[0], "EHLO spider-novel");
+    assert_eq!(
+        lines[1],
+        format!("AUTH PLAIN {}", base64::encode("\0spider\0secret"))
+    );
+    assert_eq!(lines[2], "MAIL FROM:<spider@localhost>");
+    assert_eq!(lines[3], "RCPT TO:<me@localhost>");
+    assert_eq!(lines[4], "RCPT TO:<a@localhost>");
+    assert_eq!(lines[5], "DATA");
+    assert!(lines.contains(&format!(
+        "Subject: =?UTF-8?B?{}?=",
+        base64::encode("《遮天》更新了")
+    )));
+    assert_eq!(lines.last().unwrap(), "QUIT");
+
+    // 没有收件人时不连接服务器
+    let n = SmtpNotifier::new("127.0.0.1:1", "spider@localhost", vec![]);
+    n.notify(&event(1, "第二章", 1)).await.unwrap();
+}
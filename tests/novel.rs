@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use tokio::test;
+
+use spider_novel::common::snowid::IdGen;
+use spider_novel::keeper::data::{author, novel};
+use spider_novel::spider::Novel;
+
+mod common;
+
+fn novel(id: i64, source_url: &str) -> Novel {
+    Novel {
+        id: id.into(),
+        name: String::from("遮天"),
+        cover: None,
+        author: String::from("辰东"),
+        intro: None,
+        last_updated_at: None,
+        last_updated_section_name: None,
+        state: None,
+        word_count: None,
+        tags: vec![],
+        category: None,
+        aliases: vec![],
+        source_url: Some(String::from(source_url)),
+    }
+}
+
+#[test]
+async fn novel_rows() {
+    let db = common::migrated_db("novel-rows").await;
+    let ids = IdGen::sequential(1);
+
+    let a = Novel {
+        intro: Some(String::from("冰冷与黑暗并存的宇宙深处")),
+        last_updated_section_name: Some(String::from("第一章 星空中的青铜巨棺")),
+        ..novel(10, "https://a.com/book/10.html")
+    };
+    let id = novel::add_or_recover(&db, &ids, "a", &a).await.unwrap();
+
+    // 另一个来源没有简介时保留已有的简介，最新章节使用新的数据
+    let b = Novel {
+        last_updated_section_name: Some(String::from("第二章 素问")),
+        ..novel(20, "https://b.com/20/")
+    };
+    assert_eq!(novel::add_or_recover(&db, &ids, "b", &b).await.unwrap(), id);
+
+    let x = novel::novel_by_id(&db, id).await.unwrap().unwrap();
+    assert_eq!(x.intro, a.intro);
+    assert_eq!(x.last_section_name, b.last_updated_section_name);
+
+    // 来源地址保存在各自的关联上
+    let v = author::novels(&db, "辰东").await.unwrap();
+    assert_eq!(v.len(), 1);
+    let mut urls: Vec<_> = v[0]
+        .1
+        .iter()
+        .map(|x| (x.spider_kind_id.as_str(), x.source_url.as_deref()))
+        .collect();
+    urls.sort();
+    assert_eq!(
+        urls,
+        vec![
+            ("a", Some("https://a.com/book/10.html")),
+            ("b", Some("https://b.com/20/")),
+        ]
+    );
+
+    // 地址变化后更新关联
+    novel::add_or_recover(&db, &ids, "b", &novel(20, "https://b.com/book/20/"))
+        .await
+        .unwrap();
+    let v = author::novels(&db, "辰东").await.unwrap();
+    assert!(v[0]
+        .1
+        .iter()
+        .any(|x| x.source_url.as_deref() == Some("https://b.com/book/20/")));
+    assert_eq!(v[0].1.len(), 2);
+}
+
+#[test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_novel_rows() {
+    let db = Arc::new(common::migrated_db("novel-concurrent").await);
+    let ids = IdGen::sequential(1);
+
+    // 多个任务同时保存同一本新小说时都返回同一条记录
+    let tasks: Vec<_> = (0..8)
+        .map(|x| {
+            let db = db.clone();
+            let ids = ids.clone();
+            tokio::spawn(async move {
+                let data = novel(x % 2, &format!("https://a.com/book/{}.html", x % 2));
+                novel::add_or_recover(&db, &ids, "a", &data).await
+            })
+        })
+        .collect();
+    let mut v = Vec::new();
+    for x in tasks {
+        v.push(x.await.unwrap().unwrap());
+    }
+    v.dedup();
+    assert_eq!(v.len(), 1);
+
+    let v = author::novels(&db, "辰东").await.unwrap();
+    assert_eq!(v.len(), 1);
+    assert_eq!(v[0].1.len(), 2);
+}
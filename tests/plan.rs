@@ -0,0 +1,149 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::test;
+
+use spider_novel::keeper::queue::JobKind;
+use spider_novel::keeper::{Keeper, Policy};
+use spider_novel::spider::{Capabilities, Pagination, Position, RateLimit, Sort, Support};
+
+use common::{novel, temp_db, FakeSpider};
+
+mod common;
+
+// 加载规则后才知道自己的能力，注册时只声明了搜索
+const SUPPORT: Support = Support {
+    get_sort: false,
+    get_novel_from_sort: false,
+    search_novel: true,
+    search_author: false,
+};
+
+// positions 记录收到的列表页位置
+fn rule_spider(positions: Arc<Mutex<Vec<String>>>) -> FakeSpider {
+    FakeSpider::new()
+        .with_id("plan")
+        .support(SUPPORT)
+        .sorts(sorts())
+        .novels(move |pos| {
+            let pos = match pos {
+                Position::Full => String::from("full"),
+                Position::Specify(x) => format!("page {x}"),
+                _ => String::from("other"),
+            };
+            let first = pos == "full" || pos == "page 1";
+            positions.lock().unwrap().push(pos);
+
+            if first {
+                Ok(vec![novel(1, "遮天", "辰东"), novel(2, "完美世界", "辰东")])
+            } else {
+                Ok(vec![])
+            }
+        })
+        .search(|_| async { Ok(vec![]) })
+}
+
+fn rule_capabilities() -> Capabilities {
+    Capabilities {
+        get_sort: true,
+        get_novel_from_sort: true,
+        pagination: Pagination::Sequential,
+        chapter_updated_at: true,
+        rate_limit: Some(RateLimit {
+            concurrency: 1,
+            interval: Duration::from_millis(500),
+        }),
+        ..SUPPORT.into()
+    }
+}
+
+fn sorts() -> Vec<Sort> {
+    vec![
+        Sort {
+            id: 1.into(),
+            name: String::from("玄幻"),
+        },
+        Sort {
+            id: 2.into(),
+            name: String::from("都市"),
+        },
+    ]
+}
+
+#[test]
+async fn runtime_capabilities() {
+    let db = Arc::new(temp_db("plan-capabilities").await);
+    let mut keeper = Keeper::new(db);
+    rule_spider(Arc::default()).add_to(&mut keeper).unwrap();
+    keeper.migrate().await.unwrap();
+
+    // 没有运行时能力时使用注册时的描述
+    let plan = keeper.plan("plan").unwrap();
+    assert_eq!(plan.kinds, vec![JobKind::SectionsByNovel]);
+    assert!(plan.resumable);
+    assert!(!plan.exclusive);
+    assert!(keeper
+        .enqueue(JobKind::NovelsBySort, "plan", 1)
+        .await
+        .is_err());
+    assert!(keeper.plan_sorts("plan").await.is_err());
+    assert!(keeper.plan("missing").is_none());
+}
+
+#[test]
+async fn plan_from_capabilities() {
+    let db = Arc::new(temp_db("plan-jobs").await);
+    let positions = Arc::new(Mutex::new(Vec::new()));
+    let mut keeper = Keeper::with_policy(
+        db.clone(),
+        Policy {
+            job_poll_interval: chrono::Duration::milliseconds(50),
+            ..Policy::default()
+        },
+    );
+    rule_spider(positions.clone())
+        .capabilities(rule_capabilities())
+        .add_to(&mut keeper)
+        .unwrap();
+    keeper.migrate().await.unwrap();
+
+    let x = keeper.capabilities("plan").unwrap();
+    assert!(x.chapter_updated_at);
+    assert_eq!(x.pagination, Pagination::Sequential);
+    assert!(
+        keeper
+            .spider_info("plan")
+            .unwrap()
+            .capabilities
+            .get_novel_from_sort
+    );
+
+    let plan = keeper.plan("plan").unwrap();
+    assert_eq!(
+        plan.kinds,
+        vec![JobKind::NovelsBySort, JobKind::SectionsByNovel]
+    );
+    assert!(!plan.resumable);
+    assert!(plan.exclusive);
+
+    assert_eq!(keeper.plan_sorts("plan").await.unwrap(), 2);
+    // 重复添加时返回未完成的任务
+    assert_eq!(keeper.plan_sorts("plan").await.unwrap(), 2);
+
+    let keeper = Arc::new(keeper);
+    let runner = keeper.clone();
+    let jobs = tokio::spawn(async move { runner.run_jobs(2).await });
+
+    // 两个分类任务都执行
+    let _ = tokio::time::timeout(Duration::from_secs(10), async {
+        while positions.lock().unwrap().len() < 2 {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await;
+    keeper.shutdown(Duration::from_secs(5)).await.unwrap();
+    jobs.await.unwrap().unwrap();
+
+    // 不能直接访问页码时一次获取全部页面
+    assert_eq!(*positions.lock().unwrap(), vec!["full", "full"]);
+}
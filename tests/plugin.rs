@@ -0,0 +1,238 @@
+#![cfg(feature = "wasm")]
+
+use std::net::SocketAddr;
+
+use hyper::{Body, Request, Response};
+use tokio::test;
+
+use spider_novel::common::shutdown::CancellationToken;
+use spider_novel::plugin::abi::{Page, Reply};
+use spider_novel::plugin::runtime::{Limits, Runtime};
+use spider_novel::plugin::WasmSpider;
+use spider_novel::spider::{CrawlError, NovelID, Position, SortID, SourceKind, Spider};
+
+mod common;
+
+// 生成测试用的插件，每个函数返回固定的JSON，echo 原样返回参数，spin 不会结束
+fn plugin(funcs: &[(&str, String)]) -> String {
+    let mut data = String::new();
+    let mut exports = String::new();
+    let mut offset = 1024u32;
+
+    let mut add = |text: &str, data: &mut String| {
+        let escaped: String = text.bytes().map(|b| format!("\\{b:02x}")).collect();
+        data.push_str(&format!("  (data (i32.const {offset}) \"{escaped}\")\n"));
+        let packed = ((offset as u64) << 32) | text.len() as u64;
+        offset += text.len() as u32;
+        packed
+    };
+
+    let prefix = add("{\"ok\":", &mut data) >> 32;
+    for (name, text) in funcs {
+        let packed = add(text, &mut data);
+        let params = if *name == "spider_info" {
+            ""
+        } else {
+            "(param i32 i32)"
+        };
+        exports.push_str(&format!(
+            "  (func (export \"{name}\") {params} (result i64) (i64.const {packed}))\n"
+        ));
+    }
+
+    format!(
+        r#"(module
+  (import "spider" "log" (func $log (param i32 i32 i32)))
+  (memory (export "memory") 2)
+  (global $heap (mut i32) (i32.const 32768))
+{data}
+  (func $alloc (export "spider_alloc") (param $len i32) (result i32)
+    (local $p i32)
+    (local.set $p (global.get $heap))
+    (global.set $heap (i32.add (global.get $heap) (local.get $len)))
+    (local.get $p))
+  (func (export "echo") (param $ptr i32) (param $len i32) (result i64)
+    (local $out i32)
+    (call $log (i32.const 2) (local.get $ptr) (local.get $len))
+    (local.set $out (call $alloc (i32.add (local.get $len) (i32.const 7))))
+    (memory.copy (local.get $out) (i32.const {prefix}) (i32.const 6))
+    (memory.copy (i32.add (local.get $out) (i32.const 6)) (local.get $ptr) (local.get $len))
+    (i32.store8
+      (i32.add (i32.add (local.get $out) (i32.const 6)) (local.get $len))
+      (i32.const 125))
+    (i64.or
+      (i64.shl (i64.extend_i32_u (local.get $out)) (i64.const 32))
+      (i64.extend_i32_u (i32.add (local.get $len) (i32.const 7)))))
+  (func (export "spin") (param i32 i32) (result i64)
+    (loop $l (br $l))
+    (i64.const 0))
+{exports})"#
+    )
+}
+
+fn info(base: &str) -> String {
+    format!(
+        r#"{{"id":"wasm-test","name":"测试插件","version":"0.2.0","sorts_url":"{base}/sorts","sort_url":"{base}/sort/{{id}}/{{page}}.html","novel_url":"{base}/book/{{id}}/"}}"#
+    )
+}
+
+fn site_plugin(base: &str, detail: &str) -> String {
+    plugin(&[
+        ("spider_info", info(base)),
+        (
+            "parse_sorts",
+            String::from(r#"{"ok":[{"id":1,"name":"玄幻"},{"id":2,"name":"都市"}]}"#),
+        ),
+        (
+            "parse_novels",
+            String::from(
+                r#"{"ok":{"novels":[{"id":7,"name":"遮天","author":"辰东","state":"finished","last_updated_at":"2022-09-01T08:00:00+08:00"},{"id":8,"name":"完美世界","author":"辰东"}],"pages":1}}"#,
+            ),
+        ),
+        ("parse_novel", String::from(detail)),
+        (
+            "parse_chapter",
+            String::from(r#"{"ok":{"text":"第一章 正文内容"}}"#),
+        ),
+    ])
+}
+
+const DETAIL: &str = r#"{"ok":{"novel":{"id":7,"name":"遮天","author":"辰东","intro":"冰冷与黑暗并存的宇宙深处"},"chapters":[{"name":"第一章","url":"1.html"},{"name":"第二章","url":"2.html"}]}}"#;
+
+async fn serve() -> SocketAddr {
+    common::serve(|req: Request<Body>| async move {
+        let body = format!("<html><body>{}</body></html>", req.uri().path());
+        Response::new(Body::from(body))
+    })
+}
+
+#[test]
+async fn call_plugin() {
+    let wat = plugin(&[("spider_info", info("http://localhost"))]);
+    let runtime = Runtime::new(wat.as_bytes(), Limits::default()).unwrap();
+
+    let x = runtime.info().unwrap();
+    assert_eq!(x.id, "wasm-test");
+    assert_eq!(x.version, "0.2.0");
+    assert!(x.capabilities().get_novel_from_sort);
+    assert!(!x.capabilities().search_novel);
+
+    let page = Page {
+        url: String::from("http://localhost/book/7/"),
+        body: String::from("<html>遮天</html>"),
+    };
+    let r: Reply<Page> = runtime.call("echo", &page).unwrap();
+    assert_eq!(r, Reply::Ok(page));
+}
+
+#[test]
+async fn sandbox_limits() {
+    let wat = plugin(&[("spider_info", info("http://localhost"))]);
+    let page = Page {
+        url: String::new(),
+        body: String::new(),
+    };
+
+    // 指令数用完后调用失败
+    let runtime = Runtime::new(
+        wat.as_bytes(),
+        Limits {
+            fuel: 100_000,
+            ..Limits::default()
+        },
+    )
+    .unwrap();
+    assert!(runtime
+        .call::<Page, serde_json::Value>("spin", &page)
+        .is_err());
+    // 每次调用使用新的实例，失败不影响后续调用
+    assert!(runtime.info().is_ok());
+
+    // 插件声明的内存超过限制时无法实例化
+    let runtime = Runtime::new(
+        wat.as_bytes(),
+        Limits {
+            memory: 64 << 10,
+            ..Limits::default()
+        },
+    )
+    .unwrap();
+    assert!(runtime.info().is_err());
+}
+
+#[test]
+async fn wasm_spider() {
+    let addr = serve().await;
+    let base = format!("http://{addr}");
+    let wat = site_plugin(&base, DETAIL);
+    let mut spider = WasmSpider::new(
+        Runtime::new(wat.as_bytes(), Limits::default()).unwrap(),
+        None,
+    )
+    .unwrap();
+
+    let info = spider.info();
+    assert_eq!(info.id, "wasm-test");
+    assert_eq!(info.name, "测试插件");
+    assert_eq!(info.source, SourceKind::Wasm);
+    assert!(info.capabilities.get_sort);
+    assert!(!info.capabilities.search_author);
+
+    spider.load_sorts().await.unwrap();
+    let sorts: Vec<_> = spider.sorts().iter().map(|x| x.name.as_str()).collect();
+    assert_eq!(sorts, vec!["玄幻", "都市"]);
+
+    let cancel = CancellationToken::new();
+    let mut rx = spider
+        .novels_by_sort_id(&SortID::from(1), Position::First, &cancel)
+        .await
+        .unwrap();
+    let mut novels = Vec::new();
+    while let Some(x) = rx.recv().await {
+        novels.push(x.unwrap());
+    }
+    assert_eq!(novels.len(), 2);
+    assert_eq!(novels[0].name, "遮天");
+    assert!(novels[0].last_updated_at.is_some());
+    assert_eq!(
+        novels[0].source_url.as_deref(),
+        Some(format!("{base}/book/7/").as_str())
+    );
+
+    let mut rx = spider
+        .sections_by_novel_id(&NovelID::from(7), Position::Full, &cancel)
+        .await
+        .unwrap();
+    let mut sections = Vec::new();
+    while let Some(x) = rx.recv().await {
+        sections.push(x.unwrap());
+    }
+    let v: Vec<_> = sections.iter().map(|x| (x.seq, x.name.as_str())).collect();
+    assert_eq!(v, vec![(0, "第一章"), (1, "第二章")]);
+    assert_eq!(sections[1].text, "第一章 正文内容");
+
+    let novel = spider.fetch_novel(&NovelID::from(7)).await.unwrap();
+    assert_eq!(novel.intro.as_deref(), Some("冰冷与黑暗并存的宇宙深处"));
+
+    assert!(matches!(
+        spider.search("遮天").await,
+        Err(CrawlError::Unsupported)
+    ));
+    spider.wait().await;
+}
+
+#[test]
+async fn plugin_parse_error() {
+    let addr = serve().await;
+    let wat = site_plugin(&format!("http://{addr}"), r#"{"err":"页面结构已变化"}"#);
+    let spider = WasmSpider::new(
+        Runtime::new(wat.as_bytes(), Limits::default()).unwrap(),
+        None,
+    )
+    .unwrap();
+
+    assert!(matches!(
+        spider.fetch_novel(&NovelID::from(7)).await,
+        Err(CrawlError::ParseFailed)
+    ));
+}
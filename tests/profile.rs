@@ -0,0 +1,172 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, TimeZone, Utc};
+use sea_orm::Database;
+use tokio::test;
+
+use spider_novel::common::date::Precision;
+use spider_novel::config::{Config, SpiderConfig};
+use spider_novel::ddxsku::profile::Profile;
+use spider_novel::ddxsku::DDSpider;
+use spider_novel::spider::{NovelState, SpiderMetadata};
+
+const MIRROR: &str = r#"
+id = "mirror"
+name = "mirror"
+base_url = "https://www.mirror.test"
+search_path = "/modules/article/search.php"
+
+[selectors]
+novel_content = "div#content"
+
+[states]
+finished = ["完本", "已完结"]
+
+[dates]
+list = ["%y-%m-%d", "%Y-%m-%d"]
+"#;
+
+#[test]
+async fn builtin_matches_file() {
+    let file = Profile::load("profiles/ddxsku.toml").unwrap();
+    assert_eq!(file, Profile::default());
+}
+
+#[test]
+async fn partial_profile() {
+    let x = Profile::parse(MIRROR, "toml").unwrap();
+
+    assert_eq!(x.id, "mirror");
+    assert_eq!(
+        x.search_url(),
+        "https://www.mirror.test/modules/article/search.php"
+    );
+    assert_eq!(x.selectors.novel_content, "div#content");
+    // 未出现的项使用ddxsku的值
+    assert_eq!(
+        x.selectors.novel_sections,
+        Profile::default().selectors.novel_sections
+    );
+    assert_eq!(x.info_labels, Profile::default().info_labels);
+
+    assert!(matches!(x.state("已完结"), NovelState::Finished));
+    assert!(matches!(x.state(" 完本 "), NovelState::Finished));
+    assert!(matches!(x.state("连载中"), NovelState::Updating));
+    // 未配置的状态文字视为连载中
+    assert!(matches!(x.state("未知"), NovelState::Updating));
+}
+
+#[test]
+async fn dates_use_site_timezone() {
+    let expect = Utc.ymd(2022, 7, 7).and_hms(16, 0, 0);
+    let list = |x: &str| profile_date(x, true);
+    assert_eq!(list("22-07-08"), Some((expect, Precision::Day)));
+    assert_eq!(list("2022-07-08"), Some((expect, Precision::Day)));
+    // 自定义格式不匹配时使用通用解析
+    assert_eq!(
+        profile_date("2022年7月8日 12:30", false),
+        Some((Utc.ymd(2022, 7, 8).and_hms(4, 30, 0), Precision::Minute))
+    );
+    assert_eq!(
+        profile_date("2022-07-08 12:30:00", false),
+        Some((Utc.ymd(2022, 7, 8).and_hms(4, 30, 0), Precision::Second))
+    );
+    assert_eq!(list("未知"), None);
+}
+
+fn profile_date(x: &str, list: bool) -> Option<(DateTime<Utc>, Precision)> {
+    let profile = Profile::parse(MIRROR, "toml").unwrap();
+    let r = if list {
+        profile.parse_list_date(x)
+    } else {
+        profile.parse_detail_date(x)
+    };
+
+    r.map(|x| (x.at, x.precision))
+}
+
+#[test]
+async fn reject_invalid_profile() {
+    let parse = |x: &str| Profile::parse(&format!("id = \"a\"\n{x}"), "toml");
+    assert!(parse("").is_ok());
+    assert!(parse("unknown = 1").is_err());
+    assert!(parse("base_url = \"ftp://a.test\"").is_err());
+    assert!(parse("concurrency = 0").is_err());
+    assert!(parse("[dates]\ntimezone = \"CST\"").is_err());
+    assert!(parse("[selectors]\nnovel_table = \" \"").is_err());
+
+    // 必须设置id，不使用base_url代替
+    assert!(Profile::parse("base_url = \"https://www.mirror.test\"", "toml").is_err());
+    assert!(Profile::parse("id = \" \"", "toml").is_err());
+}
+
+#[test]
+async fn sibling_site_from_config() {
+    let dir = std::env::temp_dir().join("spider-novel-profile");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("mirror.toml");
+    std::fs::write(&path, MIRROR).unwrap();
+
+    let config = Config::parse(
+        &format!(
+            "[spiders.mirror]\ntemplate = \"jieqi\"\nprofile = {:?}\nconcurrency = 4\n\n[spiders.other]\nenabled = false\n",
+            path.display().to_string()
+        ),
+        "toml",
+    )
+    .unwrap();
+    config.validate().unwrap();
+
+    let names: Vec<_> = config
+        .jieqi_spiders()
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    assert_eq!(names, vec!["mirror", "ddxsku"]);
+
+    let db = Arc::new(Database::connect("sqlite::memory:").await.unwrap());
+    let mirror = DDSpider::with_config(db.clone(), &config.spider("mirror")).unwrap();
+    assert_eq!(mirror.spider_id(), "mirror");
+    assert_eq!(mirror.site().profile.concurrency, 4);
+
+    let ddxsku = DDSpider::new(db);
+    assert_eq!(ddxsku.spider_id(), DDSpider::id());
+
+    // 运行时不能修改爬虫id
+    let mut x = config.spider("mirror");
+    x.profile = None;
+    assert!(mirror.apply(&x).is_err());
+}
+
+#[test]
+async fn mirror_id_from_config() {
+    // 没有站点描述的镜像站沿用ddxsku的描述，需要在配置中设置id
+    let config = Config::parse(
+        "[spiders.mirror]\ntemplate = \"jieqi\"\nid = \"mirror\"\nbase_url = \"https://www.mirror.test\"\n",
+        "toml",
+    )
+    .unwrap();
+    config.validate().unwrap();
+
+    let db = Arc::new(Database::connect("sqlite::memory:").await.unwrap());
+    let mirror = DDSpider::with_config(db.clone(), &config.spider("mirror")).unwrap();
+    assert_eq!(mirror.spider_id(), "mirror");
+    assert_eq!(mirror.site().profile.base_url, "https://www.mirror.test");
+
+    let x = SpiderConfig {
+        template: Some(String::from("wasm")),
+        id: Some(String::from("partner")),
+        plugin: Some("partner.wasm".into()),
+        ..SpiderConfig::default()
+    };
+    assert!(x.validate().is_err());
+}
+
+#[test]
+async fn unknown_template() {
+    let x = SpiderConfig {
+        template: Some(String::from("other")),
+        ..SpiderConfig::default()
+    };
+    assert!(x.validate().is_err());
+}
@@ -0,0 +1,249 @@
+use std::net::{SocketAddr, TcpListener};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use hyper::{Body, Request, Response, StatusCode};
+use tokio::test;
+
+use spider_novel::common::httputils::{fetch, send, FetchError, CLIENT};
+use spider_novel::common::proxy::{self, Outcome, ProxyOptions, ProxyPool};
+use spider_novel::config::Config;
+use spider_novel::spider::CrawlError;
+
+mod common;
+
+// 代理直接返回页面，请求的地址为完整的url
+async fn fake_proxy(body: &'static str, status: StatusCode) -> (SocketAddr, Arc<AtomicUsize>) {
+    let count = Arc::new(AtomicUsize::new(0));
+    let shared = count.clone();
+    let addr = common::serve(move |_: Request<Body>| {
+        shared.fetch_add(1, Ordering::SeqCst);
+        async move {
+            let mut resp = Response::new(Body::from(body));
+            *resp.status_mut() = status;
+            resp
+        }
+    });
+
+    (addr, count)
+}
+
+// 没有监听的端口
+fn dead_addr() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+fn urls(addrs: &[SocketAddr]) -> Vec<String> {
+    addrs.iter().map(|x| format!("http://{x}")).collect()
+}
+
+#[test]
+async fn pick_and_report() {
+    let addrs = [dead_addr(), dead_addr(), dead_addr()];
+    let pool = ProxyPool::new(&urls(&addrs), ProxyOptions::default()).unwrap();
+    let url = |i: usize| format!("http://{}", addrs[i]);
+
+    // 分数相同时使用第一个
+    let a = pool.pick("a.com", &[]).unwrap();
+    assert_eq!(a.url(), url(0));
+
+    // 第一个代理失败较多，新的主机使用第二个
+    for _ in 0..3 {
+        pool.report(&a, "x.com", Outcome::Failure);
+    }
+    let b = pool.pick("b.com", &[]).unwrap();
+    assert_eq!(b.url(), url(1));
+
+    // 第二个代理更快，已分配的主机保持不变
+    pool.report(&b, "b.com", Outcome::Success(Duration::from_millis(50)));
+    pool.report(&a, "a.com", Outcome::Success(Duration::from_millis(900)));
+    assert_eq!(pool.pick("c.com", &[]).unwrap().url(), url(1));
+    assert_eq!(pool.pick("a.com", &[]).unwrap().url(), url(0));
+
+    // 封禁后隔离，分配给其他代理
+    pool.report(&a, "a.com", Outcome::Banned);
+    assert_eq!(pool.pick("a.com", &[]).unwrap().url(), url(1));
+    let status = pool.status();
+    assert!(status[0].quarantined);
+    assert_eq!((status[0].success, status[0].failure), (1, 4));
+    assert!(!status[1].quarantined);
+    assert!(status[1].score > status[2].score);
+
+    // 连接失败后重新选择
+    let c = pool.pick("c.com", &[]).unwrap();
+    for _ in 0..5 {
+        pool.report(&c, "c.com", Outcome::Failure);
+    }
+    assert_eq!(pool.pick("c.com", &[]).unwrap().url(), url(2));
+
+    // 重试时不再分配已经失败的代理
+    assert_eq!(pool.pick("c.com", &[url(2)]).unwrap().url(), url(1));
+    assert!(pool.pick("c.com", &[url(1), url(2)]).is_none());
+    assert!(pool.has_available(&[url(2)]));
+    assert!(!pool.has_available(&[url(1), url(2)]));
+
+    // 全部隔离时没有可以分配的代理
+    let x = pool.pick("d.com", &[]).unwrap();
+    pool.report(&x, "d.com", Outcome::Banned);
+    let x = pool.pick("d.com", &[]).unwrap();
+    pool.report(&x, "d.com", Outcome::Banned);
+    assert!(pool.pick("d.com", &[]).is_none());
+    assert!(!pool.has_available(&[]));
+
+    assert!(pool.is_banned(429, ""));
+    assert!(pool.is_banned(200, "您的IP访问过于频繁，请稍后再试"));
+    assert!(!pool.is_banned(200, "第一章"));
+    // 正文中出现封禁文字时不是封禁页面
+    let x = format!("{}访问过于频繁{}", "正文".repeat(1000), "正文".repeat(1000));
+    assert!(!pool.is_banned(200, &x));
+    assert!(pool.is_banned(403, &x));
+
+    // 5xx记为失败，不隔离代理
+    let pool = Arc::new(ProxyPool::new(&urls(&[dead_addr()]), ProxyOptions::default()).unwrap());
+    assert!(!pool.lease("e.com", &[]).unwrap().finish(502, ""));
+    let x = pool.status().remove(0);
+    assert_eq!((x.success, x.failure), (0, 1));
+    assert!(!x.quarantined);
+    assert!(ProxyPool::new(
+        &[String::from("ftp://127.0.0.1:21")],
+        ProxyOptions::default()
+    )
+    .is_err());
+}
+
+#[test]
+async fn health_check() {
+    let (good, count) = fake_proxy("ok", StatusCode::OK).await;
+    let (failing, _) = fake_proxy("error", StatusCode::BAD_GATEWAY).await;
+    let options = ProxyOptions {
+        check_url: Some(String::from("http://check.test/")),
+        timeout: Duration::from_secs(2),
+        ..ProxyOptions::default()
+    };
+    let pool = ProxyPool::new(&urls(&[dead_addr(), failing, good]), options).unwrap();
+
+    pool.check().await.unwrap();
+    pool.check().await.unwrap();
+    assert_eq!(count.load(Ordering::SeqCst), 2);
+
+    let status = pool.status();
+    assert_eq!((status[0].success, status[0].failure), (0, 2));
+    assert_eq!((status[1].success, status[1].failure), (0, 2));
+    assert_eq!((status[2].success, status[2].failure), (2, 0));
+    assert!(status[2].latency.is_some());
+    assert_eq!(
+        pool.pick("www.a.com", &[]).unwrap().url(),
+        format!("http://{good}")
+    );
+}
+
+#[test]
+async fn fetch_through_pool() {
+    let (banned, banned_count) = fake_proxy("您的IP访问过于频繁", StatusCode::OK).await;
+    let (good, good_count) = fake_proxy("第一章 正文内容", StatusCode::OK).await;
+
+    let config = Config::parse(
+        &format!(
+            r#"
+[proxy]
+proxies = ["http://{banned}", "http://{good}"]
+quarantine = "1h"
+"#
+        ),
+        "toml",
+    )
+    .unwrap();
+    let pool = Arc::new(config.proxy.to_pool().unwrap().unwrap());
+    proxy::install(pool.clone());
+
+    // 第一个代理返回封禁页面，更换代理后重试
+    let url = "http://novel.test/book/1.html";
+    assert_eq!(fetch("proxy", url).await.unwrap(), "第一章 正文内容");
+    assert_eq!(banned_count.load(Ordering::SeqCst), 1);
+    assert_eq!(good_count.load(Ordering::SeqCst), 1);
+    assert!(pool.status()[0].quarantined);
+
+    // 同一网站继续使用可用的代理
+    assert_eq!(fetch("proxy", url).await.unwrap(), "第一章 正文内容");
+    assert_eq!(banned_count.load(Ordering::SeqCst), 1);
+    assert_eq!(good_count.load(Ordering::SeqCst), 2);
+
+    // 更换代理后仍然被封禁时返回错误，而不是封禁页面
+    let (a, a_count) = fake_proxy("您的IP访问过于频繁", StatusCode::OK).await;
+    let (b, b_count) = fake_proxy("Too Many Requests", StatusCode::TOO_MANY_REQUESTS).await;
+    let (c, c_count) = fake_proxy("", StatusCode::FORBIDDEN).await;
+    let pool = ProxyPool::new(&urls(&[a, b, c]), ProxyOptions::default()).unwrap();
+    proxy::install(Arc::new(pool));
+    let e = fetch("proxy-banned", url).await.unwrap_err();
+    assert!(matches!(e, FetchError::Banned(_)));
+    assert_eq!(e.kind(), "banned");
+    assert!(matches!(CrawlError::fetch(None, e), CrawlError::Banned(_)));
+    assert_eq!(a_count.load(Ordering::SeqCst), 1);
+    assert_eq!(b_count.load(Ordering::SeqCst), 1);
+    assert_eq!(c_count.load(Ordering::SeqCst), 1);
+
+    // 代理都在隔离中时返回错误，不直接访问
+    let e = fetch("proxy-banned", url).await.unwrap_err();
+    assert!(matches!(e, FetchError::NoProxy(_)));
+    assert_eq!(e.kind(), "no_proxy");
+    assert!(matches!(CrawlError::fetch(None, e), CrawlError::NoProxy(_)));
+    assert_eq!(a_count.load(Ordering::SeqCst), 1);
+
+    // 代理连接失败时更换其他代理
+    let (good, good_count) = fake_proxy("第一章 正文内容", StatusCode::OK).await;
+    let pool = ProxyPool::new(&urls(&[dead_addr(), good]), ProxyOptions::default()).unwrap();
+    proxy::install(Arc::new(pool));
+    assert_eq!(fetch("proxy-dead", url).await.unwrap(), "第一章 正文内容");
+    assert_eq!(good_count.load(Ordering::SeqCst), 1);
+
+    // 只有一个代理时连接失败不会重试同一个代理
+    let pool = ProxyPool::new(&urls(&[dead_addr()]), ProxyOptions::default()).unwrap();
+    proxy::install(Arc::new(pool));
+    assert!(matches!(
+        fetch("proxy-dead", url).await,
+        Err(FetchError::Http(_))
+    ));
+
+    // POST请求同样检查封禁页面
+    let mut addrs = Vec::new();
+    for _ in 0..3 {
+        addrs.push(fake_proxy("您的IP访问过于频繁", StatusCode::OK).await.0);
+    }
+    let pool = ProxyPool::new(&urls(&addrs), ProxyOptions::default()).unwrap();
+    proxy::install(Arc::new(pool));
+    let req = CLIENT.post(url).form(&[("searchkey", "斗破")]);
+    assert!(matches!(
+        send("proxy-banned", req).await,
+        Err(FetchError::Banned(_))
+    ));
+
+    // 设置 direct_fallback 后代理都不可用时直接访问
+    let direct =
+        common::serve(|_: Request<Body>| async { Response::new(Body::from("直接访问")) });
+    let (a, _) = fake_proxy("您的IP访问过于频繁", StatusCode::OK).await;
+    let config = Config::parse(
+        &format!("[proxy]\nproxies = [\"http://{a}\"]\ndirect_fallback = true\n"),
+        "toml",
+    )
+    .unwrap();
+    let pool = Arc::new(config.proxy.to_pool().unwrap().unwrap());
+    assert!(pool.options().direct_fallback);
+    proxy::install(pool);
+    let url = format!("http://{direct}/book/1.html");
+    assert!(matches!(
+        fetch("proxy-fallback", &url).await,
+        Err(FetchError::Banned(_))
+    ));
+    assert_eq!(fetch("proxy-fallback", &url).await.unwrap(), "直接访问");
+    proxy::uninstall();
+
+    assert!(Config::parse("[proxy]\nproxies = [\"ftp://a\"]", "toml")
+        .unwrap()
+        .proxy
+        .to_pool()
+        .is_err());
+}
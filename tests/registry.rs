@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use tokio::test;
+
+use spider_novel::keeper::Keeper;
+use spider_novel::spider::{CrawlError, SourceKind, SpiderInfo, Support};
+
+use common::{temp_db, FakeSpider};
+
+mod common;
+
+const SUPPORT: Support = Support {
+    get_sort: false,
+    get_novel_from_sort: false,
+    search_novel: true,
+    search_author: false,
+};
+
+fn searcher() -> FakeSpider {
+    FakeSpider::new()
+        .support(SUPPORT)
+        .search(|_| async { Err(CrawlError::ParseFailed) })
+}
+
+fn info(id: &str) -> SpiderInfo {
+    SpiderInfo {
+        name: format!("站点 {id}"),
+        ..searcher().with_id(id).spider_info()
+    }
+}
+
+#[test]
+async fn register_spiders() {
+    let db = Arc::new(temp_db("registry").await);
+    let mut keeper = Keeper::new(db);
+
+    // 按类型注册时使用类型的id与能力
+    keeper.add_spider(searcher()).unwrap();
+    keeper
+        .add_spider_info(
+            SpiderInfo {
+                source: SourceKind::Rule,
+                ..info("site-a")
+            },
+            searcher(),
+        )
+        .unwrap();
+
+    // id重复或为空时拒绝
+    assert!(keeper.add_spider_info(info("site-a"), searcher()).is_err());
+    assert!(keeper.add_spider(searcher()).is_err());
+    assert!(keeper.add_spider_as("site-a", searcher()).is_err());
+    assert!(keeper.register(info(" "), Box::new(searcher())).is_err());
+
+    let v = keeper.registry();
+    let ids: Vec<_> = v.iter().map(|x| x.info.id.as_str()).collect();
+    assert_eq!(ids, vec!["fake", "site-a"]);
+    assert_eq!(v[0].info.source, SourceKind::Builtin);
+    assert_eq!(v[0].info.version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(v[1].info.source, SourceKind::Rule);
+    assert_eq!(v[1].info.name, "站点 site-a");
+    assert!(v[1].info.capabilities.search_novel);
+    assert!(v.iter().all(|x| x.enabled));
+
+    assert_eq!(keeper.spider_info("site-a").unwrap().name, "站点 site-a");
+    assert!(keeper.spider_info("missing").is_none());
+    assert!(keeper.set_enabled("missing", false).is_err());
+}
+
+#[test]
+async fn disable_spider() {
+    let db = Arc::new(temp_db("registry-disable").await);
+    let mut keeper = Keeper::new(db);
+    searcher().with_id("registry").add_to(&mut keeper).unwrap();
+    keeper.add_spider_info(info("site-b"), searcher()).unwrap();
+
+    let r = keeper.search("遮天").await;
+    assert_eq!(r.failed.len(), 2);
+
+    // 停用的爬虫不参与搜索
+    keeper.set_enabled("site-b", false).unwrap();
+    assert!(!keeper.is_enabled("site-b"));
+    let r = keeper.search("遮天").await;
+    let failed: Vec<_> = r.failed.iter().map(|(id, _)| id.as_str()).collect();
+    assert_eq!(failed, vec!["registry"]);
+
+    let v = keeper.registry();
+    assert!(v.iter().any(|x| x.info.id == "site-b" && !x.enabled));
+
+    keeper.set_enabled("site-b", true).unwrap();
+    assert_eq!(keeper.search("遮天").await.failed.len(), 2);
+}
@@ -0,0 +1,299 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use hyper::header::{COOKIE, LOCATION, SET_COOKIE};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use tokio::test;
+
+use spider_novel::common::{cookiejar, httputils};
+use spider_novel::spider::CrawlError;
+use spider_novel::webook::credential::{CredentialStore, Credentials};
+use spider_novel::webook::session::{self, Session};
+use spider_novel::webook::BookSource;
+
+mod common;
+
+#[derive(Default)]
+struct Site {
+    // 服务端当前有效的会话
+    sid: Option<String>,
+    logins: usize,
+}
+
+// 需要登录才能查看章节的网站，未登录时返回提示页面而不是错误状态码
+async fn site() -> (SocketAddr, Arc<Mutex<Site>>) {
+    let state = Arc::new(Mutex::new(Site::default()));
+    let shared = state.clone();
+    let addr = common::serve(move |req: Request<Body>| {
+        let state = shared.clone();
+        async move { handle(req, &state).await }
+    });
+
+    (addr, state)
+}
+
+async fn handle(req: Request<Body>, state: &Mutex<Site>) -> Response<Body> {
+    match (req.method(), req.uri().path()) {
+        (&Method::POST, "/login") => {
+            let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+            let form = String::from_utf8_lossy(&body);
+            if !form.contains("username=reader") || !form.contains("password=secret") {
+                let mut resp = Response::new(Body::from("用户名或密码错误"));
+                *resp.status_mut() = StatusCode::UNAUTHORIZED;
+                return resp;
+            }
+
+            let mut site = state.lock().unwrap();
+            site.logins += 1;
+            let sid = format!("s{}", site.logins);
+            site.sid = Some(sid.clone());
+
+            Response::builder()
+                .status(StatusCode::FOUND)
+                .header(LOCATION, "/")
+                .header(SET_COOKIE, format!("sid={sid}; Path=/; HttpOnly"))
+                .body(Body::empty())
+                .unwrap()
+        }
+        (_, "/") => Response::new(Body::from("<html><body>首页</body></html>")),
+        (_, "/chapter") => {
+            let cookie = req
+                .headers()
+                .get(COOKIE)
+                .and_then(|x| x.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            let logged_in = match &state.lock().unwrap().sid {
+                Some(sid) => cookie.contains(&format!("sid={sid}")),
+                None => false,
+            };
+            let body = if logged_in {
+                "<html><body>第一章 正文内容</body></html>"
+            } else {
+                "<html><body>请先登录后阅读</body></html>"
+            };
+            Response::new(Body::from(body))
+        }
+        _ => {
+            let mut resp = Response::new(Body::empty());
+            *resp.status_mut() = StatusCode::NOT_FOUND;
+            resp
+        }
+    }
+}
+
+fn credentials(password: &str) -> Credentials {
+    Credentials::from([
+        (String::from("username"), String::from("reader")),
+        (String::from("password"), String::from(password)),
+    ])
+}
+
+fn source(addr: SocketAddr, login: &str) -> Arc<BookSource> {
+    let x = BookSource::new(&format!("http://{addr}/"), "测试书源").with_login(
+        Some(String::from(login)),
+        None,
+        Some(String::from("@js:result.indexOf('请先登录') < 0")),
+    );
+    Arc::new(x)
+}
+
+#[test]
+async fn credential_store() {
+    let path = std::env::temp_dir().join("spider-novel-credentials.enc");
+    let _ = std::fs::remove_file(&path);
+
+    let mut store = CredentialStore::open(&path, "hunter2").unwrap();
+    assert!(store.sources().is_empty());
+    store.set("http://a.com/", credentials("secret")).unwrap();
+    store.set("http://b.com/", credentials("other")).unwrap();
+
+    // 文件中没有明文
+    let data = std::fs::read_to_string(&path).unwrap();
+    assert!(!data.contains("secret"));
+    assert!(!data.contains("reader"));
+
+    let mut store = CredentialStore::open(&path, "hunter2").unwrap();
+    assert_eq!(store.sources(), vec!["http://a.com/", "http://b.com/"]);
+    assert_eq!(store.get("http://a.com/"), Some(&credentials("secret")));
+    assert!(CredentialStore::open(&path, "wrong").is_err());
+
+    assert!(store.remove("http://b.com/").unwrap());
+    assert!(!store.remove("http://b.com/").unwrap());
+    let store = CredentialStore::open(&path, "hunter2").unwrap();
+    assert_eq!(store.sources(), vec!["http://a.com/"]);
+}
+
+#[test]
+async fn form_login_and_relogin() {
+    let (addr, state) = site().await;
+    let session = Session::new(
+        "session-form",
+        source(addr, "/login"),
+        Some(credentials("secret")),
+    );
+    let url = format!("http://{addr}/chapter");
+
+    // 第一次请求发现未登录，登录后重试
+    let body = session.get(&url).await.unwrap();
+    assert!(body.contains("正文内容"));
+    assert_eq!(state.lock().unwrap().logins, 1);
+    assert_eq!(session.cookies().get("sid").map(|x| x.as_str()), Some("s1"));
+    // 登录状态保存在爬虫的cookie中
+    let jar = cookiejar::jar("session-form").unwrap();
+    assert!(jar
+        .cookies()
+        .iter()
+        .any(|x| x.name == "sid" && x.value == "s1"));
+
+    // 登录状态有效时不重复登录
+    session.get(&url).await.unwrap();
+    assert_eq!(state.lock().unwrap().logins, 1);
+
+    // 服务端会话失效后自动重新登录
+    state.lock().unwrap().sid = None;
+    let body = session.get(&url).await.unwrap();
+    assert!(body.contains("正文内容"));
+    assert_eq!(state.lock().unwrap().logins, 2);
+
+    // 并发请求同时发现登出时只登录一次
+    state.lock().unwrap().sid = None;
+    let session = Arc::new(session);
+    let tasks: Vec<_> = (0..4)
+        .map(|_| {
+            let session = session.clone();
+            let url = url.clone();
+            tokio::spawn(async move { session.get(&url).await })
+        })
+        .collect();
+    for x in tasks {
+        assert!(x.await.unwrap().unwrap().contains("正文内容"));
+    }
+    assert_eq!(state.lock().unwrap().logins, 3);
+}
+
+#[test]
+async fn js_login() {
+    let (addr, state) = site().await;
+    let js = "@js:function login() {
+        return {
+            url: source.url + 'login',
+            method: 'POST',
+            form: {username: credentials.username, password: credentials.password},
+        };
+    }";
+    let session = Session::new("session-js", source(addr, js), Some(credentials("secret")));
+    session.login().await.unwrap();
+    assert_eq!(state.lock().unwrap().logins, 1);
+
+    let body = session
+        .get(&format!("http://{addr}/chapter"))
+        .await
+        .unwrap();
+    assert!(body.contains("正文内容"));
+    assert_eq!(state.lock().unwrap().logins, 1);
+}
+
+#[test]
+async fn login_failed() {
+    let (addr, state) = site().await;
+    let url = format!("http://{addr}/chapter");
+
+    let session = Session::new(
+        "session-failed",
+        source(addr, "/login"),
+        Some(credentials("wrong")),
+    );
+    assert!(session.login().await.is_err());
+    assert!(matches!(
+        session.get(&url).await,
+        Err(CrawlError::LoginFailed)
+    ));
+    assert_eq!(state.lock().unwrap().logins, 0);
+
+    // 没有保存登录信息
+    let session = Session::new("session-none", source(addr, "/login"), None);
+    assert!(matches!(
+        session.get(&url).await,
+        Err(CrawlError::LoginFailed)
+    ));
+
+    // 未设置登录检测时按状态码判断
+    let x = BookSource::new(&format!("http://{addr}/"), "测试书源");
+    let session = Session::new("session-status", Arc::new(x), None);
+    assert!(session.check(&url, 200, "请先登录").await.unwrap());
+    assert!(!session.check(&url, 401, "").await.unwrap());
+}
+
+#[test]
+async fn scripts_are_bounded() {
+    let (addr, state) = site().await;
+    let url = format!("http://{addr}/chapter");
+
+    // 登录检测js可以是使用return的语句
+    let x = BookSource::new(&format!("http://{addr}/"), "测试书源").with_login(
+        None,
+        None,
+        Some(String::from(
+            "<js>if (status != 200) { return false; } return url.length > 0;</js>",
+        )),
+    );
+    let session = Session::new("session-return", Arc::new(x), None);
+    assert!(session.check(&url, 200, "").await.unwrap());
+    assert!(!session.check(&url, 500, "").await.unwrap());
+
+    // 不会结束的脚本在超时后返回错误
+    let x = BookSource::new(&format!("http://{addr}/"), "测试书源").with_login(
+        Some(String::from("@js:while (true) {}")),
+        None,
+        Some(String::from("@js:(function() { while (true) {} })()")),
+    );
+    let session = Session::new("session-loop", Arc::new(x), Some(credentials("secret")));
+    let start = Instant::now();
+    assert!(session.check(&url, 200, "").await.is_err());
+    assert!(session.login().await.is_err());
+    assert!(start.elapsed() < Duration::from_secs(10));
+    assert_eq!(state.lock().unwrap().logins, 0);
+}
+
+#[test]
+async fn spider_fetch_uses_session() {
+    let (addr, state) = site().await;
+    let url = format!("http://{addr}/chapter");
+
+    // 没有注册会话时按普通页面获取
+    let body = httputils::fetch("session-spider", &url).await.unwrap();
+    assert!(body.contains("请先登录"));
+
+    let session = Session::new(
+        "session-spider",
+        source(addr, "/login"),
+        Some(credentials("secret")),
+    );
+    session::register(Arc::new(session));
+    let body = httputils::fetch("session-spider", &url).await.unwrap();
+    assert!(body.contains("正文内容"));
+    assert_eq!(state.lock().unwrap().logins, 1);
+
+    // 会话失效后爬虫的请求自动重新登录
+    state.lock().unwrap().sid = None;
+    let body = httputils::fetch("session-spider", &url).await.unwrap();
+    assert!(body.contains("正文内容"));
+    assert_eq!(state.lock().unwrap().logins, 2);
+
+    let session = Session::new(
+        "session-spider-failed",
+        source(addr, "/login"),
+        Some(credentials("wrong")),
+    );
+    session::register(Arc::new(session));
+    assert!(matches!(
+        httputils::fetch("session-spider-failed", &url).await,
+        Err(httputils::FetchError::Login(_))
+    ));
+
+    assert!(session::unregister("session-spider").is_some());
+    let body = httputils::fetch("session-spider", &url).await;
+    assert!(body.unwrap().contains("正文内容"));
+}
@@ -0,0 +1,183 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use hyper::{Body, Request, Response};
+use tokio::test;
+use tokio::time;
+
+use spider_novel::common::migrate::Migrator;
+use spider_novel::common::shutdown::{CancellationToken, TaskTracker};
+use spider_novel::ddxsku::data::novel_by_id;
+use spider_novel::ddxsku::profile::Profile;
+use spider_novel::ddxsku::{DDSpider, SortEntity};
+use spider_novel::keeper::Keeper;
+use spider_novel::spider::{Position, Spider, SpiderMetadata};
+
+use common::FakeSpider;
+
+mod common;
+
+#[test]
+async fn tracker_wait() {
+    let tracker = TaskTracker::new();
+    let cancel = CancellationToken::new();
+
+    for x in 1..=10 {
+        let cancel = cancel.clone();
+        tracker.spawn(async move {
+            tokio::select! {
+                _ = time::sleep(Duration::from_secs(x * 60)) => {},
+                _ = cancel.cancelled() => {},
+            }
+        });
+    }
+
+    assert_eq!(tracker.len(), 10);
+
+    cancel.cancel();
+    time::timeout(Duration::from_secs(1), tracker.wait())
+        .await
+        .expect("等待任务结束超时");
+
+    assert!(tracker.is_empty());
+}
+
+#[test]
+async fn tracker_wait_empty() {
+    let tracker = TaskTracker::new();
+
+    time::timeout(Duration::from_millis(100), tracker.wait())
+        .await
+        .expect("没有任务时不应该等待");
+}
+
+#[test]
+async fn keeper_shutdown_waits_for_tasks() {
+    let db = Arc::new(common::migrated_db("shutdown-wait").await);
+    let mut keeper = Keeper::new(db);
+    // 任务由测试直接派发到tracker上，keeper关闭时通过wait等待它们结束
+    let tracker = TaskTracker::new();
+    keeper
+        .add_spider(FakeSpider::new().tracker(tracker.clone()))
+        .unwrap();
+
+    // 取消后还需要一段时间保存数据的任务
+    let saved = Arc::new(AtomicBool::new(false));
+    let cancel = keeper.cancel_token();
+    {
+        let saved = saved.clone();
+        tracker.spawn(async move {
+            cancel.cancelled().await;
+            time::sleep(Duration::from_millis(100)).await;
+            saved.store(true, Ordering::SeqCst);
+        });
+    }
+
+    keeper.shutdown(Duration::from_secs(5)).await.unwrap();
+    assert!(saved.load(Ordering::SeqCst));
+    assert!(tracker.is_empty());
+}
+
+#[test]
+async fn keeper_shutdown_deadline() {
+    let db = Arc::new(common::migrated_db("shutdown-deadline").await);
+    let mut keeper = Keeper::new(db);
+    let tracker = TaskTracker::new();
+    keeper
+        .add_spider(FakeSpider::new().tracker(tracker.clone()))
+        .unwrap();
+
+    // 不响应取消的任务
+    tracker.spawn(time::sleep(Duration::from_secs(60)));
+
+    let start = Instant::now();
+    assert!(keeper.shutdown(Duration::from_millis(200)).await.is_err());
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(keeper.cancel_token().is_cancelled());
+    assert_eq!(tracker.len(), 1);
+}
+
+const SORT_PAGE: &str = r#"<html><body><table><tbody>
+<tr><th>书名</th><th>最新章节</th><th>作者</th><th>字数</th><th>更新</th><th>状态</th></tr>
+<tr><td><a href="{base}/book/1.html">遮天</a></td><td><a href="{base}/book/1/">第一章</a></td><td>辰东</td><td>1</td><td>22-07-08</td><td>连载中</td></tr>
+<tr><td><a href="{base}/book/2.html">完美世界</a></td><td><a href="{base}/book/2/">第一章</a></td><td>辰东</td><td>1</td><td>22-07-08</td><td>连载中</td></tr>
+<tr><td><a href="{base}/book/3.html">圣墟</a></td><td><a href="{base}/book/3/">第一章</a></td><td>辰东</td><td>1</td><td>22-07-08</td><td>连载中</td></tr>
+</tbody></table><a class="last">1</a></body></html>"#;
+
+#[test]
+async fn ddxsku_cancel_mid_crawl() {
+    // 列表页立即返回，详情页一直不返回，取消时爬取停在获取详情页
+    let details = Arc::new(AtomicUsize::new(0));
+    let addr = {
+        let details = details.clone();
+        common::serve(move |req: Request<Body>| {
+            let details = details.clone();
+            async move {
+                if req.uri().path().starts_with("/book/") {
+                    details.fetch_add(1, Ordering::SeqCst);
+                    time::sleep(Duration::from_secs(60)).await;
+                }
+                let host = req.headers()["host"].to_str().unwrap();
+                let page = SORT_PAGE.replace("{base}", &format!("http://{host}"));
+                Response::new(Body::from(page))
+            }
+        })
+    };
+
+    let db = common::temp_db("shutdown-ddxsku").await;
+    let mut migrator = Migrator::new();
+    migrator.register(DDSpider::id(), DDSpider::migrations());
+    migrator.up(&db).await.unwrap();
+    let db = Arc::new(db);
+
+    let profile = Profile {
+        base_url: format!("http://{addr}"),
+        ..Profile::default()
+    };
+    let mut spider = DDSpider::with_profile(db.clone(), profile).unwrap();
+    spider
+        .set_sort(&vec![SortEntity {
+            name: String::from("全部"),
+            link: format!("http://{addr}/sort/{{{{page}}}}.html"),
+        }])
+        .await
+        .unwrap();
+    let sort = spider.sorts()[0].id;
+
+    let cancel = CancellationToken::new();
+    let mut rx = spider
+        .novels_by_sort_id(&sort, Position::First, &cancel)
+        .await
+        .unwrap();
+
+    time::timeout(Duration::from_secs(5), async {
+        while details.load(Ordering::SeqCst) < 3 {
+            time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("没有开始获取详情页");
+
+    cancel.cancel();
+    time::timeout(Duration::from_secs(5), spider.wait())
+        .await
+        .expect("取消后爬虫的任务没有结束");
+
+    // 取消时跳过详情页，列表页中的信息仍然写入数据库
+    let mut names = Vec::new();
+    while let Some(x) = rx.recv().await {
+        let x = x.unwrap();
+        assert!(x.intro.is_none());
+
+        let row = novel_by_id(&db, &x.id)
+            .await
+            .unwrap()
+            .expect("小说没有保存");
+        assert_eq!(row.name, x.name);
+        assert_eq!(row.author, "辰东");
+        names.push(x.name);
+    }
+    names.sort();
+    assert_eq!(names, vec!["圣墟", "完美世界", "遮天"]);
+}
@@ -0,0 +1,86 @@
+use std::collections::HashSet;
+
+use spider_novel::common::snowid::{self, IdGen};
+
+#[test]
+fn sequential_is_deterministic() {
+    let gen = IdGen::sequential(100);
+    let other = gen.clone();
+
+    assert_eq!(gen.generate(), 100);
+    assert_eq!(other.generate(), 101);
+    assert_eq!(gen.generate(), 102);
+}
+
+#[test]
+fn reject_invalid_node() {
+    assert!(IdGen::snowflake(32, 1).is_err());
+    assert!(IdGen::snowflake(1, -1).is_err());
+    assert!(IdGen::snowflake(31, 31).is_ok());
+}
+
+#[test]
+fn node_bits() {
+    let a = IdGen::snowflake(1, 2).unwrap().generate_at(1_000);
+    let b = IdGen::snowflake(2, 1).unwrap().generate_at(1_000);
+
+    assert_ne!(a, b);
+    assert_eq!(a >> 22, 1_000);
+    assert_eq!((a >> 17) & 0x1f, 1);
+    assert_eq!((a >> 12) & 0x1f, 2);
+}
+
+#[test]
+fn clock_rollback() {
+    let gen = IdGen::snowflake(1, 1).unwrap();
+
+    let mut last = gen.generate_at(10_000);
+    // 时钟回拨后仍单调递增
+    for now in [9_000, 9_500, 10_000, 10_001] {
+        let id = gen.generate_at(now);
+        assert!(id > last, "{id} <= {last}");
+        last = id;
+    }
+}
+
+#[test]
+fn sequence_overflow() {
+    let gen = IdGen::snowflake(0, 0).unwrap();
+
+    let ids: Vec<_> = (0..5000).map(|_| gen.generate_at(1)).collect();
+    let uniq: HashSet<_> = ids.iter().collect();
+    assert_eq!(uniq.len(), ids.len());
+    assert!(ids.windows(2).all(|x| x[0] < x[1]));
+    // 序号用尽后借用下一毫秒
+    assert_eq!(ids.last().unwrap() >> 22, 2);
+}
+
+#[test]
+fn concurrent_unique() {
+    let gen = IdGen::snowflake(3, 4).unwrap();
+
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let gen = gen.clone();
+            std::thread::spawn(move || (0..10_000).map(|_| gen.generate()).collect::<Vec<_>>())
+        })
+        .collect();
+
+    let mut all = HashSet::new();
+    for h in handles {
+        for id in h.join().unwrap() {
+            assert!(all.insert(id));
+        }
+    }
+}
+
+#[test]
+fn install_global() {
+    snowid::install(IdGen::sequential(7));
+
+    assert_eq!(snowid::global().generate(), 7);
+    assert_eq!(snowid::global().generate(), 8);
+
+    snowid::set(1, 1).unwrap();
+    assert!(snowid::global().generate() > 1 << 22);
+}
@@ -0,0 +1,28 @@
+use spider_novel::common::snowid::{IdGen, ENV_MACHINE_ID, ENV_NODE_ID};
+use spider_novel::config::Config;
+
+// 修改环境变量会影响同一进程中的其他测试，因此单独放在一个测试文件中
+#[test]
+fn snowflake_env() {
+    std::env::remove_var(ENV_MACHINE_ID);
+    std::env::remove_var(ENV_NODE_ID);
+    assert!(IdGen::from_env().unwrap().is_none());
+    assert!(Config::default().id_gen().is_err());
+
+    std::env::set_var(ENV_MACHINE_ID, "4");
+    assert!(IdGen::from_env().unwrap().is_some());
+    assert_eq!(
+        (Config::default().id_gen().unwrap().generate() >> 12) & 0x3FF,
+        4 << 5 | 1
+    );
+
+    // 值无效时报错，不使用默认节点
+    std::env::set_var(ENV_NODE_ID, "x");
+    assert!(IdGen::from_env().is_err());
+    assert!(Config::default().validate().is_err());
+    let x = Config::parse("[snowflake]\nsingle_node = true", "toml").unwrap();
+    assert!(x.id_gen().is_err());
+
+    std::env::set_var(ENV_NODE_ID, "32");
+    assert!(IdGen::from_env().is_err());
+}
@@ -1,6 +1,7 @@
 use rand::Rng;
 use sea_orm::Database;
 use spider_novel::common::sender::WrapSender;
+use spider_novel::common::shutdown::CancellationToken;
 use spider_novel::common::snowid::set;
 use spider_novel::ddxsku::{DDSpider, SortEntity};
 use spider_novel::spider::{NovelID, Position, SortID, Spider};
@@ -24,7 +25,7 @@ async fn ddxsku_spider() -> DDSpider {
 
 #[test]
 async fn set_sorts() {
-    set(1, 1);
+    set(1, 1).unwrap();
     let mut spider = ddxsku_spider().await;
     spider
         .set_sort(&vec![SortEntity {
@@ -47,25 +48,18 @@ async fn get_novels() {
 
     tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
 
-    set(1, 1);
+    set(1, 1).unwrap();
     let mut spider = ddxsku_spider().await;
     spider.load_sorts().await.unwrap();
 
     let id: SortID = 6953631192986030081.into();
 
     let mut rx = spider
-        .novels_by_sort_id(&id, Position::Range(1..10))
+        .novels_by_sort_id(&id, Position::Range(1..10), &CancellationToken::new())
         .await
         .unwrap();
-    loop {
-        match rx.recv().await {
-            Some(x) => {
-                println!("{:?}", x);
-            }
-            None => {
-                break;
-            }
-        }
+    while let Some(x) = rx.recv().await {
+        println!("{:?}", x);
     }
 
     println!("ok")
@@ -83,22 +77,18 @@ async fn get_novels2() {
 
     tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
 
-    set(1, 1);
+    set(1, 1).unwrap();
     let mut spider = ddxsku_spider().await;
     spider.load_sorts().await.unwrap();
 
     let id: SortID = 6953709975051046913.into();
 
-    let mut rx = spider.novels_by_sort_id(&id, Position::Full).await.unwrap();
-    loop {
-        match rx.recv().await {
-            Some(x) => {
-                println!("{:?}", x);
-            }
-            None => {
-                break;
-            }
-        }
+    let mut rx = spider
+        .novels_by_sort_id(&id, Position::Full, &CancellationToken::new())
+        .await
+        .unwrap();
+    while let Some(x) = rx.recv().await {
+        println!("{:?}", x);
     }
 
     println!("ok")
@@ -116,14 +106,14 @@ async fn test_sections1() {
 
     tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
 
-    set(1, 1);
+    set(1, 1).unwrap();
     let mut spider = ddxsku_spider().await;
     spider.load_sorts().await.unwrap();
 
     let id: NovelID = 6953632287334469633.into();
 
     let mut tx = spider
-        .sections_by_novel_id(&id, Position::Full)
+        .sections_by_novel_id(&id, Position::Full, &CancellationToken::new())
         .await
         .unwrap();
     let novel_name = "我，宇智波义勇，没有被讨厌！";
@@ -134,27 +124,25 @@ async fn test_sections1() {
         .await
         .unwrap();
 
-    loop {
-        match tx.recv().await {
-            Some(x) => match x {
-                Ok(section) => {
-                    let section_path = format!("{path}/{}-{}", section.seq, section.name);
-
-                    let mut f = OpenOptions::new()
-                        .create(true)
-                        .write(true)
-                        .open(&section_path)
-                        .await
-                        .unwrap();
-                    f.write_all(&section.text.as_bytes()).await.unwrap();
-
-                    f.flush().await.unwrap();
-                }
-                Err(e) => {
-                    println!("获取章节错误：{e}");
-                }
-            },
-            None => break,
+    while let Some(x) = tx.recv().await {
+        match x {
+            Ok(section) => {
+                let section_path = format!("{path}/{}-{}", section.seq, section.name);
+
+                let mut f = OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .truncate(true)
+                    .open(&section_path)
+                    .await
+                    .unwrap();
+                f.write_all(section.text.as_bytes()).await.unwrap();
+
+                f.flush().await.unwrap();
+            }
+            Err(e) => {
+                println!("获取章节错误：{e}");
+            }
         }
     }
 }
@@ -171,14 +159,14 @@ async fn test_sections2() {
 
     tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
 
-    set(1, 1);
+    set(1, 1).unwrap();
     let mut spider = ddxsku_spider().await;
     spider.load_sorts().await.unwrap();
 
     let id: NovelID = 6953718276044230657.into();
 
     let mut tx = spider
-        .sections_by_novel_id(&id, Position::Full)
+        .sections_by_novel_id(&id, Position::Full, &CancellationToken::new())
         .await
         .unwrap();
     let novel_name = "大唐全能奶爸";
@@ -189,27 +177,25 @@ async fn test_sections2() {
         .await
         .unwrap();
 
-    loop {
-        match tx.recv().await {
-            Some(x) => match x {
-                Ok(section) => {
-                    let section_path = format!("{path}/{}-{}", section.seq, section.name);
-
-                    let mut f = OpenOptions::new()
-                        .create(true)
-                        .write(true)
-                        .open(&section_path)
-                        .await
-                        .unwrap();
-                    f.write_all(&section.text.as_bytes()).await.unwrap();
-
-                    f.flush().await.unwrap();
-                }
-                Err(e) => {
-                    println!("获取章节错误：{e}");
-                }
-            },
-            None => break,
+    while let Some(x) = tx.recv().await {
+        match x {
+            Ok(section) => {
+                let section_path = format!("{path}/{}-{}", section.seq, section.name);
+
+                let mut f = OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .truncate(true)
+                    .open(&section_path)
+                    .await
+                    .unwrap();
+                f.write_all(section.text.as_bytes()).await.unwrap();
+
+                f.flush().await.unwrap();
+            }
+            Err(e) => {
+                println!("获取章节错误：{e}");
+            }
         }
     }
 }
@@ -227,21 +213,14 @@ async fn sender_permit() {
             println!("task {x} sleep {dur} second");
             time::sleep(Duration::from_secs(dur as u64)).await;
 
-            tx.send(x)
+            tx.send(x).await
         });
     }
 
     drop(tx);
 
-    loop {
-        match rx.recv().await {
-            Some(x) => {
-                println!("recv {x}")
-            }
-            None => {
-                return;
-            }
-        }
+    while let Some(x) = rx.recv().await {
+        println!("recv {x}")
     }
 }
 
@@ -257,7 +236,7 @@ async fn fetch_novel() {
 
     tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
 
-    set(1, 1);
+    set(1, 1).unwrap();
     let mut spider = ddxsku_spider().await;
     spider.load_sorts().await.unwrap();
 
@@ -279,7 +258,7 @@ async fn search() {
 
     tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
 
-    set(1, 1);
+    set(1, 1).unwrap();
     let mut spider = ddxsku_spider().await;
     spider.load_sorts().await.unwrap();
 